@@ -0,0 +1,151 @@
+//! Benchmarks for palette matching (per-pixel vs batch vs cached)
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use epd_dither::color::distance::{
+    batch_find_closest_colors, find_closest_color_with_metric, ColorMatchCache, DistanceMetric,
+};
+use epd_dither::color::{palette::PaletteManager, Rgb};
+use epd_dither::dither::{algorithms::error_diffusion, ErrorDiffusionKernel};
+use std::hint::black_box;
+
+fn gradient_colors(width: usize, height: usize) -> Vec<Rgb> {
+    let mut colors = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            colors.push(Rgb::new(
+                (x * 255 / width) as u8,
+                (y * 255 / height) as u8,
+                ((x + y) * 255 / (width + height)) as u8,
+            ));
+        }
+    }
+    colors
+}
+
+fn spectra6() -> Vec<Rgb> {
+    PaletteManager::new()
+        .expect("Embedded palettes should load")
+        .get_palette("spectra6")
+        .expect("spectra6 palette should exist")
+        .colors
+}
+
+fn bench_distance(c: &mut Criterion) {
+    let palette = spectra6();
+    let colors = gradient_colors(256, 256);
+
+    for (name, metric) in [
+        ("euclidean", DistanceMetric::Euclidean),
+        ("redmean", DistanceMetric::RedMean),
+        ("cielab", DistanceMetric::CieLab),
+        ("oklab", DistanceMetric::Oklab),
+    ] {
+        let mut group = c.benchmark_group(format!("closest_256x256_{}", name));
+
+        group.bench_function("per_pixel", |b| {
+            b.iter(|| {
+                colors
+                    .iter()
+                    .map(|color| {
+                        find_closest_color_with_metric(black_box(color), &palette, metric)
+                            .map(|(idx, _)| idx)
+                    })
+                    .collect::<Vec<_>>()
+            })
+        });
+
+        group.bench_function("batch", |b| {
+            b.iter(|| batch_find_closest_colors(black_box(&colors), &palette, metric))
+        });
+
+        group.finish();
+    }
+}
+
+fn bench_error_diffusion(c: &mut Criterion) {
+    let palette = spectra6();
+    let (width, height) = (1600, 1200);
+    let source: Vec<u8> = gradient_colors(width, height)
+        .iter()
+        .flat_map(|color| color.0)
+        .collect();
+
+    // The colors error diffusion actually matches: each pixel's value after
+    // receiving error, i.e. its output color plus its quantization error
+    let mut dithered = source.clone();
+    let errors = error_diffusion::apply_error_diffusion_collecting_errors(
+        &mut dithered,
+        width,
+        height,
+        &palette,
+        ErrorDiffusionKernel::FloydSteinberg,
+        false,
+    );
+    let diffused: Vec<Rgb> = dithered
+        .chunks_exact(3)
+        .zip(&errors)
+        .map(|(pixel, error)| {
+            Rgb(std::array::from_fn(|c| {
+                (pixel[c] as f64 + error[c]).round().clamp(0.0, 255.0) as u8
+            }))
+        })
+        .collect();
+
+    let mut cache = ColorMatchCache::new(palette.len());
+    for color in &diffused {
+        cache.find_or_compute(color, &palette, DistanceMetric::Euclidean);
+    }
+    let hit_rate = 1.0 - cache.len() as f64 / diffused.len() as f64;
+    println!(
+        "Cache hit rate on error-diffused colors: {:.2}%",
+        hit_rate * 100.0
+    );
+
+    let mut group = c.benchmark_group("error_diffusion_1600x1200_euclidean");
+    group.sample_size(10);
+
+    group.bench_function("floyd_steinberg", |b| {
+        b.iter(|| {
+            let mut buffer = source.clone();
+            error_diffusion::apply_error_diffusion(
+                black_box(&mut buffer),
+                width,
+                height,
+                &palette,
+                ErrorDiffusionKernel::FloydSteinberg,
+                false,
+            );
+            buffer
+        })
+    });
+
+    group.bench_function("lookups_uncached", |b| {
+        b.iter(|| {
+            for color in &diffused {
+                black_box(find_closest_color_with_metric(
+                    black_box(color),
+                    &palette,
+                    DistanceMetric::Euclidean,
+                ));
+            }
+        })
+    });
+
+    group.bench_function("lookups_cached", |b| {
+        b.iter(|| {
+            let mut cache = ColorMatchCache::new(palette.len());
+            for color in &diffused {
+                black_box(cache.find_or_compute(
+                    black_box(color),
+                    &palette,
+                    DistanceMetric::Euclidean,
+                ));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_distance, bench_error_diffusion);
+criterion_main!(benches);
@@ -0,0 +1,98 @@
+//! Benchmarks for ordered dithering threshold map reuse
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use epd_dither::color::Palette;
+use epd_dither::dither::algorithms::ordered;
+use epd_dither::dither::engine::{self, BayerCache};
+use epd_dither::dither::{DitherOptions, DitheringAlgorithm};
+use image::RgbImage;
+use std::hint::black_box;
+
+const CALLS: usize = 1000;
+
+fn bench_bayer_maps(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bayer_maps_1000_calls");
+
+    group.bench_function("regenerate", |b| {
+        b.iter(|| {
+            for _ in 0..CALLS {
+                black_box(ordered::create_bayer_matrix(black_box(4), black_box(4)));
+            }
+        })
+    });
+
+    group.bench_function("cached", |b| {
+        let mut cache = BayerCache::new();
+        b.iter(|| {
+            for _ in 0..CALLS {
+                black_box(cache.get_or_create(black_box(4), black_box(4)));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_ordered_dither(c: &mut Criterion) {
+    let source = RgbImage::from_fn(32, 32, |x, y| {
+        image::Rgb([(x * 8) as u8, (y * 8) as u8, 128])
+    });
+    let options = DitherOptions {
+        algorithm: DitheringAlgorithm::Ordered {
+            width: 4,
+            height: 4,
+        },
+        palette: Palette::default(),
+        serpentine: false,
+        ..Default::default()
+    };
+    engine::precompute_bayer_maps(&[(4, 4)]);
+
+    c.bench_function("ordered_dither_32x32_1000_calls", |b| {
+        b.iter(|| {
+            for _ in 0..CALLS {
+                let mut img = source.clone();
+                engine::dither_image(black_box(&mut img), &options).unwrap();
+            }
+        })
+    });
+}
+
+fn bench_bayer_lookup(c: &mut Criterion) {
+    let (width, height) = (1920, 1080);
+    let source = RgbImage::from_fn(width, height, |x, y| {
+        image::Rgb([(x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8])
+    });
+    let matrix = ordered::create_bayer_matrix(8, 8);
+    let lookup = engine::precompute_bayer_lookup(&matrix, 64.0);
+
+    let mut group = c.benchmark_group("ordered_adjust_1920x1080");
+
+    group.bench_function("matrix", |b| {
+        b.iter(|| {
+            for (x, y, pixel) in source.enumerate_pixels() {
+                let (x, y) = (x as usize, y as usize);
+                black_box(ordered::apply_ordered_dither(pixel.0, x, y, &matrix, 64.0));
+            }
+        })
+    });
+
+    group.bench_function("lookup", |b| {
+        b.iter(|| {
+            for (x, y, pixel) in source.enumerate_pixels() {
+                let (x, y) = (x as usize, y as usize);
+                black_box(engine::apply_ordered_dither_fast(pixel.0, x, y, &lookup, 8));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_bayer_maps,
+    bench_ordered_dither,
+    bench_bayer_lookup
+);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+//! Benchmarks for quantization-only dithering (scalar vs SIMD)
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use epd_dither::color::{palette::PaletteManager, Rgb};
+use epd_dither::dither::algorithms::quantize;
+use std::hint::black_box;
+
+fn gradient_buffer(width: usize, height: usize) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            buffer.extend_from_slice(&[
+                (x * 255 / width) as u8,
+                (y * 255 / height) as u8,
+                ((x + y) * 255 / (width + height)) as u8,
+            ]);
+        }
+    }
+    buffer
+}
+
+fn spectra6() -> Vec<Rgb> {
+    PaletteManager::new()
+        .expect("Embedded palettes should load")
+        .get_palette("spectra6")
+        .expect("spectra6 palette should exist")
+        .colors
+}
+
+fn bench_quantize(c: &mut Criterion) {
+    let palette = spectra6();
+    let source = gradient_buffer(1024, 1024);
+
+    let mut group = c.benchmark_group("quantize_1024x1024");
+
+    group.bench_function("scalar", |b| {
+        b.iter(|| {
+            let mut buffer = source.clone();
+            quantize::quantize_buffer(black_box(&mut buffer), &palette);
+            buffer
+        })
+    });
+
+    #[cfg(feature = "simd")]
+    group.bench_function("simd", |b| {
+        b.iter(|| {
+            let mut buffer = source.clone();
+            quantize::quantize_buffer_simd(black_box(&mut buffer), &palette);
+            buffer
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_quantize);
+criterion_main!(benches);
@@ -0,0 +1,676 @@
+//! Image adjustments applied before dithering
+
+use super::{convert::rgb_to_luma, Rgb};
+use crate::dither::engine::Region;
+use anyhow::Result;
+use image::{GrayImage, Luma, RgbImage};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Reduce each channel to `levels` evenly spaced values
+///
+/// Each value `v` is mapped to `round(v / step) * step` with
+/// `step = 255 / (levels - 1)`, so `levels = 2` leaves only 0 and 255.
+pub fn posterize(img: &mut RgbImage, levels: u8) -> Result<()> {
+    if levels < 2 {
+        anyhow::bail!("Posterize levels must be at least 2, got {}", levels);
+    }
+
+    let step = 255.0 / (levels - 1) as f64;
+    for value in img.iter_mut() {
+        *value = ((*value as f64 / step).round() * step).round() as u8;
+    }
+
+    Ok(())
+}
+
+/// Remove a color cast assuming the scene averages to gray
+///
+/// Each channel is scaled by `mean_gray / mean_channel`, where `mean_gray`
+/// is the average of the three channel means. Channels with a zero mean
+/// are left unchanged.
+pub fn auto_white_balance_gray_world(img: &mut RgbImage) {
+    let pixel_count = img.width() as f64 * img.height() as f64;
+    if pixel_count == 0.0 {
+        return;
+    }
+
+    let mut sums = [0.0f64; 3];
+    for pixel in img.pixels() {
+        for (sum, &value) in sums.iter_mut().zip(pixel.0.iter()) {
+            *sum += value as f64;
+        }
+    }
+
+    let means = sums.map(|sum| sum / pixel_count);
+    let mean_gray = means.iter().sum::<f64>() / 3.0;
+    scale_channels(
+        img,
+        means.map(|mean| if mean > 0.0 { mean_gray / mean } else { 1.0 }),
+    );
+}
+
+/// Scale channels so the brightest pixel becomes pure white
+///
+/// The brightest pixel is the one with the largest channel sum. Channels
+/// that are zero in that pixel are left unchanged.
+pub fn auto_white_balance_max_white(img: &mut RgbImage) {
+    let Some(brightest) = img
+        .pixels()
+        .max_by_key(|p| p[0] as u32 + p[1] as u32 + p[2] as u32)
+        .copied()
+    else {
+        return;
+    };
+
+    scale_channels(
+        img,
+        brightest
+            .0
+            .map(|value| if value > 0 { 255.0 / value as f64 } else { 1.0 }),
+    );
+}
+
+/// Map every channel value through a 256-entry tone curve
+pub fn apply_curves(img: &mut RgbImage, lut: &[u8; 256]) {
+    for value in img.iter_mut() {
+        *value = lut[*value as usize];
+    }
+}
+
+/// Tone curve that leaves every value unchanged
+pub fn identity_lut() -> [u8; 256] {
+    std::array::from_fn(|i| i as u8)
+}
+
+/// Power-law tone curve `255 * (v / 255)^gamma`
+///
+/// `gamma > 1` darkens midtones and `gamma < 1` brightens them.
+/// Non-positive values give the identity curve.
+pub fn gamma_lut(gamma: f64) -> [u8; 256] {
+    if gamma <= 0.0 {
+        return identity_lut();
+    }
+    std::array::from_fn(|i| ((i as f64 / 255.0).powf(gamma) * 255.0).round() as u8)
+}
+
+/// Sigmoid contrast curve that keeps black, mid-gray and white fixed
+///
+/// The logistic gain is `10 * strength`, rescaled so 0 and 255 map to
+/// themselves. Larger strengths steepen the midtones; non-positive
+/// strengths give the identity curve.
+pub fn s_curve_lut(strength: f64) -> [u8; 256] {
+    if strength <= 0.0 {
+        return identity_lut();
+    }
+
+    let gain = 10.0 * strength;
+    let sigmoid = |x: f64| 1.0 / (1.0 + (-gain * (x - 0.5)).exp());
+    let (low, high) = (sigmoid(0.0), sigmoid(1.0));
+    std::array::from_fn(|i| {
+        let y = (sigmoid(i as f64 / 255.0) - low) / (high - low);
+        (y * 255.0).round().clamp(0.0, 255.0) as u8
+    })
+}
+
+/// Add Gaussian noise to every channel to break up flat areas
+///
+/// Error diffusion produces regular artifacts on perfectly flat fills
+/// (screenshots, graphics); a little noise with standard deviation
+/// `sigma` gives more natural patterns. The same `seed` always produces
+/// the same noise.
+pub fn dequantize_noise(img: &mut RgbImage, sigma: f64, seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    for value in img.iter_mut() {
+        // Box-Muller transform; 1 - u keeps the logarithm's argument in (0, 1]
+        let u1: f64 = 1.0 - rng.gen::<f64>();
+        let u2: f64 = rng.gen();
+        let noise = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos() * sigma;
+        *value = (*value as f64 + noise).round().clamp(0.0, 255.0) as u8;
+    }
+}
+
+/// Darken the image towards its corners
+///
+/// `radius` (0–1) is the fraction of the half-diagonal where darkening
+/// starts and `strength` (0–1) the darkening at the corners. Each pixel is
+/// scaled by `1 - strength * smooth_step(radius, 1, distance)`, where
+/// `distance` runs from 0 at the center to 1 at the corners.
+pub fn apply_vignette(img: &mut RgbImage, strength: f64, radius: f64) {
+    let strength = strength.clamp(0.0, 1.0);
+    let radius = radius.clamp(0.0, 1.0);
+    let (center_x, center_y) = (img.width() as f64 / 2.0, img.height() as f64 / 2.0);
+    let half_diagonal = center_x.hypot(center_y);
+    if strength == 0.0 || radius >= 1.0 || half_diagonal == 0.0 {
+        return;
+    }
+
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let distance = (x as f64 + 0.5 - center_x).hypot(y as f64 + 0.5 - center_y) / half_diagonal;
+        let factor = 1.0 - strength * smooth_step(radius, 1.0, distance);
+        for value in pixel.0.iter_mut() {
+            *value = (*value as f64 * factor).round() as u8;
+        }
+    }
+}
+
+/// Hermite interpolation from 0 at `edge0` to 1 at `edge1`
+fn smooth_step(edge0: f64, edge1: f64, x: f64) -> f64 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Apply `f` to every pixel inside `region`
+///
+/// The part of the region outside the image is ignored.
+pub fn apply_adjustment_to_region<F: Fn(&mut Rgb)>(img: &mut RgbImage, region: Region, f: F) {
+    let x_end = region.x.saturating_add(region.width).min(img.width());
+    let y_end = region.y.saturating_add(region.height).min(img.height());
+
+    for y in region.y..y_end {
+        for x in region.x..x_end {
+            let pixel = img.get_pixel_mut(x, y);
+            let mut color = Rgb(pixel.0);
+            f(&mut color);
+            pixel.0 = color.0;
+        }
+    }
+}
+
+/// Invert pixel values (`255 - v`) inside `region` only
+pub fn invert_colors_in_region(img: &mut RgbImage, region: Region) {
+    apply_adjustment_to_region(img, region, |color| {
+        color.0 = color.0.map(|v| 255 - v);
+    });
+}
+
+/// Mask of the pixels whose luma is above `threshold`
+///
+/// Brighter pixels are 255 and the rest 0, ready for
+/// [`crate::dither::engine::dither_with_mask`].
+pub fn luminance_threshold_mask(img: &RgbImage, threshold: u8) -> GrayImage {
+    GrayImage::from_fn(img.width(), img.height(), |x, y| {
+        let luma = rgb_to_luma(&Rgb(img.get_pixel(x, y).0));
+        Luma([if luma > threshold { 255 } else { 0 }])
+    })
+}
+
+/// Sobel gradient magnitude of the luma channel, normalized to 0–255
+///
+/// Edges are bright and flat areas dark; border pixels repeat their
+/// nearest neighbors. An image without any gradient gives an all-black
+/// mask. Invert it to make [`crate::dither::engine::dither_with_mask`]
+/// dither flat areas more strongly than edges.
+pub fn gradient_magnitude_mask(img: &RgbImage) -> GrayImage {
+    let (width, height) = img.dimensions();
+    let luma: Vec<f64> = img
+        .pixels()
+        .map(|p| rgb_to_luma(&Rgb(p.0)) as f64)
+        .collect();
+    let at = |x: i64, y: i64| {
+        let x = x.clamp(0, width as i64 - 1) as u32;
+        let y = y.clamp(0, height as i64 - 1) as u32;
+        luma[(y * width + x) as usize]
+    };
+
+    let mut magnitudes = Vec::with_capacity(luma.len());
+    for y in 0..height as i64 {
+        for x in 0..width as i64 {
+            let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x - 1, y)
+                - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x, y - 1)
+                - at(x + 1, y - 1);
+            magnitudes.push((gx * gx + gy * gy).sqrt());
+        }
+    }
+
+    let max = magnitudes.iter().copied().fold(0.0, f64::max);
+    let scale = if max > 0.0 { 255.0 / max } else { 0.0 };
+    let values = magnitudes
+        .iter()
+        .map(|m| (m * scale).round() as u8)
+        .collect();
+    GrayImage::from_raw(width, height, values).expect("Mask size matches the image")
+}
+
+/// Local luma variance, normalized to 0–255
+///
+/// Each pixel gets the variance over the `window_size`×`window_size`
+/// window centered on it (clipped to the image; 0 counts as 1). Busy areas
+/// are bright and flat ones dark, so passing the result to
+/// [`crate::dither::engine::dither_with_mask`] keeps dithering noise out
+/// of smooth regions. An image without any variance gives an all-black
+/// mask.
+pub fn compute_variance_mask(img: &RgbImage, window_size: u32) -> GrayImage {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let before = window_size.max(1) as usize / 2;
+    let after = (window_size.max(1) as usize - 1) / 2;
+
+    // Summed-area tables of luma and luma², one row and column of padding
+    let stride = width + 1;
+    let mut sums = vec![0.0; stride * (height + 1)];
+    let mut squares = vec![0.0; stride * (height + 1)];
+    for (i, pixel) in img.pixels().enumerate() {
+        let (x, y) = (i % width, i / width);
+        let luma = rgb_to_luma(&Rgb(pixel.0)) as f64;
+        let (idx, up, left, diagonal) = (
+            (y + 1) * stride + x + 1,
+            y * stride + x + 1,
+            (y + 1) * stride + x,
+            y * stride + x,
+        );
+        sums[idx] = luma + sums[up] + sums[left] - sums[diagonal];
+        squares[idx] = luma * luma + squares[up] + squares[left] - squares[diagonal];
+    }
+
+    let mut variances = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let (y0, y1) = (y.saturating_sub(before), (y + after + 1).min(height));
+        for x in 0..width {
+            let (x0, x1) = (x.saturating_sub(before), (x + after + 1).min(width));
+            let area = |table: &[f64]| {
+                table[y1 * stride + x1] - table[y0 * stride + x1] - table[y1 * stride + x0]
+                    + table[y0 * stride + x0]
+            };
+            let count = ((x1 - x0) * (y1 - y0)) as f64;
+            let mean = area(&sums) / count;
+            variances.push((area(&squares) / count - mean * mean).max(0.0));
+        }
+    }
+
+    let max = variances.iter().copied().fold(0.0, f64::max);
+    let scale = if max > 0.0 { 255.0 / max } else { 0.0 };
+    let values = variances
+        .iter()
+        .map(|v| (v * scale).round() as u8)
+        .collect();
+    GrayImage::from_raw(width as u32, height as u32, values).expect("Mask size matches the image")
+}
+
+fn scale_channels(img: &mut RgbImage, factors: [f64; 3]) {
+    for pixel in img.pixels_mut() {
+        for (value, factor) in pixel.0.iter_mut().zip(factors) {
+            *value = (*value as f64 * factor).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+/// Color matrix that leaves every pixel unchanged
+pub const IDENTITY_MATRIX: [[f64; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// Classic sepia tone matrix
+pub const SEPIA_MATRIX: [[f64; 3]; 3] = [
+    [0.393, 0.769, 0.189],
+    [0.349, 0.686, 0.168],
+    [0.272, 0.534, 0.131],
+];
+
+/// Gray with the same luma weights as [`super::convert::rgb_to_luma`]
+pub const DESATURATE_MATRIX: [[f64; 3]; 3] = [
+    [0.299, 0.587, 0.114],
+    [0.299, 0.587, 0.114],
+    [0.299, 0.587, 0.114],
+];
+
+/// Shift colors towards orange by boosting red and cutting blue
+pub const WARM_FILTER_MATRIX: [[f64; 3]; 3] = [[1.1, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 0.9]];
+
+/// Shift colors towards blue by cutting red and boosting blue
+pub const COOL_FILTER_MATRIX: [[f64; 3]; 3] = [[0.9, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.1]];
+
+/// Multiply every pixel's `[r, g, b]` column vector by `matrix`
+///
+/// Row `i` of the matrix gives the weights of the input channels in output
+/// channel `i`. Results are rounded and clamped to 0–255.
+pub fn apply_color_matrix(img: &mut RgbImage, matrix: [[f64; 3]; 3]) {
+    for pixel in img.pixels_mut() {
+        let [r, g, b] = pixel.0.map(f64::from);
+        pixel.0 = matrix.map(|row| {
+            (row[0] * r + row[1] * g + row[2] * b)
+                .round()
+                .clamp(0.0, 255.0) as u8
+        });
+    }
+}
+
+/// Convert an sRGB image into a display's color space
+///
+/// The ICC profile at `profile_path` describes the display, so the result
+/// holds the device values that reproduce the original colors on it.
+#[cfg(feature = "icc")]
+pub fn apply_display_icc(img: &mut RgbImage, profile_path: &std::path::Path) -> Result<()> {
+    use super::palette::IccProfile;
+
+    let display = IccProfile::from_file(profile_path)?;
+    IccProfile::srgb()?.transform_rgb(&display, img.as_mut())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn gradient() -> RgbImage {
+        RgbImage::from_fn(256, 1, |x, _| image::Rgb([x as u8, x as u8, 255 - x as u8]))
+    }
+
+    fn distinct_values(img: &RgbImage) -> BTreeSet<u8> {
+        img.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_posterize_two_levels() {
+        let mut img = gradient();
+        posterize(&mut img, 2).unwrap();
+        assert_eq!(distinct_values(&img), BTreeSet::from([0, 255]));
+    }
+
+    #[test]
+    fn test_posterize_four_levels() {
+        let mut img = gradient();
+        posterize(&mut img, 4).unwrap();
+        assert_eq!(distinct_values(&img), BTreeSet::from([0, 85, 170, 255]));
+    }
+
+    #[test]
+    fn test_posterize_invalid_levels() {
+        let mut img = gradient();
+        assert!(posterize(&mut img, 1).is_err());
+        assert!(posterize(&mut img, 0).is_err());
+    }
+
+    fn channel_means(img: &RgbImage) -> [f64; 3] {
+        let count = (img.width() * img.height()) as f64;
+        let mut sums = [0.0; 3];
+        for pixel in img.pixels() {
+            for c in 0..3 {
+                sums[c] += pixel[c] as f64;
+            }
+        }
+        sums.map(|sum| sum / count)
+    }
+
+    #[test]
+    fn test_gray_world() {
+        // Mean is exactly [100, 200, 50]
+        let mut img = RgbImage::from_fn(4, 4, |x, _| {
+            if x % 2 == 0 {
+                image::Rgb([80, 180, 40])
+            } else {
+                image::Rgb([120, 220, 60])
+            }
+        });
+        assert_eq!(channel_means(&img), [100.0, 200.0, 50.0]);
+
+        auto_white_balance_gray_world(&mut img);
+        let [r, g, b] = channel_means(&img);
+        assert!(
+            (r - g).abs() < 2.0 && (g - b).abs() < 2.0,
+            "{} {} {}",
+            r,
+            g,
+            b
+        );
+    }
+
+    #[test]
+    fn test_max_white() {
+        let mut img = RgbImage::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                image::Rgb([200, 240, 120])
+            } else {
+                image::Rgb([100, 120, 60])
+            }
+        });
+
+        auto_white_balance_max_white(&mut img);
+        assert_eq!(img.get_pixel(0, 0).0, [255, 255, 255]);
+        assert!(img.get_pixel(1, 0).0.iter().all(|v| v.abs_diff(128) <= 1));
+    }
+
+    fn spread(img: &RgbImage) -> u8 {
+        img.iter().max().unwrap() - img.iter().min().unwrap()
+    }
+
+    #[test]
+    fn test_identity_curve() {
+        let mut img = gradient();
+        apply_curves(&mut img, &identity_lut());
+        assert_eq!(img, gradient());
+        assert_eq!(gamma_lut(1.0), identity_lut());
+        assert_eq!(s_curve_lut(0.0), identity_lut());
+    }
+
+    #[test]
+    fn test_s_curve_increases_contrast() {
+        let midtones = || RgbImage::from_fn(128, 1, |x, _| image::Rgb([64 + x as u8; 3]));
+        let mut img = midtones();
+        let lut = s_curve_lut(1.0);
+        apply_curves(&mut img, &lut);
+
+        assert!(spread(&img) > spread(&midtones()));
+        assert_eq!((lut[0], lut[255]), (0, 255));
+        assert!(lut.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn test_gamma_lut() {
+        assert!(gamma_lut(2.2)[128] < 128);
+        assert!(gamma_lut(0.5)[128] > 128);
+    }
+
+    #[test]
+    fn test_dequantize_noise() {
+        let flat = RgbImage::from_pixel(32, 32, image::Rgb([128, 128, 128]));
+
+        let mut noisy = flat.clone();
+        dequantize_noise(&mut noisy, 10.0, 42);
+        let mean = noisy.iter().map(|&v| v as f64).sum::<f64>() / noisy.len() as f64;
+        let variance = noisy
+            .iter()
+            .map(|&v| (v as f64 - mean).powi(2))
+            .sum::<f64>()
+            / noisy.len() as f64;
+        assert!(
+            variance.sqrt() > 5.0,
+            "Standard deviation too small: {}",
+            variance.sqrt()
+        );
+
+        let mut again = flat.clone();
+        dequantize_noise(&mut again, 10.0, 42);
+        assert_eq!(noisy, again);
+
+        let mut other_seed = flat;
+        dequantize_noise(&mut other_seed, 10.0, 7);
+        assert_ne!(noisy, other_seed);
+    }
+
+    #[test]
+    fn test_invert_colors_in_region() {
+        let original = RgbImage::from_fn(10, 8, |x, y| image::Rgb([x as u8 * 20, y as u8 * 30, 7]));
+        let region = Region::new(2, 3, 4, 2);
+        let mut img = original.clone();
+        invert_colors_in_region(&mut img, region);
+
+        for (x, y, pixel) in img.enumerate_pixels() {
+            let source = original.get_pixel(x, y).0;
+            if region.contains(x, y) {
+                assert_eq!(
+                    pixel.0,
+                    source.map(|v| 255 - v),
+                    "({}, {}) not inverted",
+                    x,
+                    y
+                );
+            } else {
+                assert_eq!(pixel.0, source, "({}, {}) changed", x, y);
+            }
+        }
+
+        // Corners of the region are inside, the pixels just past its edges are not
+        assert_ne!(img.get_pixel(2, 3), original.get_pixel(2, 3));
+        assert_ne!(img.get_pixel(5, 4), original.get_pixel(5, 4));
+        assert_eq!(img.get_pixel(6, 4), original.get_pixel(6, 4));
+        assert_eq!(img.get_pixel(5, 5), original.get_pixel(5, 5));
+    }
+
+    #[test]
+    fn test_region_adjustment_clips_to_image() {
+        let mut img = RgbImage::from_pixel(4, 4, image::Rgb([0, 0, 0]));
+        apply_adjustment_to_region(&mut img, Region::new(2, 2, 10, 10), |c| {
+            *c = Rgb::new(9, 9, 9)
+        });
+
+        assert_eq!(img.get_pixel(3, 3).0, [9, 9, 9]);
+        assert_eq!(img.get_pixel(1, 3).0, [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_apply_vignette() {
+        let mut img = RgbImage::from_pixel(101, 61, image::Rgb([200, 200, 200]));
+        apply_vignette(&mut img, 0.8, 0.3);
+
+        assert_eq!(img.get_pixel(50, 30).0, [200, 200, 200]);
+        for (x, y) in [(0, 0), (100, 0), (0, 60), (100, 60)] {
+            let value = img.get_pixel(x, y)[0] as f64;
+            assert!(
+                value <= 200.0 * (1.0 - 0.8 * 0.9),
+                "corner ({}, {}) is {}",
+                x,
+                y,
+                value
+            );
+        }
+
+        // Darkening increases monotonically towards the corner
+        let row: Vec<u8> = (50..101).map(|x| img.get_pixel(x, 60)[0]).collect();
+        assert!(row.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+
+    fn split_image() -> RgbImage {
+        // Left half dark gray, right half light gray
+        RgbImage::from_fn(20, 10, |x, _| {
+            if x < 10 {
+                image::Rgb([40, 40, 40])
+            } else {
+                image::Rgb([220, 220, 220])
+            }
+        })
+    }
+
+    #[test]
+    fn test_luminance_threshold_mask() {
+        let img = split_image();
+        let mask = luminance_threshold_mask(&img, 128);
+
+        assert_eq!(mask.dimensions(), img.dimensions());
+        for (x, _, value) in mask.enumerate_pixels() {
+            assert_eq!(value[0], if x < 10 { 0 } else { 255 }, "x = {}", x);
+        }
+
+        // Values equal to the threshold are not brighter than it
+        assert!(luminance_threshold_mask(&img, 220).iter().all(|&v| v == 0));
+        assert!(luminance_threshold_mask(&img, 39).iter().all(|&v| v == 255));
+    }
+
+    #[test]
+    fn test_gradient_magnitude_mask() {
+        let mask = gradient_magnitude_mask(&split_image());
+
+        for (x, _, value) in mask.enumerate_pixels() {
+            match x {
+                9 | 10 => assert_eq!(value[0], 255, "x = {}", x),
+                _ => assert_eq!(value[0], 0, "x = {}", x),
+            }
+        }
+
+        let flat = RgbImage::from_pixel(8, 8, image::Rgb([100, 100, 100]));
+        assert!(gradient_magnitude_mask(&flat).iter().all(|&v| v == 0));
+    }
+
+    #[cfg(feature = "icc")]
+    #[test]
+    fn test_apply_display_icc_srgb_is_identity() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let srgb = crate::color::palette::IccProfile::srgb().unwrap();
+        std::fs::write(file.path(), srgb.0).unwrap();
+
+        let original = RgbImage::from_fn(16, 4, |x, y| {
+            image::Rgb([(x * 16) as u8, (y * 60) as u8, 128])
+        });
+        let mut img = original.clone();
+        apply_display_icc(&mut img, file.path()).unwrap();
+        assert!(img
+            .iter()
+            .zip(original.iter())
+            .all(|(a, b)| a.abs_diff(*b) <= 1));
+    }
+
+    #[test]
+    fn test_compute_variance_mask_flat_center() {
+        // Flat gray center, noisy 10-pixel border
+        let mut rng = StdRng::seed_from_u64(1);
+        let img = RgbImage::from_fn(50, 50, |x, y| {
+            if (10..40).contains(&x) && (10..40).contains(&y) {
+                image::Rgb([128; 3])
+            } else {
+                image::Rgb([rng.gen(); 3])
+            }
+        });
+        let mask = compute_variance_mask(&img, 5);
+
+        assert_eq!(mask.dimensions(), (50, 50));
+        assert_eq!(mask.get_pixel(25, 25)[0], 0);
+        let mask = &mask;
+        let block_mean = |from: u32, to: u32| {
+            let values: Vec<f64> = (from..to)
+                .flat_map(|y| (from..to).map(move |x| mask.get_pixel(x, y)[0] as f64))
+                .collect();
+            values.iter().sum::<f64>() / values.len() as f64
+        };
+        // The window reaches 2 pixels, so the middle stays clear of the border
+        assert_eq!(block_mean(15, 35), 0.0);
+        assert!(block_mean(0, 8) > 50.0, "{}", block_mean(0, 8));
+        assert!(compute_variance_mask(&RgbImage::new(4, 4), 0)
+            .iter()
+            .all(|&v| v == 0));
+    }
+
+    #[test]
+    fn test_apply_color_matrix_identity() {
+        let original = gradient();
+        let mut img = original.clone();
+        apply_color_matrix(&mut img, IDENTITY_MATRIX);
+        assert_eq!(img, original);
+    }
+
+    #[test]
+    fn test_apply_color_matrix_sepia() {
+        // Expected values are the sepia formula
+        // r' = .393r + .769g + .189b, g' = .349r + .686g + .168b,
+        // b' = .272r + .534g + .131b, rounded and clamped
+        let cases = [
+            ([50, 100, 150], [125, 111, 87]),
+            ([200, 100, 50], [165, 147, 114]),
+            ([255, 255, 255], [255, 255, 239]),
+            ([0, 0, 0], [0, 0, 0]),
+        ];
+        let mut img = RgbImage::from_fn(cases.len() as u32, 1, |x, _| {
+            image::Rgb(cases[x as usize].0)
+        });
+        apply_color_matrix(&mut img, SEPIA_MATRIX);
+
+        for (x, (input, expected)) in cases.iter().enumerate() {
+            assert_eq!(
+                img.get_pixel(x as u32, 0).0,
+                *expected,
+                "sepia of {:?}",
+                input
+            );
+        }
+    }
+}
@@ -21,7 +21,7 @@ pub fn hex_to_rgb(hex: &str) -> Result<[u8; 3]> {
     // Handle shorthand notation (#RGB -> #RRGGBB)
     let expanded = if hex.len() == 3 {
         hex.chars()
-            .flat_map(|c| std::iter::repeat(c).take(2))
+            .flat_map(|c| std::iter::repeat_n(c, 2))
             .collect::<String>()
     } else {
         hex.to_string()
@@ -46,6 +46,283 @@ pub fn rgb_to_hex(rgb: &Rgb) -> String {
     format!("#{:02X}{:02X}{:02X}", rgb.r(), rgb.g(), rgb.b())
 }
 
+/// Luma (perceived brightness) of a color using Rec. 601 weights
+pub fn rgb_to_luma(rgb: &Rgb) -> u8 {
+    ((rgb.r() as u32 * 299 + rgb.g() as u32 * 587 + rgb.b() as u32 * 114) / 1000) as u8
+}
+
+/// Convert an sRGB color to CIE L*a*b* (D65 white point)
+///
+/// Returns `[L, a, b]` with `L` in 0–100.
+pub fn rgb_to_lab(rgb: &Rgb) -> [f64; 3] {
+    let [r, g, b] = rgb.0.map(srgb_to_linear);
+
+    // Linear sRGB -> XYZ, normalized to the D65 reference white
+    let x = (0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / 0.95047;
+    let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+    let z = (0.0193339 * r + 0.1191920 * g + 0.9503041 * b) / 1.08883;
+
+    let f = |t: f64| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// Convert a CIE L*a*b* color (D65 white point) back to sRGB
+///
+/// Out-of-gamut values are clamped to the 0–255 range.
+pub fn lab_to_rgb(lab: [f64; 3]) -> Rgb {
+    let fy = (lab[0] + 16.0) / 116.0;
+    let fx = fy + lab[1] / 500.0;
+    let fz = fy - lab[2] / 200.0;
+
+    let f_inv = |t: f64| {
+        if t > 6.0 / 29.0 {
+            t * t * t
+        } else {
+            (116.0 * t - 16.0) * 27.0 / 24389.0
+        }
+    };
+    let x = f_inv(fx) * 0.95047;
+    let y = f_inv(fy);
+    let z = f_inv(fz) * 1.08883;
+
+    // XYZ -> linear sRGB
+    let r = 3.2404542 * x - 1.5371385 * y - 0.4985314 * z;
+    let g = -0.9692660 * x + 1.8760108 * y + 0.0415560 * z;
+    let b = 0.0556434 * x - 0.2040259 * y + 1.0572252 * z;
+
+    Rgb([r, g, b].map(linear_to_srgb))
+}
+
+/// A color in the Oklab perceptual color space (Björn Ottosson, 2020)
+///
+/// `l` is perceived lightness (0 for black, 1 for white); `a` and `b` are
+/// the green–red and blue–yellow axes.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Oklab {
+    pub l: f64,
+    pub a: f64,
+    pub b: f64,
+}
+
+/// Convert an sRGB color to Oklab
+///
+/// Uses the published M1 (linear sRGB to LMS) and M2 (cone response to
+/// Lab) matrices.
+pub fn rgb_to_oklab(rgb: &Rgb) -> Oklab {
+    let [r, g, b] = rgb.0.map(srgb_to_linear);
+
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+    Oklab {
+        l: 0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        a: 1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        b: 0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    }
+}
+
+/// Convert an Oklab color back to sRGB
+///
+/// Out-of-gamut values are clamped to the 0–255 range.
+pub fn oklab_to_rgb(lab: &Oklab) -> Rgb {
+    let l = (lab.l + 0.3963377774 * lab.a + 0.2158037573 * lab.b).powi(3);
+    let m = (lab.l - 0.1055613458 * lab.a - 0.0638541728 * lab.b).powi(3);
+    let s = (lab.l - 0.0894841775 * lab.a - 1.2914855480 * lab.b).powi(3);
+
+    let r = 4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s;
+    let g = -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s;
+    let b = -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s;
+
+    Rgb([r, g, b].map(linear_to_srgb))
+}
+
+/// A color in BT.601 YUV, on the 0–255 scale of the RGB channels
+///
+/// `y` is luma (0–255); `u` and `v` are the blue and red color
+/// differences (about ±111 and ±157).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Yuv {
+    pub y: f64,
+    pub u: f64,
+    pub v: f64,
+}
+
+/// BT.601 luma weights of red and blue
+const YUV_WR: f64 = 0.299;
+const YUV_WB: f64 = 0.114;
+/// BT.601 scale of the U and V color differences
+const YUV_U_MAX: f64 = 0.436;
+const YUV_V_MAX: f64 = 0.615;
+
+/// Convert an RGB color to BT.601 YUV
+pub fn rgb_to_yuv(rgb: &Rgb) -> Yuv {
+    let [r, g, b] = rgb.0.map(f64::from);
+    let y = YUV_WR * r + (1.0 - YUV_WR - YUV_WB) * g + YUV_WB * b;
+
+    Yuv {
+        y,
+        u: YUV_U_MAX * (b - y) / (1.0 - YUV_WB),
+        v: YUV_V_MAX * (r - y) / (1.0 - YUV_WR),
+    }
+}
+
+/// Convert a BT.601 YUV color back to RGB
+///
+/// Out-of-gamut values are clamped to the 0–255 range.
+pub fn yuv_to_rgb(yuv: &Yuv) -> Rgb {
+    let r = yuv.y + yuv.v * (1.0 - YUV_WR) / YUV_V_MAX;
+    let b = yuv.y + yuv.u * (1.0 - YUV_WB) / YUV_U_MAX;
+    let g = (yuv.y - YUV_WR * r - YUV_WB * b) / (1.0 - YUV_WR - YUV_WB);
+    let encode = |v: f64| v.round().clamp(0.0, 255.0) as u8;
+
+    Rgb::new(encode(r), encode(g), encode(b))
+}
+
+/// Encode linear light (0–1) with the sRGB transfer function
+///
+/// Uses the piecewise IEC 61966-2-1 curve: linear below 0.0031308 and a
+/// 1/2.4 power with offset above, rather than a plain power law.
+pub fn apply_srgb_gamma(linear: f64) -> f64 {
+    if linear <= 0.0031308 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Decode an sRGB-encoded value (0–1) to linear light
+///
+/// Inverse of [`apply_srgb_gamma`].
+pub fn remove_srgb_gamma(srgb: f64) -> f64 {
+    if srgb <= 0.04045 {
+        srgb / 12.92
+    } else {
+        ((srgb + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Decode an 8-bit sRGB channel to linear light (0–1)
+fn srgb_to_linear(value: u8) -> f64 {
+    remove_srgb_gamma(value as f64 / 255.0)
+}
+
+/// Encode linear light as an 8-bit sRGB channel, clamping out-of-gamut values
+fn linear_to_srgb(v: f64) -> u8 {
+    (apply_srgb_gamma(v) * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+/// WCAG 2 relative luminance (0 for black, 1 for white)
+pub fn relative_luminance(rgb: &Rgb) -> f64 {
+    let [r, g, b] = rgb.0.map(srgb_to_linear);
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// WCAG 2 contrast ratio between two colors, from 1 (identical) to 21
+pub fn contrast_ratio(a: &Rgb, b: &Rgb) -> f64 {
+    let (la, lb) = (relative_luminance(a), relative_luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+/// Convert RGB to HSL
+///
+/// Returns `[h, s, l]` with hue in degrees (0–360) and saturation and
+/// lightness in 0–1. Grays have hue and saturation 0.
+pub fn rgb_to_hsl(rgb: &Rgb) -> [f64; 3] {
+    let (max, min, hue) = hue_components(rgb);
+    let lightness = (max + min) / 2.0;
+    let delta = max - min;
+    if delta == 0.0 {
+        return [0.0, 0.0, lightness];
+    }
+
+    [
+        hue,
+        delta / (1.0 - (2.0 * lightness - 1.0).abs()),
+        lightness,
+    ]
+}
+
+/// Convert HSL (hue in degrees, saturation and lightness in 0–1) to RGB
+///
+/// Hue wraps around 360°; saturation and lightness are clamped to 0–1.
+pub fn hsl_to_rgb(hsl: [f64; 3]) -> Rgb {
+    let saturation = hsl[1].clamp(0.0, 1.0);
+    let lightness = hsl[2].clamp(0.0, 1.0);
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+
+    chroma_to_rgb(hsl[0], chroma, lightness - chroma / 2.0)
+}
+
+/// Convert RGB to HSV
+///
+/// Returns `[h, s, v]` with hue in degrees (0–360) and saturation and
+/// value in 0–1. Grays have hue and saturation 0.
+pub fn rgb_to_hsv(rgb: &Rgb) -> [f64; 3] {
+    let (max, min, hue) = hue_components(rgb);
+    if max == min {
+        return [0.0, 0.0, max];
+    }
+
+    [hue, (max - min) / max, max]
+}
+
+/// Convert HSV (hue in degrees, saturation and value in 0–1) to RGB
+///
+/// Hue wraps around 360°; saturation and value are clamped to 0–1.
+pub fn hsv_to_rgb(hsv: [f64; 3]) -> Rgb {
+    let value = hsv[2].clamp(0.0, 1.0);
+    let chroma = value * hsv[1].clamp(0.0, 1.0);
+
+    chroma_to_rgb(hsv[0], chroma, value - chroma)
+}
+
+/// Maximum and minimum channel (0–1) and hue in degrees, shared by HSL and HSV
+fn hue_components(rgb: &Rgb) -> (f64, f64, f64) {
+    let [r, g, b] = rgb.0.map(|c| c as f64 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+
+    (max, min, hue)
+}
+
+/// RGB from hue (degrees), chroma and the offset `m` added to every channel
+fn chroma_to_rgb(hue: f64, chroma: f64, m: f64) -> Rgb {
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+
+    let (r, g, b) = match sector as u8 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let encode = |v: f64| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+
+    Rgb::new(encode(r), encode(g), encode(b))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -70,4 +347,167 @@ mod tests {
         assert_eq!(rgb_to_hex(&Rgb::new(255, 255, 255)), "#FFFFFF");
         assert_eq!(rgb_to_hex(&Rgb::new(255, 0, 0)), "#FF0000");
     }
+
+    #[test]
+    fn test_rgb_to_lab() {
+        let black = rgb_to_lab(&Rgb::new(0, 0, 0));
+        assert!(black.iter().all(|v| v.abs() < 1e-6));
+
+        let white = rgb_to_lab(&Rgb::new(255, 255, 255));
+        assert!((white[0] - 100.0).abs() < 0.01);
+        assert!(white[1].abs() < 0.01 && white[2].abs() < 0.01);
+
+        // Pure red has a strongly positive a* component
+        let red = rgb_to_lab(&Rgb::new(255, 0, 0));
+        assert!((red[0] - 53.24).abs() < 0.1);
+        assert!(red[1] > 75.0);
+    }
+
+    #[test]
+    fn test_lab_round_trip() {
+        for value in (0..=255).step_by(15) {
+            let color = Rgb::new(value, 255 - value, value / 2);
+            assert_eq!(lab_to_rgb(rgb_to_lab(&color)), color);
+        }
+    }
+
+    #[test]
+    fn test_hsl_hsv_primaries() {
+        let cases = [
+            (Rgb::new(255, 0, 0), [0.0, 1.0, 0.5], [0.0, 1.0, 1.0]),
+            (Rgb::new(0, 255, 0), [120.0, 1.0, 0.5], [120.0, 1.0, 1.0]),
+            (Rgb::new(0, 0, 255), [240.0, 1.0, 0.5], [240.0, 1.0, 1.0]),
+            (Rgb::new(255, 0, 255), [300.0, 1.0, 0.5], [300.0, 1.0, 1.0]),
+            (Rgb::new(255, 255, 255), [0.0, 0.0, 1.0], [0.0, 0.0, 1.0]),
+            (Rgb::new(0, 0, 0), [0.0, 0.0, 0.0], [0.0, 0.0, 0.0]),
+        ];
+
+        for (rgb, hsl, hsv) in cases {
+            assert_eq!(rgb_to_hsl(&rgb), hsl);
+            assert_eq!(rgb_to_hsv(&rgb), hsv);
+            assert_eq!(hsl_to_rgb(hsl), rgb);
+            assert_eq!(hsv_to_rgb(hsv), rgb);
+        }
+
+        // Hue wraps around
+        assert_eq!(hsl_to_rgb([-240.0, 1.0, 0.5]), Rgb::new(0, 255, 0));
+    }
+
+    #[test]
+    fn test_hsl_hsv_round_trip() {
+        for rgb in [
+            Rgb::new(12, 200, 99),
+            Rgb::new(250, 3, 180),
+            Rgb::new(90, 90, 91),
+        ] {
+            assert_eq!(hsl_to_rgb(rgb_to_hsl(&rgb)), rgb);
+            assert_eq!(hsv_to_rgb(rgb_to_hsv(&rgb)), rgb);
+        }
+    }
+
+    #[test]
+    fn test_oklab_reference_values() {
+        let close = |lab: Oklab, [l, a, b]: [f64; 3]| {
+            (lab.l - l).abs() < 0.001 && (lab.a - a).abs() < 0.001 && (lab.b - b).abs() < 0.001
+        };
+
+        assert!(close(
+            rgb_to_oklab(&Rgb::new(255, 255, 255)),
+            [1.0, 0.0, 0.0]
+        ));
+        assert!(close(rgb_to_oklab(&Rgb::new(0, 0, 0)), [0.0, 0.0, 0.0]));
+        assert!(close(
+            rgb_to_oklab(&Rgb::new(255, 0, 0)),
+            [0.62796, 0.22486, 0.12585]
+        ));
+        assert!(close(
+            rgb_to_oklab(&Rgb::new(0, 0, 255)),
+            [0.45201, -0.03246, -0.31153]
+        ));
+
+        // sRGB (0.5, 0.5, 0.5) has L = 0.5982; #808080 is slightly lighter at 128/255
+        assert!(close(
+            rgb_to_oklab(&Rgb::new(128, 128, 128)),
+            [0.59987, 0.0, 0.0]
+        ));
+    }
+
+    #[test]
+    fn test_oklab_round_trip() {
+        for rgb in [
+            Rgb::new(12, 200, 99),
+            Rgb::new(250, 3, 180),
+            Rgb::new(90, 90, 91),
+        ] {
+            assert_eq!(oklab_to_rgb(&rgb_to_oklab(&rgb)), rgb);
+        }
+    }
+
+    #[test]
+    fn test_contrast_ratio() {
+        let (black, white) = (Rgb::new(0, 0, 0), Rgb::new(255, 255, 255));
+        assert!((contrast_ratio(&black, &white) - 21.0).abs() < 1e-9);
+        assert_eq!(
+            contrast_ratio(&white, &black),
+            contrast_ratio(&black, &white)
+        );
+        assert_eq!(contrast_ratio(&white, &white), 1.0);
+
+        // #777 on white is the classic just-below-AA gray
+        let ratio = contrast_ratio(&Rgb::new(0x77, 0x77, 0x77), &white);
+        assert!((ratio - 4.48).abs() < 0.01, "ratio = {}", ratio);
+    }
+
+    #[test]
+    fn test_yuv_round_trip() {
+        let white = rgb_to_yuv(&Rgb::new(255, 255, 255));
+        assert!((white.y - 255.0).abs() < 1e-9 && white.u.abs() < 1e-9 && white.v.abs() < 1e-9);
+        let blue = rgb_to_yuv(&Rgb::new(0, 0, 255));
+        assert!((blue.u - 0.436 * 255.0).abs() < 1e-9, "u = {}", blue.u);
+
+        for rgb in [
+            Rgb::new(0, 0, 0),
+            Rgb::new(255, 0, 0),
+            Rgb::new(12, 200, 99),
+        ] {
+            assert_eq!(yuv_to_rgb(&rgb_to_yuv(&rgb)), rgb);
+        }
+
+        // YUV values only lose the rounding to 8-bit RGB
+        for yuv in [
+            Yuv {
+                y: 128.0,
+                u: 0.0,
+                v: 0.0,
+            },
+            Yuv {
+                y: 100.0,
+                u: 20.5,
+                v: -30.25,
+            },
+            Yuv {
+                y: 180.3,
+                u: -40.0,
+                v: 15.0,
+            },
+        ] {
+            let back = rgb_to_yuv(&yuv_to_rgb(&yuv));
+            for (a, b) in [(back.y, yuv.y), (back.u, yuv.u), (back.v, yuv.v)] {
+                assert!((a - b).abs() < 1.0, "{:?} became {:?}", yuv, back);
+            }
+        }
+    }
+
+    #[test]
+    fn test_srgb_gamma() {
+        for x in [0.0, 0.0031308, 0.5, 1.0] {
+            assert!(
+                (remove_srgb_gamma(apply_srgb_gamma(x)) - x).abs() < 1e-9,
+                "{}",
+                x
+            );
+        }
+        assert!((remove_srgb_gamma(0.5) - 0.2140).abs() < 1e-4);
+        assert!((apply_srgb_gamma(0.2140) - 0.5).abs() < 1e-3);
+    }
 }
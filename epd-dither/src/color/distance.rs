@@ -1,11 +1,29 @@
-//! Color distance calculations in RGB color space
+//! Color distance calculations and palette matching
 
-use super::Rgb;
+use super::{
+    convert::{rgb_to_lab, rgb_to_oklab, Oklab},
+    Rgb,
+};
+use std::collections::HashMap;
+
+/// Metric used to compare colors when matching against a palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DistanceMetric {
+    /// Straight Euclidean distance in RGB space
+    #[default]
+    Euclidean,
+    /// Weighted RGB distance approximating human perception ("redmean")
+    RedMean,
+    /// Euclidean distance in CIE L*a*b* space (CIE76)
+    CieLab,
+    /// Euclidean distance in Oklab space, with more uniform hues than CIE Lab
+    Oklab,
+}
 
 /// Calculate Euclidean distance between two colors in RGB space
 ///
 /// This is the same method used in the original JavaScript implementation.
-/// For better perceptual accuracy, consider using LAB color space in the future.
+/// For better perceptual accuracy, see [`DistanceMetric::CieLab`].
 ///
 /// # Examples
 /// ```
@@ -23,28 +41,304 @@ pub fn euclidean_distance(color1: &Rgb, color2: &Rgb) -> f64 {
     (r_diff * r_diff + g_diff * g_diff + b_diff * b_diff).sqrt()
 }
 
+/// Calculate the "redmean" weighted distance between two colors
+///
+/// Weights the red and blue channels by the average red level, a cheap
+/// approximation of perceptual difference that stays in RGB space.
+pub fn redmean_distance(color1: &Rgb, color2: &Rgb) -> f64 {
+    redmean_from_channels(
+        [color1.r() as f64, color1.g() as f64, color1.b() as f64],
+        [color2.r() as f64, color2.g() as f64, color2.b() as f64],
+    )
+}
+
+/// Calculate the CIE76 distance between two colors (Euclidean in Lab)
+pub fn cie_lab_distance(color1: &Rgb, color2: &Rgb) -> f64 {
+    lab_distance(&rgb_to_lab(color1), &rgb_to_lab(color2))
+}
+
+/// Calculate the Euclidean distance between two colors in Oklab space
+pub fn oklab_distance(color1: &Rgb, color2: &Rgb) -> f64 {
+    oklab_components_distance(&rgb_to_oklab(color1), &rgb_to_oklab(color2))
+}
+
+/// Calculate the distance between two colors using the given metric
+pub fn color_distance(color1: &Rgb, color2: &Rgb, metric: DistanceMetric) -> f64 {
+    match metric {
+        DistanceMetric::Euclidean => euclidean_distance(color1, color2),
+        DistanceMetric::RedMean => redmean_distance(color1, color2),
+        DistanceMetric::CieLab => cie_lab_distance(color1, color2),
+        DistanceMetric::Oklab => oklab_distance(color1, color2),
+    }
+}
+
+/// Approximate volume of the sRGB gamut in CIE L*a*b* space (ΔE³)
+const SRGB_LAB_GAMUT_VOLUME: f64 = 830_000.0;
+
+/// Score how evenly a palette spans the visible (sRGB) color space
+///
+/// The minimum pairwise CIE76 ΔE is divided by the spacing expected when
+/// `n` colors share the gamut evenly, `(gamut volume / n)^(1/3)`. Scores
+/// near 1.0 or above mean the colors are well distributed; low scores mean
+/// at least two colors are hard to tell apart. Palettes with fewer than
+/// two colors score 0.
+///
+/// # Examples
+/// ```
+/// # use epd_dither::color::{Rgb, distance::perceptual_palette_quality};
+/// let bw = [Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)];
+/// let grays = [Rgb::new(120, 120, 120), Rgb::new(125, 125, 125)];
+/// assert!(perceptual_palette_quality(&bw) > perceptual_palette_quality(&grays));
+/// ```
+pub fn perceptual_palette_quality(palette: &[Rgb]) -> f64 {
+    if palette.len() < 2 {
+        return 0.0;
+    }
+
+    let labs: Vec<[f64; 3]> = palette.iter().map(rgb_to_lab).collect();
+    let min_delta_e = labs
+        .iter()
+        .enumerate()
+        .flat_map(|(i, a)| labs[i + 1..].iter().map(move |b| lab_distance(a, b)))
+        .fold(f64::INFINITY, f64::min);
+    let expected = (SRGB_LAB_GAMUT_VOLUME / palette.len() as f64).cbrt();
+
+    min_delta_e / expected
+}
+
+fn redmean_from_channels(color1: [f64; 3], color2: [f64; 3]) -> f64 {
+    let r_mean = (color1[0] + color2[0]) / 2.0;
+    let r_diff = color1[0] - color2[0];
+    let g_diff = color1[1] - color2[1];
+    let b_diff = color1[2] - color2[2];
+
+    ((2.0 + r_mean / 256.0) * r_diff * r_diff
+        + 4.0 * g_diff * g_diff
+        + (2.0 + (255.0 - r_mean) / 256.0) * b_diff * b_diff)
+        .sqrt()
+}
+
+fn oklab_components_distance(lab1: &Oklab, lab2: &Oklab) -> f64 {
+    lab_distance(&[lab1.l, lab1.a, lab1.b], &[lab2.l, lab2.a, lab2.b])
+}
+
+fn lab_distance(lab1: &[f64; 3], lab2: &[f64; 3]) -> f64 {
+    let l_diff = lab1[0] - lab2[0];
+    let a_diff = lab1[1] - lab2[1];
+    let b_diff = lab1[2] - lab2[2];
+
+    (l_diff * l_diff + a_diff * a_diff + b_diff * b_diff).sqrt()
+}
+
+/// Index of the smallest distance, keeping the first entry on ties
+fn closest_index(distances: impl Iterator<Item = f64>) -> Option<usize> {
+    distances
+        .enumerate()
+        .min_by(|(_, dist1), (_, dist2)| {
+            dist1
+                .partial_cmp(dist2)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .map(|(idx, _)| idx)
+}
+
 /// Find the closest color in a palette to the given color
 ///
 /// Returns the index and reference to the closest color
-pub fn find_closest_color<'a>(
+pub fn find_closest_color<'a>(color: &Rgb, palette: &'a [Rgb]) -> Option<(usize, &'a Rgb)> {
+    find_closest_color_with_metric(color, palette, DistanceMetric::Euclidean)
+}
+
+/// Find the closest color in a palette using the given distance metric
+pub fn find_closest_color_with_metric<'a>(
     color: &Rgb,
     palette: &'a [Rgb],
+    metric: DistanceMetric,
 ) -> Option<(usize, &'a Rgb)> {
-    if palette.is_empty() {
-        return None;
+    closest_index(
+        palette
+            .iter()
+            .map(|palette_color| color_distance(color, palette_color, metric)),
+    )
+    .map(|idx| (idx, &palette[idx]))
+}
+
+/// Memoized nearest-color lookups for one palette and metric
+///
+/// Photos with flat areas (skies, backgrounds) match the same colors over
+/// and over; the cache remembers the palette index found for each exact
+/// color. A cache must only be used with a single palette and metric.
+/// Passing a palette of a different length clears it.
+///
+/// A hash lookup costs more than a Euclidean search of a small palette, even
+/// when most lookups hit, so the cache only pays off for the perceptual
+/// metrics on images with few distinct colors. Error diffusion doesn't use
+/// it (see the `distance` bench).
+#[derive(Debug, Clone, Default)]
+pub struct ColorMatchCache {
+    cache: HashMap<[u8; 3], usize>,
+    palette_len: usize,
+}
+
+impl ColorMatchCache {
+    pub fn new(palette_len: usize) -> Self {
+        Self {
+            cache: HashMap::new(),
+            palette_len,
+        }
+    }
+
+    /// Look up the closest palette color, computing and storing it on a miss
+    ///
+    /// Returns the same result as [`find_closest_color_with_metric`].
+    ///
+    /// # Panics
+    /// Panics if `palette` is empty.
+    pub fn find_or_compute<'a>(
+        &mut self,
+        color: &Rgb,
+        palette: &'a [Rgb],
+        metric: DistanceMetric,
+    ) -> (usize, &'a Rgb) {
+        if palette.len() != self.palette_len {
+            self.cache.clear();
+            self.palette_len = palette.len();
+        }
+
+        let idx = *self.cache.entry(color.0).or_insert_with(|| {
+            find_closest_color_with_metric(color, palette, metric)
+                .expect("Palette should not be empty")
+                .0
+        });
+        (idx, &palette[idx])
+    }
+
+    /// Number of distinct colors cached so far
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
     }
+}
 
-    palette
+/// Find the `n` closest palette colors, sorted by ascending distance
+///
+/// Returns `(index, color, distance)` triples; fewer than `n` if the palette
+/// is smaller. Equal distances are ordered by palette index, so `n = 1`
+/// gives the same color as [`find_closest_color_with_metric`].
+pub fn find_n_closest_colors<'a>(
+    color: &Rgb,
+    palette: &'a [Rgb],
+    n: usize,
+    metric: DistanceMetric,
+) -> Vec<(usize, &'a Rgb, f64)> {
+    let mut candidates: Vec<(usize, &'a Rgb, f64)> = palette
         .iter()
         .enumerate()
         .map(|(idx, palette_color)| {
-            let distance = euclidean_distance(color, palette_color);
-            (idx, palette_color, distance)
-        })
-        .min_by(|(_, _, dist1), (_, _, dist2)| {
-            dist1.partial_cmp(dist2).unwrap_or(std::cmp::Ordering::Equal)
+            (
+                idx,
+                palette_color,
+                color_distance(color, palette_color, metric),
+            )
         })
-        .map(|(idx, color, _)| (idx, color))
+        .collect();
+
+    let by_distance = |a: &(usize, &Rgb, f64), b: &(usize, &Rgb, f64)| {
+        a.2.partial_cmp(&b.2)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.0.cmp(&b.0))
+    };
+
+    let n = n.min(candidates.len());
+    if n == 0 {
+        return Vec::new();
+    }
+    if n < candidates.len() {
+        candidates.select_nth_unstable_by(n - 1, by_distance);
+        candidates.truncate(n);
+    }
+    candidates.sort_unstable_by(by_distance);
+    candidates
+}
+
+/// Find the closest palette index for each color in a slice
+///
+/// Equivalent to calling [`find_closest_color_with_metric`] per color, but
+/// per-palette work is done once per batch: Lab conversion for `CieLab`
+/// and `Oklab`, and channel widening for `RedMean`. With the `parallel`
+/// feature the colors are matched on the rayon thread pool.
+///
+/// # Panics
+/// Panics if `palette` is empty and `colors` is not.
+pub fn batch_find_closest_colors(
+    colors: &[Rgb],
+    palette: &[Rgb],
+    metric: DistanceMetric,
+) -> Vec<usize> {
+    match metric {
+        DistanceMetric::Euclidean => map_colors(colors, |color| {
+            closest_index(palette.iter().map(|p| euclidean_distance(color, p)))
+        }),
+        DistanceMetric::RedMean => {
+            let palette_channels: Vec<[f64; 3]> = palette
+                .iter()
+                .map(|p| [p.r() as f64, p.g() as f64, p.b() as f64])
+                .collect();
+            map_colors(colors, |color| {
+                let channels = [color.r() as f64, color.g() as f64, color.b() as f64];
+                closest_index(
+                    palette_channels
+                        .iter()
+                        .map(|&p| redmean_from_channels(channels, p)),
+                )
+            })
+        }
+        DistanceMetric::CieLab => {
+            let palette_lab: Vec<[f64; 3]> = palette.iter().map(rgb_to_lab).collect();
+            map_colors(colors, |color| {
+                let lab = rgb_to_lab(color);
+                closest_index(palette_lab.iter().map(|p| lab_distance(&lab, p)))
+            })
+        }
+        DistanceMetric::Oklab => {
+            let palette_lab: Vec<Oklab> = palette.iter().map(rgb_to_oklab).collect();
+            map_colors(colors, |color| {
+                let lab = rgb_to_oklab(color);
+                closest_index(
+                    palette_lab
+                        .iter()
+                        .map(|p| oklab_components_distance(&lab, p)),
+                )
+            })
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+fn map_colors<F>(colors: &[Rgb], closest: F) -> Vec<usize>
+where
+    F: Fn(&Rgb) -> Option<usize> + Sync,
+{
+    use rayon::prelude::*;
+
+    colors
+        .par_iter()
+        .map(|color| closest(color).expect("Palette should not be empty"))
+        .collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+fn map_colors<F>(colors: &[Rgb], closest: F) -> Vec<usize>
+where
+    F: Fn(&Rgb) -> Option<usize>,
+{
+    colors
+        .iter()
+        .map(|color| closest(color).expect("Palette should not be empty"))
+        .collect()
 }
 
 #[cfg(test)]
@@ -94,4 +388,169 @@ mod tests {
         let (idx, _) = find_closest_color(&orange, &palette).unwrap();
         assert_eq!(idx, 2);
     }
+
+    #[test]
+    fn test_batch_matches_per_pixel() {
+        let palette = vec![
+            Rgb::new(0, 0, 0),
+            Rgb::new(255, 255, 255),
+            Rgb::new(255, 0, 0),
+            Rgb::new(0, 255, 0),
+            Rgb::new(0, 0, 255),
+            Rgb::new(255, 255, 0),
+        ];
+        let colors: Vec<Rgb> = (0..500u32)
+            .map(|i| {
+                Rgb::new(
+                    (i * 37 % 256) as u8,
+                    (i * 91 % 256) as u8,
+                    (i * 173 % 256) as u8,
+                )
+            })
+            .collect();
+
+        for metric in [
+            DistanceMetric::Euclidean,
+            DistanceMetric::RedMean,
+            DistanceMetric::CieLab,
+            DistanceMetric::Oklab,
+        ] {
+            let batch = batch_find_closest_colors(&colors, &palette, metric);
+            assert_eq!(batch.len(), colors.len());
+            for (color, &idx) in colors.iter().zip(&batch) {
+                let (expected, _) =
+                    find_closest_color_with_metric(color, &palette, metric).unwrap();
+                assert_eq!(idx, expected, "{:?} mismatch for {:?}", metric, color);
+            }
+        }
+    }
+
+    #[test]
+    fn test_metrics_agree_on_identity() {
+        let color = Rgb::new(12, 200, 99);
+        for metric in [
+            DistanceMetric::Euclidean,
+            DistanceMetric::RedMean,
+            DistanceMetric::CieLab,
+            DistanceMetric::Oklab,
+        ] {
+            assert_eq!(color_distance(&color, &color, metric), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_color_match_cache() {
+        let palette = vec![
+            Rgb::new(0, 0, 0),
+            Rgb::new(255, 255, 255),
+            Rgb::new(255, 0, 0),
+            Rgb::new(0, 0, 255),
+        ];
+        let mut cache = ColorMatchCache::new(palette.len());
+
+        for metric in [DistanceMetric::Euclidean, DistanceMetric::CieLab] {
+            let mut cache = ColorMatchCache::new(palette.len());
+            for round in 0..2 {
+                for i in 0..64u8 {
+                    let color = Rgb::new(i * 4, 255 - i * 3, i.wrapping_mul(37));
+                    let cached = cache.find_or_compute(&color, &palette, metric);
+                    let fresh = find_closest_color_with_metric(&color, &palette, metric).unwrap();
+                    assert_eq!(cached, fresh, "Mismatch in round {}", round);
+                }
+            }
+            assert_eq!(cache.len(), 64);
+        }
+
+        // A different palette invalidates the cache
+        let red = Rgb::new(200, 0, 0);
+        cache.find_or_compute(&red, &palette, DistanceMetric::Euclidean);
+        let gray = [Rgb::new(0, 0, 0), Rgb::new(128, 128, 128)];
+        let (idx, _) = cache.find_or_compute(&red, &gray, DistanceMetric::Euclidean);
+        assert_eq!(idx, 1);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_find_n_closest_colors() {
+        let black = Rgb::new(0, 0, 0);
+        let white = Rgb::new(255, 255, 255);
+        let gray = Rgb::new(100, 100, 100);
+
+        let bw = [black, white];
+        let matches = find_n_closest_colors(&gray, &bw, 2, DistanceMetric::Euclidean);
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[0].0, *matches[0].1), (0, black));
+        assert_eq!((matches[1].0, *matches[1].1), (1, white));
+        assert!(matches[0].2 > 0.0 && matches[1].2 > 0.0);
+        assert!(matches[0].2 <= matches[1].2);
+
+        // Asking for more colors than the palette has returns all of them
+        assert_eq!(
+            find_n_closest_colors(&gray, &bw, 5, DistanceMetric::Euclidean).len(),
+            2
+        );
+        assert!(find_n_closest_colors(&gray, &bw, 0, DistanceMetric::Euclidean).is_empty());
+    }
+
+    #[test]
+    fn test_find_n_closest_matches_find_closest() {
+        let palette = vec![
+            Rgb::new(0, 0, 0),
+            Rgb::new(255, 255, 255),
+            Rgb::new(255, 0, 0),
+            Rgb::new(0, 255, 0),
+            Rgb::new(0, 0, 255),
+            Rgb::new(255, 255, 0),
+        ];
+
+        for i in 0..200u32 {
+            let color = Rgb::new(
+                (i * 37 % 256) as u8,
+                (i * 91 % 256) as u8,
+                (i * 173 % 256) as u8,
+            );
+            let (expected, _) = find_closest_color(&color, &palette).unwrap();
+            let matches = find_n_closest_colors(&color, &palette, 1, DistanceMetric::Euclidean);
+            assert_eq!(matches[0].0, expected);
+
+            let all =
+                find_n_closest_colors(&color, &palette, palette.len(), DistanceMetric::CieLab);
+            assert!(all.windows(2).all(|pair| pair[0].2 <= pair[1].2));
+        }
+    }
+
+    #[test]
+    fn test_perceptual_palette_quality() {
+        let bw = [Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)];
+        let grays = [
+            Rgb::new(0, 0, 0),
+            Rgb::new(128, 128, 128),
+            Rgb::new(130, 130, 130),
+        ];
+        let hues = [
+            Rgb::new(0, 0, 0),
+            Rgb::new(255, 255, 255),
+            Rgb::new(255, 0, 0),
+            Rgb::new(0, 0, 255),
+        ];
+
+        let bw_score = perceptual_palette_quality(&bw);
+        assert!(bw_score > perceptual_palette_quality(&grays));
+        assert!(bw_score < perceptual_palette_quality(&hues));
+
+        assert_eq!(perceptual_palette_quality(&bw[..1]), 0.0);
+        assert_eq!(perceptual_palette_quality(&[bw[0], bw[0]]), 0.0);
+    }
+
+    #[test]
+    fn test_oklab_distance_is_perceptual() {
+        // RGB distance rates these light greens further apart than the two
+        // mid grays, while they look much more alike
+        let (green, similar_green) = (Rgb::new(20, 220, 20), Rgb::new(60, 240, 60));
+        let (gray, light_gray) = (Rgb::new(128, 128, 128), Rgb::new(158, 158, 158));
+
+        let rgb = |a, b| euclidean_distance(a, b);
+        assert!(rgb(&green, &similar_green) > rgb(&gray, &light_gray));
+        assert!(oklab_distance(&green, &similar_green) < oklab_distance(&gray, &light_gray));
+    }
 }
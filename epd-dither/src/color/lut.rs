@@ -0,0 +1,196 @@
+//! 3D color lookup tables
+
+use super::Rgb;
+use anyhow::{Context, Result};
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A 3D color lookup table
+///
+/// `data` holds `size³` entries indexed as `[r_idx][g_idx][b_idx]`, i.e.
+/// entry `(r_idx * size + g_idx) * size + b_idx`. Lattice point `i` along
+/// each axis corresponds to the input value `i * 255 / (size - 1)`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColorLut {
+    pub size: u8,
+    pub data: Vec<Rgb>,
+}
+
+impl ColorLut {
+    /// A LUT that maps every color to itself
+    ///
+    /// Applying it is an exact no-op when `size - 1` divides 255 (e.g. 2,
+    /// 4, 16 or 18); other sizes may be off by one from lattice rounding.
+    pub fn identity(size: u8) -> Self {
+        let size = size.max(2);
+        let level = |i: usize| (i as f64 * 255.0 / (size - 1) as f64).round() as u8;
+
+        let n = size as usize;
+        let mut data = Vec::with_capacity(n * n * n);
+        for r in 0..n {
+            for g in 0..n {
+                for b in 0..n {
+                    data.push(Rgb::new(level(r), level(g), level(b)));
+                }
+            }
+        }
+
+        Self { size, data }
+    }
+
+    /// Load a LUT from a JSON or binary file
+    ///
+    /// JSON files hold `{"size": N, "data": [[r, g, b], ...]}`. Binary files
+    /// hold one size byte followed by `size³` RGB triples. Files starting
+    /// with `{` are read as JSON.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read LUT file: {}", path.display()))?;
+
+        let lut = if bytes.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{') {
+            serde_json::from_slice(&bytes)
+                .with_context(|| format!("Failed to parse LUT file: {}", path.display()))?
+        } else {
+            let (&size, triples) = bytes
+                .split_first()
+                .with_context(|| format!("LUT file is empty: {}", path.display()))?;
+            if triples.len() % 3 != 0 {
+                anyhow::bail!("LUT file {} has a truncated RGB entry", path.display());
+            }
+            Self {
+                size,
+                data: triples
+                    .chunks_exact(3)
+                    .map(|c| Rgb::new(c[0], c[1], c[2]))
+                    .collect(),
+            }
+        };
+
+        lut.validate()
+            .with_context(|| format!("Invalid LUT file: {}", path.display()))?;
+        Ok(lut)
+    }
+
+    /// Check that the table has at least 2 points per axis and `size³` entries
+    pub fn validate(&self) -> Result<()> {
+        if self.size < 2 {
+            anyhow::bail!("LUT size must be at least 2, got {}", self.size);
+        }
+
+        let expected = (self.size as usize).pow(3);
+        if self.data.len() != expected {
+            anyhow::bail!(
+                "LUT of size {} needs {} entries, got {}",
+                self.size,
+                expected,
+                self.data.len()
+            );
+        }
+
+        Ok(())
+    }
+
+    fn entry(&self, r: usize, g: usize, b: usize) -> &Rgb {
+        let n = self.size as usize;
+        &self.data[(r * n + g) * n + b]
+    }
+
+    /// Look up a color with trilinear interpolation between lattice points
+    ///
+    /// Panics on a malformed table; callers validate it first.
+    pub(crate) fn lookup(&self, color: &Rgb) -> Rgb {
+        let max_index = (self.size - 1) as usize;
+        let axis = |value: u8| {
+            let position = value as f64 * max_index as f64 / 255.0;
+            let low = (position.floor() as usize).min(max_index - 1);
+            (low, position - low as f64)
+        };
+        let (r0, fr) = axis(color.r());
+        let (g0, fg) = axis(color.g());
+        let (b0, fb) = axis(color.b());
+
+        let mut result = [0.0f64; 3];
+        for (dr, wr) in [(0, 1.0 - fr), (1, fr)] {
+            for (dg, wg) in [(0, 1.0 - fg), (1, fg)] {
+                for (db, wb) in [(0, 1.0 - fb), (1, fb)] {
+                    let weight = wr * wg * wb;
+                    let corner = self.entry(r0 + dr, g0 + dg, b0 + db);
+                    for (channel, &value) in result.iter_mut().zip(corner.as_slice()) {
+                        *channel += value as f64 * weight;
+                    }
+                }
+            }
+        }
+
+        Rgb(result.map(|v| v.round().clamp(0.0, 255.0) as u8))
+    }
+}
+
+/// Map every pixel of an image through a LUT
+///
+/// The LUT must be valid (see [`ColorLut::validate`]); the public entry
+/// point is [`crate::dither::engine::apply_color_lookup_table`], which
+/// checks it.
+pub(crate) fn apply(img: &mut RgbImage, lut: &ColorLut) {
+    for pixel in img.pixels_mut() {
+        pixel.0 = lut.lookup(&Rgb(pixel.0)).0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_image() -> RgbImage {
+        RgbImage::from_fn(16, 16, |x, y| {
+            image::Rgb([(x * 17) as u8, (y * 16 + 3) as u8, ((x * y) % 256) as u8])
+        })
+    }
+
+    #[test]
+    fn test_identity_is_noop() {
+        for size in [2, 4, 16, 18] {
+            let lut = ColorLut::identity(size);
+            lut.validate().unwrap();
+
+            let mut img = test_image();
+            apply(&mut img, &lut);
+            assert_eq!(
+                img,
+                test_image(),
+                "identity LUT of size {} changed the image",
+                size
+            );
+        }
+    }
+
+    #[test]
+    fn test_inverting_lut() {
+        let mut lut = ColorLut::identity(2);
+        for color in &mut lut.data {
+            *color = Rgb::new(255 - color.r(), 255 - color.g(), 255 - color.b());
+        }
+
+        assert_eq!(lut.lookup(&Rgb::new(0, 255, 100)), Rgb::new(255, 0, 155));
+    }
+
+    #[test]
+    fn test_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let lut = ColorLut::identity(4);
+
+        let json_path = dir.path().join("lut.json");
+        std::fs::write(&json_path, serde_json::to_string(&lut).unwrap()).unwrap();
+        assert_eq!(ColorLut::from_file(&json_path).unwrap(), lut);
+
+        let mut binary = vec![lut.size];
+        binary.extend(lut.data.iter().flat_map(|c| c.0));
+        let binary_path = dir.path().join("lut.bin");
+        std::fs::write(&binary_path, &binary).unwrap();
+        assert_eq!(ColorLut::from_file(&binary_path).unwrap(), lut);
+
+        std::fs::write(&binary_path, &binary[..binary.len() - 3]).unwrap();
+        assert!(ColorLut::from_file(&binary_path).is_err());
+    }
+}
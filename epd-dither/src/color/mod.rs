@@ -1,12 +1,17 @@
 //! Color types and utilities for palette management and color space operations
 
+pub mod adjust;
 pub mod convert;
 pub mod distance;
+pub mod lut;
 pub mod palette;
+pub mod preprocess;
+pub mod simulate;
 
 #[cfg(test)]
 mod tests;
 
+pub use distance::DistanceMetric;
 pub use palette::Palette;
 
 /// RGB color (8-bit per channel)
@@ -33,6 +38,135 @@ impl Rgb {
     pub fn as_slice(&self) -> &[u8; 3] {
         &self.0
     }
+
+    /// Linearly interpolate towards `other` in RGB space
+    ///
+    /// `t` is clamped to 0.0–1.0; `t = 0` returns `self` and `t = 1`
+    /// returns `other` exactly.
+    pub fn blend(self, other: Rgb, t: f64) -> Rgb {
+        Rgb(blend_channels(self.0, other.0, t))
+    }
+
+    /// Create a color from HSL: hue in degrees, saturation and lightness in 0–1
+    ///
+    /// ```
+    /// # use epd_dither::color::Rgb;
+    /// assert_eq!(Rgb::from_hsl(120.0, 1.0, 0.5), Rgb::new(0, 255, 0));
+    /// ```
+    pub fn from_hsl(h: f64, s: f64, l: f64) -> Rgb {
+        convert::hsl_to_rgb([h, s, l])
+    }
+
+    /// Hue (degrees), saturation and lightness (0–1) of this color
+    ///
+    /// ```
+    /// # use epd_dither::color::Rgb;
+    /// assert_eq!(Rgb::new(0, 0, 255).to_hsl(), (240.0, 1.0, 0.5));
+    /// ```
+    pub fn to_hsl(self) -> (f64, f64, f64) {
+        let [h, s, l] = convert::rgb_to_hsl(&self);
+        (h, s, l)
+    }
+
+    /// Create a color from HSV: hue in degrees, saturation and value in 0–1
+    ///
+    /// ```
+    /// # use epd_dither::color::Rgb;
+    /// assert_eq!(Rgb::from_hsv(0.0, 1.0, 0.5), Rgb::new(128, 0, 0));
+    /// ```
+    pub fn from_hsv(h: f64, s: f64, v: f64) -> Rgb {
+        convert::hsv_to_rgb([h, s, v])
+    }
+
+    /// Hue (degrees), saturation and value (0–1) of this color
+    ///
+    /// ```
+    /// # use epd_dither::color::Rgb;
+    /// assert_eq!(Rgb::new(255, 255, 0).to_hsv(), (60.0, 1.0, 1.0));
+    /// ```
+    pub fn to_hsv(self) -> (f64, f64, f64) {
+        let [h, s, v] = convert::rgb_to_hsv(&self);
+        (h, s, v)
+    }
+
+    /// Interpolate towards `other` in CIE Lab space
+    ///
+    /// Produces perceptually more even gradients than [`Rgb::blend`].
+    pub fn blend_lab(self, other: Rgb, t: f64) -> Rgb {
+        let t = t.clamp(0.0, 1.0);
+        let from = convert::rgb_to_lab(&self);
+        let to = convert::rgb_to_lab(&other);
+
+        convert::lab_to_rgb([
+            from[0] * (1.0 - t) + to[0] * t,
+            from[1] * (1.0 - t) + to[1] * t,
+            from[2] * (1.0 - t) + to[2] * t,
+        ])
+    }
+
+    /// Add a signed offset to each channel, saturating at 0 and 255
+    ///
+    /// ```
+    /// # use epd_dither::color::Rgb;
+    /// let color = Rgb::new(10, 128, 250).saturating_add_i16([-20, 7, 20]);
+    /// assert_eq!(color, Rgb::new(0, 135, 255));
+    /// ```
+    pub fn saturating_add_i16(self, delta: [i16; 3]) -> Rgb {
+        Rgb(std::array::from_fn(|i| {
+            (self.0[i] as i16).saturating_add(delta[i]).clamp(0, 255) as u8
+        }))
+    }
+
+    fn map_channels(self, f: impl Fn(f64) -> f64) -> Rgb {
+        Rgb(self
+            .0
+            .map(|value| f(value as f64).round().clamp(0.0, 255.0) as u8))
+    }
+}
+
+/// Channel-wise addition, saturating at 255
+impl std::ops::Add for Rgb {
+    type Output = Rgb;
+
+    fn add(self, other: Rgb) -> Rgb {
+        Rgb(std::array::from_fn(|i| {
+            self.0[i].saturating_add(other.0[i])
+        }))
+    }
+}
+
+/// Channel-wise subtraction, saturating at 0
+impl std::ops::Sub for Rgb {
+    type Output = Rgb;
+
+    fn sub(self, other: Rgb) -> Rgb {
+        Rgb(std::array::from_fn(|i| {
+            self.0[i].saturating_sub(other.0[i])
+        }))
+    }
+}
+
+/// Scale every channel, rounding and clamping to 0–255
+impl std::ops::Mul<f64> for Rgb {
+    type Output = Rgb;
+
+    fn mul(self, factor: f64) -> Rgb {
+        self.map_channels(|value| value * factor)
+    }
+}
+
+/// Divide every channel, rounding and clamping to 0–255
+impl std::ops::Div<f64> for Rgb {
+    type Output = Rgb;
+
+    fn div(self, divisor: f64) -> Rgb {
+        self.map_channels(|value| value / divisor)
+    }
+}
+
+fn blend_channels<const N: usize>(from: [u8; N], to: [u8; N], t: f64) -> [u8; N] {
+    let t = t.clamp(0.0, 1.0);
+    std::array::from_fn(|i| (from[i] as f64 * (1.0 - t) + to[i] as f64 * t) as u8)
 }
 
 /// RGBA color (8-bit per channel including alpha)
@@ -63,6 +197,11 @@ impl Rgba {
     pub fn to_rgb(&self) -> Rgb {
         Rgb([self.0[0], self.0[1], self.0[2]])
     }
+
+    /// Linearly interpolate all four channels (including alpha) towards `other`
+    pub fn blend(self, other: Rgba, t: f64) -> Rgba {
+        Rgba(blend_channels(self.0, other.0, t))
+    }
 }
 
 impl From<Rgb> for Rgba {
@@ -1,12 +1,91 @@
 //! Color palette management and loading
 
-use super::{convert, Rgb};
-use anyhow::{anyhow, Result};
+use super::{
+    convert,
+    distance::{
+        color_distance, euclidean_distance, find_closest_color, find_n_closest_colors,
+        DistanceMetric,
+    },
+    Rgb,
+};
+use crate::dither::{engine, DitherOptions, DitheringAlgorithm};
+use anyhow::{anyhow, Context, Result};
+use image::{imageops::FilterType, RgbImage};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Channel values of the web-safe palette (multiples of 51)
+const WEB_SAFE_LEVELS: [u8; 6] = [0, 51, 102, 153, 204, 255];
+
+/// The 216 web-safe colors, red varying slowest
+pub const WEB_SAFE_COLORS: [Rgb; 216] = {
+    let mut colors = [Rgb([0; 3]); 216];
+    let mut i = 0;
+    while i < 216 {
+        let (r, g, b) = (i / 36, i / 6 % 6, i % 6);
+        colors[i] = Rgb([WEB_SAFE_LEVELS[r], WEB_SAFE_LEVELS[g], WEB_SAFE_LEVELS[b]]);
+        i += 1;
+    }
+    colors
+};
+
+/// The web-safe colors as a palette named "web-safe"
+///
+/// A `static` rather than a `const`, since palettes own their name and
+/// colors.
+pub static WEB_SAFE_PALETTE: LazyLock<Palette> =
+    LazyLock::new(|| Palette::new("web-safe", WEB_SAFE_COLORS.to_vec()));
+
+/// Raw bytes of an ICC color profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IccProfile(pub Vec<u8>);
+
+impl IccProfile {
+    /// Read a profile from an `.icc` or `.icm` file
+    pub fn from_file(path: &Path) -> Result<Self> {
+        std::fs::read(path)
+            .map(Self)
+            .with_context(|| format!("Failed to read ICC profile: {}", path.display()))
+    }
+
+    /// The standard sRGB profile
+    #[cfg(feature = "icc")]
+    pub fn srgb() -> Result<Self> {
+        let bytes = lcms2::Profile::new_srgb()
+            .icc()
+            .context("Failed to encode sRGB profile")?;
+        Ok(Self(bytes))
+    }
+
+    /// Convert packed RGB bytes in place from this profile's color space to
+    /// `to`'s, with relative colorimetric intent
+    #[cfg(feature = "icc")]
+    pub fn transform_rgb(&self, to: &IccProfile, buffer: &mut [u8]) -> Result<()> {
+        use lcms2::{Intent, PixelFormat, Profile, Transform};
+
+        let from = Profile::new_icc(&self.0).context("Invalid source ICC profile")?;
+        let to = Profile::new_icc(&to.0).context("Invalid target ICC profile")?;
+        let transform: Transform<u8, u8> = Transform::new(
+            &from,
+            PixelFormat::RGB_8,
+            &to,
+            PixelFormat::RGB_8,
+            Intent::RelativeColorimetric,
+        )
+        .context("Failed to create ICC transform")?;
+
+        transform.transform_in_place(buffer);
+        Ok(())
+    }
+}
 
 /// A color palette for dithering
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Palettes compare and hash by name and colors, so they can be used as
+/// `HashMap` keys (e.g. for caching distance tables).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Palette {
     pub name: String,
     pub colors: Vec<Rgb>,
@@ -34,6 +113,282 @@ impl Palette {
         })
     }
 
+    /// Load a palette from a text file with one hex color per line
+    ///
+    /// Whitespace is trimmed and blank lines and `//` comments are skipped
+    /// (the format used by Lospec `.hex` downloads). The palette is named
+    /// after the file stem. Fails if the file holds no colors.
+    pub fn from_hex_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read palette file: {}", path.display()))?;
+
+        let colors = contents
+            .lines()
+            .enumerate()
+            .map(|(number, line)| (number, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with("//"))
+            .map(|(number, line)| {
+                convert::hex_to_rgb(line).map(Rgb).with_context(|| {
+                    format!("Invalid color on line {} of {}", number + 1, path.display())
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if colors.is_empty() {
+            anyhow::bail!("No colors found in {}", path.display());
+        }
+
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("custom");
+
+        Ok(Self::new(name, colors))
+    }
+
+    /// Load palettes from CSS custom properties in a file
+    ///
+    /// See [`Palette::from_css_str`]; `--color-*` properties form a palette
+    /// named after the file stem.
+    pub fn from_css_file(path: &Path) -> Result<Vec<Self>> {
+        let css = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read CSS file: {}", path.display()))?;
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("custom");
+
+        Ok(Self::from_css_str(&css, name))
+    }
+
+    /// Extract palettes from CSS custom properties
+    ///
+    /// `--palette-NAME-COLOR: #HEX;` adds a color to palette `NAME` (the
+    /// text up to the last dash), and `--color-COLOR: #HEX;` adds one to the
+    /// palette `single_name`. Palettes and colors keep their order of first
+    /// appearance. Other lines are ignored, and matching properties
+    /// without a valid hex value are skipped with a warning.
+    pub fn from_css_str(css: &str, single_name: &str) -> Vec<Self> {
+        let mut palettes: Vec<Self> = Vec::new();
+
+        for (number, line) in css.lines().enumerate() {
+            let line = line.trim();
+            let (property, name) = if let Some(rest) = line.strip_prefix("--palette-") {
+                let property = rest.split(':').next().unwrap_or_default().trim();
+                match property.rsplit_once('-') {
+                    Some((name, _)) if !name.is_empty() => (property, name),
+                    _ => {
+                        let number = number + 1;
+                        log_warn!(
+                            "Skipping CSS line {}: no palette name in '{}'",
+                            number,
+                            line
+                        );
+                        continue;
+                    }
+                }
+            } else if line.starts_with("--color-") {
+                (
+                    line.split(':').next().unwrap_or_default().trim(),
+                    single_name,
+                )
+            } else {
+                continue;
+            };
+
+            let value = line
+                .split_once(':')
+                .map(|(_, value)| value.trim().trim_end_matches(';').trim());
+            let Some(Ok(rgb)) = value.map(convert::hex_to_rgb) else {
+                log_warn!(
+                    "Skipping CSS line {}: '{}' has no hex color",
+                    number + 1,
+                    property
+                );
+                continue;
+            };
+
+            match palettes.iter_mut().find(|palette| palette.name == name) {
+                Some(palette) => palette.colors.push(Rgb(rgb)),
+                None => palettes.push(Self::new(name, vec![Rgb(rgb)])),
+            }
+        }
+
+        palettes
+    }
+
+    /// Fetch a palette from lospec.com by its slug (e.g. `"pico-8"`)
+    ///
+    /// Requests `https://lospec.com/palette-list/{slug}.json` and builds a
+    /// palette named after the slug from its `colors` array. Must be called
+    /// from within a Tokio runtime.
+    #[cfg(feature = "lospec")]
+    pub async fn from_lospec_url(slug: &str) -> Result<Self> {
+        Self::fetch_lospec(LOSPEC_PALETTE_URL, slug).await
+    }
+
+    /// Blocking version of [`Palette::from_lospec_url`]
+    ///
+    /// Must not be called from within an async runtime.
+    #[cfg(feature = "lospec")]
+    pub fn from_lospec_url_blocking(slug: &str) -> Result<Self> {
+        Self::fetch_lospec_blocking(LOSPEC_PALETTE_URL, slug)
+    }
+
+    #[cfg(feature = "lospec")]
+    async fn fetch_lospec(base_url: &str, slug: &str) -> Result<Self> {
+        let url = lospec_url(base_url, slug);
+        let response = reqwest::get(&url)
+            .await
+            .with_context(|| format!("Failed to fetch Lospec palette: {}", url))?;
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .context("Failed to read Lospec response")?;
+
+        Self::from_lospec_response(slug, status, &body)
+    }
+
+    #[cfg(feature = "lospec")]
+    fn fetch_lospec_blocking(base_url: &str, slug: &str) -> Result<Self> {
+        let url = lospec_url(base_url, slug);
+        let response = reqwest::blocking::get(&url)
+            .with_context(|| format!("Failed to fetch Lospec palette: {}", url))?;
+        let status = response.status();
+        let body = response.text().context("Failed to read Lospec response")?;
+
+        Self::from_lospec_response(slug, status, &body)
+    }
+
+    /// Parse a Lospec palette response: `{"colors": ["RRGGBB", ...], ...}`
+    #[cfg(feature = "lospec")]
+    fn from_lospec_response(slug: &str, status: reqwest::StatusCode, body: &str) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct LospecPalette {
+            colors: Vec<String>,
+        }
+
+        if status == reqwest::StatusCode::NOT_FOUND {
+            anyhow::bail!("Lospec palette '{}' not found", slug);
+        }
+        if !status.is_success() {
+            anyhow::bail!(
+                "Lospec request for '{}' failed with status {}",
+                slug,
+                status
+            );
+        }
+
+        let palette: LospecPalette = serde_json::from_str(body)
+            .with_context(|| format!("Invalid Lospec palette JSON for '{}'", slug))?;
+        let hex: Vec<&str> = palette.colors.iter().map(String::as_str).collect();
+        if hex.is_empty() {
+            anyhow::bail!("Lospec palette '{}' has no colors", slug);
+        }
+
+        Self::from_hex_strings(slug, &hex)
+    }
+
+    /// Recover the palette from a PNG saved with embedded dither metadata
+    ///
+    /// Inverse of [`crate::output::png::embed_dither_metadata`]: reads the
+    /// `EpdDither` text chunk and returns the palette stored in it.
+    pub fn from_png_metadata(path: &Path) -> Result<Self> {
+        let metadata = crate::output::png::read_dither_metadata(path)?;
+        let palette = metadata
+            .get("palette")
+            .ok_or_else(|| anyhow!("No palette in the metadata of {}", path.display()))?;
+        let options = DitherOptions::try_from(serde_json::json!({ "palette": palette }))
+            .with_context(|| format!("Invalid palette in the metadata of {}", path.display()))?;
+        Ok(options.palette)
+    }
+
+    /// Average colors of circular patches of an image
+    ///
+    /// For each `(x, y)` center, averages the pixels within `sample_radius`
+    /// of it (clipped to the image), so noise in a photographed chart is
+    /// smoothed out. A radius of 0 samples the single pixel. Fails if a
+    /// center lies outside the image.
+    pub fn sample_at_positions(
+        img: &RgbImage,
+        positions: &[(u32, u32)],
+        sample_radius: u32,
+    ) -> Result<Vec<Rgb>> {
+        let (width, height) = img.dimensions();
+        let radius = sample_radius as i64;
+
+        positions
+            .iter()
+            .map(|&(cx, cy)| {
+                if cx >= width || cy >= height {
+                    anyhow::bail!(
+                        "Sample position ({}, {}) is outside the {}x{} image",
+                        cx,
+                        cy,
+                        width,
+                        height
+                    );
+                }
+
+                // Only visit the part of the bounding square inside the
+                // image; distances are squared in i128 so huge radii can't
+                // overflow
+                let (cx, cy) = (cx as i64, cy as i64);
+                let x_range = (cx - radius).max(0)..=(cx + radius).min(width as i64 - 1);
+                let y_range = (cy - radius).max(0)..=(cy + radius).min(height as i64 - 1);
+                let radius_squared = (radius as i128).pow(2);
+
+                let mut sum = [0u64; 3];
+                let mut count = 0u64;
+                for y in y_range {
+                    for x in x_range.clone() {
+                        let (dx, dy) = ((x - cx) as i128, (y - cy) as i128);
+                        if dx * dx + dy * dy > radius_squared {
+                            continue;
+                        }
+                        let pixel = img.get_pixel(x as u32, y as u32);
+                        for (total, &value) in sum.iter_mut().zip(pixel.0.iter()) {
+                            *total += value as u64;
+                        }
+                        count += 1;
+                    }
+                }
+
+                Ok(Rgb(sum.map(|total| {
+                    ((total as f64 / count as f64).round()) as u8
+                })))
+            })
+            .collect()
+    }
+
+    /// Build a palette from the patches of a photographed calibration chart
+    ///
+    /// See [`Palette::sample_at_positions`]; colors keep the order of
+    /// `positions`.
+    pub fn from_sample_positions(
+        name: &str,
+        img: &RgbImage,
+        positions: &[(u32, u32)],
+        radius: u32,
+    ) -> Result<Palette> {
+        Ok(Self::new(
+            name,
+            Self::sample_at_positions(img, positions, radius)?,
+        ))
+    }
+
+    /// Write the palette as a text file with one `#RRGGBB` color per line
+    pub fn to_hex_file(&self, path: &Path) -> Result<()> {
+        let contents: String = self
+            .colors
+            .iter()
+            .map(|color| convert::rgb_to_hex(color) + "\n")
+            .collect();
+
+        std::fs::write(path, contents)
+            .with_context(|| format!("Failed to write palette file: {}", path.display()))
+    }
+
     /// Get the number of colors in the palette
     pub fn len(&self) -> usize {
         self.colors.len()
@@ -43,6 +398,221 @@ impl Palette {
     pub fn is_empty(&self) -> bool {
         self.colors.is_empty()
     }
+
+    /// Return a copy of the palette with every color inverted
+    ///
+    /// Useful for negative-image display modes where dithering should
+    /// target the inverted colors instead of inverting the image itself.
+    pub fn inverted(&self) -> Palette {
+        Self {
+            name: self.name.clone(),
+            colors: self
+                .colors
+                .iter()
+                .map(|c| Rgb::new(255 - c.r(), 255 - c.g(), 255 - c.b()))
+                .collect(),
+        }
+    }
+
+    /// Return a copy of the palette with every color snapped to the nearest
+    /// web-safe color
+    ///
+    /// Each channel is rounded to the nearest multiple of 51, which is also
+    /// the nearest of [`WEB_SAFE_COLORS`]. Colors that snap to the same
+    /// web-safe color are kept, so indices still match the original.
+    pub fn to_nearest_web_safe(&self) -> Palette {
+        let snap = |value: u8| ((value as f64 / 51.0).round() * 51.0) as u8;
+        Self {
+            name: self.name.clone(),
+            colors: self
+                .colors
+                .iter()
+                .map(|color| Rgb(color.0.map(snap)))
+                .collect(),
+        }
+    }
+
+    /// Return a copy of the palette converted between two color spaces
+    ///
+    /// Each color is taken to be in `from_profile`'s space and transformed
+    /// into `to_profile`'s, e.g. from sRGB into a display profile measured
+    /// with a colorimeter.
+    #[cfg(feature = "icc")]
+    pub fn apply_icc_profile(
+        &self,
+        from_profile: &IccProfile,
+        to_profile: &IccProfile,
+    ) -> Result<Palette> {
+        let mut buffer: Vec<u8> = self.colors.iter().flat_map(|color| color.0).collect();
+        from_profile.transform_rgb(to_profile, &mut buffer)?;
+
+        Ok(Self {
+            name: self.name.clone(),
+            colors: buffer
+                .chunks_exact(3)
+                .map(|c| Rgb::new(c[0], c[1], c[2]))
+                .collect(),
+        })
+    }
+
+    /// Pairwise distances between all palette colors
+    ///
+    /// Returns a symmetric `N×N` matrix with a zero diagonal, where entry
+    /// `[i][j]` is the distance between colors `i` and `j`.
+    pub fn distance_matrix(&self, metric: DistanceMetric) -> Vec<Vec<f64>> {
+        let n = self.colors.len();
+        let mut matrix = vec![vec![0.0; n]; n];
+
+        for (i, color) in self.colors.iter().enumerate() {
+            for (j, other) in self.colors.iter().enumerate().skip(i + 1) {
+                let distance = color_distance(color, other, metric);
+                matrix[i][j] = distance;
+                matrix[j][i] = distance;
+            }
+        }
+
+        matrix
+    }
+
+    /// Smallest distance between any two palette colors
+    ///
+    /// Useful for spotting near-duplicate colors. Palettes with fewer than
+    /// two colors return `f64::INFINITY`.
+    pub fn min_separation(&self, metric: DistanceMetric) -> f64 {
+        let mut min = f64::INFINITY;
+        for (i, color) in self.colors.iter().enumerate() {
+            for other in &self.colors[i + 1..] {
+                min = min.min(color_distance(color, other, metric));
+            }
+        }
+        min
+    }
+
+    /// Check that every pair of colors is at least `min_distance` apart
+    pub fn is_well_separated(&self, min_distance: f64, metric: DistanceMetric) -> bool {
+        self.min_separation(metric) >= min_distance
+    }
+
+    /// Reorder the colors along a short path through color space
+    ///
+    /// Starts at the darkest color (lowest CIE L*) and repeatedly moves to
+    /// the nearest remaining color under `metric`, a greedy nearest-neighbor
+    /// approximation of the shortest path visiting every color. Neighboring
+    /// colors in the result are similar, which makes palette swatches read
+    /// as gradients.
+    pub fn perceptual_sort(&self, metric: DistanceMetric) -> Palette {
+        let mut remaining = self.colors.clone();
+        let mut colors = Vec::with_capacity(remaining.len());
+
+        let darkest = remaining
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                convert::rgb_to_lab(a)[0].total_cmp(&convert::rgb_to_lab(b)[0])
+            })
+            .map(|(idx, _)| idx);
+        let Some(mut current) = darkest else {
+            return self.clone();
+        };
+
+        loop {
+            let color = remaining.swap_remove(current);
+            colors.push(color);
+
+            let nearest = remaining.iter().enumerate().min_by(|(_, a), (_, b)| {
+                color_distance(&color, a, metric).total_cmp(&color_distance(&color, b, metric))
+            });
+            match nearest {
+                Some((idx, _)) => current = idx,
+                None => break,
+            }
+        }
+
+        Self {
+            name: self.name.clone(),
+            colors,
+        }
+    }
+
+    /// [`Palette::perceptual_sort`] in reverse, ending at the darkest color
+    pub fn perceptual_sort_reverse(&self, metric: DistanceMetric) -> Palette {
+        let mut sorted = self.perceptual_sort(metric);
+        sorted.colors.reverse();
+        sorted
+    }
+
+    /// Find the palette color closest to `color` (Euclidean distance)
+    ///
+    /// Returns `None` for an empty palette.
+    ///
+    /// # Examples
+    /// ```
+    /// # use epd_dither::{Palette, Rgb};
+    /// let palette = Palette::default();
+    /// let (index, color) = palette.nearest_color(&Rgb::new(200, 200, 200)).unwrap();
+    /// assert_eq!((index, *color), (1, Rgb::new(255, 255, 255)));
+    /// ```
+    pub fn nearest_color(&self, color: &Rgb) -> Option<(usize, &Rgb)> {
+        find_closest_color(color, &self.colors)
+    }
+
+    /// Find the palette color closest to `color` under `metric`
+    ///
+    /// Returns the index, the color and its distance, or `None` for an
+    /// empty palette.
+    ///
+    /// # Examples
+    /// ```
+    /// # use epd_dither::{DistanceMetric, Palette, Rgb};
+    /// let palette = Palette::default();
+    /// let (index, _, distance) = palette
+    ///     .nearest_color_with_metric(&Rgb::new(0, 0, 0), DistanceMetric::CieLab)
+    ///     .unwrap();
+    /// assert_eq!(index, 0);
+    /// assert_eq!(distance, 0.0);
+    /// ```
+    pub fn nearest_color_with_metric(
+        &self,
+        color: &Rgb,
+        metric: DistanceMetric,
+    ) -> Option<(usize, &Rgb, f64)> {
+        find_n_closest_colors(color, &self.colors, 1, metric)
+            .into_iter()
+            .next()
+    }
+
+    /// Check whether any palette color is within `tolerance` (Euclidean) of `color`
+    ///
+    /// # Examples
+    /// ```
+    /// # use epd_dither::{Palette, Rgb};
+    /// let palette = Palette::default();
+    /// assert!(palette.contains_approx(&Rgb::new(250, 252, 255), 10));
+    /// assert!(!palette.contains_approx(&Rgb::new(128, 128, 128), 10));
+    /// ```
+    pub fn contains_approx(&self, color: &Rgb, tolerance: u8) -> bool {
+        self.colors
+            .iter()
+            .any(|c| euclidean_distance(c, color) <= tolerance as f64)
+    }
+}
+
+/// Palettes are ordered by number of colors, then by name
+///
+/// Palettes with the same length and name fall back to comparing their
+/// colors so the ordering stays consistent with equality.
+impl PartialOrd for Palette {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(
+            self.len()
+                .cmp(&other.len())
+                .then_with(|| self.name.cmp(&other.name))
+                .then_with(|| {
+                    let colors = |p: &Palette| p.colors.iter().map(|c| c.0).collect::<Vec<_>>();
+                    colors(self).cmp(&colors(other))
+                }),
+        )
+    }
 }
 
 impl Default for Palette {
@@ -52,28 +622,267 @@ impl Default for Palette {
     }
 }
 
+impl IntoIterator for Palette {
+    type Item = Rgb;
+    type IntoIter = std::vec::IntoIter<Rgb>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.colors.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Palette {
+    type Item = &'a Rgb;
+    type IntoIter = std::slice::Iter<'a, Rgb>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.colors.iter()
+    }
+}
+
+/// Collected palettes are named "custom", like palettes given on the command line
+impl FromIterator<Rgb> for Palette {
+    fn from_iter<I: IntoIterator<Item = Rgb>>(iter: I) -> Self {
+        Self::new("custom", iter.into_iter().collect())
+    }
+}
+
+impl Extend<Rgb> for Palette {
+    fn extend<I: IntoIterator<Item = Rgb>>(&mut self, iter: I) {
+        self.colors.extend(iter);
+    }
+}
+
+/// Generate `n` text colors that are readable on `background`
+///
+/// Candidates are spread evenly over HSL space (12 hues, two saturations
+/// and grays, 9 lightness steps each). Those with a WCAG contrast ratio of
+/// at least `min_contrast_ratio` against `background` qualify; the
+/// highest-contrast one is picked first, then greedily the one farthest
+/// (in CIE Lab) from all colors picked so far. Fails if `n` is 0 or fewer
+/// than `n` candidates qualify.
+pub fn generate_wcag_compliant_palette(
+    background: Rgb,
+    min_contrast_ratio: f64,
+    n: usize,
+) -> Result<Palette> {
+    if n == 0 {
+        anyhow::bail!("A WCAG palette needs at least one color");
+    }
+
+    let mut candidates: Vec<Rgb> = Vec::new();
+    for step in 0..=8 {
+        let lightness = step as f64 / 8.0;
+        candidates.push(Rgb::from_hsl(0.0, 0.0, lightness));
+        for hue in (0..360).step_by(30) {
+            for saturation in [0.5, 1.0] {
+                candidates.push(Rgb::from_hsl(hue as f64, saturation, lightness));
+            }
+        }
+    }
+
+    let mut qualifying: Vec<Rgb> = Vec::new();
+    for color in candidates {
+        let readable = convert::contrast_ratio(&color, &background) >= min_contrast_ratio;
+        if readable && !qualifying.contains(&color) {
+            qualifying.push(color);
+        }
+    }
+    if qualifying.len() < n {
+        anyhow::bail!(
+            "Only {} colors reach a contrast ratio of {} against {}, {} requested",
+            qualifying.len(),
+            min_contrast_ratio,
+            convert::rgb_to_hex(&background),
+            n
+        );
+    }
+
+    let contrast = |color: &Rgb| convert::contrast_ratio(color, &background);
+    let first = qualifying
+        .iter()
+        .copied()
+        .max_by(|a, b| contrast(a).total_cmp(&contrast(b)))
+        .expect("At least one color qualifies");
+    let mut selected: Vec<Rgb> = Vec::with_capacity(n);
+    selected.push(first);
+    while selected.len() < n {
+        let min_distance = |color: &Rgb| {
+            selected
+                .iter()
+                .map(|picked| color_distance(color, picked, DistanceMetric::CieLab))
+                .fold(f64::INFINITY, f64::min)
+        };
+        let next = qualifying
+            .iter()
+            .copied()
+            .filter(|color| !selected.contains(color))
+            .max_by(|a, b| min_distance(a).total_cmp(&min_distance(b)))
+            .expect("Enough colors qualify");
+        selected.push(next);
+    }
+
+    Ok(Palette::new("wcag", selected))
+}
+
+/// Named lists of hex color strings, as stored in the palette JSON files
+type ColorTable = HashMap<String, Vec<String>>;
+
+/// Base URL of the Lospec palette JSON endpoint
+#[cfg(feature = "lospec")]
+const LOSPEC_PALETTE_URL: &str = "https://lospec.com/palette-list";
+
+#[cfg(feature = "lospec")]
+fn lospec_url(base_url: &str, slug: &str) -> String {
+    format!("{}/{}.json", base_url.trim_end_matches('/'), slug)
+}
+
+/// File name prefix marking device color sets in a palette directory
+const DEVICE_COLORS_FILE_PREFIX: &str = "device_";
+
+/// Default of [`PaletteManager::set_device_colors_threshold`]
+const DEFAULT_DEVICE_COLORS_THRESHOLD: f64 = 10.0;
+
 /// Palette manager for loading and managing predefined palettes
 pub struct PaletteManager {
-    palettes: HashMap<String, Vec<String>>,
-    device_colors: HashMap<String, Vec<String>>,
+    palettes: ColorTable,
+    device_colors: ColorTable,
+    device_colors_threshold: f64,
 }
 
 impl PaletteManager {
     /// Load palettes from embedded JSON data
     pub fn new() -> Result<Self> {
+        let (palettes, device_colors) = Self::load_embedded()?;
+
+        Ok(Self {
+            palettes,
+            device_colors,
+            device_colors_threshold: DEFAULT_DEVICE_COLORS_THRESHOLD,
+        })
+    }
+
+    /// Load the embedded palettes, then add or override them with the
+    /// palette files in `dir` (see [`PaletteManager::reload_from_directory`])
+    pub fn from_directory(dir: &Path) -> Result<Self> {
+        let mut manager = Self::new()?;
+        manager.reload_from_directory(dir)?;
+        Ok(manager)
+    }
+
+    /// Re-parse the embedded palette data, discarding any loaded palettes
+    ///
+    /// On error the current palettes are kept intact.
+    pub fn reload(&mut self) -> Result<()> {
+        let (palettes, device_colors) = Self::load_embedded()?;
+
+        self.palettes = palettes;
+        self.device_colors = device_colors;
+        Ok(())
+    }
+
+    /// Reload the embedded palettes and add the palettes found in `dir`
+    ///
+    /// Every `{name}.json` file holds a JSON array of hex colors and defines
+    /// the palette `name`; `device_{name}.json` files define device color
+    /// sets. Directory entries override embedded palettes of the same name.
+    ///
+    /// The reload is atomic: all files are parsed into new maps first, and
+    /// the current palettes are only replaced if every file loads. Returns
+    /// the number of palettes and device color sets loaded from `dir`.
+    pub fn reload_from_directory(&mut self, dir: &Path) -> Result<usize> {
+        let (mut palettes, mut device_colors) = Self::load_embedded()?;
+
+        let entries = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read palette directory: {}", dir.display()))?;
+
+        let mut loaded = 0;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+
+            let json = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read palette file: {}", path.display()))?;
+            let colors: Vec<String> = serde_json::from_str(&json)
+                .with_context(|| format!("Failed to parse palette file: {}", path.display()))?;
+            for hex in &colors {
+                convert::hex_to_rgb(hex)
+                    .with_context(|| format!("Invalid color in {}", path.display()))?;
+            }
+
+            match stem.strip_prefix(DEVICE_COLORS_FILE_PREFIX) {
+                Some(name) => device_colors.insert(name.to_string(), colors),
+                None => palettes.insert(stem.to_string(), colors),
+            };
+            loaded += 1;
+        }
+
+        self.palettes = palettes;
+        self.device_colors = device_colors;
+        Ok(loaded)
+    }
+
+    /// Write every palette to `dir/{name}.json` and every device color set
+    /// to `dir/device_{name}.json`
+    ///
+    /// Uses the format read by [`PaletteManager::from_directory`] and creates
+    /// `dir` if needed. A failed write does not stop the others; the error
+    /// reports how many files were written. Returns the number of files
+    /// written.
+    pub fn save_to_directory(&self, dir: &Path) -> Result<usize> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create palette directory: {}", dir.display()))?;
+
+        let files =
+            self.palettes
+                .iter()
+                .map(|(name, colors)| (name.clone(), colors))
+                .chain(self.device_colors.iter().map(|(name, colors)| {
+                    (format!("{}{}", DEVICE_COLORS_FILE_PREFIX, name), colors)
+                }));
+
+        let mut total = 0;
+        let mut failures = Vec::new();
+        for (stem, colors) in files {
+            total += 1;
+            let path = dir.join(format!("{}.json", stem));
+            let result = serde_json::to_string_pretty(colors)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| std::fs::write(&path, json).map_err(anyhow::Error::from));
+            if let Err(e) = result {
+                failures.push(format!("{}: {}", path.display(), e));
+            }
+        }
+
+        if !failures.is_empty() {
+            anyhow::bail!(
+                "Wrote {} of {} palette files; failed: {}",
+                total - failures.len(),
+                total,
+                failures.join(", ")
+            );
+        }
+
+        Ok(total)
+    }
+
+    /// Parse the palettes and device colors embedded in the binary
+    fn load_embedded() -> Result<(ColorTable, ColorTable)> {
         let palettes_json = include_str!("../data/palettes.json");
         let device_colors_json = include_str!("../data/device_colors.json");
 
-        let palettes: HashMap<String, Vec<String>> = serde_json::from_str(palettes_json)
+        let palettes: ColorTable = serde_json::from_str(palettes_json)
             .map_err(|e| anyhow!("Failed to parse palettes.json: {}", e))?;
 
-        let device_colors: HashMap<String, Vec<String>> = serde_json::from_str(device_colors_json)
+        let device_colors: ColorTable = serde_json::from_str(device_colors_json)
             .map_err(|e| anyhow!("Failed to parse device_colors.json: {}", e))?;
 
-        Ok(Self {
-            palettes,
-            device_colors,
-        })
+        Ok((palettes, device_colors))
     }
 
     /// Get a palette by name
@@ -107,6 +916,60 @@ impl PaletteManager {
             .collect()
     }
 
+    /// Find the device colors for a palette without an explicit mapping
+    ///
+    /// Uses the device color set named like the palette if there is one.
+    /// Otherwise picks the built-in palette with device colors that is
+    /// closest to `palette`, comparing colors index by index (mean RGB
+    /// Euclidean distance), and returns its device colors if that distance
+    /// is within the threshold (see
+    /// [`PaletteManager::set_device_colors_threshold`]). Only sets with as
+    /// many colors as the palette are considered; on equal distances the
+    /// alphabetically first palette wins.
+    pub fn auto_device_colors(&self, palette: &Palette) -> Option<Vec<Rgb>> {
+        let matching_length = |colors: &Vec<Rgb>| colors.len() == palette.len();
+        if let Ok(colors) = self.get_device_colors(&palette.name) {
+            return Some(colors).filter(matching_length);
+        }
+        if palette.is_empty() {
+            return None;
+        }
+
+        // Sorted names make ties go to the alphabetically first set
+        let mut best: Option<(f64, String)> = None;
+        for name in self.list_device_colors() {
+            let Ok(builtin) = self.get_palette(&name) else {
+                continue;
+            };
+            if builtin.len() != palette.len() {
+                continue;
+            }
+            let distance = palette
+                .colors
+                .iter()
+                .zip(&builtin.colors)
+                .map(|(a, b)| euclidean_distance(a, b))
+                .sum::<f64>()
+                / palette.len() as f64;
+            if best
+                .as_ref()
+                .is_none_or(|(best_distance, _)| distance < *best_distance)
+            {
+                best = Some((distance, name));
+            }
+        }
+
+        best.filter(|(distance, _)| *distance <= self.device_colors_threshold)
+            .and_then(|(_, name)| self.get_device_colors(&name).ok())
+    }
+
+    /// Set the largest mean color distance at which
+    /// [`PaletteManager::auto_device_colors`] accepts a similar palette
+    /// (default 10)
+    pub fn set_device_colors_threshold(&mut self, threshold: f64) {
+        self.device_colors_threshold = threshold;
+    }
+
     /// List all available palette names
     pub fn list_palettes(&self) -> Vec<String> {
         let mut names: Vec<_> = self.palettes.keys().cloned().collect();
@@ -114,6 +977,63 @@ impl PaletteManager {
         names
     }
 
+    /// Find the palette colors closest to `target` across all palettes
+    ///
+    /// Returns up to `top_n` `(palette_name, color)` pairs sorted by
+    /// ascending distance. Ties keep palette name order.
+    pub fn search_by_color(
+        &self,
+        target: &Rgb,
+        metric: DistanceMetric,
+        top_n: usize,
+    ) -> Vec<(String, Rgb)> {
+        let mut matches: Vec<(f64, String, Rgb)> = self
+            .list_palettes()
+            .into_iter()
+            .filter_map(|name| self.get_palette(&name).ok())
+            .flat_map(|palette| {
+                palette.colors.into_iter().map(move |color| {
+                    let distance = color_distance(target, &color, metric);
+                    (distance, palette.name.clone(), color)
+                })
+            })
+            .collect();
+
+        matches.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        matches
+            .into_iter()
+            .take(top_n)
+            .map(|(_, name, color)| (name, color))
+            .collect()
+    }
+
+    /// Pick the candidate palette that reproduces `img` with the least error
+    ///
+    /// Each candidate quantizes a 10% downsampled copy of the image (no
+    /// dithering) and is scored by the mean squared error; the lowest
+    /// score wins, with ties going to the earlier candidate.
+    pub fn closest_palette(&self, img: &RgbImage, candidates: &[&str]) -> Result<String> {
+        let width = (img.width() / 10).max(1);
+        let height = (img.height() / 10).max(1);
+        let sample = image::imageops::resize(img, width, height, FilterType::Triangle);
+
+        let mut best: Option<(f64, &str)> = None;
+        for &name in candidates {
+            let options = DitherOptions {
+                algorithm: DitheringAlgorithm::QuantizationOnly,
+                palette: self.get_palette(name)?,
+                ..Default::default()
+            };
+            let (_, stats) = engine::dither_image_copy(&sample, &options)?;
+            if best.is_none_or(|(error, _)| stats.mse < error) {
+                best = Some((stats.mse, name));
+            }
+        }
+
+        best.map(|(_, name)| name.to_string())
+            .ok_or_else(|| anyhow!("No candidate palettes given"))
+    }
+
     /// List all available device color sets
     pub fn list_device_colors(&self) -> Vec<String> {
         let mut names: Vec<_> = self.device_colors.keys().cloned().collect();
@@ -155,4 +1075,555 @@ mod tests {
         assert!(palettes.contains(&"default".to_string()));
         assert!(palettes.contains(&"spectra6".to_string()));
     }
+
+    #[test]
+    fn test_palette_inverted() {
+        let palette = Palette::from_hex_strings("test", &["#000000", "#FF8000"]).unwrap();
+        let inverted = palette.inverted();
+        assert_eq!(inverted.colors[0], Rgb::new(255, 255, 255));
+        assert_eq!(inverted.colors[1], Rgb::new(0, 127, 255));
+
+        let restored = inverted.inverted();
+        assert_eq!(restored.name, palette.name);
+        assert_eq!(restored.colors, palette.colors);
+    }
+
+    #[test]
+    fn test_palette_equality_and_hash() {
+        use std::collections::HashSet;
+
+        let a = Palette::from_hex_strings("bw", &["#000000", "#FFFFFF"]).unwrap();
+        let b = Palette::new("bw", vec![Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)]);
+        let renamed = Palette::new("other", b.colors.clone());
+        assert_eq!(a, b);
+        assert_ne!(a, renamed);
+
+        let set: HashSet<Palette> = [a, b, renamed].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn test_palette_iterators() {
+        let palette = Palette::default();
+        let other = Palette::new("rg", vec![Rgb::new(255, 0, 0), Rgb::new(0, 255, 0)]);
+
+        let mut seen = Vec::new();
+        for color in &palette {
+            seen.push(*color);
+        }
+        assert_eq!(seen, palette.colors);
+
+        let doubled: Palette = palette.clone().into_iter().chain(other.clone()).collect();
+        assert_eq!(doubled.name, "custom");
+        assert_eq!(doubled.len(), 4);
+        assert_eq!(&doubled.colors[..2], &palette.colors[..]);
+        assert_eq!(&doubled.colors[2..], &other.colors[..]);
+
+        let mut extended = palette.clone();
+        extended.extend(other.clone());
+        assert_eq!(extended.name, palette.name);
+        assert_eq!(extended.colors, doubled.colors);
+    }
+
+    #[test]
+    fn test_palette_ordering() {
+        let two = Palette::from_hex_strings("zeta", &["#000", "#FFF"]).unwrap();
+        let three = Palette::from_hex_strings("alpha", &["#000", "#FFF", "#F00"]).unwrap();
+        let two_named_a = Palette::from_hex_strings("alpha", &["#000", "#FFF"]).unwrap();
+
+        // Length first, then name
+        assert!(two < three);
+        assert!(two_named_a < two);
+
+        let mut palettes = vec![three.clone(), two.clone(), two_named_a.clone()];
+        palettes.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(palettes, vec![two_named_a, two, three]);
+    }
+
+    #[test]
+    fn test_reload_from_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("sunset.json"),
+            r##"["#FF4500", "#FFD700", "#000"]"##,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("device_sunset.json"),
+            r##"["#F00", "#FF0", "#000"]"##,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "not a palette").unwrap();
+
+        let mut manager = PaletteManager::new().unwrap();
+        assert!(manager.get_palette("sunset").is_err());
+
+        assert_eq!(manager.reload_from_directory(dir.path()).unwrap(), 2);
+        let sunset = manager.get_palette("sunset").unwrap();
+        assert_eq!(sunset.colors[0], Rgb::new(255, 69, 0));
+        assert_eq!(manager.get_device_colors("sunset").unwrap().len(), 3);
+
+        // Embedded palettes are still available
+        assert!(manager.get_palette("spectra6").is_ok());
+
+        // A plain reload drops the directory palettes again
+        manager.reload().unwrap();
+        assert!(manager.get_palette("sunset").is_err());
+    }
+
+    #[test]
+    fn test_reload_from_directory_is_atomic() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("good.json"), r##"["#000", "#FFF"]"##).unwrap();
+        std::fs::write(dir.path().join("bad.json"), r##"["#000", "not-a-color"]"##).unwrap();
+
+        let mut manager = PaletteManager::new().unwrap();
+        let before = manager.list_palettes();
+
+        assert!(manager.reload_from_directory(dir.path()).is_err());
+        assert_eq!(manager.list_palettes(), before);
+        assert!(manager.get_palette("good").is_err());
+    }
+
+    #[test]
+    fn test_distance_matrix() {
+        let manager = PaletteManager::new().unwrap();
+        let palette = manager.get_palette("spectra6").unwrap();
+
+        for metric in [
+            DistanceMetric::Euclidean,
+            DistanceMetric::RedMean,
+            DistanceMetric::CieLab,
+            DistanceMetric::Oklab,
+        ] {
+            let matrix = palette.distance_matrix(metric);
+            assert_eq!(matrix.len(), palette.len());
+            for (i, row) in matrix.iter().enumerate() {
+                assert_eq!(row.len(), palette.len());
+                assert_eq!(row[i], 0.0);
+                for (j, &distance) in row.iter().enumerate() {
+                    assert_eq!(distance, matrix[j][i]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_min_separation() {
+        let bw = Palette::default();
+        let separation = bw.min_separation(DistanceMetric::Euclidean);
+        assert!((separation - 441.67).abs() < 0.01);
+        assert!(bw.is_well_separated(400.0, DistanceMetric::Euclidean));
+        assert!(!bw.is_well_separated(500.0, DistanceMetric::Euclidean));
+
+        let near_duplicates = Palette::new(
+            "dupes",
+            vec![
+                Rgb::new(0, 0, 0),
+                Rgb::new(255, 255, 255),
+                Rgb::new(1, 0, 0),
+            ],
+        );
+        assert_eq!(
+            near_duplicates.min_separation(DistanceMetric::Euclidean),
+            1.0
+        );
+
+        let single = Palette::new("single", vec![Rgb::new(0, 0, 0)]);
+        assert_eq!(
+            single.min_separation(DistanceMetric::Euclidean),
+            f64::INFINITY
+        );
+    }
+
+    #[test]
+    fn test_to_nearest_web_safe() {
+        assert_eq!(WEB_SAFE_PALETTE.len(), 216);
+        assert_eq!(WEB_SAFE_COLORS[0], Rgb::new(0, 0, 0));
+        assert_eq!(WEB_SAFE_COLORS[215], Rgb::new(255, 255, 255));
+
+        let palette = Palette::new(
+            "test",
+            vec![
+                Rgb::new(100, 100, 100),
+                Rgb::new(25, 26, 230),
+                Rgb::new(255, 128, 0),
+            ],
+        );
+        let snapped = palette.to_nearest_web_safe();
+        assert_eq!(snapped.name, "test");
+        assert_eq!(snapped.colors[0], Rgb::new(102, 102, 102));
+        assert_eq!(snapped.colors[1], Rgb::new(0, 51, 255));
+        for color in &snapped {
+            assert!(
+                WEB_SAFE_PALETTE.colors.contains(color),
+                "{:?} is not web-safe",
+                color
+            );
+            let (_, nearest) = WEB_SAFE_PALETTE.nearest_color(color).unwrap();
+            assert_eq!(nearest, color);
+        }
+    }
+
+    #[test]
+    fn test_perceptual_sort() {
+        let gray = |v: u8| Rgb::new(v, v, v);
+        let palette = Palette::new(
+            "shuffled",
+            vec![
+                gray(128),
+                Rgb::new(150, 120, 110),
+                gray(0),
+                gray(255),
+                gray(64),
+                gray(192),
+            ],
+        );
+        let lightness = |color: &Rgb| convert::rgb_to_lab(color)[0];
+
+        for metric in [DistanceMetric::CieLab, DistanceMetric::Oklab] {
+            let sorted = palette.perceptual_sort(metric);
+            assert_eq!(sorted.len(), palette.len());
+            assert!(palette.colors.iter().all(|c| sorted.colors.contains(c)));
+
+            let darkest = palette
+                .colors
+                .iter()
+                .map(lightness)
+                .fold(f64::INFINITY, f64::min);
+            let lightest = palette.colors.iter().map(lightness).fold(0.0, f64::max);
+            assert_eq!(lightness(&sorted.colors[0]), darkest);
+            assert_eq!(lightness(sorted.colors.last().unwrap()), lightest);
+
+            let mut reversed = palette.perceptual_sort_reverse(metric);
+            assert_eq!(reversed.colors[0], gray(255));
+            reversed.colors.reverse();
+            assert_eq!(reversed, sorted);
+        }
+
+        assert!(Palette::new("empty", vec![])
+            .perceptual_sort(DistanceMetric::Oklab)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_search_by_color() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("primary.json"),
+            r##"["#FF0000", "#00FF00", "#0000FF"]"##,
+        )
+        .unwrap();
+        let manager = PaletteManager::from_directory(dir.path()).unwrap();
+
+        let red = Rgb::new(255, 0, 0);
+        let matches = manager.search_by_color(&red, DistanceMetric::Euclidean, 5);
+        assert_eq!(matches.len(), 5);
+        assert_eq!(matches[0], ("primary".to_string(), red));
+        assert_eq!(
+            color_distance(&red, &matches[0].1, DistanceMetric::Euclidean),
+            0.0
+        );
+
+        // Results are sorted by ascending distance
+        let distances: Vec<f64> = matches
+            .iter()
+            .map(|(_, color)| color_distance(&red, color, DistanceMetric::Euclidean))
+            .collect();
+        assert!(distances.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn test_hex_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sunset.hex");
+        std::fs::write(
+            &path,
+            "// Sunset palette\n#1A1C2C\n  #5D275D  \n\n#B13E53\n#EF7D57\n#FFF\n",
+        )
+        .unwrap();
+
+        let palette = Palette::from_hex_file(&path).unwrap();
+        assert_eq!(palette.name, "sunset");
+        assert_eq!(palette.len(), 5);
+        assert_eq!(palette.colors[4], Rgb::new(255, 255, 255));
+
+        let copy_dir = tempfile::tempdir().unwrap();
+        let copy = copy_dir.path().join("sunset.hex");
+        palette.to_hex_file(&copy).unwrap();
+        assert_eq!(Palette::from_hex_file(&copy).unwrap(), palette);
+
+        std::fs::write(&path, "#000000\nnot-a-color\n").unwrap();
+        let err = Palette::from_hex_file(&path).unwrap_err();
+        assert!(format!("{:#}", err).contains("line 2"));
+
+        std::fs::write(&path, "// Empty palette\n\n   \n").unwrap();
+        let err = Palette::from_hex_file(&path).unwrap_err();
+        assert!(format!("{:#}", err).contains("No colors"));
+    }
+
+    #[test]
+    fn test_closest_palette() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("warm.json"), r##"["#000000", "#E01010"]"##).unwrap();
+        std::fs::write(dir.path().join("cool.json"), r##"["#0000FF", "#00FF00"]"##).unwrap();
+        let manager = PaletteManager::from_directory(dir.path()).unwrap();
+
+        let red = RgbImage::from_pixel(40, 30, image::Rgb([255, 0, 0]));
+        assert_eq!(
+            manager.closest_palette(&red, &["cool", "warm"]).unwrap(),
+            "warm"
+        );
+
+        assert!(manager.closest_palette(&red, &[]).is_err());
+        assert!(manager.closest_palette(&red, &["missing"]).is_err());
+    }
+
+    #[test]
+    fn test_from_css_str() {
+        let css = r##":root {
+            --palette-brand-primary: #FF0000;
+            --palette-brand-accent: #00ff00;
+            --palette-night-sky: #000033;
+            --palette-brand-broken: red;
+            --palette-night-star: #FFFFEE;
+            --color-ink: #111111;
+            --spacing-small: 4px;
+        }"##;
+
+        let palettes = Palette::from_css_str(css, "site");
+        let names: Vec<&str> = palettes.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["brand", "night", "site"]);
+
+        assert_eq!(
+            palettes[0].colors,
+            [Rgb::new(255, 0, 0), Rgb::new(0, 255, 0)]
+        );
+        assert_eq!(
+            palettes[1].colors,
+            [Rgb::new(0, 0, 0x33), Rgb::new(0xFF, 0xFF, 0xEE)]
+        );
+        assert_eq!(palettes[2].colors, [Rgb::new(0x11, 0x11, 0x11)]);
+    }
+
+    #[test]
+    fn test_save_to_directory_round_trip() {
+        let manager = PaletteManager::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let export_dir = dir.path().join("exported");
+
+        let written = manager.save_to_directory(&export_dir).unwrap();
+        assert_eq!(
+            written,
+            manager.list_palettes().len() + manager.list_device_colors().len()
+        );
+        assert_eq!(std::fs::read_dir(&export_dir).unwrap().count(), written);
+
+        let mut reloaded = PaletteManager::new().unwrap();
+        assert_eq!(
+            reloaded.reload_from_directory(&export_dir).unwrap(),
+            written
+        );
+        for name in manager.list_palettes() {
+            assert_eq!(
+                reloaded.get_palette(&name).unwrap(),
+                manager.get_palette(&name).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_generate_wcag_compliant_palette() {
+        let white = Rgb::new(255, 255, 255);
+        let palette = generate_wcag_compliant_palette(white, 4.5, 4).unwrap();
+
+        assert_eq!(palette.len(), 4);
+        assert_eq!(palette.colors[0], Rgb::new(0, 0, 0));
+        for color in &palette {
+            let ratio = convert::contrast_ratio(color, &white);
+            assert!(ratio >= 4.5, "{:?} has contrast {}", color, ratio);
+        }
+        for (i, a) in palette.colors.iter().enumerate() {
+            assert!(
+                !palette.colors[i + 1..].contains(a),
+                "Duplicate color {:?}",
+                a
+            );
+        }
+
+        let gray = Rgb::new(128, 128, 128);
+        let palette = generate_wcag_compliant_palette(gray, 3.0, 2).unwrap();
+        assert!(palette
+            .colors
+            .iter()
+            .all(|c| convert::contrast_ratio(c, &gray) >= 3.0));
+
+        // Nothing reaches 21:1 against mid-gray
+        assert!(generate_wcag_compliant_palette(gray, 21.0, 1).is_err());
+        assert!(generate_wcag_compliant_palette(white, 4.5, 0).is_err());
+    }
+
+    #[cfg(feature = "lospec")]
+    mod lospec {
+        use super::*;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        async fn mock_server() -> MockServer {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/palette-list/sweetie-4.json"))
+                .respond_with(ResponseTemplate::new(200).set_body_string(
+                    r#"{"name": "Sweetie 4", "colors": ["1a1c2c", "5d275d", "b13e53", "ffcd75"]}"#,
+                ))
+                .mount(&server)
+                .await;
+            Mock::given(method("GET"))
+                .respond_with(ResponseTemplate::new(404))
+                .mount(&server)
+                .await;
+            server
+        }
+
+        #[tokio::test]
+        async fn test_from_lospec() {
+            let server = mock_server().await;
+            let base_url = format!("{}/palette-list", server.uri());
+
+            let palette = Palette::fetch_lospec(&base_url, "sweetie-4").await.unwrap();
+            assert_eq!(palette.name, "sweetie-4");
+            assert_eq!(palette.colors[0], Rgb::new(0x1a, 0x1c, 0x2c));
+            assert_eq!(palette.colors[3], Rgb::new(0xff, 0xcd, 0x75));
+
+            let error = Palette::fetch_lospec(&base_url, "missing")
+                .await
+                .unwrap_err();
+            assert!(error.to_string().contains("not found"), "{}", error);
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn test_from_lospec_blocking() {
+            let server = mock_server().await;
+            let base_url = format!("{}/palette-list", server.uri());
+
+            let result = tokio::task::spawn_blocking(move || {
+                (
+                    Palette::fetch_lospec_blocking(&base_url, "sweetie-4"),
+                    Palette::fetch_lospec_blocking(&base_url, "missing"),
+                )
+            })
+            .await
+            .unwrap();
+            assert_eq!(result.0.unwrap().len(), 4);
+            assert!(result.1.is_err());
+        }
+
+        #[test]
+        fn test_lospec_response_parsing() {
+            let ok = reqwest::StatusCode::OK;
+            assert!(Palette::from_lospec_response("x", ok, r#"{"colors": []}"#).is_err());
+            assert!(Palette::from_lospec_response("x", ok, r#"{"colors": ["zzzzzz"]}"#).is_err());
+            assert!(Palette::from_lospec_response("x", ok, "<html>").is_err());
+            let error = reqwest::StatusCode::INTERNAL_SERVER_ERROR;
+            assert!(Palette::from_lospec_response("x", error, "{}").is_err());
+        }
+    }
+
+    #[test]
+    fn test_sample_at_positions() {
+        // Left half red, right half blue, with a noisy pixel in the red patch
+        let mut img = RgbImage::from_fn(20, 10, |x, _| {
+            image::Rgb(if x < 10 { [200, 0, 0] } else { [0, 0, 200] })
+        });
+        img.put_pixel(4, 5, image::Rgb([210, 10, 10]));
+
+        let colors = Palette::sample_at_positions(&img, &[(4, 5), (15, 5), (0, 0)], 2).unwrap();
+        // 13 pixels in the radius-2 circle, one of them 10 higher
+        assert_eq!(
+            colors,
+            vec![
+                Rgb::new(201, 1, 1),
+                Rgb::new(0, 0, 200),
+                Rgb::new(200, 0, 0)
+            ]
+        );
+        assert_eq!(
+            Palette::sample_at_positions(&img, &[(4, 5)], 0).unwrap()[0].0,
+            [210, 10, 10]
+        );
+        assert!(Palette::sample_at_positions(&img, &[(20, 5)], 2).is_err());
+        // A radius far beyond the image averages the whole image without
+        // overflowing or walking the full square
+        assert_eq!(
+            Palette::sample_at_positions(&img, &[(15, 5)], u32::MAX).unwrap()[0].0,
+            [100, 0, 100]
+        );
+
+        let palette = Palette::from_sample_positions("chart", &img, &[(15, 2), (2, 8)], 1).unwrap();
+        assert_eq!(palette.name, "chart");
+        assert_eq!(
+            palette.colors,
+            vec![Rgb::new(0, 0, 200), Rgb::new(200, 0, 0)]
+        );
+    }
+
+    #[cfg(feature = "icc")]
+    #[test]
+    fn test_apply_icc_profile_identity() {
+        let srgb = IccProfile::srgb().unwrap();
+        let palette = Palette::new(
+            "test",
+            vec![
+                Rgb::new(0, 0, 0),
+                Rgb::new(255, 255, 255),
+                Rgb::new(200, 40, 90),
+            ],
+        );
+        let converted = palette.apply_icc_profile(&srgb, &srgb).unwrap();
+
+        assert_eq!(converted.name, "test");
+        for (a, b) in palette.colors.iter().zip(&converted.colors) {
+            assert!(
+                a.0.iter().zip(b.0).all(|(&x, y)| x.abs_diff(y) <= 1),
+                "{:?} vs {:?}",
+                a,
+                b
+            );
+        }
+        assert!(palette
+            .apply_icc_profile(&IccProfile(vec![1, 2, 3]), &srgb)
+            .is_err());
+    }
+
+    #[test]
+    fn test_auto_device_colors() {
+        let mut manager = PaletteManager::new().unwrap();
+        let spectra6 = manager.get_palette("spectra6").unwrap();
+        let device_colors = manager.get_device_colors("spectra6").unwrap();
+        assert_eq!(
+            manager.auto_device_colors(&spectra6),
+            Some(device_colors.clone())
+        );
+
+        let shifted = Palette::new(
+            "custom",
+            spectra6
+                .colors
+                .iter()
+                .map(|c| Rgb(c.0.map(|v| v.saturating_add(1))))
+                .collect(),
+        );
+        assert_eq!(manager.auto_device_colors(&shifted), Some(device_colors));
+
+        let unrelated = Palette::new(
+            "custom",
+            (0..spectra6.len())
+                .map(|i| Rgb::new(128, (i * 20) as u8, 64))
+                .collect(),
+        );
+        assert_eq!(manager.auto_device_colors(&unrelated), None);
+
+        manager.set_device_colors_threshold(0.5);
+        assert_eq!(manager.auto_device_colors(&shifted), None);
+    }
 }
@@ -0,0 +1,356 @@
+//! Composable preprocessing steps applied before dithering
+
+use super::{
+    adjust,
+    convert::{remove_srgb_gamma, rgb_to_luma},
+    Rgb,
+};
+use image::RgbImage;
+use std::sync::Arc;
+
+/// One image adjustment in a preprocessing chain
+pub trait Preprocessor: Send + Sync {
+    /// Adjust `img` in place
+    fn apply(&self, img: &mut RgbImage);
+
+    /// Name shown in logs and reports
+    fn name(&self) -> &str;
+}
+
+impl std::fmt::Debug for dyn Preprocessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Preprocessor({})", self.name())
+    }
+}
+
+/// A preprocessor chosen at runtime
+pub type BoxedPreprocessor = Box<dyn Preprocessor>;
+
+/// Apply every preprocessor of a chain in order
+pub fn apply_chain(img: &mut RgbImage, chain: &[BoxedPreprocessor]) {
+    for preprocessor in chain {
+        preprocessor.apply(img);
+    }
+}
+
+/// Preprocessors applied by the dithering entry points, in order
+///
+/// Steps are shared, so cloning the chain (and the options holding it) is
+/// cheap. Two chains are equal when they hold the same step instances.
+#[derive(Clone, Default)]
+pub struct PreprocessingChain(Vec<Arc<dyn Preprocessor>>);
+
+impl PreprocessingChain {
+    /// Append a step to the end of the chain
+    pub fn push(&mut self, preprocessor: BoxedPreprocessor) {
+        self.0.push(Arc::from(preprocessor));
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Apply every step in order
+    pub fn apply(&self, img: &mut RgbImage) {
+        for preprocessor in &self.0 {
+            preprocessor.apply(img);
+        }
+    }
+}
+
+impl std::fmt::Debug for PreprocessingChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(&self.0).finish()
+    }
+}
+
+impl PartialEq for PreprocessingChain {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len() && self.0.iter().zip(&other.0).all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+}
+
+impl Eq for PreprocessingChain {}
+
+impl std::hash::Hash for PreprocessingChain {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        for preprocessor in &self.0 {
+            preprocessor.name().hash(state);
+        }
+    }
+}
+
+/// A closure used as a preprocessing step, named "lambda"
+pub struct LambdaPreprocessor<F>(pub F);
+
+impl<F: Fn(&mut RgbImage) + Send + Sync> Preprocessor for LambdaPreprocessor<F> {
+    fn apply(&self, img: &mut RgbImage) {
+        (self.0)(img);
+    }
+
+    fn name(&self) -> &str {
+        "lambda"
+    }
+}
+
+/// Share of pixels clipped at each end by [`AutoLevels`]
+const AUTO_LEVELS_CLIP: f64 = 0.005;
+
+/// Stretch the luma range so the darkest and lightest pixels reach 0 and 255
+///
+/// The darkest and lightest 0.5% of pixels are clipped so a few outliers
+/// do not prevent the stretch. All channels use the same mapping, which
+/// keeps hues unchanged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoLevels;
+
+impl Preprocessor for AutoLevels {
+    fn apply(&self, img: &mut RgbImage) {
+        let mut histogram = [0usize; 256];
+        for pixel in img.pixels() {
+            histogram[rgb_to_luma(&Rgb(pixel.0)) as usize] += 1;
+        }
+
+        let clip = (img.pixels().len() as f64 * AUTO_LEVELS_CLIP) as usize;
+        let level_at = |mut levels: Box<dyn Iterator<Item = usize>>| {
+            let mut seen = 0;
+            levels
+                .find(|&level| {
+                    seen += histogram[level];
+                    seen > clip
+                })
+                .unwrap_or(0)
+        };
+        let low = level_at(Box::new(0..256));
+        let high = level_at(Box::new((0..256).rev()));
+        if high <= low {
+            return;
+        }
+
+        let scale = 255.0 / (high - low) as f64;
+        let lut: [u8; 256] = std::array::from_fn(|i| {
+            ((i as f64 - low as f64) * scale).round().clamp(0.0, 255.0) as u8
+        });
+        adjust::apply_curves(img, &lut);
+    }
+
+    fn name(&self) -> &str {
+        "auto-levels"
+    }
+}
+
+/// Sharpen by adding back the difference to a Gaussian-blurred copy
+///
+/// `radius` is the blur sigma in pixels and `amount` the share of the
+/// difference added back. Differences below `threshold` are left alone so
+/// flat areas and noise are not amplified.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnsharpMask {
+    pub radius: f32,
+    pub amount: f32,
+    pub threshold: u8,
+}
+
+impl Preprocessor for UnsharpMask {
+    fn apply(&self, img: &mut RgbImage) {
+        let blurred = image::imageops::blur(img, self.radius);
+        for (value, &smooth) in img.iter_mut().zip(blurred.iter()) {
+            let difference = *value as f32 - smooth as f32;
+            if difference.abs() >= self.threshold as f32 {
+                *value = (*value as f32 + self.amount * difference)
+                    .round()
+                    .clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "unsharp-mask"
+    }
+}
+
+/// Compensate a display gamma by raising values to `1 / gamma`
+///
+/// `gamma > 1` brightens midtones, which counters panels that render
+/// darker than sRGB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GammaCorrection {
+    pub gamma: f64,
+}
+
+impl Preprocessor for GammaCorrection {
+    fn apply(&self, img: &mut RgbImage) {
+        if self.gamma > 0.0 {
+            adjust::apply_curves(img, &adjust::gamma_lut(1.0 / self.gamma));
+        }
+    }
+
+    fn name(&self) -> &str {
+        "gamma-correction"
+    }
+}
+
+/// Gamma handling chosen by transfer function
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GammaPreprocessor {
+    /// Same as [`GammaCorrection`] with this gamma
+    PowerLaw(f64),
+    /// Replace each channel with its sRGB-decoded value, stored as 8 bits;
+    /// see [`crate::color::convert::remove_srgb_gamma`]
+    ///
+    /// The palette stays in sRGB, so this works as a strong darkening curve
+    /// rather than dithering in linear light: mid gray 128 becomes 55, and
+    /// the shadows collapse onto a few levels, which shows as banding.
+    SRgb,
+}
+
+impl Preprocessor for GammaPreprocessor {
+    fn apply(&self, img: &mut RgbImage) {
+        match *self {
+            GammaPreprocessor::PowerLaw(gamma) => GammaCorrection { gamma }.apply(img),
+            GammaPreprocessor::SRgb => {
+                let lut = std::array::from_fn(|i| {
+                    (remove_srgb_gamma(i as f64 / 255.0) * 255.0).round() as u8
+                });
+                adjust::apply_curves(img, &lut);
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            GammaPreprocessor::PowerLaw(_) => "gamma-correction",
+            GammaPreprocessor::SRgb => "srgb-linearize",
+        }
+    }
+}
+
+/// 3×3 Laplacian sharpening, strengthening every edge
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sharpen;
+
+impl Preprocessor for Sharpen {
+    fn apply(&self, img: &mut RgbImage) {
+        #[rustfmt::skip]
+        const KERNEL: [f32; 9] = [
+             0.0, -1.0,  0.0,
+            -1.0,  5.0, -1.0,
+             0.0, -1.0,  0.0,
+        ];
+        *img = image::imageops::filter3x3(img, &KERNEL);
+    }
+
+    fn name(&self) -> &str {
+        "sharpen"
+    }
+}
+
+/// Strength of the S-curve used by [`ContrastBoost`]
+const CONTRAST_BOOST_STRENGTH: f64 = 0.5;
+
+/// Steepen midtone contrast with an S-curve, keeping black and white fixed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContrastBoost;
+
+impl Preprocessor for ContrastBoost {
+    fn apply(&self, img: &mut RgbImage) {
+        adjust::apply_curves(img, &adjust::s_curve_lut(CONTRAST_BOOST_STRENGTH));
+    }
+
+    fn name(&self) -> &str {
+        "contrast-boost"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auto_levels_stretches_range() {
+        let mut img = RgbImage::from_fn(100, 1, |x, _| image::Rgb([(50 + x) as u8; 3]));
+        AutoLevels.apply(&mut img);
+
+        assert_eq!(img.get_pixel(0, 0).0, [0; 3]);
+        assert_eq!(img.get_pixel(99, 0).0, [255; 3]);
+    }
+
+    #[test]
+    fn test_unsharp_mask_threshold() {
+        let flat = RgbImage::from_pixel(8, 8, image::Rgb([100, 100, 100]));
+        let mut img = flat.clone();
+        UnsharpMask {
+            radius: 1.0,
+            amount: 1.0,
+            threshold: 3,
+        }
+        .apply(&mut img);
+        assert_eq!(img, flat);
+
+        // An edge gets steeper on both sides
+        let mut edge =
+            RgbImage::from_fn(8, 1, |x, _| image::Rgb([if x < 4 { 80 } else { 160 }; 3]));
+        UnsharpMask {
+            radius: 1.0,
+            amount: 1.0,
+            threshold: 3,
+        }
+        .apply(&mut edge);
+        assert!(edge.get_pixel(3, 0)[0] < 80);
+        assert!(edge.get_pixel(4, 0)[0] > 160);
+    }
+
+    #[test]
+    fn test_chain_order() {
+        let chain: Vec<BoxedPreprocessor> = vec![
+            Box::new(GammaCorrection { gamma: 2.0 }),
+            Box::new(ContrastBoost),
+        ];
+        let mut img = RgbImage::from_pixel(2, 2, image::Rgb([64, 64, 64]));
+        apply_chain(&mut img, &chain);
+
+        // sqrt(64 / 255) * 255 = 128, which the S-curve keeps fixed
+        assert_eq!(img.get_pixel(0, 0).0, [128; 3]);
+        assert_eq!(format!("{:?}", chain[1]), "Preprocessor(contrast-boost)");
+    }
+
+    #[test]
+    fn test_preprocessing_chain() {
+        let mut chain = PreprocessingChain::default();
+        chain.push(Box::new(LambdaPreprocessor(|img: &mut RgbImage| {
+            img.fill(10)
+        })));
+        chain.push(Box::new(GammaCorrection { gamma: 1.0 }));
+        let mut img = RgbImage::new(2, 2);
+        chain.apply(&mut img);
+
+        assert_eq!(img.get_pixel(1, 1).0, [10; 3]);
+        assert_eq!(
+            format!("{:?}", chain),
+            "[Preprocessor(lambda), Preprocessor(gamma-correction)]"
+        );
+        assert_eq!(chain.clone(), chain);
+        assert_ne!(chain, PreprocessingChain::default());
+    }
+
+    #[test]
+    fn test_gamma_preprocessor() {
+        let source = RgbImage::from_fn(256, 1, |x, _| image::Rgb([x as u8; 3]));
+
+        let mut power_law = source.clone();
+        GammaPreprocessor::PowerLaw(2.0).apply(&mut power_law);
+        let mut corrected = source.clone();
+        GammaCorrection { gamma: 2.0 }.apply(&mut corrected);
+        assert_eq!(power_law, corrected);
+
+        let mut linear = source;
+        GammaPreprocessor::SRgb.apply(&mut linear);
+        // sRGB 50% gray is about 21.4% linear light
+        assert_eq!(linear.get_pixel(128, 0).0, [55; 3]);
+        assert_eq!(linear.get_pixel(255, 0).0, [255; 3]);
+    }
+}
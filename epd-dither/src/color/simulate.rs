@@ -0,0 +1,89 @@
+//! Perceptual simulation of how a display looks from a distance
+
+use anyhow::Result;
+use image::RgbImage;
+
+/// Finest detail the eye resolves, in cycles per degree of visual angle
+pub const EYE_RESOLUTION_CYCLES_PER_DEGREE: f64 = 30.0;
+
+const CM_PER_INCH: f64 = 2.54;
+
+/// Gaussian sigma in pixels matching the eye's resolution limit
+///
+/// One degree of visual angle spans `distance * tan(1°)` cm on the display,
+/// which gives the finest resolvable frequency in cycles per cm. The sigma
+/// is `pixels_per_cm / (2π * cycles_per_cm)`.
+pub fn eye_blur_sigma(viewer_distance_cm: f64, display_ppi: u32) -> f64 {
+    let cm_per_degree = viewer_distance_cm * 1f64.to_radians().tan();
+    let cycles_per_cm = EYE_RESOLUTION_CYCLES_PER_DEGREE / cm_per_degree;
+    let pixels_per_cm = display_ppi as f64 / CM_PER_INCH;
+
+    pixels_per_cm / (2.0 * std::f64::consts::PI * cycles_per_cm)
+}
+
+/// Approximate how an image on a display looks from `viewer_distance_cm`
+///
+/// Blurs with [`eye_blur_sigma`] so dither dots merge as they do for the
+/// eye. Distance and PPI must be positive.
+pub fn simulate_display(
+    img: &RgbImage,
+    viewer_distance_cm: f64,
+    display_ppi: u32,
+) -> Result<RgbImage> {
+    if viewer_distance_cm.is_nan() || viewer_distance_cm <= 0.0 {
+        anyhow::bail!(
+            "Viewing distance must be positive, got {} cm",
+            viewer_distance_cm
+        );
+    }
+    if display_ppi == 0 {
+        anyhow::bail!("Display PPI must be positive");
+    }
+
+    let sigma = eye_blur_sigma(viewer_distance_cm, display_ppi);
+    Ok(image::imageops::blur(img, sigma as f32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Energy of differences between horizontally and vertically adjacent
+    /// pixels, a cheap stand-in for high-frequency spectral power
+    fn high_frequency_power(img: &RgbImage) -> f64 {
+        let mut power = 0.0;
+        for (x, y, pixel) in img.enumerate_pixels() {
+            for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                if nx < img.width() && ny < img.height() {
+                    let neighbor = img.get_pixel(nx, ny);
+                    for (a, b) in pixel.0.iter().zip(neighbor.0.iter()) {
+                        power += (*a as f64 - *b as f64).powi(2);
+                    }
+                }
+            }
+        }
+        power
+    }
+
+    #[test]
+    fn test_simulate_display_removes_high_frequencies() {
+        let checker = RgbImage::from_fn(32, 32, |x, y| {
+            image::Rgb(if (x + y) % 2 == 0 {
+                [0, 0, 0]
+            } else {
+                [255, 255, 255]
+            })
+        });
+
+        let simulated = simulate_display(&checker, 100.0, 200).unwrap();
+        assert_eq!(simulated.dimensions(), checker.dimensions());
+        assert!(high_frequency_power(&simulated) < high_frequency_power(&checker) / 2.0);
+    }
+
+    #[test]
+    fn test_eye_blur_sigma_grows_with_distance() {
+        assert!(eye_blur_sigma(200.0, 200) > eye_blur_sigma(50.0, 200));
+        assert!(simulate_display(&RgbImage::new(4, 4), 0.0, 200).is_err());
+        assert!(simulate_display(&RgbImage::new(4, 4), 50.0, 0).is_err());
+    }
+}
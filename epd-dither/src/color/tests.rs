@@ -112,3 +112,81 @@ fn test_rgba_equality() {
     assert_eq!(rgba1, rgba2);
     assert_ne!(rgba1, rgba3);
 }
+
+#[test]
+fn test_rgb_blend() {
+    let black = Rgb::new(0, 0, 0);
+    let white = Rgb::new(255, 255, 255);
+    assert_eq!(black.blend(white, 0.5), Rgb::new(127, 127, 127));
+
+    let a = Rgb::new(12, 200, 99);
+    let b = Rgb::new(250, 3, 180);
+    assert_eq!(a.blend(b, 0.0), a);
+    assert_eq!(a.blend(b, 1.0), b);
+
+    // t is clamped to 0.0-1.0
+    assert_eq!(a.blend(b, -2.0), a);
+    assert_eq!(a.blend(b, 3.0), b);
+}
+
+#[test]
+fn test_rgb_blend_lab() {
+    let black = Rgb::new(0, 0, 0);
+    let white = Rgb::new(255, 255, 255);
+    assert_eq!(black.blend_lab(white, 0.0), black);
+    assert_eq!(black.blend_lab(white, 1.0), white);
+
+    // Lab midpoint is L* = 50, which is sRGB gray 119 rather than 127
+    let mid = black.blend_lab(white, 0.5);
+    assert!(mid.r().abs_diff(mid.g()) <= 1 && mid.g().abs_diff(mid.b()) <= 1);
+    assert!(mid.r().abs_diff(119) <= 1);
+}
+
+#[test]
+fn test_rgba_blend() {
+    let a = Rgba::new(0, 0, 0, 0);
+    let b = Rgba::new(255, 255, 255, 255);
+    assert_eq!(a.blend(b, 0.5), Rgba::new(127, 127, 127, 127));
+    assert_eq!(a.blend(b, 0.0), a);
+    assert_eq!(a.blend(b, 1.0), b);
+}
+
+#[test]
+fn test_rgb_hsl_hsv_methods() {
+    let close = |a: (f64, f64, f64), b: (f64, f64, f64)| {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9 && (a.2 - b.2).abs() < 1e-9
+    };
+
+    assert!(close(
+        Rgb::from_hsl(0.0, 1.0, 0.5).to_hsl(),
+        (0.0, 1.0, 0.5)
+    ));
+    assert!(close(
+        Rgb::from_hsv(0.0, 1.0, 1.0).to_hsv(),
+        (0.0, 1.0, 1.0)
+    ));
+
+    let color = Rgb::new(12, 200, 99);
+    let (h, s, l) = color.to_hsl();
+    assert_eq!(Rgb::from_hsl(h, s, l), color);
+    let (h, s, v) = color.to_hsv();
+    assert_eq!(Rgb::from_hsv(h, s, v), color);
+}
+
+#[test]
+fn test_rgb_arithmetic() {
+    assert_eq!(
+        Rgb::new(200, 100, 50) + Rgb::new(100, 200, 50),
+        Rgb::new(255, 255, 100)
+    );
+    assert_eq!(
+        Rgb::new(50, 100, 200) - Rgb::new(100, 40, 200),
+        Rgb::new(0, 60, 0)
+    );
+    assert_eq!(Rgb::new(100, 100, 100) * 2.0, Rgb::new(200, 200, 200));
+    assert_eq!(Rgb::new(100, 200, 3) * 1.5, Rgb::new(150, 255, 5));
+    assert_eq!(Rgb::new(100, 200, 3) * -1.0, Rgb::new(0, 0, 0));
+    assert_eq!(Rgb::new(100, 201, 255) / 2.0, Rgb::new(50, 101, 128));
+    let shifted = Rgb::new(1, 2, 3).saturating_add_i16([300, -300, i16::MAX]);
+    assert_eq!(shifted, Rgb::new(255, 0, 255));
+}
@@ -0,0 +1,148 @@
+//! Image content classification for choosing a dithering algorithm
+
+use crate::color::{convert::rgb_to_luma, Rgb};
+use image::RgbImage;
+use std::collections::HashMap;
+
+/// Broad kind of image content, used by [`super::suggest_algorithm`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageContentType {
+    /// Continuous-tone images with many colors and smooth gradients
+    Photo,
+    /// Sparse strokes on a flat background (drawings, diagrams)
+    LineArt,
+    /// Dense dark strokes on a light background (scanned or rendered text)
+    TextDocument,
+    /// Flat areas of a few colors (cartoons, icons, charts)
+    Illustration,
+}
+
+/// Number of most common colors considered "flat" content
+const DOMINANT_COLORS: usize = 16;
+/// Share of pixels the dominant colors must cover for non-photo content
+const DOMINANT_COVERAGE: f64 = 0.9;
+/// Largest channel spread of a pixel still counted as gray
+const GRAY_TOLERANCE: u8 = 16;
+/// Share of pixels that must be gray for line art and text
+const GRAY_COVERAGE: f64 = 0.95;
+/// Luma step between neighbors counted as an edge
+const EDGE_THRESHOLD: u8 = 64;
+/// Edge density separating text (dense strokes) from line art
+const TEXT_EDGE_DENSITY: f64 = 0.15;
+
+/// Guess the content type of an image from simple statistics
+///
+/// Images whose [`DOMINANT_COLORS`] most common colors cover less than
+/// 90% of the pixels are photos. Otherwise, gray images are text when at
+/// least 15% of pixels sit on a strong luma edge and line art when fewer
+/// do, and colored images are illustrations.
+pub fn detect_content_type(img: &RgbImage) -> ImageContentType {
+    let pixel_count = img.pixels().len();
+    if pixel_count == 0 {
+        return ImageContentType::Illustration;
+    }
+
+    let mut counts: HashMap<[u8; 3], usize> = HashMap::new();
+    for pixel in img.pixels() {
+        *counts.entry(pixel.0).or_insert(0) += 1;
+    }
+    let mut frequencies: Vec<usize> = counts.into_values().collect();
+    frequencies.sort_unstable_by(|a, b| b.cmp(a));
+    let dominant: usize = frequencies.iter().take(DOMINANT_COLORS).sum();
+    if (dominant as f64) < DOMINANT_COVERAGE * pixel_count as f64 {
+        return ImageContentType::Photo;
+    }
+
+    let gray = img
+        .pixels()
+        .filter(|p| {
+            let (min, max) = (p.0.iter().min().unwrap(), p.0.iter().max().unwrap());
+            max - min <= GRAY_TOLERANCE
+        })
+        .count();
+    if (gray as f64) < GRAY_COVERAGE * pixel_count as f64 {
+        return ImageContentType::Illustration;
+    }
+
+    if edge_density(img) >= TEXT_EDGE_DENSITY {
+        ImageContentType::TextDocument
+    } else {
+        ImageContentType::LineArt
+    }
+}
+
+/// Share of pixels whose right or lower neighbor differs strongly in luma
+fn edge_density(img: &RgbImage) -> f64 {
+    let (width, height) = img.dimensions();
+    let luma = |x: u32, y: u32| rgb_to_luma(&Rgb(img.get_pixel(x, y).0));
+
+    let mut edges = 0;
+    for y in 0..height {
+        for x in 0..width {
+            let value = luma(x, y);
+            let right = x + 1 < width && value.abs_diff(luma(x + 1, y)) >= EDGE_THRESHOLD;
+            let below = y + 1 < height && value.abs_diff(luma(x, y + 1)) >= EDGE_THRESHOLD;
+            if right || below {
+                edges += 1;
+            }
+        }
+    }
+
+    edges as f64 / (width as f64 * height as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: image::Rgb<u8> = image::Rgb([255, 255, 255]);
+    const BLACK: image::Rgb<u8> = image::Rgb([0, 0, 0]);
+
+    #[test]
+    fn test_detect_photo() {
+        let img = RgbImage::from_fn(64, 64, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 4) as u8, ((x * y) % 256) as u8])
+        });
+        assert_eq!(detect_content_type(&img), ImageContentType::Photo);
+    }
+
+    #[test]
+    fn test_detect_line_art() {
+        // A rectangle outline and a diagonal on white
+        let img = RgbImage::from_fn(128, 128, |x, y| {
+            if x == 16 || x == 112 || y == 16 || y == 112 || x == y {
+                BLACK
+            } else {
+                WHITE
+            }
+        });
+        assert_eq!(detect_content_type(&img), ImageContentType::LineArt);
+    }
+
+    #[test]
+    fn test_detect_text() {
+        // Lines of narrow vertical strokes, like glyphs
+        let img = RgbImage::from_fn(64, 64, |x, y| {
+            if x % 4 == 0 && y % 10 < 7 {
+                BLACK
+            } else {
+                WHITE
+            }
+        });
+        assert_eq!(detect_content_type(&img), ImageContentType::TextDocument);
+    }
+
+    #[test]
+    fn test_detect_illustration() {
+        let colors = [
+            [230, 40, 40],
+            [40, 160, 60],
+            [250, 220, 60],
+            [255, 255, 255],
+        ];
+        let img = RgbImage::from_fn(64, 64, |x, y| {
+            image::Rgb(colors[(x / 32 + (y / 32) * 2) as usize])
+        });
+        assert_eq!(detect_content_type(&img), ImageContentType::Illustration);
+    }
+}
@@ -1,8 +1,20 @@
 //! Device database and management for e-ink displays
 
+use crate::color::{
+    palette::PaletteManager,
+    preprocess::{
+        AutoLevels, BoxedPreprocessor, ContrastBoost, GammaCorrection, Sharpen, UnsharpMask,
+    },
+};
+use crate::dither::{DitheringAlgorithm, ErrorDiffusionKernel};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+pub mod heuristics;
+
+pub use heuristics::{detect_content_type, ImageContentType};
 
 /// Resolution of a display
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,6 +23,12 @@ pub struct Resolution {
     pub height: u32,
 }
 
+impl std::fmt::Display for Resolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}×{}", self.width, self.height)
+    }
+}
+
 /// Recommended settings for a device
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RecommendedSettings {
@@ -29,15 +47,173 @@ pub struct DeviceSpec {
     pub resolution: Resolution,
     pub ppi: u32,
     pub palette: String,
+    /// Highest rate of full-screen updates the panel supports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_framerate_hz: Option<f32>,
+    /// Highest rate of partial updates, or `None` without partial refresh
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partial_refresh_framerate_hz: Option<f32>,
     pub recommended_settings: RecommendedSettings,
 }
 
+impl DeviceSpec {
+    /// Serialize the specification as pretty-printed JSON
+    pub fn to_json_pretty(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize device spec")
+    }
+
+    /// Serialize the specification as TOML
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string_pretty(self).context("Failed to serialize device spec")
+    }
+
+    /// Check the specification for consistency
+    ///
+    /// Verifies that the resolution, size and PPI are non-zero, that a
+    /// palette is set and exists in `palette_manager`, and that the
+    /// recommended algorithm is a known algorithm name.
+    pub fn validate(&self, palette_manager: &PaletteManager) -> Result<()> {
+        if self.resolution.width == 0 || self.resolution.height == 0 {
+            anyhow::bail!(
+                "Device '{}' has invalid resolution {}x{} (width and height must be greater than 0)",
+                self.name,
+                self.resolution.width,
+                self.resolution.height
+            );
+        }
+
+        if self.size_inches.is_nan() || self.size_inches <= 0.0 {
+            anyhow::bail!(
+                "Device '{}' has invalid size {} inches (must be greater than 0)",
+                self.name,
+                self.size_inches
+            );
+        }
+
+        if self.ppi == 0 {
+            anyhow::bail!(
+                "Device '{}' has invalid PPI 0 (must be greater than 0)",
+                self.name
+            );
+        }
+
+        if self.palette.is_empty() {
+            anyhow::bail!("Device '{}' has no palette", self.name);
+        }
+
+        palette_manager
+            .get_palette(&self.palette)
+            .with_context(|| format!("Device '{}' uses an unknown palette", self.name))?;
+
+        if DitheringAlgorithm::from_name(&self.recommended_settings.algorithm).is_none() {
+            anyhow::bail!(
+                "Device '{}' recommends unknown algorithm '{}'",
+                self.name,
+                self.recommended_settings.algorithm
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Full-screen updates per second the panel can sustain
+    ///
+    /// Uses `max_framerate_hz` when known. Otherwise monochrome panels are
+    /// assumed to take 2 seconds per full refresh and color panels, which
+    /// drive several ink particle types, 15 seconds.
+    pub fn frames_per_second_for_full_update(&self) -> f32 {
+        self.max_framerate_hz
+            .unwrap_or(if self.palette_colors() <= 2 {
+                0.5
+            } else {
+                1.0 / 15.0
+            })
+    }
+
+    /// Whether the panel should be powered off (deep sleep) after each update
+    ///
+    /// Panels without partial refresh redraw the whole screen every time,
+    /// and their controllers must not stay powered between updates, or the
+    /// image slowly degrades.
+    pub fn requires_power_off_between_updates(&self) -> bool {
+        self.partial_refresh_framerate_hz.is_none()
+    }
+
+    /// Number of colors in the device's palette, or 2 if the palette is unknown
+    fn palette_colors(&self) -> usize {
+        PaletteManager::new()
+            .and_then(|manager| manager.get_palette(&self.palette))
+            .map_or(2, |palette| palette.len())
+    }
+
+    /// Preprocessing steps suited to this device and kind of image content
+    ///
+    /// * Photos are stretched with [`AutoLevels`], since e-paper has far less
+    ///   contrast than the screens photos are edited on. An [`UnsharpMask`]
+    ///   recovers detail lost to dithering, with a 0.5 px radius on panels
+    ///   of 200 PPI or more and 1 px below that, so the halo stays about a
+    ///   dot wide. Finally [`GammaCorrection`] lifts the midtones, which
+    ///   dithered e-paper renders too dark: 1.8 for monochrome panels and
+    ///   a gentler 1.4 for color panels, whose inks lose saturation when
+    ///   lightened.
+    /// * Line art is [`Sharpen`]ed so thin strokes survive quantization,
+    ///   then given a [`ContrastBoost`] to push anti-aliased grays to ink
+    ///   or paper.
+    /// * Text documents get [`AutoLevels`] to turn scanned paper white and
+    ///   ink black, then a [`ContrastBoost`]. They are not sharpened, since
+    ///   halos around glyphs hurt legibility more than soft edges.
+    /// * Illustrations only get [`AutoLevels`]; their flat colors are
+    ///   usually chosen deliberately.
+    pub fn optimal_preprocessing_chain(&self, content: ImageContentType) -> Vec<BoxedPreprocessor> {
+        match content {
+            ImageContentType::Photo => {
+                let radius = if self.ppi >= 200 { 0.5 } else { 1.0 };
+                let gamma = if self.palette_colors() <= 2 { 1.8 } else { 1.4 };
+                vec![
+                    Box::new(AutoLevels),
+                    Box::new(UnsharpMask {
+                        radius,
+                        amount: 0.3,
+                        threshold: 3,
+                    }),
+                    Box::new(GammaCorrection { gamma }),
+                ]
+            }
+            ImageContentType::LineArt => vec![Box::new(Sharpen), Box::new(ContrastBoost)],
+            ImageContentType::TextDocument => vec![Box::new(AutoLevels), Box::new(ContrastBoost)],
+            ImageContentType::Illustration => vec![Box::new(AutoLevels)],
+        }
+    }
+}
+
+/// One-line summary: `name (size in, W×H px, PPI, palette, technology)`
+impl std::fmt::Display for DeviceSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}in, {}px, {}PPI, {}, {})",
+            self.name,
+            self.size_inches,
+            self.resolution,
+            self.ppi,
+            self.palette,
+            self.display_technology
+        )
+    }
+}
+
 /// Database of all devices
 #[derive(Debug, Deserialize)]
 struct DeviceDatabase {
     devices: HashMap<String, DeviceSpec>,
 }
 
+/// Serializable view of the database with devices sorted by ID
+#[derive(Serialize)]
+struct DeviceDatabaseExport<'a> {
+    devices: BTreeMap<&'a str, &'a DeviceSpec>,
+}
+
 /// Manager for e-ink device specifications
 pub struct DeviceManager {
     devices: HashMap<String, DeviceSpec>,
@@ -55,6 +231,92 @@ impl DeviceManager {
         })
     }
 
+    /// Load a device database from a JSON file
+    ///
+    /// The file uses the same schema as the built-in `devices.json`. Every
+    /// device is validated against the built-in palettes.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let json_data = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read device database: {}", path.display()))?;
+        let database: DeviceDatabase = serde_json::from_str(&json_data)
+            .with_context(|| format!("Failed to parse device database: {}", path.display()))?;
+
+        let palette_manager = PaletteManager::new()?;
+        for (id, spec) in &database.devices {
+            spec.validate(&palette_manager)
+                .with_context(|| format!("Invalid device '{}' in {}", id, path.display()))?;
+        }
+
+        Ok(Self {
+            devices: database.devices,
+        })
+    }
+
+    /// Remove all devices
+    pub fn clear(&mut self) {
+        self.devices.clear();
+    }
+
+    /// Export the device database as JSON
+    ///
+    /// The output uses the same schema as the built-in `devices.json`,
+    /// with devices sorted by ID.
+    pub fn export_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(&self.export_view())
+            .context("Failed to serialize device database")
+    }
+
+    /// Import devices from JSON in the `devices.json` schema
+    ///
+    /// Devices are validated before any are added; existing devices with the
+    /// same ID are replaced. Returns the number of newly added devices.
+    pub fn import_json(&mut self, json: &str) -> Result<usize> {
+        let database: DeviceDatabase =
+            serde_json::from_str(json).context("Failed to parse device database JSON")?;
+        self.import_database(database)
+    }
+
+    /// Export the device database as TOML
+    pub fn export_toml(&self) -> Result<String> {
+        toml::to_string_pretty(&self.export_view()).context("Failed to serialize device database")
+    }
+
+    /// Import devices from TOML (same structure as the JSON schema)
+    ///
+    /// Behaves like [`DeviceManager::import_json`].
+    pub fn import_toml(&mut self, toml: &str) -> Result<usize> {
+        let database: DeviceDatabase =
+            toml::from_str(toml).context("Failed to parse device database TOML")?;
+        self.import_database(database)
+    }
+
+    fn export_view(&self) -> DeviceDatabaseExport<'_> {
+        DeviceDatabaseExport {
+            devices: self
+                .devices
+                .iter()
+                .map(|(id, spec)| (id.as_str(), spec))
+                .collect(),
+        }
+    }
+
+    fn import_database(&mut self, database: DeviceDatabase) -> Result<usize> {
+        let palette_manager = PaletteManager::new()?;
+        for (id, spec) in &database.devices {
+            spec.validate(&palette_manager)
+                .with_context(|| format!("Invalid device '{}'", id))?;
+        }
+
+        let mut added = 0;
+        for (id, spec) in database.devices {
+            if self.devices.insert(id, spec).is_none() {
+                added += 1;
+            }
+        }
+
+        Ok(added)
+    }
+
     /// Get a device specification by ID
     pub fn get_device(&self, device_id: &str) -> Result<DeviceSpec> {
         self.devices
@@ -77,7 +339,7 @@ impl DeviceManager {
         for (id, spec) in &self.devices {
             grouped
                 .entry(spec.display_technology.clone())
-                .or_insert_with(Vec::new)
+                .or_default()
                 .push((id.clone(), spec.clone()));
         }
 
@@ -92,11 +354,135 @@ impl DeviceManager {
 
         grouped
     }
+
+    /// Get all devices grouped by palette name
+    pub fn devices_by_palette(&self) -> HashMap<String, Vec<(String, DeviceSpec)>> {
+        let mut grouped: HashMap<String, Vec<(String, DeviceSpec)>> = HashMap::new();
+
+        for (id, spec) in &self.devices {
+            grouped
+                .entry(spec.palette.clone())
+                .or_default()
+                .push((id.clone(), spec.clone()));
+        }
+
+        for devices in grouped.values_mut() {
+            sort_by_size(devices);
+        }
+
+        grouped
+    }
+
+    /// Get the devices using a palette, sorted by size
+    pub fn devices_with_palette(&self, palette_name: &str) -> Vec<(String, &DeviceSpec)> {
+        let mut devices: Vec<(String, &DeviceSpec)> = self
+            .devices
+            .iter()
+            .filter(|(_, spec)| spec.palette == palette_name)
+            .map(|(id, spec)| (id.clone(), spec))
+            .collect();
+
+        sort_by_size(&mut devices);
+        devices
+    }
+
+    /// Get the devices with exactly this resolution, sorted by size
+    ///
+    /// Several panels (e.g. from different vendors) may share a resolution.
+    pub fn device_for_resolution(&self, width: u32, height: u32) -> Vec<(String, &DeviceSpec)> {
+        let mut devices: Vec<(String, &DeviceSpec)> = self
+            .devices
+            .iter()
+            .filter(|(_, spec)| (spec.resolution.width, spec.resolution.height) == (width, height))
+            .map(|(id, spec)| (id.clone(), spec))
+            .collect();
+
+        sort_by_size(&mut devices);
+        devices
+    }
+
+    /// Like [`DeviceManager::device_for_resolution`], also matching devices
+    /// in the other orientation (`height`×`width`)
+    ///
+    /// Exact matches come first.
+    pub fn device_for_resolution_landscape(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Vec<(String, &DeviceSpec)> {
+        let mut devices = self.device_for_resolution(width, height);
+        if width != height {
+            devices.extend(self.device_for_resolution(height, width));
+        }
+        devices
+    }
+}
+
+/// Suggest a dithering algorithm for a device and kind of image content
+///
+/// Unlike the device's recommended settings, this depends on the content:
+///
+/// | Content        | 2 colors             | 3–4 colors      | 5+ colors       |
+/// |----------------|----------------------|-----------------|-----------------|
+/// | Photo          | Jarvis               | Floyd-Steinberg | Floyd-Steinberg |
+/// | Line art       | none (nearest color) | Ordered 2×2     | Ordered 2×2     |
+/// | Text document  | none (nearest color) | none            | none            |
+/// | Illustration   | Ordered 4×4          | Ordered 4×4     | Sierra 2-4A     |
+///
+/// Photos on color displays should also use serpentine scanning (see
+/// [`suggest_serpentine`]). The number of colors comes from the device's
+/// palette; devices with an unknown palette are treated as monochrome.
+pub fn suggest_algorithm(device: &DeviceSpec, content: ImageContentType) -> DitheringAlgorithm {
+    let colors = device.palette_colors();
+
+    match content {
+        ImageContentType::Photo if colors <= 2 => {
+            DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Jarvis)
+        }
+        ImageContentType::Photo => {
+            DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::FloydSteinberg)
+        }
+        ImageContentType::LineArt if colors <= 2 => DitheringAlgorithm::QuantizationOnly,
+        ImageContentType::LineArt => DitheringAlgorithm::Ordered {
+            width: 2,
+            height: 2,
+        },
+        ImageContentType::TextDocument => DitheringAlgorithm::QuantizationOnly,
+        ImageContentType::Illustration if colors <= 4 => DitheringAlgorithm::Ordered {
+            width: 4,
+            height: 4,
+        },
+        ImageContentType::Illustration => {
+            DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Sierra2_4A)
+        }
+    }
+}
+
+/// Whether to scan serpentine with the [`suggest_algorithm`] result
+///
+/// True for photos on color displays, where alternating the direction of
+/// error diffusion avoids the diagonal artifacts visible in smooth color
+/// gradients.
+pub fn suggest_serpentine(device: &DeviceSpec, content: ImageContentType) -> bool {
+    content == ImageContentType::Photo && device.palette_colors() > 2
+}
+
+/// Sort `(id, spec)` pairs by display size, then by ID
+fn sort_by_size<S: std::borrow::Borrow<DeviceSpec>>(devices: &mut [(String, S)]) {
+    devices.sort_by(|a, b| {
+        a.1.borrow()
+            .size_inches
+            .partial_cmp(&b.1.borrow().size_inches)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.0.cmp(&b.0))
+    });
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::color::preprocess;
+    use image::RgbImage;
 
     #[test]
     fn test_device_manager_creation() {
@@ -120,4 +506,288 @@ mod tests {
         assert!(devices.contains(&"spectra6-7.3".to_string()));
         assert!(devices.contains(&"acep-7.3".to_string()));
     }
+
+    fn device_json(width: u32) -> String {
+        format!(
+            r#"{{
+                "name": "Test Display",
+                "display_technology": "Spectra 6",
+                "size_inches": 7.3,
+                "resolution": {{ "width": {}, "height": 480 }},
+                "ppi": 200,
+                "palette": "spectra6",
+                "recommended_settings": {{
+                    "algorithm": "floyd-steinberg",
+                    "serpentine": true,
+                    "fit_mode": "letterbox",
+                    "scaling_algorithm": "lanczos3"
+                }}
+            }}"#,
+            width
+        )
+    }
+
+    #[test]
+    fn test_builtin_devices_are_valid() {
+        let manager = DeviceManager::new().unwrap();
+        let palettes = PaletteManager::new().unwrap();
+        for id in manager.list_devices() {
+            manager
+                .get_device(&id)
+                .unwrap()
+                .validate(&palettes)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_validate_zero_width() {
+        let palettes = PaletteManager::new().unwrap();
+        let spec: DeviceSpec = serde_json::from_str(&device_json(0)).unwrap();
+
+        let err = spec.validate(&palettes).unwrap_err();
+        assert!(
+            err.to_string().contains("invalid resolution 0x480"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_validate_unknown_palette_and_algorithm() {
+        let palettes = PaletteManager::new().unwrap();
+        let spec: DeviceSpec = serde_json::from_str(&device_json(800)).unwrap();
+        assert!(spec.validate(&palettes).is_ok());
+
+        let mut unknown_palette = spec.clone();
+        unknown_palette.palette = "no-such-palette".to_string();
+        assert!(unknown_palette.validate(&palettes).is_err());
+
+        let mut unknown_algorithm = spec;
+        unknown_algorithm.recommended_settings.algorithm = "magic".to_string();
+        let err = unknown_algorithm.validate(&palettes).unwrap_err();
+        assert!(err.to_string().contains("unknown algorithm 'magic'"));
+    }
+
+    #[test]
+    fn test_from_file_rejects_invalid_device() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("devices.json");
+        std::fs::write(
+            &path,
+            format!(r#"{{ "devices": {{ "broken": {} }} }}"#, device_json(0)),
+        )
+        .unwrap();
+
+        let err = DeviceManager::from_file(&path)
+            .err()
+            .expect("validation should fail");
+        let message = format!("{:#}", err);
+        assert!(message.contains("Invalid device 'broken'"), "{}", message);
+        assert!(message.contains("invalid resolution"), "{}", message);
+
+        std::fs::write(
+            &path,
+            format!(r#"{{ "devices": {{ "ok": {} }} }}"#, device_json(800)),
+        )
+        .unwrap();
+        let manager = DeviceManager::from_file(&path).unwrap();
+        assert_eq!(manager.list_devices(), vec!["ok".to_string()]);
+    }
+
+    #[test]
+    fn test_toml_round_trip() {
+        let manager = DeviceManager::new().unwrap();
+        let toml = manager.export_toml().unwrap();
+
+        let mut imported = DeviceManager::new().unwrap();
+        imported.clear();
+        assert_eq!(imported.import_toml(&toml).unwrap(), manager.devices.len());
+        assert_eq!(imported.list_devices(), manager.list_devices());
+
+        let device = imported.get_device("spectra6-7.3").unwrap();
+        assert_eq!(device.resolution.width, 800);
+        assert_eq!(device.size_inches, 7.3);
+    }
+
+    #[test]
+    fn test_import_counts_only_new_devices() {
+        let mut manager = DeviceManager::new().unwrap();
+        let json = manager.export_json().unwrap();
+        assert_eq!(manager.import_json(&json).unwrap(), 0);
+
+        let invalid = format!(r#"{{ "devices": {{ "broken": {} }} }}"#, device_json(0));
+        let count = manager.devices.len();
+        assert!(manager.import_json(&invalid).is_err());
+        assert_eq!(manager.devices.len(), count);
+    }
+
+    #[test]
+    fn test_devices_with_palette() {
+        let manager = DeviceManager::new().unwrap();
+        let devices = manager.devices_with_palette("spectra6");
+        assert!(!devices.is_empty());
+        assert!(devices.iter().all(|(_, spec)| spec.palette == "spectra6"));
+        assert!(devices
+            .windows(2)
+            .all(|w| w[0].1.size_inches <= w[1].1.size_inches));
+
+        let grouped = manager.devices_by_palette();
+        assert_eq!(grouped["spectra6"].len(), devices.len());
+        assert!(manager.devices_with_palette("no-such-palette").is_empty());
+    }
+
+    #[test]
+    fn test_device_spec_display() {
+        let manager = DeviceManager::new().unwrap();
+        let device = manager.get_device("spectra6-7.3").unwrap();
+
+        assert_eq!(device.resolution.to_string(), "800×480");
+        let summary = device.to_string();
+        assert!(
+            summary.contains("800×480"),
+            "Unexpected summary: {}",
+            summary
+        );
+        assert!(summary.contains("spectra6"));
+    }
+
+    #[test]
+    fn test_device_spec_serialization() {
+        let manager = DeviceManager::new().unwrap();
+        let device = manager.get_device("spectra6-7.3").unwrap();
+
+        let from_json: DeviceSpec =
+            serde_json::from_str(&device.to_json_pretty().unwrap()).unwrap();
+        assert_eq!(from_json.to_string(), device.to_string());
+
+        let from_toml: DeviceSpec = toml::from_str(&device.to_toml().unwrap()).unwrap();
+        assert_eq!(from_toml.to_string(), device.to_string());
+    }
+
+    #[test]
+    fn test_suggest_algorithm() {
+        let manager = DeviceManager::new().unwrap();
+        let color = manager.get_device("spectra6-7.3").unwrap();
+        let mut mono = color.clone();
+        mono.palette = "default".to_string();
+        let mut four = color.clone();
+        four.palette = "gameboy".to_string();
+
+        let floyd_steinberg =
+            DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::FloydSteinberg);
+        let jarvis = DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Jarvis);
+        let ordered_2x2 = DitheringAlgorithm::Ordered {
+            width: 2,
+            height: 2,
+        };
+        let ordered_4x4 = DitheringAlgorithm::Ordered {
+            width: 4,
+            height: 4,
+        };
+        let sierra = DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Sierra2_4A);
+        let none = DitheringAlgorithm::QuantizationOnly;
+
+        let cases = [
+            (&color, ImageContentType::Photo, &floyd_steinberg),
+            (&mono, ImageContentType::Photo, &jarvis),
+            (&mono, ImageContentType::LineArt, &none),
+            (&color, ImageContentType::LineArt, &ordered_2x2),
+            (&mono, ImageContentType::TextDocument, &none),
+            (&color, ImageContentType::TextDocument, &none),
+            (&four, ImageContentType::Illustration, &ordered_4x4),
+            (&color, ImageContentType::Illustration, &sierra),
+        ];
+        for (device, content, expected) in cases {
+            assert_eq!(
+                &suggest_algorithm(device, content),
+                expected,
+                "{:?}",
+                content
+            );
+            assert_eq!(
+                suggest_serpentine(device, content),
+                content == ImageContentType::Photo && std::ptr::eq(device, &color),
+                "{:?}",
+                content
+            );
+        }
+
+        // Unknown palettes fall back to the monochrome rules
+        mono.palette = "missing".to_string();
+        assert_eq!(suggest_algorithm(&mono, ImageContentType::Photo), jarvis);
+    }
+
+    #[test]
+    fn test_optimal_preprocessing_chain() {
+        let manager = DeviceManager::new().unwrap();
+        let device = manager.get_device("spectra6-7.3").unwrap();
+        let source = RgbImage::from_fn(16, 16, |x, y| {
+            image::Rgb([(x * 16) as u8, (y * 16) as u8, ((x + y) * 8) as u8])
+        });
+
+        for content in [
+            ImageContentType::Photo,
+            ImageContentType::LineArt,
+            ImageContentType::TextDocument,
+            ImageContentType::Illustration,
+        ] {
+            let chain = device.optimal_preprocessing_chain(content);
+            assert!(!chain.is_empty(), "{:?}", content);
+
+            let mut img = source.clone();
+            preprocess::apply_chain(&mut img, &chain);
+            assert_eq!(img.dimensions(), source.dimensions());
+        }
+
+        let photo = device.optimal_preprocessing_chain(ImageContentType::Photo);
+        let names: Vec<&str> = photo.iter().map(|step| step.name()).collect();
+        assert_eq!(names, ["auto-levels", "unsharp-mask", "gamma-correction"]);
+    }
+
+    #[test]
+    fn test_framerate_fields() {
+        let without: DeviceSpec = serde_json::from_str(&device_json(800)).unwrap();
+        assert_eq!(without.max_framerate_hz, None);
+        assert_eq!(without.partial_refresh_framerate_hz, None);
+        assert!(without.requires_power_off_between_updates());
+        assert!((without.frames_per_second_for_full_update() - 1.0 / 15.0).abs() < 1e-6);
+        assert!(!without.to_json_pretty().unwrap().contains("framerate"));
+
+        let json = device_json(800).replace(
+            r#""ppi": 200,"#,
+            r#""ppi": 200, "max_framerate_hz": 0.25, "partial_refresh_framerate_hz": 4.0,"#,
+        );
+        let with: DeviceSpec = serde_json::from_str(&json).unwrap();
+        assert_eq!(with.max_framerate_hz, Some(0.25));
+        assert_eq!(with.partial_refresh_framerate_hz, Some(4.0));
+        assert_eq!(with.frames_per_second_for_full_update(), 0.25);
+        assert!(!with.requires_power_off_between_updates());
+
+        let from_toml: DeviceSpec = toml::from_str(&with.to_toml().unwrap()).unwrap();
+        assert_eq!(from_toml.partial_refresh_framerate_hz, Some(4.0));
+
+        let manager = DeviceManager::new().unwrap();
+        let carta = manager.get_device("carta-6.0").unwrap();
+        assert_eq!(carta.partial_refresh_framerate_hz, Some(8.0));
+        assert!(manager
+            .get_device("spectra6-7.3")
+            .unwrap()
+            .requires_power_off_between_updates());
+    }
+
+    #[test]
+    fn test_device_for_resolution() {
+        let manager = DeviceManager::new().unwrap();
+        let devices = manager.device_for_resolution(800, 480);
+        assert!(!devices.is_empty());
+        assert!(devices
+            .iter()
+            .all(|(_, spec)| (spec.resolution.width, spec.resolution.height) == (800, 480)));
+        assert!(manager.device_for_resolution(480, 800).is_empty());
+
+        let rotated = manager.device_for_resolution_landscape(480, 800);
+        assert_eq!(rotated.len(), devices.len());
+        assert!(manager.device_for_resolution_landscape(1, 1).is_empty());
+    }
 }
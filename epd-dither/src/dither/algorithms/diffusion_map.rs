@@ -0,0 +1,66 @@
+//! Error diffusion pattern visualization for debugging kernels
+
+use crate::dither::{matrices, DitheringAlgorithm, ErrorDiffusionKernel, ALGORITHM_NAMES};
+use image::RgbImage;
+use std::collections::HashMap;
+
+/// Render how a kernel spreads the error of a single pixel
+///
+/// The source pixel sits at the center of a black `size`×`size` image and
+/// stays black; every neighbor that receives error is gray with brightness
+/// `factor * 255`. Neighbors outside the image are dropped.
+pub fn generate_diffusion_pattern(kernel: ErrorDiffusionKernel, size: u32) -> RgbImage {
+    let mut img = RgbImage::new(size, size);
+    let center = (size / 2) as i64;
+
+    for entry in matrices::get_diffusion_matrix(kernel) {
+        let x = center + entry.offset[0] as i64;
+        let y = center + entry.offset[1] as i64;
+        if (0..size as i64).contains(&x) && (0..size as i64).contains(&y) {
+            let value = (entry.factor * 255.0).round().clamp(0.0, 255.0) as u8;
+            img.put_pixel(x as u32, y as u32, image::Rgb([value; 3]));
+        }
+    }
+
+    img
+}
+
+/// Render the pattern of every error diffusion kernel, keyed by CLI name
+pub fn generate_all_patterns(size: u32) -> HashMap<String, RgbImage> {
+    ALGORITHM_NAMES
+        .iter()
+        .filter_map(|&name| match DitheringAlgorithm::from_name(name) {
+            Some(DitheringAlgorithm::ErrorDiffusion(kernel)) => {
+                Some((name.to_string(), generate_diffusion_pattern(kernel, size)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_floyd_steinberg_pattern() {
+        let img = generate_diffusion_pattern(ErrorDiffusionKernel::FloydSteinberg, 5);
+
+        let lit: Vec<(u32, u32, u8)> = img
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p[0] > 0)
+            .map(|(x, y, p)| (x, y, p[0]))
+            .collect();
+
+        // Source at (2, 2): 7/16 right, 3/16 below-left, 5/16 below, 1/16 below-right
+        assert_eq!(lit, vec![(3, 2, 112), (1, 3, 48), (2, 3, 80), (3, 3, 16)]);
+    }
+
+    #[test]
+    fn test_all_patterns() {
+        let patterns = generate_all_patterns(7);
+        assert_eq!(patterns.len(), 8);
+        assert!(patterns.contains_key("jarvis"));
+        assert!(patterns.values().all(|img| img.dimensions() == (7, 7)));
+    }
+}
@@ -1,7 +1,15 @@
 //! Error diffusion dithering algorithms
 
-use crate::color::{distance::find_closest_color, Rgb};
-use crate::dither::{matrices, ErrorDiffusionKernel};
+use crate::color::{
+    convert::{rgb_to_yuv, Yuv},
+    distance::find_closest_color,
+    Rgb,
+};
+use crate::dither::{
+    matrices::{self, DiffusionEntry},
+    ErrorDiffusionKernel,
+};
+use std::sync::mpsc::{self, Receiver, Sender};
 
 /// Apply error diffusion dithering to an image
 ///
@@ -14,12 +22,136 @@ pub fn apply_error_diffusion(
     palette: &[Rgb],
     kernel: ErrorDiffusionKernel,
     serpentine: bool,
+) {
+    apply_error_diffusion_region(
+        buffer,
+        width,
+        height,
+        palette,
+        kernel,
+        serpentine,
+        (0, 0, width, height),
+    );
+}
+
+/// Apply error diffusion dithering to a rectangular region of an image
+///
+/// `region` is `(x, y, region_width, region_height)`; the parts of it
+/// outside the image are ignored. Only pixels inside the region are modified: error diffusion
+/// propagates normally within it, and error that would cross the region
+/// boundary is discarded. Useful for partial screen refreshes.
+pub fn apply_error_diffusion_region(
+    buffer: &mut [u8],
+    width: usize,
+    height: usize,
+    palette: &[Rgb],
+    kernel: ErrorDiffusionKernel,
+    serpentine: bool,
+    region: (usize, usize, usize, usize),
+) {
+    diffuse_region(
+        buffer,
+        width,
+        height,
+        palette,
+        kernel,
+        serpentine,
+        region,
+        None,
+        &[],
+        &mut |_| true,
+        None,
+    );
+}
+
+/// Apply error diffusion dithering, calling `on_row(y)` after each row
+///
+/// Dithering stops early if `on_row` returns `false`, leaving the rows
+/// below unprocessed. Returns whether every row was processed.
+pub fn apply_error_diffusion_with_progress(
+    buffer: &mut [u8],
+    width: usize,
+    height: usize,
+    palette: &[Rgb],
+    kernel: ErrorDiffusionKernel,
+    serpentine: bool,
+    on_row: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    diffuse_region(
+        buffer,
+        width,
+        height,
+        palette,
+        kernel,
+        serpentine,
+        (0, 0, width, height),
+        None,
+        &[],
+        on_row,
+        None,
+    )
+}
+
+/// Apply error diffusion dithering and return each pixel's quantization error
+///
+/// The error is the pixel's value after receiving diffused error minus the
+/// palette color it was mapped to, one `[r, g, b]` entry per pixel in
+/// row-major order. This is what gets distributed to the neighbors.
+pub fn apply_error_diffusion_collecting_errors(
+    buffer: &mut [u8],
+    width: usize,
+    height: usize,
+    palette: &[Rgb],
+    kernel: ErrorDiffusionKernel,
+    serpentine: bool,
+) -> Vec<[f64; 3]> {
+    let mut errors = vec![[0.0; 3]; width * height];
+    diffuse_region(
+        buffer,
+        width,
+        height,
+        palette,
+        kernel,
+        serpentine,
+        (0, 0, width, height),
+        None,
+        &[],
+        &mut |_| true,
+        Some(&mut errors),
+    );
+    errors
+}
+
+/// Apply error diffusion dithering to one horizontal strip of an image
+///
+/// `buffer` starts at the strip's first row, which is row `first_row` of
+/// the full image. Its first `rows` rows are dithered; any rows after them
+/// only receive the error diffused across the strip's bottom edge, which
+/// carries it into the next strip. Dithering consecutive strips this way,
+/// each followed by the kernel's lookahead rows, gives the same result as
+/// [`apply_error_diffusion`] on the whole image.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_error_diffusion_strip(
+    buffer: &mut [u8],
+    width: usize,
+    palette: &[Rgb],
+    kernel: ErrorDiffusionKernel,
+    serpentine: bool,
+    first_row: usize,
+    rows: usize,
 ) {
     let diffusion_matrix = matrices::get_diffusion_matrix(kernel);
+    let height = if width == 0 {
+        0
+    } else {
+        buffer.len() / (width * 3)
+    };
+    debug_assert!(rows <= height, "Strip must lie within the buffer");
 
-    for y in 0..height {
-        // Serpentine scanning: alternate direction for each row
-        let x_range: Box<dyn Iterator<Item = usize>> = if serpentine && y % 2 == 1 {
+    for y in 0..rows {
+        // Serpentine direction follows the row's position in the full image
+        let reverse = serpentine && (first_row + y) % 2 == 1;
+        let x_range: Box<dyn Iterator<Item = usize>> = if reverse {
             Box::new((0..width).rev())
         } else {
             Box::new(0..width)
@@ -27,27 +159,306 @@ pub fn apply_error_diffusion(
 
         for x in x_range {
             let idx = (y * width + x) * 3;
-
-            // Get current pixel color
             let old_pixel = Rgb::new(buffer[idx], buffer[idx + 1], buffer[idx + 2]);
+            let (_, &new_pixel) =
+                find_closest_color(&old_pixel, palette).expect("Palette should not be empty");
+            buffer[idx..idx + 3].copy_from_slice(new_pixel.as_slice());
+
+            let error = [0, 1, 2].map(|c| old_pixel.0[c] as f64 - new_pixel.0[c] as f64);
+
+            for entry in diffusion_matrix {
+                let nx = if reverse {
+                    x as i32 - entry.offset[0]
+                } else {
+                    x as i32 + entry.offset[0]
+                };
+                let ny = y as i32 + entry.offset[1];
+
+                if nx < 0 || nx >= width as i32 || ny < 0 || ny >= height as i32 {
+                    continue;
+                }
+
+                let neighbor_idx = (ny as usize * width + nx as usize) * 3;
+                for (c, channel_error) in error.iter().enumerate() {
+                    buffer[neighbor_idx + c] = (buffer[neighbor_idx + c] as f64
+                        + channel_error * entry.factor)
+                        .clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+    }
+}
+
+/// Apply error diffusion dithering with rows running concurrently
+///
+/// Rows are dealt out to `threads` threads in turn. Row `y + 1` only
+/// dithers a pixel once row `y` has passed every pixel that still diffuses
+/// error into it or into the pixels it diffuses to (a wavefront schedule),
+/// and applies that error in the same order as a serial pass. The result
+/// is identical to [`apply_error_diffusion`] without serpentine scanning.
+///
+/// # Panics
+/// Panics if the kernel diffuses error further than the next row.
+pub fn apply_error_diffusion_wavefront(
+    buffer: &mut [u8],
+    width: usize,
+    palette: &[Rgb],
+    kernel: ErrorDiffusionKernel,
+    threads: usize,
+) {
+    let diffusion_matrix = matrices::get_diffusion_matrix(kernel);
+    assert!(
+        diffusion_matrix.iter().all(|entry| entry.offset[1] <= 1),
+        "Wavefront error diffusion needs a kernel that reaches only the next row"
+    );
+    if width == 0 || buffer.is_empty() {
+        return;
+    }
+
+    // How far ahead of a pixel the row above must be: its own row reaches
+    // `same_row` pixels to the right, each of which receives error from
+    // up to `next_row_left` pixels further right in the row above
+    let same_row = diffusion_matrix
+        .iter()
+        .filter(|entry| entry.offset[1] == 0)
+        .map(|entry| entry.offset[0].max(0) as usize)
+        .max()
+        .unwrap_or(0);
+    let next_row_left = diffusion_matrix
+        .iter()
+        .filter(|entry| entry.offset[1] == 1)
+        .map(|entry| (-entry.offset[0]).max(0) as usize)
+        .max()
+        .unwrap_or(0);
+    let lead = same_row + next_row_left;
+
+    // Row `y` receives the errors of row `y - 1` and sends its own to `y + 1`
+    let rows: Vec<&mut [u8]> = buffer.chunks_mut(width * 3).collect();
+    let (senders, receivers): (Vec<_>, Vec<_>) =
+        (1..rows.len()).map(|_| mpsc::channel::<[f64; 3]>()).unzip();
+    let threads = threads.clamp(1, rows.len());
+    let mut jobs: Vec<Vec<_>> = (0..threads).map(|_| Vec::new()).collect();
+    let (mut senders, mut receivers) = (senders.into_iter(), receivers.into_iter());
+    for (y, row) in rows.into_iter().enumerate() {
+        let above = if y > 0 { receivers.next() } else { None };
+        jobs[y % threads].push((row, above, senders.next()));
+    }
+
+    std::thread::scope(|scope| {
+        for rows in jobs {
+            scope.spawn(move || {
+                for (row, above, below) in rows {
+                    diffuse_wavefront_row(row, above, below, palette, diffusion_matrix, lead);
+                }
+            });
+        }
+    });
+}
+
+/// Dither one row of [`apply_error_diffusion_wavefront`]
+fn diffuse_wavefront_row(
+    row: &mut [u8],
+    above: Option<Receiver<[f64; 3]>>,
+    below: Option<Sender<[f64; 3]>>,
+    palette: &[Rgb],
+    diffusion_matrix: &[DiffusionEntry],
+    lead: usize,
+) {
+    let width = row.len() / 3;
+    let add_error = |row: &mut [u8], x: i32, error: [f64; 3], factor: f64| {
+        if (0..width as i32).contains(&x) {
+            let idx = x as usize * 3;
+            for (value, error) in row[idx..idx + 3].iter_mut().zip(error) {
+                *value = (*value as f64 + error * factor).clamp(0.0, 255.0) as u8;
+            }
+        }
+    };
+    // Pixels of the row above whose error has been applied
+    let mut received = 0;
+
+    for x in 0..width {
+        if let Some(above) = &above {
+            while received < (x + lead + 1).min(width) {
+                let error = above
+                    .recv()
+                    .expect("Row above should send an error for every pixel");
+                for entry in diffusion_matrix.iter().filter(|entry| entry.offset[1] == 1) {
+                    add_error(row, received as i32 + entry.offset[0], error, entry.factor);
+                }
+                received += 1;
+            }
+        }
+
+        let idx = x * 3;
+        let old_pixel = Rgb::new(row[idx], row[idx + 1], row[idx + 2]);
+        let (_, &new_pixel) =
+            find_closest_color(&old_pixel, palette).expect("Palette should not be empty");
+        row[idx..idx + 3].copy_from_slice(new_pixel.as_slice());
+
+        let error = [0, 1, 2].map(|c| old_pixel.0[c] as f64 - new_pixel.0[c] as f64);
+        for entry in diffusion_matrix.iter().filter(|entry| entry.offset[1] == 0) {
+            add_error(row, x as i32 + entry.offset[0], error, entry.factor);
+        }
+        if let Some(below) = &below {
+            below
+                .send(error)
+                .expect("Row below should still be receiving");
+        }
+    }
+}
+
+/// Apply error diffusion dithering with per-pixel strength
+///
+/// `mask` holds one value per pixel. The error a pixel receives from its
+/// neighbors is scaled by `mask / 255`, so 255 dithers normally and 0
+/// quantizes the original color without any diffused error.
+pub fn apply_error_diffusion_masked(
+    buffer: &mut [u8],
+    width: usize,
+    height: usize,
+    palette: &[Rgb],
+    kernel: ErrorDiffusionKernel,
+    serpentine: bool,
+    mask: &[u8],
+) {
+    debug_assert_eq!(mask.len(), width * height, "Mask must cover the image");
+    diffuse_region(
+        buffer,
+        width,
+        height,
+        palette,
+        kernel,
+        serpentine,
+        (0, 0, width, height),
+        Some(mask),
+        &[],
+        &mut |_| true,
+        None,
+    );
+}
+
+/// Apply error diffusion with noise shaping of order `noise_shape_order`
+///
+/// Before a pixel is quantized, the quantization errors of the previous
+/// `noise_shape_order` pixels in the row are fed back through a FIR filter
+/// with the alternating-sign binomial coefficients of `(1 - z⁻¹)ⁿ / 2ⁿ`.
+/// The kernel diffuses the rest of each error, so together they pass on
+/// exactly the quantization error and flat areas keep their tone.
+/// This high-pass pre-emphasis pushes quantization noise towards higher
+/// spatial frequencies, where it is less visible. The filter state resets
+/// at the start of each row, and rows are scanned left to right. Order 0
+/// is identical to [`apply_error_diffusion`] without serpentine scanning.
+pub fn apply_error_diffusion_noise_shaped(
+    buffer: &mut [u8],
+    width: usize,
+    height: usize,
+    palette: &[Rgb],
+    kernel: ErrorDiffusionKernel,
+    noise_shape_order: u8,
+) {
+    diffuse_region(
+        buffer,
+        width,
+        height,
+        palette,
+        kernel,
+        false,
+        (0, 0, width, height),
+        None,
+        &noise_shaping_coefficients(noise_shape_order),
+        &mut |_| true,
+        None,
+    );
+}
+
+/// Feedback coefficients for the previous 1..=order errors
+fn noise_shaping_coefficients(order: u8) -> Vec<f64> {
+    let scale = 2f64.powi(order as i32);
+    let mut binomial = 1.0;
+    (1..=order as u32)
+        .map(|k| {
+            binomial = binomial * (order as u32 - k + 1) as f64 / k as f64;
+            let sign = if k % 2 == 1 { 1.0 } else { -1.0 };
+            sign * binomial / scale
+        })
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn diffuse_region(
+    buffer: &mut [u8],
+    width: usize,
+    height: usize,
+    palette: &[Rgb],
+    kernel: ErrorDiffusionKernel,
+    serpentine: bool,
+    region: (usize, usize, usize, usize),
+    mask: Option<&[u8]>,
+    noise_shaping: &[f64],
+    on_row: &mut dyn FnMut(usize) -> bool,
+    mut errors: Option<&mut [[f64; 3]]>,
+) -> bool {
+    let diffusion_matrix = matrices::get_diffusion_matrix(kernel);
+    // The feedback filter already passes on this share of each error
+    let diffusion_scale = 1.0 - noise_shaping.iter().sum::<f64>();
+    // Clip the region to the image
+    let (region_x, region_y, region_width, region_height) = region;
+    let (region_x, region_y) = (region_x.min(width), region_y.min(height));
+    let x_end = region_x.saturating_add(region_width).min(width);
+    let y_end = region_y.saturating_add(region_height).min(height);
+
+    for y in region_y..y_end {
+        // Serpentine scanning: alternate direction for each row
+        let reverse = serpentine && (y - region_y) % 2 == 1;
+        let x_range: Box<dyn Iterator<Item = usize>> = if reverse {
+            Box::new((region_x..x_end).rev())
+        } else {
+            Box::new(region_x..x_end)
+        };
+        // Most recent quantization error first
+        let mut error_history: Vec<[f64; 3]> = Vec::with_capacity(noise_shaping.len());
+
+        for x in x_range {
+            let idx = (y * width + x) * 3;
+
+            // Get current pixel color, plus the noise shaping feedback
+            let shaped = |channel: usize| {
+                let feedback: f64 = noise_shaping
+                    .iter()
+                    .zip(&error_history)
+                    .map(|(coefficient, error)| coefficient * error[channel])
+                    .sum();
+                buffer[idx + channel] as f64 + feedback
+            };
+            let target = [shaped(0), shaped(1), shaped(2)];
+            let channel = |value: f64| value.round().clamp(0.0, 255.0) as u8;
+            let old_pixel = Rgb::new(channel(target[0]), channel(target[1]), channel(target[2]));
 
             // Find closest palette color
-            let (_, &new_pixel) = find_closest_color(&old_pixel, palette)
-                .expect("Palette should not be empty");
+            let (_, &new_pixel) =
+                find_closest_color(&old_pixel, palette).expect("Palette should not be empty");
 
             // Set the new color
             buffer[idx] = new_pixel.r();
             buffer[idx + 1] = new_pixel.g();
             buffer[idx + 2] = new_pixel.b();
 
-            // Calculate quantization error
-            let error_r = old_pixel.r() as f64 - new_pixel.r() as f64;
-            let error_g = old_pixel.g() as f64 - new_pixel.g() as f64;
-            let error_b = old_pixel.b() as f64 - new_pixel.b() as f64;
+            // Calculate quantization error (from the unclamped target, so
+            // feedback that overshoots the channel range isn't lost)
+            let error_r = target[0] - new_pixel.r() as f64;
+            let error_g = target[1] - new_pixel.g() as f64;
+            let error_b = target[2] - new_pixel.b() as f64;
+            if let Some(errors) = errors.as_deref_mut() {
+                errors[y * width + x] = [error_r, error_g, error_b];
+            }
+            if !noise_shaping.is_empty() {
+                error_history.truncate(noise_shaping.len() - 1);
+                error_history.insert(0, [error_r, error_g, error_b]);
+            }
 
             // Distribute error to neighboring pixels
             for entry in diffusion_matrix {
-                let nx = if serpentine && y % 2 == 1 {
+                let nx = if reverse {
                     // For right-to-left scan, flip the x offset
                     x as i32 - entry.offset[0]
                 } else {
@@ -55,20 +466,301 @@ pub fn apply_error_diffusion(
                 };
                 let ny = y as i32 + entry.offset[1];
 
-                // Check bounds
-                if nx < 0 || nx >= width as i32 || ny < 0 || ny >= height as i32 {
+                // Check bounds (error leaving the region is discarded)
+                if nx < region_x as i32
+                    || nx >= x_end as i32
+                    || ny < region_y as i32
+                    || ny >= y_end as i32
+                {
                     continue;
                 }
 
-                let neighbor_idx = (ny as usize * width + nx as usize) * 3;
+                let neighbor = ny as usize * width + nx as usize;
+                let neighbor_idx = neighbor * 3;
+                let factor = match mask {
+                    Some(mask) => entry.factor * (mask[neighbor] as f64 / 255.0),
+                    None => entry.factor,
+                } * diffusion_scale;
 
                 // Add weighted error to neighbor
-                buffer[neighbor_idx] = (buffer[neighbor_idx] as f64 + error_r * entry.factor)
-                    .clamp(0.0, 255.0) as u8;
-                buffer[neighbor_idx + 1] = (buffer[neighbor_idx + 1] as f64 + error_g * entry.factor)
-                    .clamp(0.0, 255.0) as u8;
-                buffer[neighbor_idx + 2] = (buffer[neighbor_idx + 2] as f64 + error_b * entry.factor)
-                    .clamp(0.0, 255.0) as u8;
+                buffer[neighbor_idx] =
+                    (buffer[neighbor_idx] as f64 + error_r * factor).clamp(0.0, 255.0) as u8;
+                buffer[neighbor_idx + 1] =
+                    (buffer[neighbor_idx + 1] as f64 + error_g * factor).clamp(0.0, 255.0) as u8;
+                buffer[neighbor_idx + 2] =
+                    (buffer[neighbor_idx + 2] as f64 + error_b * factor).clamp(0.0, 255.0) as u8;
+            }
+        }
+
+        if !on_row(y) {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Apply error diffusion dithering, visiting pixels in the given order
+///
+/// `order` must yield every pixel of the image exactly once. Kernel offsets
+/// are applied as in a left-to-right raster scan. The share of the error
+/// aimed at already visited pixels is spread over the unvisited targets in
+/// proportion to their weights, so it isn't lost; error aimed outside the
+/// image is discarded as in a raster scan, and so is all of it if every
+/// target has been visited.
+pub fn apply_error_diffusion_ordered(
+    buffer: &mut [u8],
+    width: usize,
+    height: usize,
+    palette: &[Rgb],
+    kernel: ErrorDiffusionKernel,
+    order: impl Iterator<Item = (u32, u32)>,
+) {
+    let diffusion_matrix = matrices::get_diffusion_matrix(kernel);
+    let mut visited = vec![false; width * height];
+
+    for (x, y) in order {
+        let (x, y) = (x as usize, y as usize);
+        visited[y * width + x] = true;
+        let idx = (y * width + x) * 3;
+
+        let old_pixel = Rgb::new(buffer[idx], buffer[idx + 1], buffer[idx + 2]);
+        let (_, &new_pixel) =
+            find_closest_color(&old_pixel, palette).expect("Palette should not be empty");
+        buffer[idx..idx + 3].copy_from_slice(new_pixel.as_slice());
+
+        let errors = [
+            old_pixel.r() as f64 - new_pixel.r() as f64,
+            old_pixel.g() as f64 - new_pixel.g() as f64,
+            old_pixel.b() as f64 - new_pixel.b() as f64,
+        ];
+
+        let mut targets = Vec::with_capacity(diffusion_matrix.len());
+        let mut visited_weight = 0.0;
+        for entry in diffusion_matrix {
+            let nx = x as i32 + entry.offset[0];
+            let ny = y as i32 + entry.offset[1];
+            if nx < 0 || nx >= width as i32 || ny < 0 || ny >= height as i32 {
+                continue;
+            }
+
+            let neighbor = ny as usize * width + nx as usize;
+            if visited[neighbor] {
+                visited_weight += entry.factor;
+            } else {
+                targets.push((neighbor, entry.factor));
+            }
+        }
+
+        let target_weight: f64 = targets.iter().map(|&(_, factor)| factor).sum();
+        let scale = if target_weight > 0.0 {
+            (target_weight + visited_weight) / target_weight
+        } else {
+            0.0
+        };
+        for (neighbor, factor) in targets {
+            for (channel, error) in errors.iter().enumerate() {
+                let value = &mut buffer[neighbor * 3 + channel];
+                *value = (*value as f64 + error * factor * scale).clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
+
+/// Apply error diffusion dithering to a single-channel gray buffer
+///
+/// Each value is replaced by the nearest of `levels` (gray values, e.g.
+/// palette luminances) and the error is diffused like the RGB version.
+pub fn apply_error_diffusion_gray(
+    buffer: &mut [u8],
+    width: usize,
+    height: usize,
+    levels: &[u8],
+    kernel: ErrorDiffusionKernel,
+    serpentine: bool,
+) {
+    let diffusion_matrix = matrices::get_diffusion_matrix(kernel);
+
+    for y in 0..height {
+        let reverse = serpentine && y % 2 == 1;
+        let x_range: Box<dyn Iterator<Item = usize>> = if reverse {
+            Box::new((0..width).rev())
+        } else {
+            Box::new(0..width)
+        };
+
+        for x in x_range {
+            let idx = y * width + x;
+            let old_value = buffer[idx];
+            let new_value = *levels
+                .iter()
+                .min_by_key(|&&level| level.abs_diff(old_value))
+                .expect("Palette should not be empty");
+            buffer[idx] = new_value;
+
+            let error = old_value as f64 - new_value as f64;
+
+            for entry in diffusion_matrix {
+                let nx = if reverse {
+                    x as i32 - entry.offset[0]
+                } else {
+                    x as i32 + entry.offset[0]
+                };
+                let ny = y as i32 + entry.offset[1];
+
+                if nx < 0 || nx >= width as i32 || ny < 0 || ny >= height as i32 {
+                    continue;
+                }
+
+                let neighbor_idx = ny as usize * width + nx as usize;
+                buffer[neighbor_idx] =
+                    (buffer[neighbor_idx] as f64 + error * entry.factor).clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
+
+/// Apply error diffusion to each RGB channel independently
+///
+/// Every channel is snapped to the nearest value of its own level set
+/// (`r_levels`, `g_levels`, `b_levels`) and its error is diffused without
+/// affecting the other channels. The output may combine levels into colors
+/// that are not in any palette, which suits displays driving each channel
+/// separately.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_error_diffusion_per_channel(
+    buffer: &mut [u8],
+    width: usize,
+    height: usize,
+    r_levels: &[u8],
+    g_levels: &[u8],
+    b_levels: &[u8],
+    kernel: ErrorDiffusionKernel,
+    serpentine: bool,
+) {
+    let diffusion_matrix = matrices::get_diffusion_matrix(kernel);
+    let levels = [r_levels, g_levels, b_levels];
+
+    for y in 0..height {
+        let reverse = serpentine && y % 2 == 1;
+        let x_range: Box<dyn Iterator<Item = usize>> = if reverse {
+            Box::new((0..width).rev())
+        } else {
+            Box::new(0..width)
+        };
+
+        for x in x_range {
+            let idx = (y * width + x) * 3;
+
+            for (channel, channel_levels) in levels.iter().enumerate() {
+                let old_value = buffer[idx + channel];
+                let new_value = *channel_levels
+                    .iter()
+                    .min_by_key(|&&level| level.abs_diff(old_value))
+                    .expect("Channel levels should not be empty");
+                buffer[idx + channel] = new_value;
+
+                let error = old_value as f64 - new_value as f64;
+
+                for entry in diffusion_matrix {
+                    let nx = if reverse {
+                        x as i32 - entry.offset[0]
+                    } else {
+                        x as i32 + entry.offset[0]
+                    };
+                    let ny = y as i32 + entry.offset[1];
+
+                    if nx < 0 || nx >= width as i32 || ny < 0 || ny >= height as i32 {
+                        continue;
+                    }
+
+                    let neighbor_idx = (ny as usize * width + nx as usize) * 3 + channel;
+                    buffer[neighbor_idx] = (buffer[neighbor_idx] as f64 + error * entry.factor)
+                        .clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+    }
+}
+
+/// Apply error diffusion in BT.601 YUV space with separate luma and chroma kernels
+///
+/// Pixels are matched to the palette color nearest in YUV. The luma (Y)
+/// error is diffused with `luma_kernel` and the chroma (U, V) errors with
+/// `chroma_kernel`, so e.g. a wide kernel can spread chroma error, to which
+/// the eye is less sensitive, while luma keeps a tight pattern. Errors are
+/// accumulated in floating point rather than clamped into the image.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_error_diffusion_yuv(
+    buffer: &mut [u8],
+    width: usize,
+    height: usize,
+    palette: &[Rgb],
+    luma_kernel: ErrorDiffusionKernel,
+    chroma_kernel: ErrorDiffusionKernel,
+    serpentine: bool,
+) {
+    let luma_matrix = matrices::get_diffusion_matrix(luma_kernel);
+    let chroma_matrix = matrices::get_diffusion_matrix(chroma_kernel);
+    let palette_yuv: Vec<Yuv> = palette.iter().map(rgb_to_yuv).collect();
+    let mut errors = vec![[0.0f64; 3]; width * height];
+
+    for y in 0..height {
+        let reverse = serpentine && y % 2 == 1;
+        let x_range: Box<dyn Iterator<Item = usize>> = if reverse {
+            Box::new((0..width).rev())
+        } else {
+            Box::new(0..width)
+        };
+
+        for x in x_range {
+            let pixel = y * width + x;
+            let idx = pixel * 3;
+            let original = rgb_to_yuv(&Rgb::new(buffer[idx], buffer[idx + 1], buffer[idx + 2]));
+            let [error_y, error_u, error_v] = errors[pixel];
+            let target = Yuv {
+                y: original.y + error_y,
+                u: original.u + error_u,
+                v: original.v + error_v,
+            };
+
+            let (nearest, chosen) = palette_yuv
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| {
+                    let distance = |c: &Yuv| {
+                        (c.y - target.y).powi(2)
+                            + (c.u - target.u).powi(2)
+                            + (c.v - target.v).powi(2)
+                    };
+                    distance(a).total_cmp(&distance(b))
+                })
+                .expect("Palette should not be empty");
+            buffer[idx..idx + 3].copy_from_slice(palette[nearest].as_slice());
+
+            let error = [
+                target.y - chosen.y,
+                target.u - chosen.u,
+                target.v - chosen.v,
+            ];
+            for (channels, matrix) in [(0..1, luma_matrix), (1..3, chroma_matrix)] {
+                for entry in matrix {
+                    let nx = if reverse {
+                        x as i32 - entry.offset[0]
+                    } else {
+                        x as i32 + entry.offset[0]
+                    };
+                    let ny = y as i32 + entry.offset[1];
+
+                    if nx < 0 || nx >= width as i32 || ny < 0 || ny >= height as i32 {
+                        continue;
+                    }
+
+                    let neighbor = ny as usize * width + nx as usize;
+                    for channel in channels.clone() {
+                        errors[neighbor][channel] += error[channel] * entry.factor;
+                    }
+                }
             }
         }
     }
@@ -97,7 +789,352 @@ mod tests {
 
         // All pixels should now be either 0 or 255
         for &val in &buffer {
-            assert!(val == 0 || val == 255, "Pixel value should be 0 or 255, got {}", val);
+            assert!(
+                val == 0 || val == 255,
+                "Pixel value should be 0 or 255, got {}",
+                val
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_diffusion_region_leaves_outside_untouched() {
+        let (width, height) = (8, 8);
+        let original = vec![100u8; width * height * 3];
+        let mut buffer = original.clone();
+        let palette = vec![Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)];
+
+        apply_error_diffusion_region(
+            &mut buffer,
+            width,
+            height,
+            &palette,
+            ErrorDiffusionKernel::Jarvis,
+            false,
+            (2, 3, 4, 2),
+        );
+
+        for y in 0..height {
+            for x in 0..width {
+                let idx = (y * width + x) * 3;
+                let inside = (2..6).contains(&x) && (3..5).contains(&y);
+                if inside {
+                    assert!(buffer[idx] == 0 || buffer[idx] == 255);
+                } else {
+                    assert_eq!(
+                        &buffer[idx..idx + 3],
+                        &original[idx..idx + 3],
+                        "({}, {})",
+                        x,
+                        y
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_diffusion_region_matches_cropped_image() {
+        // Dithering a region must behave as if the region were its own
+        // image: no error flows in from or out to the surrounding pixels
+        let (width, height) = (6, 5);
+        let buffer: Vec<u8> = (0..width * height * 3)
+            .map(|i| (i * 7 % 256) as u8)
+            .collect();
+        let palette = vec![
+            Rgb::new(0, 0, 0),
+            Rgb::new(255, 255, 255),
+            Rgb::new(255, 0, 0),
+        ];
+        let (rx, ry, rw, rh) = (1, 1, 4, 3);
+
+        let mut full = buffer.clone();
+        apply_error_diffusion_region(
+            &mut full,
+            width,
+            height,
+            &palette,
+            ErrorDiffusionKernel::FloydSteinberg,
+            true,
+            (rx, ry, rw, rh),
+        );
+
+        let mut cropped: Vec<u8> = (ry..ry + rh)
+            .flat_map(|y| buffer[(y * width + rx) * 3..(y * width + rx + rw) * 3].to_vec())
+            .collect();
+        apply_error_diffusion(
+            &mut cropped,
+            rw,
+            rh,
+            &palette,
+            ErrorDiffusionKernel::FloydSteinberg,
+            true,
+        );
+
+        for y in 0..rh {
+            let row = &full[((ry + y) * width + rx) * 3..((ry + y) * width + rx + rw) * 3];
+            assert_eq!(row, &cropped[y * rw * 3..(y + 1) * rw * 3]);
+        }
+    }
+
+    #[test]
+    fn test_error_diffusion_region_is_clipped_to_image() {
+        let (width, height) = (6, 4);
+        let palette = vec![Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)];
+        let buffer: Vec<u8> = (0..width * height * 3)
+            .map(|i| (i * 11 % 256) as u8)
+            .collect();
+        let dither = |region| {
+            let mut dithered = buffer.clone();
+            apply_error_diffusion_region(
+                &mut dithered,
+                width,
+                height,
+                &palette,
+                ErrorDiffusionKernel::FloydSteinberg,
+                false,
+                region,
+            );
+            dithered
+        };
+
+        // Past the right and bottom edges: same as the part inside
+        assert_eq!(dither((2, 1, 100, 100)), dither((2, 1, 4, 3)));
+        assert_eq!(dither((0, 0, usize::MAX, usize::MAX)), dither((0, 0, 6, 4)));
+        // Entirely outside: nothing changes
+        assert_eq!(dither((6, 0, 3, 3)), buffer);
+        assert_eq!(dither((0, 9, 3, 3)), buffer);
+    }
+
+    #[test]
+    fn test_noise_shaping_coefficients() {
+        assert!(noise_shaping_coefficients(0).is_empty());
+        assert_eq!(noise_shaping_coefficients(1), vec![0.5]);
+        assert_eq!(noise_shaping_coefficients(2), vec![0.5, -0.25]);
+        assert_eq!(noise_shaping_coefficients(3), vec![0.375, -0.375, 0.125]);
+    }
+
+    #[test]
+    fn test_noise_shaped_error_diffusion() {
+        let palette = vec![Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)];
+        let gradient: Vec<u8> = (0..64 * 16).flat_map(|i| [(i % 64 * 4) as u8; 3]).collect();
+        let dither = |order: u8| {
+            let mut buffer = gradient.clone();
+            apply_error_diffusion_noise_shaped(
+                &mut buffer,
+                64,
+                16,
+                &palette,
+                ErrorDiffusionKernel::FloydSteinberg,
+                order,
+            );
+            buffer
+        };
+
+        let mut standard = gradient.clone();
+        apply_error_diffusion(
+            &mut standard,
+            64,
+            16,
+            &palette,
+            ErrorDiffusionKernel::FloydSteinberg,
+            false,
+        );
+        assert_eq!(dither(0), standard);
+
+        for order in 1..=3 {
+            assert!(dither(order).iter().all(|&v| v == 0 || v == 255));
+        }
+    }
+
+    #[test]
+    fn test_noise_shaping_keeps_mean_tone() {
+        let palette = vec![Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)];
+        let (width, height) = (256, 64);
+        for gray in [32u8, 64, 128, 224] {
+            for order in 0..=3 {
+                let mut buffer = vec![gray; width * height * 3];
+                apply_error_diffusion_noise_shaped(
+                    &mut buffer,
+                    width,
+                    height,
+                    &palette,
+                    ErrorDiffusionKernel::FloydSteinberg,
+                    order,
+                );
+                let mean = buffer.iter().map(|&v| v as f64).sum::<f64>() / buffer.len() as f64;
+                // Order 0 itself ends up to ~2.5 darker, from truncating the
+                // diffused values to whole numbers
+                assert!(
+                    (mean - gray as f64).abs() < 3.0,
+                    "gray {} at order {} has mean {:.2}",
+                    gray,
+                    order,
+                    mean
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_per_channel_uses_channel_levels() {
+        let (width, height) = (16, 16);
+        let mut buffer: Vec<u8> = (0..width * height * 3)
+            .map(|i| (i * 37 % 256) as u8)
+            .collect();
+        let (r_levels, g_levels, b_levels) = ([0, 255], [0, 128, 255], [64, 192]);
+
+        apply_error_diffusion_per_channel(
+            &mut buffer,
+            width,
+            height,
+            &r_levels,
+            &g_levels,
+            &b_levels,
+            ErrorDiffusionKernel::FloydSteinberg,
+            true,
+        );
+
+        for pixel in buffer.chunks_exact(3) {
+            assert!(
+                r_levels.contains(&pixel[0]),
+                "Red {} not in its levels",
+                pixel[0]
+            );
+            assert!(
+                g_levels.contains(&pixel[1]),
+                "Green {} not in its levels",
+                pixel[1]
+            );
+            assert!(
+                b_levels.contains(&pixel[2]),
+                "Blue {} not in its levels",
+                pixel[2]
+            );
+        }
+    }
+
+    #[test]
+    fn test_per_channel_keeps_channels_independent() {
+        // A flat mid-gray red channel dithers to about half on, while the
+        // green and blue channels stay exactly on their levels
+        let (width, height) = (32, 32);
+        let mut buffer = [128u8, 0, 255].repeat(width * height);
+
+        apply_error_diffusion_per_channel(
+            &mut buffer,
+            width,
+            height,
+            &[0, 255],
+            &[0, 255],
+            &[0, 255],
+            ErrorDiffusionKernel::FloydSteinberg,
+            false,
+        );
+
+        let red_on = buffer.chunks_exact(3).filter(|p| p[0] == 255).count();
+        assert!((450..=574).contains(&red_on), "Red on count: {}", red_on);
+        assert!(buffer.chunks_exact(3).all(|p| p[1] == 0 && p[2] == 255));
+    }
+
+    #[test]
+    fn test_yuv_decoupled_uses_palette() {
+        let palette = vec![
+            Rgb::new(0, 0, 0),
+            Rgb::new(255, 255, 255),
+            Rgb::new(255, 0, 0),
+            Rgb::new(0, 0, 255),
+        ];
+        let (width, height) = (24, 24);
+        let source: Vec<u8> = (0..width * height * 3)
+            .map(|i| (i * 53 % 256) as u8)
+            .collect();
+
+        let mut buffer = source.clone();
+        apply_error_diffusion_yuv(
+            &mut buffer,
+            width,
+            height,
+            &palette,
+            ErrorDiffusionKernel::FloydSteinberg,
+            ErrorDiffusionKernel::Jarvis,
+            true,
+        );
+        for pixel in buffer.chunks_exact(3) {
+            assert!(palette.contains(&Rgb::new(pixel[0], pixel[1], pixel[2])));
+        }
+
+        // The chroma kernel changes the result independently of the luma kernel
+        let mut other = source;
+        apply_error_diffusion_yuv(
+            &mut other,
+            width,
+            height,
+            &palette,
+            ErrorDiffusionKernel::FloydSteinberg,
+            ErrorDiffusionKernel::FloydSteinberg,
+            true,
+        );
+        assert_ne!(buffer, other);
+    }
+
+    #[test]
+    fn test_wavefront_matches_serial() {
+        let (width, height) = (29, 13);
+        let source: Vec<u8> = (0..width * height * 3)
+            .map(|i| ((i * 37 + i / 7) % 256) as u8)
+            .collect();
+        let palette = [
+            Rgb::new(0, 0, 0),
+            Rgb::new(255, 255, 255),
+            Rgb::new(255, 0, 0),
+            Rgb::new(0, 0, 255),
+        ];
+
+        for kernel in [
+            ErrorDiffusionKernel::FloydSteinberg,
+            ErrorDiffusionKernel::Burkes,
+            ErrorDiffusionKernel::Sierra2,
+            ErrorDiffusionKernel::Sierra2_4A,
+        ] {
+            let mut serial = source.clone();
+            apply_error_diffusion(&mut serial, width, height, &palette, kernel, false);
+
+            for threads in [1, 3, 8] {
+                let mut wavefront = source.clone();
+                apply_error_diffusion_wavefront(&mut wavefront, width, &palette, kernel, threads);
+                assert_eq!(serial, wavefront, "{:?} on {} threads", kernel, threads);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ordered_keeps_error_aimed_at_visited_pixels() {
+        // Along a Morton curve most pixels have visited kernel targets; the
+        // error must still reach the unvisited ones to keep the mean level
+        // (dropping it leaves flat dark gray completely black)
+        let (width, height) = (32, 32);
+        let palette = [Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)];
+        for gray in [8u8, 128, 248] {
+            let mut buffer = vec![gray; width * height * 3];
+            apply_error_diffusion_ordered(
+                &mut buffer,
+                width,
+                height,
+                &palette,
+                ErrorDiffusionKernel::FloydSteinberg,
+                crate::dither::scan::morton_curve_order(width as u32, height as u32),
+            );
+
+            let white = buffer.chunks_exact(3).filter(|p| p[0] == 255).count();
+            let expected = width * height * gray as usize / 255;
+            assert!(
+                white.abs_diff(expected) <= width * height / 50,
+                "{} white pixels for gray {}, expected about {}",
+                white,
+                gray,
+                expected
+            );
         }
     }
 }
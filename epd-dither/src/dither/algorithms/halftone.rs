@@ -0,0 +1,78 @@
+//! Angle-rotated halftone screen dithering
+
+use crate::color::{distance::find_closest_color, Rgb};
+
+/// Evaluate the halftone screen threshold (0–255) at a pixel position
+///
+/// The screen is a sine wave along the axis rotated by `angle_degrees`,
+/// with `frequency` in radians per pixel.
+pub fn halftone_threshold(x: usize, y: usize, angle_degrees: f64, frequency: f64) -> f64 {
+    let angle = angle_degrees.to_radians();
+    let position = x as f64 * angle.cos() + y as f64 * angle.sin();
+
+    ((position * frequency).sin() + 1.0) / 2.0 * 255.0
+}
+
+/// Apply halftone screen dithering to an RGB buffer in place
+///
+/// Each pixel is offset by the screen threshold (centered around zero)
+/// and then mapped to the nearest palette color, which produces the
+/// classic screen-printed line pattern.
+pub fn apply_halftone(
+    buffer: &mut [u8],
+    width: usize,
+    height: usize,
+    palette: &[Rgb],
+    angle_degrees: f64,
+    frequency: f64,
+) {
+    for y in 0..height {
+        for x in 0..width {
+            let idx = (y * width + x) * 3;
+            let adjustment = halftone_threshold(x, y, angle_degrees, frequency) - 127.5;
+
+            let shifted = |value: u8| (value as f64 + adjustment).clamp(0.0, 255.0) as u8;
+            let color = Rgb::new(
+                shifted(buffer[idx]),
+                shifted(buffer[idx + 1]),
+                shifted(buffer[idx + 2]),
+            );
+
+            let (_, &new_color) =
+                find_closest_color(&color, palette).expect("Palette should not be empty");
+            buffer[idx..idx + 3].copy_from_slice(new_color.as_slice());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_halftone_threshold_range() {
+        for y in 0..16 {
+            for x in 0..16 {
+                let t = halftone_threshold(x, y, 45.0, 0.7);
+                assert!((0.0..=255.0).contains(&t), "Threshold out of range: {}", t);
+            }
+        }
+    }
+
+    #[test]
+    fn test_halftone_angle_changes_pattern() {
+        let palette = vec![Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)];
+        let source = vec![128u8; 16 * 16 * 3];
+
+        let mut at_0 = source.clone();
+        apply_halftone(&mut at_0, 16, 16, &palette, 0.0, 0.8);
+
+        let mut at_45 = source;
+        apply_halftone(&mut at_45, 16, 16, &palette, 45.0, 0.8);
+
+        for &value in at_0.iter().chain(at_45.iter()) {
+            assert!(value == 0 || value == 255);
+        }
+        assert_ne!(at_0, at_45);
+    }
+}
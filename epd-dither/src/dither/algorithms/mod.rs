@@ -1,5 +1,10 @@
 //! Dithering algorithm implementations
 
+pub mod diffusion_map;
 pub mod error_diffusion;
+pub mod halftone;
 pub mod ordered;
+pub mod quantize;
 pub mod random;
+pub mod riemersma;
+pub mod void_and_cluster;
@@ -1,5 +1,7 @@
 //! Ordered dithering using Bayer matrices
 
+use anyhow::{Context, Result};
+
 /// Generate a Bayer threshold matrix of the given size
 ///
 /// The JS implementation uses a pre-computed 8x8 matrix and extracts
@@ -50,6 +52,100 @@ pub fn create_bayer_matrix(width: u8, height: u8) -> Vec<Vec<usize>> {
     matrix
 }
 
+/// Sizes supported by [`create_magic_square_matrix`]
+pub const MAGIC_SQUARE_SIZES: [usize; 2] = [5, 9];
+
+/// Return a magic-square threshold map of the given size
+///
+/// Every row, column and main diagonal of a magic square has the same sum,
+/// so each line of the screen carries the same average threshold. These are
+/// classical constructions, not published dither-specific tables such as
+/// DM65:
+///
+/// - 5x5: `5 * ((y + 2x) mod 5) + ((2y + x) mod 5)`, a pandiagonal square
+///   (wrapped diagonals share the sum too). Each row and column holds one
+///   value from every fifth of the range, and consecutive levels are a
+///   knight move apart, except for a diagonal step after every fifth one.
+/// - 9x9: `9 * L[y mod 3][x mod 3] + L[y div 3][x div 3]`, where `L` is the
+///   Lo Shu square numbered from 0, so the square is nested inside itself
+///   the way Bayer matrices nest 2x2 blocks. Each 3x3 block holds one value
+///   from every ninth of the range, and consecutive levels take the same
+///   spot in successive blocks, visited in Lo Shu order.
+///
+/// Returns `None` for sizes other than those in [`MAGIC_SQUARE_SIZES`].
+pub fn create_magic_square_matrix(size: usize) -> Option<Vec<Vec<usize>>> {
+    #[rustfmt::skip]
+    const MAGIC_5: [[usize; 5]; 5] = [
+        [ 0, 11, 22,  8, 19],
+        [ 7, 18,  4, 10, 21],
+        [14, 20,  6, 17,  3],
+        [16,  2, 13, 24,  5],
+        [23,  9, 15,  1, 12],
+    ];
+
+    #[rustfmt::skip]
+    const MAGIC_9: [[usize; 9]; 9] = [
+        [70,  7, 52, 63,  0, 45, 68,  5, 50],
+        [25, 43, 61, 18, 36, 54, 23, 41, 59],
+        [34, 79, 16, 27, 72,  9, 32, 77, 14],
+        [65,  2, 47, 67,  4, 49, 69,  6, 51],
+        [20, 38, 56, 22, 40, 58, 24, 42, 60],
+        [29, 74, 11, 31, 76, 13, 33, 78, 15],
+        [66,  3, 48, 71,  8, 53, 64,  1, 46],
+        [21, 39, 57, 26, 44, 62, 19, 37, 55],
+        [30, 75, 12, 35, 80, 17, 28, 73, 10],
+    ];
+
+    match size {
+        5 => Some(MAGIC_5.iter().map(|row| row.to_vec()).collect()),
+        9 => Some(MAGIC_9.iter().map(|row| row.to_vec()).collect()),
+        _ => None,
+    }
+}
+
+/// Check that a user-supplied threshold map is usable for ordered dithering
+///
+/// The matrix must be non-empty and rectangular, and its values must be a
+/// permutation of `0..width * height`.
+pub fn validate_threshold_matrix(matrix: &[Vec<usize>]) -> Result<()> {
+    let width = matrix.first().map_or(0, Vec::len);
+    if width == 0 {
+        anyhow::bail!("Threshold matrix must be at least 1x1");
+    }
+    if let Some(y) = matrix.iter().position(|row| row.len() != width) {
+        anyhow::bail!(
+            "Threshold matrix row {} has {} values, expected {}",
+            y,
+            matrix[y].len(),
+            width
+        );
+    }
+
+    let count = width * matrix.len();
+    let mut seen = vec![false; count];
+    for &value in matrix.iter().flatten() {
+        if value >= count {
+            anyhow::bail!("Threshold value {} out of range 0..{}", value, count);
+        }
+        if std::mem::replace(&mut seen[value], true) {
+            anyhow::bail!("Duplicate threshold value {}", value);
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse a threshold map from a 2D JSON array, e.g. `[[0, 2], [3, 1]]`
+///
+/// The matrix is checked with [`validate_threshold_matrix`].
+pub fn load_threshold_matrix_from_json(json: &str) -> Result<Vec<Vec<usize>>> {
+    let matrix: Vec<Vec<usize>> =
+        serde_json::from_str(json).context("Threshold matrix must be a 2D array of integers")?;
+    validate_threshold_matrix(&matrix)?;
+
+    Ok(matrix)
+}
+
 /// Apply ordered dithering to a pixel value
 ///
 /// # Arguments
@@ -94,7 +190,10 @@ mod tests {
         // Test that all values are unique and in range
         let mut flat: Vec<_> = matrix.iter().flat_map(|row| row.iter().copied()).collect();
         flat.sort_unstable();
-        assert_eq!(flat, vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+        assert_eq!(
+            flat,
+            vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+        );
 
         // Test 8x8 matrix
         let matrix_8x8 = create_bayer_matrix(8, 8);
@@ -112,4 +211,100 @@ mod tests {
         // Result is u8, so always valid (no need to assert <= 255)
         assert!(result.len() == 3);
     }
+
+    #[test]
+    fn test_magic_square_matrices() {
+        for size in MAGIC_SQUARE_SIZES {
+            let matrix = create_magic_square_matrix(size).unwrap();
+            assert_eq!(matrix.len(), size);
+
+            let mut flat: Vec<_> = matrix.iter().flat_map(|row| row.iter().copied()).collect();
+            flat.sort_unstable();
+            assert_eq!(flat, (0..size * size).collect::<Vec<_>>());
+
+            // Rows, columns and both main diagonals share the magic sum
+            let magic_sum = size * (size * size - 1) / 2;
+            for i in 0..size {
+                assert_eq!(matrix[i].iter().sum::<usize>(), magic_sum);
+                assert_eq!(matrix.iter().map(|row| row[i]).sum::<usize>(), magic_sum);
+            }
+            let diagonal: usize = (0..size).map(|i| matrix[i][i]).sum();
+            let anti_diagonal: usize = (0..size).map(|i| matrix[i][size - 1 - i]).sum();
+            assert_eq!((diagonal, anti_diagonal), (magic_sum, magic_sum));
+        }
+
+        assert!(create_magic_square_matrix(4).is_none());
+    }
+
+    #[test]
+    fn test_magic_square_5_construction() {
+        let matrix = create_magic_square_matrix(5).unwrap();
+        for (y, row) in matrix.iter().enumerate() {
+            for (x, &value) in row.iter().enumerate() {
+                assert_eq!(value, 5 * ((y + 2 * x) % 5) + (2 * y + x) % 5);
+            }
+        }
+
+        // Pandiagonal: wrapped diagonals in both directions share the sum
+        for shift in 0..5 {
+            let down: usize = (0..5).map(|i| matrix[i][(i + shift) % 5]).sum();
+            let up: usize = (0..5).map(|i| matrix[i][(shift + 5 - i) % 5]).sum();
+            assert_eq!((down, up), (60, 60));
+        }
+
+        // Balanced: each row and column has one value from every fifth
+        for i in 0..5 {
+            let mut row: Vec<_> = matrix[i].iter().map(|v| v / 5).collect();
+            let mut column: Vec<_> = matrix.iter().map(|r| r[i] / 5).collect();
+            row.sort_unstable();
+            column.sort_unstable();
+            assert_eq!(row, [0, 1, 2, 3, 4]);
+            assert_eq!(column, [0, 1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn test_magic_square_9_construction() {
+        const LO_SHU: [[usize; 3]; 3] = [[7, 0, 5], [2, 4, 6], [3, 8, 1]];
+        let matrix = create_magic_square_matrix(9).unwrap();
+        for (y, row) in matrix.iter().enumerate() {
+            for (x, &value) in row.iter().enumerate() {
+                assert_eq!(value, 9 * LO_SHU[y % 3][x % 3] + LO_SHU[y / 3][x / 3]);
+            }
+        }
+
+        // Balanced: each 3x3 block has one value from every ninth
+        for block in 0..9 {
+            let (block_x, block_y) = (block % 3 * 3, block / 3 * 3);
+            let mut ninths: Vec<_> = (0..9)
+                .map(|i| matrix[block_y + i / 3][block_x + i % 3] / 9)
+                .collect();
+            ninths.sort_unstable();
+            assert_eq!(ninths, (0..9).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_validate_threshold_matrix() {
+        assert!(validate_threshold_matrix(&create_bayer_matrix(4, 2)).is_ok());
+        assert!(validate_threshold_matrix(&[vec![0]]).is_ok());
+
+        // Duplicate value (and therefore a gap)
+        assert!(validate_threshold_matrix(&[vec![0, 1], vec![1, 3]]).is_err());
+        // Gap without duplicates is out of range
+        assert!(validate_threshold_matrix(&[vec![0, 1], vec![2, 4]]).is_err());
+        // Ragged rows and empty matrices
+        assert!(validate_threshold_matrix(&[vec![0, 1], vec![2]]).is_err());
+        assert!(validate_threshold_matrix(&[]).is_err());
+        assert!(validate_threshold_matrix(&[vec![]]).is_err());
+    }
+
+    #[test]
+    fn test_load_threshold_matrix_from_json() {
+        let matrix = load_threshold_matrix_from_json("[[0, 2], [3, 1]]").unwrap();
+        assert_eq!(matrix, vec![vec![0, 2], vec![3, 1]]);
+
+        assert!(load_threshold_matrix_from_json("[[0, 2], [2, 1]]").is_err());
+        assert!(load_threshold_matrix_from_json("{\"matrix\": []}").is_err());
+    }
 }
@@ -0,0 +1,116 @@
+//! Plain nearest-color quantization (no dithering)
+
+use crate::color::{distance::find_closest_color, Rgb};
+
+/// Largest palette handled by the SIMD path
+pub const MAX_SIMD_PALETTE_SIZE: usize = 16;
+
+/// Quantize every pixel of an RGB buffer to its nearest palette color
+pub fn quantize_buffer(buffer: &mut [u8], palette: &[Rgb]) {
+    for pixel in buffer.chunks_exact_mut(3) {
+        let color = Rgb::new(pixel[0], pixel[1], pixel[2]);
+        let (_, &new_color) =
+            find_closest_color(&color, palette).expect("Palette should not be empty");
+        pixel.copy_from_slice(new_color.as_slice());
+    }
+}
+
+/// Quantize an RGB buffer using SIMD, four pixels at a time
+///
+/// Distances to every palette color are computed for four pixels in
+/// parallel lanes and the minimum is selected per lane. Squared integer
+/// distances are exact, and ties keep the first palette entry, so the
+/// result is identical to [`quantize_buffer`]. Palettes larger than
+/// [`MAX_SIMD_PALETTE_SIZE`] fall back to the scalar path.
+#[cfg(feature = "simd")]
+pub fn quantize_buffer_simd(buffer: &mut [u8], palette: &[Rgb]) {
+    use wide::{i32x4, CmpLt};
+
+    if palette.is_empty() {
+        panic!("Palette should not be empty");
+    }
+    if palette.len() > MAX_SIMD_PALETTE_SIZE {
+        quantize_buffer(buffer, palette);
+        return;
+    }
+
+    let mut batches = buffer.chunks_exact_mut(12);
+    for batch in &mut batches {
+        let channel = |offset: usize| {
+            i32x4::new([
+                batch[offset] as i32,
+                batch[3 + offset] as i32,
+                batch[6 + offset] as i32,
+                batch[9 + offset] as i32,
+            ])
+        };
+        let (r, g, b) = (channel(0), channel(1), channel(2));
+
+        let mut best_distance = i32x4::splat(i32::MAX);
+        let mut best_index = i32x4::splat(0);
+
+        for (idx, color) in palette.iter().enumerate() {
+            let dr = r - i32x4::splat(color.r() as i32);
+            let dg = g - i32x4::splat(color.g() as i32);
+            let db = b - i32x4::splat(color.b() as i32);
+            let distance = dr * dr + dg * dg + db * db;
+
+            // Strictly-less keeps the first match on ties, like the scalar path
+            let closer = distance.cmp_lt(best_distance);
+            best_distance = closer.blend(distance, best_distance);
+            best_index = closer.blend(i32x4::splat(idx as i32), best_index);
+        }
+
+        for (pixel, &idx) in batch.chunks_exact_mut(3).zip(best_index.to_array().iter()) {
+            pixel.copy_from_slice(palette[idx as usize].as_slice());
+        }
+    }
+
+    quantize_buffer(batches.into_remainder(), palette);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_buffer() -> Vec<u8> {
+        (0..(37 * 3)).map(|i| ((i * 97 + 13) % 256) as u8).collect()
+    }
+
+    fn test_palette() -> Vec<Rgb> {
+        vec![
+            Rgb::new(0, 0, 0),
+            Rgb::new(255, 255, 255),
+            Rgb::new(255, 0, 0),
+            Rgb::new(0, 255, 0),
+            Rgb::new(0, 0, 255),
+            Rgb::new(255, 255, 0),
+        ]
+    }
+
+    #[test]
+    fn test_quantize_buffer() {
+        let palette = test_palette();
+        let mut buffer = test_buffer();
+        quantize_buffer(&mut buffer, &palette);
+
+        for pixel in buffer.chunks_exact(3) {
+            assert!(palette.contains(&Rgb::new(pixel[0], pixel[1], pixel[2])));
+        }
+    }
+
+    #[cfg(feature = "simd")]
+    #[test]
+    fn test_simd_matches_scalar() {
+        let palette = test_palette();
+
+        let mut scalar = test_buffer();
+        quantize_buffer(&mut scalar, &palette);
+
+        // 37 pixels exercises both full batches and the scalar remainder
+        let mut simd = test_buffer();
+        quantize_buffer_simd(&mut simd, &palette);
+
+        assert_eq!(scalar, simd);
+    }
+}
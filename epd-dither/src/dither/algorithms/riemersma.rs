@@ -0,0 +1,102 @@
+//! Riemersma dithering: error diffusion along a Hilbert curve
+
+use crate::color::{distance::find_closest_color, Rgb};
+use crate::dither::scan::hilbert_curve_order;
+
+/// Number of recent quantization errors remembered
+const HISTORY_LEN: usize = 16;
+
+/// Weight of the most recent error relative to the oldest one
+const MAX_WEIGHT: f64 = 16.0;
+
+/// Apply Riemersma dithering to an RGB buffer in place
+///
+/// Pixels are visited along a Hilbert curve, which stays local in both
+/// directions and so avoids the directional artifacts of row scanning.
+/// Each pixel is offset by a weighted sum of the last 16 quantization
+/// errors (newest weighted 16 times the oldest, falling off exponentially)
+/// and mapped to the nearest palette color. Errors are kept per RGB
+/// channel, so any palette works, not just black and white.
+pub fn apply_riemersma(buffer: &mut [u8], width: usize, height: usize, palette: &[Rgb]) {
+    // Oldest first: weights[i] = MAX_WEIGHT^(i / (HISTORY_LEN - 1))
+    let weights: [f64; HISTORY_LEN] =
+        std::array::from_fn(|i| MAX_WEIGHT.powf(i as f64 / (HISTORY_LEN - 1) as f64) / MAX_WEIGHT);
+    let mut history = [[0.0f64; 3]; HISTORY_LEN];
+
+    for (x, y) in hilbert_curve_order(width as u32, height as u32) {
+        let idx = (y as usize * width + x as usize) * 3;
+        let original = [buffer[idx], buffer[idx + 1], buffer[idx + 2]];
+
+        let adjusted = std::array::from_fn(|c| {
+            let error: f64 = weights.iter().zip(&history).map(|(w, e)| w * e[c]).sum();
+            (original[c] as f64 + error).round().clamp(0.0, 255.0) as u8
+        });
+        let (_, &new_color) =
+            find_closest_color(&Rgb(adjusted), palette).expect("Palette should not be empty");
+        buffer[idx..idx + 3].copy_from_slice(new_color.as_slice());
+
+        history.rotate_left(1);
+        history[HISTORY_LEN - 1] =
+            std::array::from_fn(|c| original[c] as f64 - new_color.0[c] as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::palette::PaletteManager;
+
+    /// Horizontal gradient through every color of the palette in turn
+    fn palette_gradient(palette: &[Rgb], width: usize, height: usize) -> Vec<u8> {
+        let segments = (palette.len() - 1) as f64;
+        let mut buffer = Vec::with_capacity(width * height * 3);
+        for _ in 0..height {
+            for x in 0..width {
+                let t = x as f64 / (width - 1) as f64 * segments;
+                let i = (t.floor() as usize).min(palette.len() - 2);
+                let f = t - i as f64;
+                for c in 0..3 {
+                    let (a, b) = (palette[i].0[c] as f64, palette[i + 1].0[c] as f64);
+                    buffer.push((a + (b - a) * f).round() as u8);
+                }
+            }
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_riemersma_multicolor_palettes() {
+        let manager = PaletteManager::new().unwrap();
+        for name in ["spectra6", "acep"] {
+            let palette = manager.get_palette(name).unwrap().colors;
+            let source = palette_gradient(&palette, 96, 32);
+            let mut buffer = source.clone();
+            apply_riemersma(&mut buffer, 96, 32, &palette);
+
+            for pixel in buffer.chunks_exact(3) {
+                assert!(
+                    palette.contains(&Rgb::new(pixel[0], pixel[1], pixel[2])),
+                    "{}",
+                    name
+                );
+            }
+
+            // Error is carried along, so the average color is preserved
+            for c in 0..3 {
+                let error: f64 = source
+                    .chunks_exact(3)
+                    .zip(buffer.chunks_exact(3))
+                    .map(|(s, d)| s[c] as f64 - d[c] as f64)
+                    .sum::<f64>()
+                    / (96 * 32) as f64;
+                assert!(
+                    error.abs() < 0.05 * 255.0,
+                    "{} channel {}: {}",
+                    name,
+                    c,
+                    error
+                );
+            }
+        }
+    }
+}
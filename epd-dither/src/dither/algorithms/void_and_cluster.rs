@@ -0,0 +1,306 @@
+//! Void-and-cluster blue-noise threshold maps, batch and streaming
+
+use anyhow::Result;
+use rand::{rngs::StdRng, seq::index::sample, SeedableRng};
+
+/// Standard deviation of the Gaussian energy filter, in pixels
+const SIGMA: f64 = 1.5;
+
+/// Share of pixels set in the initial binary pattern
+const INITIAL_DENSITY: f64 = 0.1;
+
+/// Seed of the initial pattern, so every map of a given size is the same
+const INITIAL_PATTERN_SEED: u64 = 0x5eed;
+
+/// Ranking step the generator is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// Ranking the initial pattern's pixels by removing its tightest clusters
+    RemoveClusters,
+    /// Ranking the remaining pixels by filling the largest voids
+    FillVoids,
+    Done,
+}
+
+/// Ranking state of the void-and-cluster algorithm
+///
+/// Each [`VcState::step`] ranks one more pixel. The pixels set in the
+/// initial pattern are ranked first (from the top rank of that pattern
+/// down), then all others from there up.
+#[derive(Debug, Clone)]
+struct VcState {
+    width: usize,
+    height: usize,
+    /// Toroidal Gaussian weight for each (dy, dx) offset
+    weights: Vec<f64>,
+    initial: Vec<bool>,
+    initial_ones: usize,
+    pattern: Vec<bool>,
+    energy: Vec<f64>,
+    ranks: Vec<Option<usize>>,
+    phase: Phase,
+    next_rank: usize,
+}
+
+impl VcState {
+    fn new(width: usize, height: usize) -> Self {
+        let cells = width * height;
+        let weights = (0..cells)
+            .map(|idx| {
+                let (dx, dy) = (idx % width, idx / width);
+                let dx = dx.min(width - dx) as f64;
+                let dy = dy.min(height - dy) as f64;
+                (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+            })
+            .collect();
+
+        let mut state = Self {
+            width,
+            height,
+            weights,
+            initial: vec![false; cells],
+            initial_ones: 0,
+            pattern: vec![false; cells],
+            energy: vec![0.0; cells],
+            ranks: vec![None; cells],
+            phase: Phase::RemoveClusters,
+            next_rank: 0,
+        };
+
+        let ones = ((cells as f64 * INITIAL_DENSITY).round() as usize).max(1);
+        let mut rng = StdRng::seed_from_u64(INITIAL_PATTERN_SEED);
+        for idx in sample(&mut rng, cells, ones) {
+            state.set(idx, true);
+        }
+
+        // Move the tightest cluster into the largest void until that no
+        // longer changes anything
+        for _ in 0..cells {
+            let cluster = state.tightest_cluster();
+            state.set(cluster, false);
+            let void = state.largest_void();
+            state.set(void, true);
+            if void == cluster {
+                break;
+            }
+        }
+
+        state.initial = state.pattern.clone();
+        state.initial_ones = ones;
+        state.next_rank = ones;
+        state
+    }
+
+    fn is_done(&self) -> bool {
+        self.phase == Phase::Done
+    }
+
+    /// Set or clear one pixel of the pattern, updating the energy field
+    fn set(&mut self, idx: usize, value: bool) {
+        self.pattern[idx] = value;
+        let sign = if value { 1.0 } else { -1.0 };
+        let (px, py) = (idx % self.width, idx / self.width);
+
+        for (q, energy) in self.energy.iter_mut().enumerate() {
+            let dx = (q % self.width + self.width - px) % self.width;
+            let dy = (q / self.width + self.height - py) % self.height;
+            *energy += sign * self.weights[dy * self.width + dx];
+        }
+    }
+
+    /// Set pixel with the highest energy (first one on ties)
+    fn tightest_cluster(&self) -> usize {
+        let mut best = None;
+        for (idx, &energy) in self.energy.iter().enumerate() {
+            if self.pattern[idx] && best.is_none_or(|(_, e)| energy > e) {
+                best = Some((idx, energy));
+            }
+        }
+        best.expect("Pattern should have a set pixel").0
+    }
+
+    /// Unset pixel with the lowest energy (first one on ties)
+    fn largest_void(&self) -> usize {
+        let mut best = None;
+        for (idx, &energy) in self.energy.iter().enumerate() {
+            if !self.pattern[idx] && best.is_none_or(|(_, e)| energy < e) {
+                best = Some((idx, energy));
+            }
+        }
+        best.expect("Pattern should have an unset pixel").0
+    }
+
+    /// Rank one more pixel
+    fn step(&mut self) {
+        match self.phase {
+            Phase::RemoveClusters => {
+                let cluster = self.tightest_cluster();
+                self.set(cluster, false);
+                self.next_rank -= 1;
+                self.ranks[cluster] = Some(self.next_rank);
+
+                if self.next_rank == 0 {
+                    // Start again from the initial pattern for the other pixels
+                    for idx in 0..self.pattern.len() {
+                        if self.initial[idx] {
+                            self.set(idx, true);
+                        }
+                    }
+                    self.next_rank = self.initial_ones;
+                    self.phase = if self.next_rank == self.ranks.len() {
+                        Phase::Done
+                    } else {
+                        Phase::FillVoids
+                    };
+                }
+            }
+            Phase::FillVoids => {
+                let void = self.largest_void();
+                self.set(void, true);
+                self.ranks[void] = Some(self.next_rank);
+                self.next_rank += 1;
+
+                if self.next_rank == self.ranks.len() {
+                    self.phase = Phase::Done;
+                }
+            }
+            Phase::Done => {}
+        }
+    }
+}
+
+/// Generate a `width`×`height` void-and-cluster threshold map
+///
+/// Values are a permutation of `0..width * height` whose thresholded
+/// patterns are blue noise at every level, so the map can be used like a
+/// Bayer matrix (e.g. with [`crate::dither::DitheringAlgorithm::CustomOrdered`])
+/// without its grid artifacts. The map wraps around seamlessly when tiled.
+/// Generation takes time quadratic in the number of cells.
+pub fn void_and_cluster_matrix(width: usize, height: usize) -> Result<Vec<Vec<usize>>> {
+    check_size(width, height)?;
+
+    let mut state = VcState::new(width, height);
+    while !state.is_done() {
+        state.step();
+    }
+
+    Ok(state
+        .ranks
+        .chunks(width)
+        .map(|row| {
+            row.iter()
+                .map(|rank| rank.expect("Every cell is ranked"))
+                .collect()
+        })
+        .collect())
+}
+
+/// Row-by-row void-and-cluster threshold map generator
+///
+/// Ranks are assigned on demand: [`next_row`](Self::next_row) runs the
+/// algorithm only until every cell of the requested row is ranked, so
+/// dithering can start before the rest of the map is done. The ranking
+/// order is global, so the first rows usually need most of the work while
+/// later ones are often free. The values equal [`void_and_cluster_matrix`]
+/// divided by the number of cells.
+#[derive(Debug, Clone)]
+pub struct VoidAndClusterStream {
+    width: usize,
+    height: usize,
+    state: VcState,
+    row: usize,
+}
+
+impl VoidAndClusterStream {
+    /// Start generating a `width`×`height` map; both must be at least 1
+    pub fn new(width: usize, height: usize) -> Result<Self> {
+        check_size(width, height)?;
+        Ok(Self {
+            width,
+            height,
+            state: VcState::new(width, height),
+            row: 0,
+        })
+    }
+
+    /// Threshold values (0–1) of the next row of the map
+    ///
+    /// Rows repeat after `height` calls, tiling the map vertically.
+    pub fn next_row(&mut self) -> Vec<f64> {
+        let cells = (self.width * self.height) as f64;
+        let row = self.row * self.width..(self.row + 1) * self.width;
+        while self.state.ranks[row.clone()].iter().any(Option::is_none) {
+            self.state.step();
+        }
+        self.row = (self.row + 1) % self.height;
+
+        self.state.ranks[row]
+            .iter()
+            .map(|rank| rank.expect("Row is ranked") as f64 / cells)
+            .collect()
+    }
+}
+
+fn check_size(width: usize, height: usize) -> Result<()> {
+    if width == 0 || height == 0 {
+        anyhow::bail!(
+            "Void-and-cluster map must be at least 1x1, got {}x{}",
+            width,
+            height
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dither::algorithms::ordered::validate_threshold_matrix;
+
+    #[test]
+    fn test_matrix_is_permutation() {
+        for (width, height) in [(1, 1), (8, 8), (5, 3)] {
+            let matrix = void_and_cluster_matrix(width, height).unwrap();
+            assert_eq!((matrix[0].len(), matrix.len()), (width, height));
+            validate_threshold_matrix(&matrix).unwrap();
+        }
+        assert!(void_and_cluster_matrix(0, 4).is_err());
+        assert!(VoidAndClusterStream::new(4, 0).is_err());
+    }
+
+    #[test]
+    fn test_stream_matches_batch() {
+        let matrix = void_and_cluster_matrix(8, 8).unwrap();
+        let mut stream = VoidAndClusterStream::new(8, 8).unwrap();
+
+        // Two passes: the stream wraps around after the last row
+        for y in 0..16 {
+            let expected: Vec<f64> = matrix[y % 8]
+                .iter()
+                .map(|&rank| rank as f64 / 64.0)
+                .collect();
+            assert_eq!(stream.next_row(), expected, "row {}", y);
+        }
+    }
+
+    #[test]
+    fn test_half_threshold_is_evenly_spread() {
+        // Every 4x4 block of a blue-noise 16x16 map has about half its
+        // cells below the median, unlike clumpy white noise
+        let matrix = void_and_cluster_matrix(16, 16).unwrap();
+        for block_y in (0..16).step_by(4) {
+            for block_x in (0..16).step_by(4) {
+                let below = (0..16)
+                    .filter(|i| matrix[block_y + i / 4][block_x + i % 4] < 128)
+                    .count();
+                assert!(
+                    (5..=11).contains(&below),
+                    "{} of 16 at ({}, {})",
+                    below,
+                    block_x,
+                    block_y
+                );
+            }
+        }
+    }
+}
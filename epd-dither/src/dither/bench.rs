@@ -0,0 +1,91 @@
+//! Wall-clock timing of the built-in algorithms
+
+use super::{engine, DitherOptions, DitheringAlgorithm, ALGORITHM_NAMES};
+use crate::color::Palette;
+use anyhow::Result;
+use image::RgbImage;
+use std::time::{Duration, Instant};
+
+/// Time every built-in algorithm on a sample image
+///
+/// Each algorithm (with its default parameters) dithers a fresh copy of
+/// `img` `iterations` times. Returns `(name, average duration)` pairs
+/// sorted fastest-first. Image copies are made outside the timed section.
+pub fn benchmark_algorithms(
+    img: &RgbImage,
+    palette: &Palette,
+    iterations: u32,
+) -> Result<Vec<(String, Duration)>> {
+    if iterations == 0 {
+        anyhow::bail!("Benchmark iterations must be at least 1");
+    }
+
+    let mut results = Vec::with_capacity(ALGORITHM_NAMES.len());
+    for name in ALGORITHM_NAMES {
+        let options = DitherOptions {
+            algorithm: DitheringAlgorithm::from_name(name).expect("Built-in algorithm name"),
+            palette: palette.clone(),
+            serpentine: false,
+            ..Default::default()
+        };
+
+        let mut total = Duration::ZERO;
+        for _ in 0..iterations {
+            let mut work = img.clone();
+            let start = Instant::now();
+            engine::dither_image(&mut work, &options)?;
+            total += start.elapsed();
+        }
+
+        results.push((name.to_string(), total / iterations));
+    }
+
+    results.sort_by_key(|(_, duration)| *duration);
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_benchmark_covers_every_algorithm() {
+        let img = RgbImage::from_fn(16, 16, |x, y| image::Rgb([x as u8, y as u8, 128]));
+        let results = benchmark_algorithms(&img, &Palette::default(), 1).unwrap();
+
+        let mut names: Vec<&str> = results.iter().map(|(name, _)| name.as_str()).collect();
+        names.sort_unstable();
+        let mut expected = ALGORITHM_NAMES.to_vec();
+        expected.sort_unstable();
+        assert_eq!(names, expected);
+        assert!(results.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+
+        assert!(benchmark_algorithms(&img, &Palette::default(), 0).is_err());
+    }
+
+    // Compares wall-clock times, so it's unreliable under load or in debug
+    // builds; run with `cargo test --release -- --ignored`
+    #[test]
+    #[ignore]
+    fn test_quantization_is_fastest() {
+        let img = RgbImage::from_fn(200, 200, |x, y| image::Rgb([x as u8, y as u8, 128]));
+        let results = benchmark_algorithms(&img, &Palette::default(), 10).unwrap();
+        assert_eq!(results.len(), ALGORITHM_NAMES.len());
+
+        let duration = |name: &str| {
+            results
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, d)| *d)
+                .unwrap()
+        };
+        let quantize = duration("none");
+        for name in ALGORITHM_NAMES.iter().take(8) {
+            assert!(
+                quantize < duration(name),
+                "none was not faster than {}",
+                name
+            );
+        }
+    }
+}
@@ -1,27 +1,219 @@
 //! Main dithering engine that coordinates the various algorithms
 
 use super::{
-    algorithms::{error_diffusion, ordered, random},
-    DitheringAlgorithm, DitherOptions,
+    algorithms::{
+        error_diffusion, halftone, ordered, quantize, random, riemersma,
+        void_and_cluster::VoidAndClusterStream,
+    },
+    matrices, quality,
+    scan::{self, ScanOrder},
+    AnimationDitherOptions, DitherOptions, DitheringAlgorithm, ErrorDiffusionKernel,
 };
-use crate::color::{distance::find_closest_color, Rgb};
+use crate::color::{
+    adjust,
+    convert::rgb_to_luma,
+    distance::{
+        euclidean_distance, find_closest_color, find_closest_color_with_metric, DistanceMetric,
+    },
+    lut::{self, ColorLut},
+    Palette, Rgb,
+};
+use crate::{CancellationToken, EpdDitherError};
 use anyhow::Result;
-use image::RgbImage;
+use image::{GrayImage, RgbImage};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+thread_local! {
+    /// Per-thread cache of Bayer threshold maps used by ordered dithering
+    static BAYER_CACHE: RefCell<BayerCache> = RefCell::new(BayerCache::new());
+}
+
+/// Cache of Bayer threshold maps keyed by matrix size
+#[derive(Debug, Clone, Default)]
+pub struct BayerCache {
+    maps: HashMap<(u8, u8), Vec<Vec<usize>>>,
+}
+
+impl BayerCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the threshold map for a matrix size, generating it on first use
+    pub fn get_or_create(&mut self, width: u8, height: u8) -> &Vec<Vec<usize>> {
+        self.maps.entry((width, height)).or_insert_with(|| {
+            log_debug!("Building {}x{} Bayer threshold map", width, height);
+            ordered::create_bayer_matrix(width, height)
+        })
+    }
+
+    /// Number of cached threshold maps
+    pub fn len(&self) -> usize {
+        self.maps.len()
+    }
+
+    /// Check if the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.maps.is_empty()
+    }
+}
+
+/// Generate the Bayer threshold maps for the given sizes ahead of time
+///
+/// Ordered dithering reuses threshold maps from a per-thread cache, so
+/// maps are only generated once per thread and size. Calling this before
+/// a batch moves that work out of the first `dither_image` call.
+pub fn precompute_bayer_maps(sizes: &[(u8, u8)]) {
+    BAYER_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        for &(width, height) in sizes {
+            cache.get_or_create(width, height);
+        }
+    });
+}
+
+/// A rectangular region of an image, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Check whether the region lies entirely within an image of the given size
+    pub fn fits_within(&self, width: u32, height: u32) -> bool {
+        self.x as u64 + self.width as u64 <= width as u64
+            && self.y as u64 + self.height as u64 <= height as u64
+    }
+
+    /// Check whether a pixel lies inside the region
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && y >= self.y && x - self.x < self.width && y - self.y < self.height
+    }
+}
+
+/// Snap a channel value to the nearest of `bins` evenly spaced levels
+/// from 0 to 255
+fn snap_to_bin(value: u8, bins: u8) -> u8 {
+    let step = 255.0 / (bins.max(2) - 1) as f64;
+    ((value as f64 / step).round() * step).round() as u8
+}
+
+/// Count pixels per color after snapping each channel to `bins` levels
+///
+/// Levels are evenly spaced from 0 to 255, as in
+/// [`map_to_histogram_nearest`]; fewer than 2 bins count as 2.
+pub fn build_color_histogram(img: &RgbImage, bins: u8) -> HashMap<[u8; 3], u64> {
+    let mut histogram = HashMap::new();
+    for pixel in img.pixels() {
+        *histogram
+            .entry(pixel.0.map(|v| snap_to_bin(v, bins)))
+            .or_insert(0) += 1;
+    }
+    histogram
+}
+
+/// Copy of `img` with each channel snapped to the nearest of `bins` levels
+///
+/// Levels are evenly spaced from 0 to 255, so 2 bins leave only 0 and 255;
+/// fewer than 2 bins count as 2. Used as a pre-quantization step (see
+/// [`DitherOptions::pre_quantize_bins`]) before palette quantization.
+pub fn map_to_histogram_nearest(img: &RgbImage, bins: u8) -> RgbImage {
+    let mut mapped = img.clone();
+    for value in mapped.iter_mut() {
+        *value = snap_to_bin(*value, bins);
+    }
+    mapped
+}
+
+/// Apply [`DitherOptions::preprocessing`] and then
+/// [`DitherOptions::pre_quantize_bins`] in place
+///
+/// Each function here that dithers an `RgbImage` calls this once, as does
+/// [`crate::scaling::resize_and_dither_pipeline`] before its per-pixel
+/// pass; fallbacks go through [`dither_prepared`] so the steps are not
+/// repeated.
+pub(crate) fn prepare_input(img: &mut RgbImage, options: &DitherOptions) {
+    options.preprocessing.apply(img);
+    if let Some(bins) = options.pre_quantize_bins {
+        for value in img.iter_mut() {
+            *value = snap_to_bin(*value, bins);
+        }
+    }
+}
 
 /// Apply dithering to an image according to the given options
 pub fn dither_image(img: &mut RgbImage, options: &DitherOptions) -> Result<()> {
+    prepare_input(img, options);
+    dither_prepared(img, options)
+}
+
+/// [`dither_image`] on an image [`prepare_input`] has already been applied to
+fn dither_prepared(img: &mut RgbImage, options: &DitherOptions) -> Result<()> {
     let (width, height) = img.dimensions();
     let width = width as usize;
     let height = height as usize;
+    log_debug!(
+        "Dithering {}x{} image with {} ({} colors)",
+        width,
+        height,
+        options.algorithm.name(),
+        options.palette.len()
+    );
 
     match &options.algorithm {
-        DitheringAlgorithm::ErrorDiffusion(kernel) => {
-            error_diffusion::apply_error_diffusion(
+        DitheringAlgorithm::ErrorDiffusion(kernel) => match options.scan_order {
+            ScanOrder::Raster if options.noise_shaping_order > 0 => {
+                error_diffusion::apply_error_diffusion_noise_shaped(
+                    img.as_mut(),
+                    width,
+                    height,
+                    &options.palette.colors,
+                    *kernel,
+                    options.noise_shaping_order,
+                )
+            }
+            ScanOrder::Raster => error_diffusion::apply_error_diffusion(
+                img.as_mut(),
+                width,
+                height,
+                &options.palette.colors,
+                *kernel,
+                options.serpentine,
+            ),
+            ScanOrder::Morton => error_diffusion::apply_error_diffusion_ordered(
                 img.as_mut(),
                 width,
                 height,
                 &options.palette.colors,
                 *kernel,
+                scan::morton_curve_order(width as u32, height as u32),
+            ),
+        },
+
+        DitheringAlgorithm::ErrorDiffusionPerChannel(kernel) => {
+            let [r_levels, g_levels, b_levels] = channel_levels(&options.palette.colors);
+            error_diffusion::apply_error_diffusion_per_channel(
+                img.as_mut(),
+                width,
+                height,
+                &r_levels,
+                &g_levels,
+                &b_levels,
+                *kernel,
                 options.serpentine,
             );
         }
@@ -30,33 +222,87 @@ pub fn dither_image(img: &mut RgbImage, options: &DitherOptions) -> Result<()> {
             width: matrix_width,
             height: matrix_height,
         } => {
-            let threshold_map = ordered::create_bayer_matrix(*matrix_width, *matrix_height);
-            let threshold = 256.0 / 4.0; // Match JS implementation
+            let (lookup, lookup_width) = BAYER_CACHE.with(|cache| {
+                let mut cache = cache.borrow_mut();
+                let threshold_map = cache.get_or_create(*matrix_width, *matrix_height);
+                let threshold = 256.0 / 4.0; // Same strength as apply_threshold_map
+                (
+                    precompute_bayer_lookup(threshold_map, threshold),
+                    threshold_map[0].len(),
+                )
+            });
 
-            for y in 0..height {
-                for x in 0..width {
-                    let pixel = img.get_pixel(x as u32, y as u32);
-                    let old_color = [pixel[0], pixel[1], pixel[2]];
+            for (x, y, pixel) in img.enumerate_pixels_mut() {
+                let dithered = apply_ordered_dither_fast(
+                    pixel.0,
+                    x as usize,
+                    y as usize,
+                    &lookup,
+                    lookup_width,
+                );
+                let (_, &new_color) = find_closest_color(&Rgb(dithered), &options.palette.colors)
+                    .expect("Palette should not be empty");
+                pixel.0 = new_color.0;
+            }
+        }
 
-                    // Apply ordered dither
-                    let dithered = ordered::apply_ordered_dither(
-                        old_color,
-                        x,
-                        y,
-                        &threshold_map,
-                        threshold,
-                    );
-
-                    // Quantize to palette
-                    let quantized_rgb = Rgb::new(dithered[0], dithered[1], dithered[2]);
-                    let (_, &new_color) = find_closest_color(&quantized_rgb, &options.palette.colors)
-                        .expect("Palette should not be empty");
-
-                    img.put_pixel(x as u32, y as u32, image::Rgb([
-                        new_color.r(),
-                        new_color.g(),
-                        new_color.b(),
-                    ]));
+        DitheringAlgorithm::MagicSquare { size } => {
+            let threshold_map = magic_square_map(*size)?;
+            apply_threshold_map(img, &options.palette.colors, &threshold_map, None);
+        }
+
+        DitheringAlgorithm::CustomOrdered { matrix } => {
+            ordered::validate_threshold_matrix(matrix)?;
+            apply_threshold_map(img, &options.palette.colors, matrix, None);
+        }
+
+        DitheringAlgorithm::OrderedPerColor {
+            width: matrix_width,
+            height: matrix_height,
+        } => {
+            BAYER_CACHE.with(|cache| {
+                let mut cache = cache.borrow_mut();
+                let threshold_map = cache.get_or_create(*matrix_width, *matrix_height);
+                ordered_color_dithering(img, &options.palette.colors, threshold_map);
+            });
+        }
+
+        DitheringAlgorithm::YuvDecoupled {
+            luma_kernel,
+            chroma_kernel,
+        } => error_diffusion::apply_error_diffusion_yuv(
+            img.as_mut(),
+            width,
+            height,
+            &options.palette.colors,
+            *luma_kernel,
+            *chroma_kernel,
+            options.serpentine,
+        ),
+
+        DitheringAlgorithm::Riemersma => {
+            riemersma::apply_riemersma(img.as_mut(), width, height, &options.palette.colors)
+        }
+
+        DitheringAlgorithm::BlueNoiseStreaming {
+            width: map_width,
+            height: map_height,
+        } => {
+            let mut stream = VoidAndClusterStream::new(*map_width as usize, *map_height as usize)?;
+            let threshold = 256.0 / 4.0; // Same strength as apply_threshold_map
+
+            for y in 0..height as u32 {
+                let thresholds = stream.next_row();
+                for x in 0..width as u32 {
+                    let pixel = img.get_pixel_mut(x, y);
+                    let adjustment = thresholds[x as usize % thresholds.len()] * threshold;
+                    let dithered = pixel
+                        .0
+                        .map(|value| (value as f64 + adjustment).clamp(0.0, 255.0) as u8);
+                    let (_, &new_color) =
+                        find_closest_color(&Rgb(dithered), &options.palette.colors)
+                            .expect("Palette should not be empty");
+                    pixel.0 = new_color.0;
                 }
             }
         }
@@ -74,22 +320,80 @@ pub fn dither_image(img: &mut RgbImage, options: &DitherOptions) -> Result<()> {
             }
         }
 
-        DitheringAlgorithm::QuantizationOnly => {
+        DitheringAlgorithm::Halftone {
+            angle_degrees,
+            frequency,
+        } => {
+            halftone::apply_halftone(
+                img.as_mut(),
+                width,
+                height,
+                &options.palette.colors,
+                *angle_degrees,
+                *frequency,
+            );
+        }
+
+        DitheringAlgorithm::QuantizationOnly | DitheringAlgorithm::Posterize { .. } => {
+            if let DitheringAlgorithm::Posterize { levels } = options.algorithm {
+                adjust::posterize(img, levels)?;
+            }
+
             // Just quantize to nearest palette color, no dithering
-            for y in 0..height {
-                for x in 0..width {
-                    let pixel = img.get_pixel(x as u32, y as u32);
-                    let old_color = Rgb::new(pixel[0], pixel[1], pixel[2]);
+            #[cfg(feature = "simd")]
+            quantize::quantize_buffer_simd(img.as_mut(), &options.palette.colors);
+            #[cfg(not(feature = "simd"))]
+            quantize::quantize_buffer(img.as_mut(), &options.palette.colors);
+        }
 
-                    let (_, &new_color) = find_closest_color(&old_color, &options.palette.colors)
-                        .expect("Palette should not be empty");
+        DitheringAlgorithm::Custom(ditherer) => ditherer.dither(img, &options.palette)?,
+    }
 
-                    img.put_pixel(x as u32, y as u32, image::Rgb([
-                        new_color.r(),
-                        new_color.g(),
-                        new_color.b(),
-                    ]));
-                }
+    Ok(())
+}
+
+/// Distinct values each RGB channel takes across the palette, sorted
+fn channel_levels(palette: &[Rgb]) -> [Vec<u8>; 3] {
+    let mut levels = [0, 1, 2].map(|channel| {
+        palette
+            .iter()
+            .map(|color| color.0[channel])
+            .collect::<Vec<u8>>()
+    });
+    for channel in &mut levels {
+        channel.sort_unstable();
+        channel.dedup();
+    }
+    levels
+}
+
+/// Apply dithering to a grayscale image
+///
+/// The gray value is treated as luminance and each pixel becomes the
+/// luminance of a palette color (0 and 255 for a black and white palette).
+/// Error diffusion runs directly on the single channel, matching palette
+/// colors by luminance; other algorithms dither an RGB copy and convert
+/// the result back.
+pub fn dither_gray_image(img: &mut GrayImage, options: &DitherOptions) -> Result<()> {
+    let (width, height) = img.dimensions();
+
+    match &options.algorithm {
+        DitheringAlgorithm::ErrorDiffusion(kernel) => {
+            let levels: Vec<u8> = options.palette.colors.iter().map(rgb_to_luma).collect();
+            error_diffusion::apply_error_diffusion_gray(
+                img.as_mut(),
+                width as usize,
+                height as usize,
+                &levels,
+                *kernel,
+                options.serpentine,
+            );
+        }
+        _ => {
+            let mut rgb = image::DynamicImage::ImageLuma8(img.clone()).to_rgb8();
+            dither_image(&mut rgb, options)?;
+            for (gray, pixel) in img.pixels_mut().zip(rgb.pixels()) {
+                gray.0[0] = rgb_to_luma(&Rgb(pixel.0));
             }
         }
     }
@@ -97,87 +401,2056 @@ pub fn dither_image(img: &mut RgbImage, options: &DitherOptions) -> Result<()> {
     Ok(())
 }
 
-/// Replace colors in an image with device-specific colors
+/// Look up the magic-square threshold map for `size`
+fn magic_square_map(size: u8) -> Result<Vec<Vec<usize>>> {
+    ordered::create_magic_square_matrix(size as usize).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Invalid magic square size: {}. Valid sizes: {:?}",
+            size,
+            ordered::MAGIC_SQUARE_SIZES
+        )
+    })
+}
+
+/// Apply a threshold map, optionally scaling its strength per pixel by `mask / 255`
+fn apply_threshold_map(
+    img: &mut RgbImage,
+    palette: &[Rgb],
+    threshold_map: &[Vec<usize>],
+    mask: Option<&GrayImage>,
+) {
+    let threshold = 256.0 / 4.0; // Match JS implementation
+
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let strength = mask.map_or(1.0, |mask| mask.get_pixel(x, y)[0] as f64 / 255.0);
+        pixel.0 = threshold_pixel(pixel.0, x, y, threshold_map, threshold * strength, palette);
+    }
+}
+
+/// Flatten a threshold map into per-cell pixel adjustments
 ///
-/// This is used after dithering to convert the calibrated colors
-/// back to the actual device color values.
-pub fn replace_colors(
+/// Entry `(y % h) * w + (x % w)` holds the offset that
+/// [`ordered::apply_ordered_dither`] would add at `(x, y)` for the given
+/// `threshold`, so the division is done once per cell instead of once per
+/// pixel. Use with [`apply_ordered_dither_fast`].
+pub fn precompute_bayer_lookup(matrix: &[Vec<usize>], threshold: f64) -> Vec<f64> {
+    let cells = (matrix.len() * matrix.first().map_or(0, Vec::len)) as f64;
+
+    matrix
+        .iter()
+        .flatten()
+        .map(|&value| value as f64 / cells * threshold)
+        .collect()
+}
+
+/// Ordered dithering of one pixel using a lookup from [`precompute_bayer_lookup`]
+///
+/// `width` is the width of the threshold map the lookup was built from.
+/// The result is identical to [`ordered::apply_ordered_dither`].
+pub fn apply_ordered_dither_fast(
+    old_color: [u8; 3],
+    x: usize,
+    y: usize,
+    lookup: &[f64],
+    width: usize,
+) -> [u8; 3] {
+    let height = lookup.len() / width;
+    let adjustment = lookup[(y % height) * width + x % width];
+
+    old_color.map(|value| (value as f64 + adjustment).clamp(0.0, 255.0) as u8)
+}
+
+/// Dither one pixel against a threshold map and snap it to the palette
+fn threshold_pixel(
+    pixel: [u8; 3],
+    x: u32,
+    y: u32,
+    threshold_map: &[Vec<usize>],
+    threshold: f64,
+    palette: &[Rgb],
+) -> [u8; 3] {
+    let dithered =
+        ordered::apply_ordered_dither(pixel, x as usize, y as usize, threshold_map, threshold);
+
+    let quantized_rgb = Rgb::new(dithered[0], dithered[1], dithered[2]);
+    let (_, &new_color) =
+        find_closest_color(&quantized_rgb, palette).expect("Palette should not be empty");
+    new_color.0
+}
+
+/// Offset (columns, rows) between the threshold maps of consecutive color pairs
+const PER_COLOR_MAP_OFFSET: (usize, usize) = (3, 5);
+
+/// Ordered dithering with one threshold map per pair of adjacent palette colors
+///
+/// The palette is sorted by luma into a sequence of colors. Each pixel is
+/// projected onto the nearest segment between neighbors in that sequence,
+/// and the position along the segment is compared to the segment's
+/// threshold map to pick one of its two end colors. Unlike
+/// [`DitheringAlgorithm::Ordered`], which offsets the pixel and snaps it to
+/// the nearest color, the share of each color follows the input exactly up
+/// to the map's resolution. The map of segment `k` is `threshold_map`
+/// shifted by `k` times a fixed offset, so transitions between different
+/// color pairs do not line up.
+pub fn ordered_color_dithering(img: &mut RgbImage, palette: &[Rgb], threshold_map: &[Vec<usize>]) {
+    let sequence = luma_sequence(palette);
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        pixel.0 = per_color_pixel(pixel.0, x, y, threshold_map, &sequence);
+    }
+}
+
+/// Palette colors sorted by ascending luma (stable for equal luma)
+fn luma_sequence(palette: &[Rgb]) -> Vec<Rgb> {
+    assert!(!palette.is_empty(), "Palette should not be empty");
+    let mut sequence = palette.to_vec();
+    sequence.sort_by_key(rgb_to_luma);
+    sequence
+}
+
+/// Dither one pixel between the two ends of its nearest color segment
+fn per_color_pixel(
+    pixel: [u8; 3],
+    x: u32,
+    y: u32,
+    threshold_map: &[Vec<usize>],
+    sequence: &[Rgb],
+) -> [u8; 3] {
+    if sequence.len() == 1 {
+        return sequence[0].0;
+    }
+
+    let p = pixel.map(f64::from);
+    let mut best = (f64::INFINITY, 0, 0.0);
+    for (k, pair) in sequence.windows(2).enumerate() {
+        let (a, b) = (pair[0].0.map(f64::from), pair[1].0.map(f64::from));
+        let d = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+        let length_sq = d[0] * d[0] + d[1] * d[1] + d[2] * d[2];
+        let t = if length_sq == 0.0 {
+            0.0
+        } else {
+            (((p[0] - a[0]) * d[0] + (p[1] - a[1]) * d[1] + (p[2] - a[2]) * d[2]) / length_sq)
+                .clamp(0.0, 1.0)
+        };
+        let distance_sq = (0..3)
+            .map(|i| (p[i] - a[i] - t * d[i]).powi(2))
+            .sum::<f64>();
+        if distance_sq < best.0 {
+            best = (distance_sq, k, t);
+        }
+    }
+
+    let (_, k, t) = best;
+    let (height, width) = (threshold_map.len(), threshold_map[0].len());
+    let row = (y as usize + k * PER_COLOR_MAP_OFFSET.1) % height;
+    let column = (x as usize + k * PER_COLOR_MAP_OFFSET.0) % width;
+    let level = (threshold_map[row][column] as f64 + 0.5) / (width * height) as f64;
+
+    if t > level {
+        sequence[k + 1].0
+    } else {
+        sequence[k].0
+    }
+}
+
+/// Function dithering a single pixel at a position, see [`pixel_ditherer`]
+pub type PixelDitherer<'a> = Box<dyn Fn(u32, u32, [u8; 3]) -> [u8; 3] + 'a>;
+
+/// Per-pixel form of the algorithms whose output depends only on each
+/// pixel and its position (ordered variants, quantization only)
+///
+/// Lets callers dither while producing pixels instead of in a separate
+/// pass. Returns `None` for algorithms that need the whole image. The
+/// preprocessing steps of `options` are not applied; on input they have
+/// already been applied to, the result matches [`dither_image`].
+pub fn pixel_ditherer(options: &DitherOptions) -> Result<Option<PixelDitherer<'_>>> {
+    let palette = &options.palette.colors;
+    let threshold = 256.0 / 4.0; // Same strength as apply_threshold_map
+
+    let threshold_map = match &options.algorithm {
+        DitheringAlgorithm::Ordered { width, height } => {
+            BAYER_CACHE.with(|cache| cache.borrow_mut().get_or_create(*width, *height).clone())
+        }
+        DitheringAlgorithm::MagicSquare { size } => magic_square_map(*size)?,
+        DitheringAlgorithm::CustomOrdered { matrix } => {
+            ordered::validate_threshold_matrix(matrix)?;
+            matrix.clone()
+        }
+        DitheringAlgorithm::OrderedPerColor { width, height } => {
+            let threshold_map =
+                BAYER_CACHE.with(|cache| cache.borrow_mut().get_or_create(*width, *height).clone());
+            let sequence = luma_sequence(palette);
+            return Ok(Some(Box::new(move |x, y, pixel| {
+                per_color_pixel(pixel, x, y, &threshold_map, &sequence)
+            })));
+        }
+        DitheringAlgorithm::QuantizationOnly => {
+            return Ok(Some(Box::new(move |_, _, pixel| {
+                let (_, color) =
+                    find_closest_color(&Rgb(pixel), palette).expect("Palette should not be empty");
+                color.0
+            })));
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(Box::new(move |x, y, pixel| {
+        threshold_pixel(pixel, x, y, &threshold_map, threshold, palette)
+    })))
+}
+
+/// Dither an image, reporting `progress(rows_done, total_rows)`
+///
+/// Raster-order error diffusion, the slow case, reports after every row
+/// and checks `cancel` between rows; the rows below the stopping point are
+/// left undithered. Other algorithms are pixel-local and fast: they check
+/// `cancel` once before starting and report once when done. Cancellation
+/// returns [`EpdDitherError::Cancelled`].
+pub fn dither_image_with_progress(
     img: &mut RgbImage,
-    original_colors: &[Rgb],
-    replacement_colors: &[Rgb],
+    options: &DitherOptions,
+    mut progress: impl FnMut(u32, u32),
+    cancel: Option<&CancellationToken>,
 ) -> Result<()> {
-    if original_colors.len() != replacement_colors.len() {
+    let is_cancelled = || cancel.is_some_and(CancellationToken::is_cancelled);
+    let (width, height) = img.dimensions();
+    prepare_input(img, options);
+
+    match &options.algorithm {
+        DitheringAlgorithm::ErrorDiffusion(kernel)
+            if options.scan_order == ScanOrder::Raster && options.noise_shaping_order == 0 =>
+        {
+            let completed = error_diffusion::apply_error_diffusion_with_progress(
+                img.as_mut(),
+                width as usize,
+                height as usize,
+                &options.palette.colors,
+                *kernel,
+                options.serpentine,
+                &mut |y| {
+                    progress(y as u32 + 1, height);
+                    !is_cancelled()
+                },
+            );
+            if !completed {
+                return Err(EpdDitherError::Cancelled.into());
+            }
+        }
+        _ => {
+            if is_cancelled() {
+                return Err(EpdDitherError::Cancelled.into());
+            }
+            dither_prepared(img, options)?;
+            progress(height, height);
+        }
+    }
+
+    Ok(())
+}
+
+/// Dither with per-pixel strength taken from a grayscale mask
+///
+/// White mask pixels (255) are dithered normally and black ones (0) are
+/// only quantized, so focal regions can get a lighter pattern. Error
+/// diffusion scales the error each pixel receives (always scanning in
+/// raster order), and ordered/magic-square dithering scales the threshold
+/// map. Other algorithms have no strength to scale: pixels with a mask of
+/// at least 128 take the dithered color, the rest the quantized one.
+pub fn dither_with_mask(
+    img: &mut RgbImage,
+    mask: &GrayImage,
+    options: &DitherOptions,
+) -> Result<()> {
+    if img.dimensions() != mask.dimensions() {
         anyhow::bail!(
-            "Original and replacement color arrays must have the same length ({} vs {})",
-            original_colors.len(),
-            replacement_colors.len()
+            "Mask must match the image dimensions ({}x{} vs {}x{})",
+            mask.width(),
+            mask.height(),
+            img.width(),
+            img.height()
         );
     }
 
-    let (width, height) = img.dimensions();
-    let mut error_count = 0;
+    prepare_input(img, options);
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let palette = &options.palette.colors;
+    match &options.algorithm {
+        DitheringAlgorithm::ErrorDiffusion(kernel) => {
+            error_diffusion::apply_error_diffusion_masked(
+                img.as_mut(),
+                width,
+                height,
+                palette,
+                *kernel,
+                options.serpentine,
+                mask.as_raw(),
+            )
+        }
 
-    for y in 0..height {
-        for x in 0..width {
-            let pixel = img.get_pixel(x, y);
-            let current_color = Rgb::new(pixel[0], pixel[1], pixel[2]);
+        DitheringAlgorithm::Ordered {
+            width: matrix_width,
+            height: matrix_height,
+        } => BAYER_CACHE.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            let threshold_map = cache.get_or_create(*matrix_width, *matrix_height);
+            apply_threshold_map(img, palette, threshold_map, Some(mask));
+        }),
 
-            // Find matching color in original palette
-            if let Some(idx) = original_colors.iter().position(|&c| c == current_color) {
-                let new_color = replacement_colors[idx];
-                img.put_pixel(x, y, image::Rgb([
-                    new_color.r(),
-                    new_color.g(),
-                    new_color.b(),
-                ]));
-            } else {
-                error_count += 1;
-            }
+        DitheringAlgorithm::MagicSquare { size } => {
+            apply_threshold_map(img, palette, &magic_square_map(*size)?, Some(mask));
         }
-    }
 
-    if error_count > 0 {
-        eprintln!(
-            "Warning: {} pixels were not replaced (colors didn't match exactly)",
-            error_count
-        );
+        DitheringAlgorithm::CustomOrdered { matrix } => {
+            ordered::validate_threshold_matrix(matrix)?;
+            apply_threshold_map(img, palette, matrix, Some(mask));
+        }
+
+        _ => {
+            let mut dithered = img.clone();
+            dither_prepared(&mut dithered, options)?;
+            quantize::quantize_buffer(img.as_mut(), palette);
+
+            for ((pixel, dithered), weight) in
+                img.pixels_mut().zip(dithered.pixels()).zip(mask.pixels())
+            {
+                if weight[0] >= 128 {
+                    *pixel = *dithered;
+                }
+            }
+        }
     }
 
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::color::Palette;
+/// Summary of a dithering run
+#[derive(Debug, Clone, PartialEq)]
+pub struct DitherStats {
+    /// Number of pixels whose color changed
+    pub changed_pixels: usize,
+    /// Number of pixels using each palette color, in palette order
+    pub color_counts: Vec<usize>,
+    /// Mean squared per-channel error between the original and dithered image
+    pub mse: f64,
+    /// Shannon entropy in bits of `color_counts` (see
+    /// [`quality::color_utilization_entropy`])
+    pub color_entropy: f64,
+    /// Time spent dithering (zero when computed from existing images)
+    pub elapsed: Duration,
+}
 
-    #[test]
-    fn test_quantization_only() {
-        let mut img = RgbImage::new(2, 2);
-        img.put_pixel(0, 0, image::Rgb([100, 100, 100]));
-        img.put_pixel(1, 0, image::Rgb([200, 200, 200]));
-        img.put_pixel(0, 1, image::Rgb([50, 50, 50]));
-        img.put_pixel(1, 1, image::Rgb([150, 150, 150]));
+impl DitherStats {
+    /// Compare an original image with its dithered version
+    pub fn from_images(original: &RgbImage, dithered: &RgbImage, palette: &[Rgb]) -> Result<Self> {
+        check_same_dimensions(original, dithered)?;
 
-        let palette = Palette::new(
-            "test",
-            vec![Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)],
-        );
+        let mut changed_pixels = 0;
+        let mut color_counts = vec![0; palette.len()];
+        let mut squared_error = 0u64;
+        for (source, result) in original.pixels().zip(dithered.pixels()) {
+            if source != result {
+                changed_pixels += 1;
+            }
+            if let Some(idx) = palette.iter().position(|c| c.0 == result.0) {
+                color_counts[idx] += 1;
+            }
+            for (&a, &b) in source.0.iter().zip(result.0.iter()) {
+                squared_error += (a.abs_diff(b) as u64).pow(2);
+            }
+        }
 
-        let options = DitherOptions {
-            algorithm: DitheringAlgorithm::QuantizationOnly,
-            palette,
-            serpentine: false,
-        };
+        let samples = original.as_raw().len();
+        Ok(Self {
+            changed_pixels,
+            color_entropy: quality::entropy(&color_counts),
+            color_counts,
+            mse: if samples == 0 {
+                0.0
+            } else {
+                squared_error as f64 / samples as f64
+            },
+            elapsed: Duration::ZERO,
+        })
+    }
+}
 
-        dither_image(&mut img, &options).unwrap();
+/// Rows below the current one that a kernel diffuses error into
+fn kernel_lookahead(kernel: ErrorDiffusionKernel) -> u8 {
+    matrices::get_diffusion_matrix(kernel)
+        .iter()
+        .map(|entry| entry.offset[1])
+        .max()
+        .unwrap_or(0) as u8
+}
 
-        // All pixels should be either black or white
-        for pixel in img.pixels() {
-            assert!(
-                (pixel[0] == 0 && pixel[1] == 0 && pixel[2] == 0)
-                    || (pixel[0] == 255 && pixel[1] == 255 && pixel[2] == 255)
-            );
+/// Error diffusion with rows dithered in parallel
+///
+/// For kernels that only diffuse into the next row (Floyd-Steinberg,
+/// Burkes, Sierra2 and Sierra2_4A), rows are dithered concurrently in a
+/// wavefront: each row trails the one above by a few pixels (see
+/// [`error_diffusion::apply_error_diffusion_wavefront`]). With the
+/// `parallel` feature one thread per rayon worker is used, otherwise a
+/// single one. The output is identical to [`dither_image`]. Other kernels,
+/// serpentine scanning, scan orders, noise shaping and algorithms fall back
+/// to [`dither_image`].
+pub fn dither_image_parallel_rows(img: &mut RgbImage, options: &DitherOptions) -> Result<()> {
+    let kernel = match options.algorithm {
+        DitheringAlgorithm::ErrorDiffusion(kernel)
+            if kernel_lookahead(kernel) == 1
+                && !options.serpentine
+                && options.scan_order == ScanOrder::Raster
+                && options.noise_shaping_order == 0 =>
+        {
+            kernel
         }
+        _ => return dither_image(img, options),
+    };
+
+    #[cfg(feature = "parallel")]
+    let threads = rayon::current_num_threads();
+    #[cfg(not(feature = "parallel"))]
+    let threads = 1;
+
+    prepare_input(img, options);
+    let width = img.width() as usize;
+    error_diffusion::apply_error_diffusion_wavefront(
+        img.as_mut(),
+        width,
+        &options.palette.colors,
+        kernel,
+        threads,
+    );
+
+    Ok(())
+}
+
+/// Dither an image in horizontal strips of `strip_height` rows
+///
+/// Raster error diffusion processes the strips top to bottom, each one
+/// working only on its own rows plus the kernel's lookahead rows below,
+/// which carry the bottom row's error into the next strip. Ordered
+/// dithering and quantization only need no carry-over and are applied
+/// strip by strip. Both give the same result as [`dither_image`]; other
+/// algorithms (and Morton or noise-shaped error diffusion) dither the whole
+/// image at once.
+pub fn dither_image_chunked(
+    img: &mut RgbImage,
+    options: &DitherOptions,
+    strip_height: u32,
+) -> Result<()> {
+    dither_in_strips(img, options, strip_height, |_, _| {})
+}
+
+/// Dither an image, showing intermediate states every few rows
+///
+/// Works like [`dither_image_chunked`] with `preview_every_n_rows` as the
+/// strip height and calls `preview_fn` after each strip with a copy of the
+/// image: the rows dithered so far on top of the untouched source rows (the
+/// error already diffused into those is not shown). Algorithms that can't
+/// be split into strips produce a single preview of the finished image.
+pub fn dither_image_with_preview<F: FnMut(&RgbImage)>(
+    img: &mut RgbImage,
+    options: &DitherOptions,
+    preview_every_n_rows: u32,
+    mut preview_fn: F,
+) -> Result<()> {
+    let source = img.clone();
+    let row_len = img.width() as usize * 3;
+
+    dither_in_strips(img, options, preview_every_n_rows, |dithered, rows_done| {
+        let mut preview = source.clone();
+        let split = rows_done as usize * row_len;
+        preview.as_mut()[..split].copy_from_slice(&dithered.as_raw()[..split]);
+        preview_fn(&preview);
+    })
+}
+
+/// Dither strip by strip, calling `on_strip` with the image and the number
+/// of finished rows after each one
+fn dither_in_strips(
+    img: &mut RgbImage,
+    options: &DitherOptions,
+    strip_height: u32,
+    mut on_strip: impl FnMut(&RgbImage, u32),
+) -> Result<()> {
+    if strip_height == 0 {
+        anyhow::bail!("Strip height must be at least 1");
+    }
+
+    prepare_input(img, options);
+    let (width, height) = img.dimensions();
+    let row_len = width as usize * 3;
+    let strips = (0..height).step_by(strip_height as usize);
+
+    match options.algorithm {
+        DitheringAlgorithm::ErrorDiffusion(kernel)
+            if options.scan_order == ScanOrder::Raster && options.noise_shaping_order == 0 =>
+        {
+            let lookahead = kernel_lookahead(kernel) as u32;
+            for first_row in strips {
+                let rows = strip_height.min(height - first_row);
+                let end_row = (first_row + rows + lookahead).min(height);
+                error_diffusion::apply_error_diffusion_strip(
+                    &mut img.as_mut()[first_row as usize * row_len..end_row as usize * row_len],
+                    width as usize,
+                    &options.palette.colors,
+                    kernel,
+                    options.serpentine,
+                    first_row as usize,
+                    rows as usize,
+                );
+                on_strip(img, first_row + rows);
+            }
+        }
+        _ => match pixel_ditherer(options)? {
+            Some(ditherer) => {
+                for first_row in strips {
+                    let end_row = first_row.saturating_add(strip_height).min(height);
+                    for y in first_row..end_row {
+                        for x in 0..width {
+                            let pixel = img.get_pixel_mut(x, y);
+                            pixel.0 = ditherer(x, y, pixel.0);
+                        }
+                    }
+                    on_strip(img, end_row);
+                }
+            }
+            None => {
+                dither_prepared(img, options)?;
+                on_strip(img, height);
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Error diffusion over an image that arrives one scanline at a time
+///
+/// Keeps the quantization errors of the last few rows (as many as the
+/// kernel reaches down) and diffuses them into each new line before
+/// dithering it, in the same order as a whole-image pass. Feeding every
+/// line of an image through [`ScanlineDitherer::process_line`] therefore
+/// gives exactly the result of
+/// [`error_diffusion::apply_error_diffusion`].
+#[derive(Debug, Clone)]
+pub struct ScanlineDitherer {
+    palette: Vec<Rgb>,
+    kernel: ErrorDiffusionKernel,
+    /// Quantization errors of the previous rows, oldest row first
+    error_buffer: Vec<[f64; 3]>,
+    width: usize,
+    current_row: usize,
+    serpentine: bool,
+}
+
+impl ScanlineDitherer {
+    /// Start dithering lines of `width` pixels
+    pub fn new(
+        width: usize,
+        palette: Vec<Rgb>,
+        kernel: ErrorDiffusionKernel,
+        serpentine: bool,
+    ) -> Self {
+        let lookahead = kernel_lookahead(kernel) as usize;
+        Self {
+            palette,
+            kernel,
+            error_buffer: vec![[0.0; 3]; width * lookahead],
+            width,
+            current_row: 0,
+            serpentine,
+        }
+    }
+
+    /// Dither the next RGB scanline in place
+    ///
+    /// # Panics
+    /// Panics if `line` is not `width * 3` bytes long or the palette is
+    /// empty.
+    pub fn process_line(&mut self, line: &mut [u8]) {
+        assert_eq!(
+            line.len(),
+            self.width * 3,
+            "Scanline must be width * 3 bytes"
+        );
+        let diffusion_matrix = matrices::get_diffusion_matrix(self.kernel);
+        let lookahead = self.error_buffer.len() / self.width.max(1);
+
+        // Error from the rows above, replayed in the order they were scanned.
+        // Rows before the first one are all zero and change nothing.
+        for (i, errors) in self.error_buffer.chunks(self.width.max(1)).enumerate() {
+            let dy = (lookahead - i) as i32;
+            let row = self.current_row as i64 - dy as i64;
+            let reverse = self.is_reversed(row);
+            for x in self.scan(reverse) {
+                let error = errors[x];
+                for entry in diffusion_matrix
+                    .iter()
+                    .filter(|entry| entry.offset[1] == dy)
+                {
+                    self.diffuse(line, x, reverse, entry, error);
+                }
+            }
+        }
+
+        let reverse = self.is_reversed(self.current_row as i64);
+        let mut row_errors = vec![[0.0; 3]; self.width];
+        for x in self.scan(reverse) {
+            let idx = x * 3;
+            let old_pixel = Rgb::new(line[idx], line[idx + 1], line[idx + 2]);
+            let (_, &new_pixel) = find_closest_color_with_metric(
+                &old_pixel,
+                &self.palette,
+                DistanceMetric::Euclidean,
+            )
+            .expect("Palette should not be empty");
+            line[idx..idx + 3].copy_from_slice(new_pixel.as_slice());
+
+            let error = [0, 1, 2].map(|c| old_pixel.0[c] as f64 - new_pixel.0[c] as f64);
+            row_errors[x] = error;
+            for entry in diffusion_matrix.iter().filter(|entry| entry.offset[1] == 0) {
+                self.diffuse(line, x, reverse, entry, error);
+            }
+        }
+
+        if lookahead > 0 {
+            self.error_buffer.drain(..self.width);
+            self.error_buffer.extend(row_errors);
+        }
+        self.current_row += 1;
+    }
+
+    fn is_reversed(&self, row: i64) -> bool {
+        self.serpentine && row.rem_euclid(2) == 1
+    }
+
+    fn scan(&self, reverse: bool) -> Box<dyn Iterator<Item = usize>> {
+        if reverse {
+            Box::new((0..self.width).rev())
+        } else {
+            Box::new(0..self.width)
+        }
+    }
+
+    /// Add one kernel entry's share of `error` to the pixel it points at
+    fn diffuse(
+        &self,
+        line: &mut [u8],
+        x: usize,
+        reverse: bool,
+        entry: &matrices::DiffusionEntry,
+        error: [f64; 3],
+    ) {
+        let nx = if reverse {
+            x as i32 - entry.offset[0]
+        } else {
+            x as i32 + entry.offset[0]
+        };
+        if nx < 0 || nx >= self.width as i32 {
+            return;
+        }
+
+        let idx = nx as usize * 3;
+        for (c, channel_error) in error.iter().enumerate() {
+            line[idx + c] =
+                (line[idx + c] as f64 + channel_error * entry.factor).clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+/// Dither a copy of an image with error diffusion, also returning the
+/// quantization error at every pixel
+///
+/// Each entry is `old - new` for one pixel in row-major order, where `old`
+/// already includes the error diffused into it; see
+/// [`error_diffusion::apply_error_diffusion_collecting_errors`]. Meant for
+/// debugging kernels, so only raster-order error diffusion without noise
+/// shaping is supported.
+pub fn apply_dither_and_collect_errors(
+    img: &RgbImage,
+    options: &DitherOptions,
+) -> Result<(RgbImage, Vec<[f64; 3]>)> {
+    let DitheringAlgorithm::ErrorDiffusion(kernel) = options.algorithm else {
+        anyhow::bail!(
+            "Collecting errors needs error diffusion, not {}",
+            options.algorithm.name()
+        );
+    };
+    if options.scan_order != ScanOrder::Raster || options.noise_shaping_order != 0 {
+        anyhow::bail!("Collecting errors needs raster scanning without noise shaping");
+    }
+
+    let mut dithered = img.clone();
+    prepare_input(&mut dithered, options);
+    let (width, height) = dithered.dimensions();
+    let errors = error_diffusion::apply_error_diffusion_collecting_errors(
+        dithered.as_mut(),
+        width as usize,
+        height as usize,
+        &options.palette.colors,
+        kernel,
+        options.serpentine,
+    );
+
+    Ok((dithered, errors))
+}
+
+/// Dither a copy of an image, leaving the original untouched
+///
+/// Returns the dithered copy together with statistics comparing it to
+/// the original.
+pub fn dither_image_copy(
+    img: &RgbImage,
+    options: &DitherOptions,
+) -> Result<(RgbImage, DitherStats)> {
+    let mut dithered = img.clone();
+
+    let start = Instant::now();
+    dither_image(&mut dithered, options)?;
+    let elapsed = start.elapsed();
+
+    let stats = DitherStats {
+        elapsed,
+        ..DitherStats::from_images(img, &dithered, &options.palette.colors)?
+    };
+    Ok((dithered, stats))
+}
+
+/// Place two equally sized images next to each other (left, then right)
+pub fn side_by_side(left: &RgbImage, right: &RgbImage) -> Result<RgbImage> {
+    check_same_dimensions(left, right)?;
+
+    let width = left.width();
+    let mut combined = RgbImage::new(width * 2, left.height());
+    image::imageops::replace(&mut combined, left, 0, 0);
+    image::imageops::replace(&mut combined, right, width as i64, 0);
+    Ok(combined)
+}
+
+/// Arrange equally sized images in a grid, `columns` per row
+///
+/// Images fill the grid row by row; unused cells in the last row are black.
+pub fn tile_images(images: &[RgbImage], columns: u32) -> Result<RgbImage> {
+    let Some(first) = images.first() else {
+        anyhow::bail!("No images to tile");
+    };
+    if columns == 0 {
+        anyhow::bail!("Grid needs at least one column");
+    }
+    for img in &images[1..] {
+        check_same_dimensions(first, img)?;
+    }
+
+    let (width, height) = first.dimensions();
+    let rows = (images.len() as u32).div_ceil(columns);
+    let mut grid = RgbImage::new(width * columns.min(images.len() as u32), height * rows);
+    for (idx, img) in images.iter().enumerate() {
+        let (column, row) = (idx as u32 % columns, idx as u32 / columns);
+        image::imageops::replace(
+            &mut grid,
+            img,
+            (column * width) as i64,
+            (row * height) as i64,
+        );
+    }
+
+    Ok(grid)
+}
+
+/// Supported super-sampling factors
+pub const SUPER_SAMPLE_FACTORS: [u8; 3] = [2, 4, 8];
+
+/// Dither at `factor`× resolution and box-filter back down
+///
+/// The image is enlarged with nearest-neighbor scaling and dithered with
+/// `options`. Each `factor`×`factor` block then becomes one of its own
+/// pixels: the one whose luma rank within the block equals the rank of a
+/// `factor`×`factor` Bayer matrix at the output position. Across a flat
+/// area every rank is used equally often, so the output keeps the blocks'
+/// color proportions; averaging each block and snapping it to the nearest
+/// palette color would turn such an area into one solid color. This reduces
+/// aliasing from ordered threshold maps. The result has the input's
+/// dimensions and only palette colors.
+pub fn super_sample_dither(
+    img: &RgbImage,
+    options: &DitherOptions,
+    factor: u8,
+) -> Result<RgbImage> {
+    if !SUPER_SAMPLE_FACTORS.contains(&factor) {
+        anyhow::bail!(
+            "Super-sampling factor must be one of {:?}, got {}",
+            SUPER_SAMPLE_FACTORS,
+            factor
+        );
+    }
+
+    let (width, height) = img.dimensions();
+    let factor = factor as u32;
+    let mut enlarged = image::imageops::resize(
+        img,
+        width * factor,
+        height * factor,
+        image::imageops::FilterType::Nearest,
+    );
+    dither_image(&mut enlarged, options)?;
+
+    // Rank of each Bayer cell; ties (the 8x8 table repeats a value) are
+    // broken by position so every rank appears once
+    let bayer = ordered::create_bayer_matrix(factor as u8, factor as u8);
+    let mut cells: Vec<(usize, usize)> = (0..(factor * factor) as usize)
+        .map(|idx| (bayer[idx / factor as usize][idx % factor as usize], idx))
+        .collect();
+    cells.sort_unstable();
+    let mut ranks = vec![0; cells.len()];
+    for (rank, &(_, idx)) in cells.iter().enumerate() {
+        ranks[idx] = rank;
+    }
+
+    let mut block = Vec::with_capacity(cells.len());
+    Ok(RgbImage::from_fn(width, height, |x, y| {
+        block.clear();
+        for dy in 0..factor {
+            for dx in 0..factor {
+                block.push(*enlarged.get_pixel(x * factor + dx, y * factor + dy));
+            }
+        }
+        block.sort_by_key(|pixel| rgb_to_luma(&Rgb(pixel.0)));
+
+        block[ranks[((y % factor) * factor + x % factor) as usize]]
+    }))
+}
+
+/// Dither only a rectangular region of an image (partial refresh)
+///
+/// Pixels outside the region are left untouched and no error diffuses
+/// across the region boundary.
+pub fn dither_region(img: &mut RgbImage, options: &DitherOptions, region: Region) -> Result<()> {
+    let (width, height) = img.dimensions();
+    if !region.fits_within(width, height) {
+        anyhow::bail!(
+            "Region {}x{} at ({}, {}) does not fit within the {}x{} image",
+            region.width,
+            region.height,
+            region.x,
+            region.y,
+            width,
+            height
+        );
+    }
+
+    match &options.algorithm {
+        DitheringAlgorithm::ErrorDiffusion(kernel) if options.scan_order == ScanOrder::Raster => {
+            error_diffusion::apply_error_diffusion_region(
+                img.as_mut(),
+                width as usize,
+                height as usize,
+                &options.palette.colors,
+                *kernel,
+                options.serpentine,
+                (
+                    region.x as usize,
+                    region.y as usize,
+                    region.width as usize,
+                    region.height as usize,
+                ),
+            );
+        }
+        _ => {
+            // Position-dependent algorithms (and non-raster scans) are
+            // dithered on a copy of the region so pixels outside it are
+            // never touched
+            let mut sub_image =
+                image::imageops::crop_imm(img, region.x, region.y, region.width, region.height)
+                    .to_image();
+            dither_image(&mut sub_image, options)?;
+            image::imageops::replace(img, &sub_image, region.x as i64, region.y as i64);
+        }
+    }
+
+    Ok(())
+}
+
+/// Dither a sequence of frames with temporal error carry-over
+///
+/// After each frame is dithered, its per-pixel residual error (input minus
+/// dithered output) is scaled by `options.carry_over` and added to the next
+/// frame before it is dithered. Frames whose residual is zero are therefore
+/// dithered exactly as they would be independently.
+///
+/// All frames must have the same dimensions.
+pub fn dither_animation(frames: &mut [RgbImage], options: &AnimationDitherOptions) -> Result<()> {
+    let Some(first) = frames.first() else {
+        return Ok(());
+    };
+
+    let dimensions = first.dimensions();
+    if let Some(frame) = frames.iter().find(|f| f.dimensions() != dimensions) {
+        anyhow::bail!(
+            "All animation frames must have the same dimensions ({}x{} vs {}x{})",
+            dimensions.0,
+            dimensions.1,
+            frame.width(),
+            frame.height()
+        );
+    }
+
+    let carry_over = options.carry_over.clamp(0.0, 1.0);
+    let mut residual = vec![0.0f64; first.as_raw().len()];
+
+    for frame in frames.iter_mut() {
+        // Seed the previous frame's residual error into this frame
+        for (value, error) in frame.iter_mut().zip(&residual) {
+            *value = (*value as f64 + error * carry_over)
+                .round()
+                .clamp(0.0, 255.0) as u8;
+        }
+
+        let target = frame.clone();
+        dither_image(frame, &options.dither)?;
+
+        for ((error, &wanted), &actual) in residual.iter_mut().zip(target.iter()).zip(frame.iter())
+        {
+            *error = wanted as f64 - actual as f64;
+        }
+    }
+
+    Ok(())
+}
+
+/// How the pixels of an image map onto a palette
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ColorUsageReport {
+    /// Pixels that exactly match a palette color (and were replaced, for
+    /// [`replace_colors`])
+    pub replaced_count: u64,
+    /// Pixels that match no palette color
+    pub unmatched_count: u64,
+    /// Indices of palette colors that no pixel uses, in ascending order
+    ///
+    /// Duplicate palette colors only count towards their first index.
+    pub unused_palette_indices: Vec<usize>,
+}
+
+impl ColorUsageReport {
+    fn from_counts(counts: &[u64], unmatched_count: u64) -> Self {
+        Self {
+            replaced_count: counts.iter().sum(),
+            unmatched_count,
+            unused_palette_indices: (0..counts.len()).filter(|&idx| counts[idx] == 0).collect(),
+        }
+    }
+}
+
+/// Count how many pixels use each palette color, without modifying the image
+pub fn color_usage_report(img: &RgbImage, palette: &Palette) -> ColorUsageReport {
+    let mut counts = vec![0u64; palette.len()];
+    let mut unmatched_count = 0;
+
+    for pixel in img.pixels() {
+        match palette.colors.iter().position(|c| c.0 == pixel.0) {
+            Some(idx) => counts[idx] += 1,
+            None => unmatched_count += 1,
+        }
+    }
+
+    ColorUsageReport::from_counts(&counts, unmatched_count)
+}
+
+/// Replace colors in an image with device-specific colors
+///
+/// This is used after dithering to convert the calibrated colors
+/// back to the actual device color values. Returns which of
+/// `original_colors` were found; pixels matching none are left unchanged.
+pub fn replace_colors(
+    img: &mut RgbImage,
+    original_colors: &[Rgb],
+    replacement_colors: &[Rgb],
+) -> Result<ColorUsageReport> {
+    if original_colors.len() != replacement_colors.len() {
+        anyhow::bail!(
+            "Original and replacement color arrays must have the same length ({} vs {})",
+            original_colors.len(),
+            replacement_colors.len()
+        );
+    }
+
+    let (width, height) = img.dimensions();
+    let mut counts = vec![0u64; original_colors.len()];
+    let mut error_count = 0;
+
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = img.get_pixel(x, y);
+            let current_color = Rgb::new(pixel[0], pixel[1], pixel[2]);
+
+            // Find matching color in original palette
+            if let Some(idx) = original_colors.iter().position(|&c| c == current_color) {
+                let new_color = replacement_colors[idx];
+                img.put_pixel(
+                    x,
+                    y,
+                    image::Rgb([new_color.r(), new_color.g(), new_color.b()]),
+                );
+                counts[idx] += 1;
+            } else {
+                error_count += 1;
+            }
+        }
+    }
+
+    if error_count > 0 {
+        log_warn!(
+            "{} pixels were not replaced (colors didn't match exactly)",
+            error_count
+        );
+    }
+
+    Ok(ColorUsageReport::from_counts(&counts, error_count))
+}
+
+/// Replace colors by matching both palettes in order of luminance
+///
+/// The i-th lightest color of `original_palette` is replaced by the i-th
+/// lightest color of `device_palette`, so device colors may be listed in
+/// any order. Colors with equal luminance keep their palette order. Fails
+/// if the palettes have different lengths. Unused indices in the report
+/// refer to `original_palette`.
+pub fn replace_colors_by_luminance(
+    img: &mut RgbImage,
+    original_palette: &Palette,
+    device_palette: &Palette,
+) -> Result<ColorUsageReport> {
+    if original_palette.len() != device_palette.len() {
+        anyhow::bail!(
+            "Palettes must have the same number of colors ({} vs {})",
+            original_palette.len(),
+            device_palette.len()
+        );
+    }
+
+    let luminance_order = |colors: &[Rgb]| {
+        let mut order: Vec<usize> = (0..colors.len()).collect();
+        order.sort_by_key(|&idx| rgb_to_luma(&colors[idx]));
+        order
+    };
+    let original_order = luminance_order(&original_palette.colors);
+    let device_order = luminance_order(&device_palette.colors);
+
+    let mut report = replace_colors(
+        img,
+        &original_order
+            .iter()
+            .map(|&idx| original_palette.colors[idx])
+            .collect::<Vec<_>>(),
+        &device_order
+            .iter()
+            .map(|&idx| device_palette.colors[idx])
+            .collect::<Vec<_>>(),
+    )?;
+    for idx in &mut report.unused_palette_indices {
+        *idx = original_order[*idx];
+    }
+    report.unused_palette_indices.sort_unstable();
+
+    Ok(report)
+}
+
+/// Replace colors through a continuous 3D transform instead of a table of
+/// exact matches
+///
+/// Simulates controllers that transform colors with their own LUT on the
+/// device. Unlike [`replace_colors`], every pixel is mapped, interpolating
+/// between lattice points; see [`apply_color_lookup_table`].
+pub fn replace_colors_with_lut(img: &mut RgbImage, lut: &ColorLut) -> Result<()> {
+    apply_color_lookup_table(img, lut)
+}
+
+/// Map an image through a 3D color lookup table (e.g. a controller waveform LUT)
+///
+/// Uses trilinear interpolation between lattice points. Fails if the LUT
+/// is malformed.
+pub fn apply_color_lookup_table(img: &mut RgbImage, lut: &ColorLut) -> Result<()> {
+    lut.validate()?;
+    lut::apply(img, lut);
+    Ok(())
+}
+
+/// Highlight the pixels that dithering changed
+///
+/// Returns a copy of `dithered` where every pixel that differs from
+/// `original` is replaced by `highlight_color`.
+pub fn diff_image(
+    original: &RgbImage,
+    dithered: &RgbImage,
+    highlight_color: Rgb,
+) -> Result<RgbImage> {
+    check_same_dimensions(original, dithered)?;
+
+    let mut diff = dithered.clone();
+    for (pixel, source) in diff.pixels_mut().zip(original.pixels()) {
+        if pixel != source {
+            pixel.0 = highlight_color.0;
+        }
+    }
+
+    Ok(diff)
+}
+
+/// Visualize how much each pixel changed as a heat map
+///
+/// Unchanged pixels are black; changed pixels range from blue (small
+/// change) to red (largest possible RGB distance).
+pub fn diff_image_heat(original: &RgbImage, dithered: &RgbImage) -> Result<RgbImage> {
+    check_same_dimensions(original, dithered)?;
+
+    let max_distance = euclidean_distance(&Rgb::new(0, 0, 0), &Rgb::new(255, 255, 255));
+    let (cold, hot) = (Rgb::new(0, 0, 255), Rgb::new(255, 0, 0));
+
+    let mut heat = RgbImage::new(dithered.width(), dithered.height());
+    for ((pixel, source), result) in heat
+        .pixels_mut()
+        .zip(original.pixels())
+        .zip(dithered.pixels())
+    {
+        let distance = euclidean_distance(&Rgb(source.0), &Rgb(result.0));
+        if distance > 0.0 {
+            pixel.0 = cold.blend(hot, distance / max_distance).0;
+        }
+    }
+
+    Ok(heat)
+}
+
+/// Color each pixel by the palette entry it was dithered to
+///
+/// Palette index `i` of `n` gets hue `i * 360 / n` (index 0 is red) at full
+/// saturation and 50% lightness, so the spatial distribution of each
+/// palette color stands out regardless of how similar the colors are.
+/// Pixels map to their nearest palette color.
+pub fn generate_palette_coverage_map(dithered: &RgbImage, palette: &Palette) -> Result<RgbImage> {
+    if palette.is_empty() {
+        anyhow::bail!("Cannot map coverage of an empty palette");
+    }
+
+    let hues: Vec<Rgb> = (0..palette.len())
+        .map(|idx| Rgb::from_hsl(idx as f64 * 360.0 / palette.len() as f64, 1.0, 0.5))
+        .collect();
+
+    let mut map = RgbImage::new(dithered.width(), dithered.height());
+    for (pixel, source) in map.pixels_mut().zip(dithered.pixels()) {
+        let (idx, _) = find_closest_color(&Rgb(source.0), &palette.colors)
+            .expect("Palette should not be empty");
+        pixel.0 = hues[idx].0;
+    }
+
+    Ok(map)
+}
+
+fn check_same_dimensions(original: &RgbImage, dithered: &RgbImage) -> Result<()> {
+    if original.dimensions() != dithered.dimensions() {
+        anyhow::bail!(
+            "Images must have the same dimensions ({}x{} vs {}x{})",
+            original.width(),
+            original.height(),
+            dithered.width(),
+            dithered.height()
+        );
+    }
+    Ok(())
+}
+
+/// Invert every pixel of an image (`v` becomes `255 - v` in each channel)
+///
+/// Used for negative-image display modes (white on black). Applying it
+/// twice returns the original image.
+pub fn invert_colors(img: &mut RgbImage) {
+    for pixel in img.pixels_mut() {
+        pixel.0 = [255 - pixel[0], 255 - pixel[1], 255 - pixel[2]];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Palette;
+    use crate::dither::ErrorDiffusionKernel;
+
+    #[test]
+    fn test_quantization_only() {
+        let mut img = RgbImage::new(2, 2);
+        img.put_pixel(0, 0, image::Rgb([100, 100, 100]));
+        img.put_pixel(1, 0, image::Rgb([200, 200, 200]));
+        img.put_pixel(0, 1, image::Rgb([50, 50, 50]));
+        img.put_pixel(1, 1, image::Rgb([150, 150, 150]));
+
+        let palette = Palette::new("test", vec![Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)]);
+
+        let options = DitherOptions {
+            algorithm: DitheringAlgorithm::QuantizationOnly,
+            palette,
+            serpentine: false,
+            ..Default::default()
+        };
+
+        dither_image(&mut img, &options).unwrap();
+
+        // All pixels should be either black or white
+        for pixel in img.pixels() {
+            assert!(
+                (pixel[0] == 0 && pixel[1] == 0 && pixel[2] == 0)
+                    || (pixel[0] == 255 && pixel[1] == 255 && pixel[2] == 255)
+            );
+        }
+    }
+
+    #[test]
+    fn test_invert_colors_white_to_black() {
+        let mut img = RgbImage::from_pixel(3, 3, image::Rgb([255, 255, 255]));
+        invert_colors(&mut img);
+
+        for pixel in img.pixels() {
+            assert_eq!(pixel.0, [0, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn test_invert_colors_twice_is_identity() {
+        let mut img = RgbImage::new(4, 4);
+        for (x, y, pixel) in img.enumerate_pixels_mut() {
+            *pixel = image::Rgb([(x * 60) as u8, (y * 40) as u8, 17]);
+        }
+        let original = img.clone();
+
+        invert_colors(&mut img);
+        assert_ne!(img, original);
+        invert_colors(&mut img);
+        assert_eq!(img, original);
+    }
+
+    fn bw_options(algorithm: DitheringAlgorithm) -> DitherOptions {
+        DitherOptions {
+            algorithm,
+            palette: Palette::new("bw", vec![Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)]),
+            serpentine: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_dither_animation_identical_frames() {
+        // A frame made only of palette colors has zero residual error,
+        // so the second frame must be dithered exactly like the first
+        let mut frame = RgbImage::new(8, 8);
+        for (x, y, pixel) in frame.enumerate_pixels_mut() {
+            let value = if (x + y) % 2 == 0 { 0 } else { 255 };
+            *pixel = image::Rgb([value, value, value]);
+        }
+
+        let mut frames = vec![frame.clone(), frame];
+        let options = AnimationDitherOptions {
+            dither: bw_options(DitheringAlgorithm::ErrorDiffusion(
+                ErrorDiffusionKernel::FloydSteinberg,
+            )),
+            carry_over: 1.0,
+        };
+
+        dither_animation(&mut frames, &options).unwrap();
+        assert_eq!(frames[0], frames[1]);
+    }
+
+    #[test]
+    fn test_dither_animation_carry_over_reduces_error() {
+        let (width, height) = (32, 32);
+        let mut frame_a = RgbImage::new(width, height);
+        for (x, _, pixel) in frame_a.enumerate_pixels_mut() {
+            let value = (x * 255 / (width - 1)) as u8;
+            *pixel = image::Rgb([value, value, value]);
+        }
+        let mut frame_b = frame_a.clone();
+        invert_colors(&mut frame_b);
+
+        // Squared error of the temporal average, which is what the eye
+        // perceives when the frames alternate
+        let temporal_error = |carry_over: f64| {
+            let mut frames = vec![frame_a.clone(), frame_b.clone()];
+            let options = AnimationDitherOptions {
+                dither: bw_options(DitheringAlgorithm::ErrorDiffusion(
+                    ErrorDiffusionKernel::FloydSteinberg,
+                )),
+                carry_over,
+            };
+            dither_animation(&mut frames, &options).unwrap();
+
+            let mut total = 0.0;
+            for i in 0..frame_a.as_raw().len() {
+                let wanted = (frame_a.as_raw()[i] as f64 + frame_b.as_raw()[i] as f64) / 2.0;
+                let actual = (frames[0].as_raw()[i] as f64 + frames[1].as_raw()[i] as f64) / 2.0;
+                total += (wanted - actual).powi(2);
+            }
+            total
+        };
+
+        let independent = temporal_error(0.0);
+        let carried = temporal_error(1.0);
+        assert!(carried < independent);
+    }
+
+    #[test]
+    fn test_dither_animation_mismatched_dimensions() {
+        let mut frames = vec![RgbImage::new(4, 4), RgbImage::new(4, 5)];
+        let options = AnimationDitherOptions::default();
+        assert!(dither_animation(&mut frames, &options).is_err());
+    }
+
+    #[test]
+    fn test_dither_region() {
+        let original = RgbImage::from_pixel(10, 10, image::Rgb([90, 90, 90]));
+        let region = Region::new(2, 2, 5, 4);
+
+        for algorithm in [
+            DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::FloydSteinberg),
+            DitheringAlgorithm::Ordered {
+                width: 4,
+                height: 4,
+            },
+        ] {
+            let mut img = original.clone();
+            dither_region(&mut img, &bw_options(algorithm), region).unwrap();
+
+            for (x, y, pixel) in img.enumerate_pixels() {
+                if region.contains(x, y) {
+                    assert!(pixel.0 == [0, 0, 0] || pixel.0 == [255, 255, 255]);
+                } else {
+                    assert_eq!(pixel, original.get_pixel(x, y));
+                }
+            }
+        }
+
+        let mut img = original.clone();
+        let options = bw_options(DitheringAlgorithm::QuantizationOnly);
+        assert!(dither_region(&mut img, &options, Region::new(8, 8, 5, 5)).is_err());
+    }
+
+    #[test]
+    fn test_posterize_algorithm() {
+        let mut img = RgbImage::from_fn(16, 1, |x, _| image::Rgb([x as u8 * 16, 0, 255]));
+        let options = bw_options(DitheringAlgorithm::Posterize { levels: 2 });
+        dither_image(&mut img, &options).unwrap();
+        assert!(img.iter().all(|&value| value == 0 || value == 255));
+
+        let options = bw_options(DitheringAlgorithm::Posterize { levels: 1 });
+        assert!(dither_image(&mut img, &options).is_err());
+    }
+
+    #[test]
+    fn test_bayer_cache() {
+        let mut cache = BayerCache::new();
+        assert!(cache.is_empty());
+
+        let map = cache.get_or_create(4, 4).clone();
+        assert_eq!(map, ordered::create_bayer_matrix(4, 4));
+        cache.get_or_create(4, 4);
+        cache.get_or_create(2, 2);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_precomputed_bayer_maps_match() {
+        let original = RgbImage::from_fn(16, 16, |x, y| {
+            image::Rgb([(x * 16) as u8, (y * 16) as u8, 128])
+        });
+        let options = bw_options(DitheringAlgorithm::Ordered {
+            width: 4,
+            height: 4,
+        });
+
+        let mut first = original.clone();
+        dither_image(&mut first, &options).unwrap();
+
+        precompute_bayer_maps(&[(4, 4), (8, 8)]);
+        let mut second = original;
+        dither_image(&mut second, &options).unwrap();
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_magic_square_dithering() {
+        let original = RgbImage::from_fn(18, 18, |x, y| {
+            image::Rgb([(x * 14) as u8, (y * 14) as u8, 128])
+        });
+
+        for size in [5, 9] {
+            let mut img = original.clone();
+            let options = bw_options(DitheringAlgorithm::MagicSquare { size });
+            dither_image(&mut img, &options).unwrap();
+            assert!(img.iter().all(|&value| value == 0 || value == 255));
+        }
+
+        let mut img = original;
+        let options = bw_options(DitheringAlgorithm::MagicSquare { size: 4 });
+        assert!(dither_image(&mut img, &options).is_err());
+    }
+
+    #[test]
+    fn test_dither_gray_image() {
+        for algorithm in [
+            DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::FloydSteinberg),
+            DitheringAlgorithm::Ordered {
+                width: 2,
+                height: 2,
+            },
+        ] {
+            let mut img = GrayImage::from_fn(4, 4, |x, y| image::Luma([((x + y * 4) * 17) as u8]));
+            dither_gray_image(&mut img, &bw_options(algorithm)).unwrap();
+            assert!(img.iter().all(|&value| value == 0 || value == 255));
+            assert!(img.contains(&0) && img.contains(&255));
+        }
+    }
+
+    #[test]
+    fn test_diff_image_identical() {
+        let img = RgbImage::from_fn(8, 8, |x, y| image::Rgb([(x * 30) as u8, (y * 30) as u8, 7]));
+        let highlight = Rgb::new(255, 0, 255);
+
+        let diff = diff_image(&img, &img, highlight).unwrap();
+        assert_eq!(diff, img);
+        assert!(diff.pixels().all(|p| p.0 != highlight.0));
+
+        let heat = diff_image_heat(&img, &img).unwrap();
+        assert!(heat.iter().all(|&v| v == 0));
+    }
+
+    #[test]
+    fn test_diff_image_highlights_changes() {
+        let original = RgbImage::from_pixel(4, 4, image::Rgb([100, 100, 100]));
+        let mut dithered = original.clone();
+        dithered.put_pixel(1, 2, image::Rgb([0, 0, 0]));
+        dithered.put_pixel(3, 3, image::Rgb([120, 120, 120]));
+        let highlight = Rgb::new(255, 0, 255);
+
+        let diff = diff_image(&original, &dithered, highlight).unwrap();
+        let highlighted: Vec<_> = diff
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p.0 == highlight.0)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        assert_eq!(highlighted, vec![(1, 2), (3, 3)]);
+
+        // Larger change (100 -> 0) is hotter than the smaller one (100 -> 120)
+        let heat = diff_image_heat(&original, &dithered).unwrap();
+        assert!(heat.get_pixel(1, 2)[0] > heat.get_pixel(3, 3)[0]);
+        assert_eq!(heat.get_pixel(0, 0).0, [0, 0, 0]);
+
+        assert!(diff_image(&original, &RgbImage::new(2, 2), highlight).is_err());
+    }
+
+    #[test]
+    fn test_morton_error_diffusion() {
+        let original = RgbImage::from_fn(13, 9, |x, y| {
+            image::Rgb([(x * 19) as u8, (y * 28) as u8, 90])
+        });
+        let mut options = bw_options(DitheringAlgorithm::ErrorDiffusion(
+            ErrorDiffusionKernel::FloydSteinberg,
+        ));
+
+        let mut raster = original.clone();
+        dither_image(&mut raster, &options).unwrap();
+
+        options.scan_order = ScanOrder::Morton;
+        let mut morton = original;
+        dither_image(&mut morton, &options).unwrap();
+
+        assert!(morton.iter().all(|&v| v == 0 || v == 255));
+        assert_ne!(raster, morton);
+    }
+
+    #[test]
+    fn test_super_sample_dither() {
+        let img = RgbImage::from_fn(10, 7, |x, y| {
+            let v = ((x * 25 + y * 10) % 256) as u8;
+            image::Rgb([v, v, v])
+        });
+        let options = bw_options(DitheringAlgorithm::Ordered {
+            width: 4,
+            height: 4,
+        });
+
+        for factor in SUPER_SAMPLE_FACTORS {
+            let result = super_sample_dither(&img, &options, factor).unwrap();
+            assert_eq!(result.dimensions(), img.dimensions());
+            for pixel in result.pixels() {
+                assert!(options.palette.colors.contains(&Rgb(pixel.0)));
+            }
+        }
+
+        assert!(super_sample_dither(&img, &options, 3).is_err());
+    }
+
+    #[test]
+    fn test_super_sample_dither_keeps_pattern() {
+        // Every block of a flat gray that dithers to 50% white averages to
+        // the same gray, which must still come out as a mix of black and
+        // white. Ordered dithering only adds thresholds, so its 50% is 96.
+        let cases = [
+            (
+                DitheringAlgorithm::Ordered {
+                    width: 4,
+                    height: 4,
+                },
+                96,
+            ),
+            (
+                DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::FloydSteinberg),
+                128,
+            ),
+        ];
+
+        for (algorithm, gray) in cases {
+            let img = RgbImage::from_pixel(16, 16, image::Rgb([gray; 3]));
+            let options = bw_options(algorithm);
+            for factor in [4, 8] {
+                let result = super_sample_dither(&img, &options, factor).unwrap();
+                let white = result.pixels().filter(|p| p.0 == [255; 3]).count();
+                assert!(
+                    (112..=144).contains(&white),
+                    "{} of 256 pixels white with {} at factor {}",
+                    white,
+                    options.algorithm.name(),
+                    factor
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_dither_image_copy_keeps_original() {
+        let img = RgbImage::from_fn(8, 8, |x, y| {
+            image::Rgb([(x * 32) as u8, (y * 32) as u8, 128])
+        });
+        let before = *img.get_pixel(3, 5);
+        let options = bw_options(DitheringAlgorithm::ErrorDiffusion(
+            crate::dither::ErrorDiffusionKernel::FloydSteinberg,
+        ));
+
+        let (dithered, stats) = dither_image_copy(&img, &options).unwrap();
+        assert_eq!(*img.get_pixel(3, 5), before);
+        assert_ne!(dithered, img);
+
+        assert_eq!(stats.color_counts.iter().sum::<usize>(), 64);
+        assert_eq!(stats.changed_pixels, 64);
+        assert!(stats.mse > 0.0);
+    }
+
+    #[test]
+    fn test_side_by_side() {
+        let left = RgbImage::from_pixel(3, 2, image::Rgb([255, 0, 0]));
+        let right = RgbImage::from_pixel(3, 2, image::Rgb([0, 0, 255]));
+        let combined = side_by_side(&left, &right).unwrap();
+
+        assert_eq!(combined.dimensions(), (6, 2));
+        assert_eq!(combined.get_pixel(2, 1).0, [255, 0, 0]);
+        assert_eq!(combined.get_pixel(3, 0).0, [0, 0, 255]);
+        assert!(side_by_side(&left, &RgbImage::new(2, 2)).is_err());
+    }
+
+    #[test]
+    fn test_replace_colors_by_luminance() {
+        let mut img = RgbImage::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                image::Rgb([10, 10, 10])
+            } else {
+                image::Rgb([240, 240, 240])
+            }
+        });
+        let original = Palette::new(
+            "dither",
+            vec![Rgb::new(10, 10, 10), Rgb::new(240, 240, 240)],
+        );
+        let device = Palette::new("device", vec![Rgb::new(255, 255, 250), Rgb::new(0, 0, 5)]);
+
+        let report = replace_colors_by_luminance(&mut img, &original, &device).unwrap();
+        assert!(report.unused_palette_indices.is_empty());
+        assert_eq!(img.get_pixel(0, 0).0, [0, 0, 5]);
+        assert_eq!(img.get_pixel(1, 0).0, [255, 255, 250]);
+
+        let short = Palette::new("short", vec![Rgb::new(0, 0, 0)]);
+        assert!(replace_colors_by_luminance(&mut img, &original, &short).is_err());
+    }
+
+    #[test]
+    fn test_dither_with_mask() {
+        let img = RgbImage::from_fn(16, 16, |x, y| {
+            let v = (x * 13 + y * 7) as u8;
+            image::Rgb([v, 255 - v, v / 2])
+        });
+        // Left half full strength, right half none
+        let mask = GrayImage::from_fn(16, 16, |x, _| image::Luma([if x < 8 { 255 } else { 0 }]));
+
+        for algorithm in [
+            DitheringAlgorithm::ErrorDiffusion(crate::dither::ErrorDiffusionKernel::FloydSteinberg),
+            DitheringAlgorithm::Ordered {
+                width: 4,
+                height: 4,
+            },
+        ] {
+            let options = bw_options(algorithm);
+
+            let quantize_only = bw_options(DitheringAlgorithm::QuantizationOnly);
+            let mut quantized = img.clone();
+            dither_image(&mut quantized, &quantize_only).unwrap();
+
+            let mut masked = img.clone();
+            dither_with_mask(&mut masked, &mask, &options).unwrap();
+            for x in 8..16 {
+                for y in 0..16 {
+                    assert_eq!(masked.get_pixel(x, y), quantized.get_pixel(x, y));
+                }
+            }
+
+            let full_mask = GrayImage::from_pixel(16, 16, image::Luma([255]));
+            let mut full = img.clone();
+            dither_image(&mut full, &options).unwrap();
+            let mut masked = img.clone();
+            dither_with_mask(&mut masked, &full_mask, &options).unwrap();
+            assert_eq!(masked, full);
+        }
+
+        let mut img = img;
+        let small_mask = GrayImage::new(4, 4);
+        let options = bw_options(DitheringAlgorithm::QuantizationOnly);
+        assert!(dither_with_mask(&mut img, &small_mask, &options).is_err());
+    }
+
+    #[test]
+    fn test_pixel_ditherer_matches_dither_image() {
+        let img = RgbImage::from_fn(12, 9, |x, y| {
+            image::Rgb([(x * 20) as u8, (y * 25) as u8, 60])
+        });
+
+        for algorithm in [
+            DitheringAlgorithm::Ordered {
+                width: 4,
+                height: 4,
+            },
+            DitheringAlgorithm::MagicSquare { size: 5 },
+            DitheringAlgorithm::QuantizationOnly,
+        ] {
+            let options = bw_options(algorithm);
+            let dither_pixel = pixel_ditherer(&options).unwrap().unwrap();
+
+            let mut expected = img.clone();
+            dither_image(&mut expected, &options).unwrap();
+            let actual = RgbImage::from_fn(12, 9, |x, y| {
+                image::Rgb(dither_pixel(x, y, img.get_pixel(x, y).0))
+            });
+            assert_eq!(actual, expected);
+        }
+
+        let diffusion = bw_options(DitheringAlgorithm::ErrorDiffusion(
+            crate::dither::ErrorDiffusionKernel::FloydSteinberg,
+        ));
+        assert!(pixel_ditherer(&diffusion).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_palette_coverage_map() {
+        let palette = bw_options(DitheringAlgorithm::QuantizationOnly).palette;
+
+        let solid = RgbImage::from_pixel(6, 3, image::Rgb([255, 255, 255]));
+        let map = generate_palette_coverage_map(&solid, &palette).unwrap();
+        assert_eq!(map.dimensions(), (6, 3));
+        assert!(map.pixels().all(|p| *p == map[(0, 0)]));
+
+        let mut dithered = RgbImage::from_pixel(6, 3, image::Rgb([128, 128, 128]));
+        let options = bw_options(DitheringAlgorithm::ErrorDiffusion(
+            ErrorDiffusionKernel::FloydSteinberg,
+        ));
+        dither_image(&mut dithered, &options).unwrap();
+
+        let map = generate_palette_coverage_map(&dithered, &palette).unwrap();
+        let hues: std::collections::HashSet<_> = map.pixels().map(|p| p.0).collect();
+        // Black (index 0) is red, white (index 1) is cyan
+        assert_eq!(hues, [[255, 0, 0], [0, 255, 255]].into_iter().collect());
+
+        assert!(generate_palette_coverage_map(&solid, &Palette::new("empty", vec![])).is_err());
+    }
+
+    #[test]
+    fn test_ordered_per_color_tracks_gradient() {
+        // 16 steps of 8x8 pixels, so every step covers a whole threshold map
+        let gradient = RgbImage::from_fn(128, 8, |x, _| {
+            let value = (x / 8 * 17) as u8;
+            image::Rgb([value, value, value])
+        });
+
+        // Mean absolute difference between each step's average and its input
+        let step_error = |algorithm: DitheringAlgorithm| {
+            let mut img = gradient.clone();
+            dither_image(&mut img, &bw_options(algorithm)).unwrap();
+            (0..16)
+                .map(|step| {
+                    let sum: f64 = (0..8)
+                        .flat_map(|y| (0..8).map(move |x| (step * 8 + x, y)))
+                        .map(|(x, y)| img.get_pixel(x, y)[0] as f64)
+                        .sum();
+                    (sum / 64.0 - (step * 17) as f64).abs()
+                })
+                .sum::<f64>()
+                / 16.0
+        };
+
+        let basic = step_error(DitheringAlgorithm::Ordered {
+            width: 8,
+            height: 8,
+        });
+        let per_color = step_error(DitheringAlgorithm::OrderedPerColor {
+            width: 8,
+            height: 8,
+        });
+        assert!(
+            per_color < basic,
+            "per-color {} vs basic {}",
+            per_color,
+            basic
+        );
+        assert!(per_color < 4.0, "per-color error {}", per_color);
+    }
+
+    #[test]
+    fn test_ordered_per_color_palette_colors() {
+        let palette = vec![
+            Rgb::new(0, 0, 0),
+            Rgb::new(255, 255, 255),
+            Rgb::new(255, 0, 0),
+            Rgb::new(255, 255, 0),
+        ];
+        let mut img = RgbImage::from_fn(16, 16, |x, y| {
+            image::Rgb([(x * 16) as u8, (y * 16) as u8, ((x + y) * 8) as u8])
+        });
+        let expected = {
+            let mut copy = img.clone();
+            let options = DitherOptions {
+                algorithm: DitheringAlgorithm::OrderedPerColor {
+                    width: 4,
+                    height: 4,
+                },
+                palette: Palette::new("test", palette.clone()),
+                ..Default::default()
+            };
+            let ditherer = pixel_ditherer(&options).unwrap().unwrap();
+            for (x, y, pixel) in copy.enumerate_pixels_mut() {
+                pixel.0 = ditherer(x, y, pixel.0);
+            }
+            copy
+        };
+
+        ordered_color_dithering(&mut img, &palette, &ordered::create_bayer_matrix(4, 4));
+        assert!(img.pixels().all(|p| palette.contains(&Rgb(p.0))));
+        assert_eq!(img, expected);
+    }
+
+    #[test]
+    fn test_color_usage_report() {
+        let mut img = RgbImage::from_pixel(4, 4, image::Rgb([0, 0, 0]));
+        let options = bw_options(DitheringAlgorithm::QuantizationOnly);
+        dither_image(&mut img, &options).unwrap();
+
+        let report = color_usage_report(&img, &options.palette);
+        assert_eq!(report.replaced_count, 16);
+        assert_eq!(report.unmatched_count, 0);
+        assert_eq!(report.unused_palette_indices, vec![1]);
+
+        img.put_pixel(0, 0, image::Rgb([128, 128, 128]));
+        let device = [Rgb::new(20, 20, 20), Rgb::new(230, 230, 230)];
+        let replaced = replace_colors(&mut img, &options.palette.colors, &device).unwrap();
+        assert_eq!(replaced.replaced_count, 15);
+        assert_eq!(replaced.unmatched_count, 1);
+        assert_eq!(replaced.unused_palette_indices, vec![1]);
+
+        // Luminance matching reports indices of the original palette order
+        let mut img = RgbImage::from_pixel(2, 2, image::Rgb([255, 255, 255]));
+        let reversed = Palette::new("reversed", vec![Rgb::new(255, 255, 255), Rgb::new(0, 0, 0)]);
+        let report = replace_colors_by_luminance(&mut img, &reversed, &options.palette).unwrap();
+        assert_eq!(report.unused_palette_indices, vec![1]);
+    }
+
+    #[test]
+    fn test_tile_images() {
+        let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+        let images: Vec<RgbImage> = colors
+            .iter()
+            .map(|&color| RgbImage::from_pixel(3, 2, image::Rgb(color)))
+            .collect();
+
+        let grid = tile_images(&images, 2).unwrap();
+        assert_eq!(grid.dimensions(), (6, 4));
+        assert_eq!(grid.get_pixel(0, 0).0, colors[0]);
+        assert_eq!(grid.get_pixel(5, 1).0, colors[1]);
+        assert_eq!(grid.get_pixel(2, 3).0, colors[2]);
+        assert_eq!(grid.get_pixel(3, 2).0, [0, 0, 0]);
+
+        assert_eq!(tile_images(&images, 5).unwrap().dimensions(), (9, 2));
+        assert!(tile_images(&[], 2).is_err());
+        assert!(tile_images(&[images[0].clone(), RgbImage::new(1, 1)], 2).is_err());
+    }
+
+    #[test]
+    fn test_apply_ordered_dither_fast_matches_slow() {
+        let img = RgbImage::from_fn(37, 23, |x, y| {
+            image::Rgb([(x * 7) as u8, (y * 11) as u8, ((x * y) % 256) as u8])
+        });
+
+        for (width, height) in [(2, 2), (4, 4), (8, 8), (4, 2), (3, 5)] {
+            let matrix = ordered::create_bayer_matrix(width, height);
+            let lookup = precompute_bayer_lookup(&matrix, 64.0);
+            assert_eq!(lookup.len(), matrix.len() * matrix[0].len());
+
+            for (x, y, pixel) in img.enumerate_pixels() {
+                let (x, y) = (x as usize, y as usize);
+                assert_eq!(
+                    apply_ordered_dither_fast(pixel.0, x, y, &lookup, matrix[0].len()),
+                    ordered::apply_ordered_dither(pixel.0, x, y, &matrix, 64.0),
+                    "Mismatch at ({}, {}) with {}x{} matrix",
+                    x,
+                    y,
+                    width,
+                    height
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_kernel_lookahead() {
+        assert_eq!(kernel_lookahead(ErrorDiffusionKernel::FloydSteinberg), 1);
+        assert_eq!(kernel_lookahead(ErrorDiffusionKernel::Sierra2_4A), 1);
+        assert_eq!(kernel_lookahead(ErrorDiffusionKernel::Jarvis), 2);
+        assert_eq!(kernel_lookahead(ErrorDiffusionKernel::Stucki), 2);
+    }
+
+    #[test]
+    fn test_dither_image_parallel_rows() {
+        let source = RgbImage::from_fn(33, 17, |x, y| {
+            image::Rgb([(x * 7) as u8, (y * 15) as u8, ((x + y) * 5) as u8])
+        });
+        let palette = Palette::default();
+
+        for (kernel, serpentine) in [
+            (ErrorDiffusionKernel::FloydSteinberg, false),
+            (ErrorDiffusionKernel::Sierra2_4A, false),
+            (ErrorDiffusionKernel::FloydSteinberg, true),
+            (ErrorDiffusionKernel::Jarvis, false),
+        ] {
+            let options = DitherOptions {
+                algorithm: DitheringAlgorithm::ErrorDiffusion(kernel),
+                palette: palette.clone(),
+                serpentine,
+                ..Default::default()
+            };
+
+            let mut serial = source.clone();
+            dither_image(&mut serial, &options).unwrap();
+            let mut parallel = source.clone();
+            dither_image_parallel_rows(&mut parallel, &options).unwrap();
+
+            assert_eq!(serial, parallel, "{:?}", kernel);
+        }
+    }
+
+    #[test]
+    fn test_map_to_histogram_nearest_two_bins() {
+        let img = RgbImage::from_fn(16, 16, |x, y| {
+            image::Rgb([(x * 16) as u8, (y * 16) as u8, 127])
+        });
+
+        let mapped = map_to_histogram_nearest(&img, 2);
+        assert!(mapped.iter().all(|&v| v == 0 || v == 255));
+        assert_eq!(mapped.get_pixel(15, 0).0, [255, 0, 0]);
+
+        let histogram = build_color_histogram(&img, 2);
+        assert_eq!(histogram.values().sum::<u64>(), 256);
+        assert_eq!(histogram[&[0, 0, 0]], 64);
+        assert_eq!(histogram[&[255, 255, 0]], 64);
+
+        // Pre-quantizing already binned values changes nothing
+        assert_eq!(map_to_histogram_nearest(&mapped, 2), mapped);
+    }
+
+    #[test]
+    fn test_pre_quantize_bins_applied_before_dithering() {
+        let img = RgbImage::from_fn(16, 16, |x, _| image::Rgb([(x * 10 + 60) as u8; 3]));
+        let options = DitherOptions {
+            pre_quantize_bins: Some(2),
+            ..bw_options(DitheringAlgorithm::ErrorDiffusion(
+                ErrorDiffusionKernel::FloydSteinberg,
+            ))
+        };
+
+        let mut dithered = img.clone();
+        dither_image(&mut dithered, &options).unwrap();
+        // Binned pixels are already black or white, so no error is diffused
+        assert_eq!(dithered, map_to_histogram_nearest(&img, 2));
+    }
+
+    #[test]
+    fn test_dither_image_with_preview_every_row() {
+        let source = RgbImage::from_fn(12, 6, |x, y| image::Rgb([(x * 20 + y * 5) as u8; 3]));
+        let options = bw_options(DitheringAlgorithm::ErrorDiffusion(
+            ErrorDiffusionKernel::FloydSteinberg,
+        ));
+        let mut expected = source.clone();
+        dither_image(&mut expected, &options).unwrap();
+
+        let mut previews = Vec::new();
+        let mut img = source.clone();
+        dither_image_with_preview(&mut img, &options, 1, |preview| {
+            previews.push(preview.clone())
+        })
+        .unwrap();
+
+        assert_eq!(previews.len(), 6);
+        let first = &previews[0];
+        for (x, y, pixel) in first.enumerate_pixels() {
+            let want = if y == 0 {
+                expected.get_pixel(x, y)
+            } else {
+                source.get_pixel(x, y)
+            };
+            assert_eq!(pixel, want, "({}, {})", x, y);
+        }
+        assert_eq!(img, expected);
+        assert_eq!(previews[5], expected);
+
+        assert!(dither_image_with_preview(&mut img, &options, 0, |_| {}).is_err());
+    }
+
+    #[test]
+    fn test_preprocessing_lambda_runs_once() {
+        let white = RgbImage::from_pixel(8, 8, image::Rgb([255; 3]));
+        let options = bw_options(DitheringAlgorithm::ErrorDiffusion(
+            ErrorDiffusionKernel::FloydSteinberg,
+        ))
+        .with_preprocessing_lambda(invert_colors);
+
+        let mut img = white.clone();
+        dither_image(&mut img, &options).unwrap();
+        assert!(img.iter().all(|&v| v == 0));
+
+        // Morton order falls back to whole-image dithering after preparing
+        let morton = DitherOptions {
+            scan_order: ScanOrder::Morton,
+            ..options.clone()
+        };
+        let mut img = white.clone();
+        dither_image_chunked(&mut img, &morton, 2).unwrap();
+        assert!(img.iter().all(|&v| v == 0));
+
+        // So do the per-pixel algorithms of the fused resize pipeline
+        for algorithm in [
+            DitheringAlgorithm::Ordered {
+                width: 4,
+                height: 4,
+            },
+            DitheringAlgorithm::QuantizationOnly,
+        ] {
+            let per_pixel = DitherOptions {
+                algorithm,
+                ..options.clone()
+            };
+            let resized = crate::scaling::resize_and_dither_pipeline(
+                &white,
+                4,
+                4,
+                crate::scaling::FitMode::Fill,
+                crate::scaling::ScalingFilter::Triangle,
+                Rgb::new(255, 255, 255),
+                &per_pixel,
+            )
+            .unwrap();
+            assert!(resized.iter().all(|&v| v == 0));
+        }
+
+        let twice = options.with_preprocessing_lambdas(vec![invert_colors]);
+        let mut img = white;
+        dither_image_with_progress(&mut img, &twice, |_, _| {}, None).unwrap();
+        assert!(img.iter().all(|&v| v == 255));
+    }
+
+    #[test]
+    fn test_apply_dither_and_collect_errors() {
+        let img = RgbImage::from_fn(3, 1, |x, _| image::Rgb([[100, 200, 50][x as usize]; 3]));
+        let options = bw_options(DitheringAlgorithm::ErrorDiffusion(
+            ErrorDiffusionKernel::FloydSteinberg,
+        ));
+        let (dithered, errors) = apply_dither_and_collect_errors(&img, &options).unwrap();
+
+        // 100 -> black (error 100); 200 + 7/16 * 100 = 243 -> white (error -12);
+        // 50 + 7/16 * -12 = 44 -> black (error 44)
+        assert_eq!(errors, vec![[100.0; 3], [-12.0; 3], [44.0; 3]]);
+        assert_eq!(dithered.as_raw(), &[0, 0, 0, 255, 255, 255, 0, 0, 0]);
+
+        let mut expected = img.clone();
+        dither_image(&mut expected, &options).unwrap();
+        assert_eq!(dithered, expected);
+        let quantize = bw_options(DitheringAlgorithm::QuantizationOnly);
+        assert!(apply_dither_and_collect_errors(&img, &quantize).is_err());
+    }
+
+    #[test]
+    fn test_scanline_ditherer_matches_batch() {
+        let source = RgbImage::from_fn(13, 10, |x, y| {
+            image::Rgb([(x * 19 + y * 7) as u8, (y * 25) as u8, ((x + y) * 11) as u8])
+        });
+        let palette = Palette::default().colors;
+
+        for kernel in [
+            ErrorDiffusionKernel::FloydSteinberg,
+            ErrorDiffusionKernel::Stucki,
+        ] {
+            for serpentine in [false, true] {
+                let mut batch = source.clone();
+                error_diffusion::apply_error_diffusion(
+                    batch.as_mut(),
+                    13,
+                    10,
+                    &palette,
+                    kernel,
+                    serpentine,
+                );
+
+                let mut ditherer = ScanlineDitherer::new(13, palette.clone(), kernel, serpentine);
+                let mut streamed = source.clone();
+                for line in streamed.as_mut().chunks_exact_mut(13 * 3) {
+                    ditherer.process_line(line);
+                }
+                assert_eq!(streamed, batch, "{:?}, serpentine {}", kernel, serpentine);
+            }
+        }
+    }
+
+    #[test]
+    fn test_replace_colors_with_lut() {
+        let source = RgbImage::from_fn(16, 16, |x, y| {
+            image::Rgb([(x * 17) as u8, (y * 17) as u8, 99])
+        });
+
+        let mut img = source.clone();
+        replace_colors_with_lut(&mut img, &ColorLut::identity(16)).unwrap();
+        assert_eq!(img, source);
+
+        let red = ColorLut {
+            size: 2,
+            data: vec![Rgb::new(255, 0, 0); 8],
+        };
+        let mut img = source;
+        replace_colors_with_lut(&mut img, &red).unwrap();
+        assert!(img.pixels().all(|pixel| pixel.0 == [255, 0, 0]));
+
+        let malformed = ColorLut {
+            size: 2,
+            data: vec![],
+        };
+        assert!(replace_colors_with_lut(&mut img, &malformed).is_err());
     }
 }
@@ -1,26 +1,440 @@
 //! Dithering algorithms and engine
 
 pub mod algorithms;
+#[cfg(any(test, feature = "bench"))]
+pub mod bench;
 pub mod engine;
 pub mod matrices;
+pub mod quality;
+pub mod scan;
 
-use crate::color::Palette;
+use crate::color::{
+    convert,
+    palette::PaletteManager,
+    preprocess::{LambdaPreprocessor, PreprocessingChain},
+    Palette, Rgb,
+};
+use anyhow::{anyhow, Context, Result};
+use image::RgbImage;
+pub use scan::ScanOrder;
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+
+/// CLI names of all built-in algorithms, as accepted by [`DitheringAlgorithm::from_name`]
+pub const ALGORITHM_NAMES: [&str; 20] = [
+    "floyd-steinberg",
+    "false-floyd-steinberg",
+    "jarvis",
+    "stucki",
+    "burkes",
+    "sierra3",
+    "sierra2",
+    "sierra24a",
+    "ordered",
+    "magic-square",
+    "ordered-per-color",
+    "random-rgb",
+    "random-bw",
+    "halftone",
+    "none",
+    "posterize",
+    "per-channel",
+    "blue-noise-streaming",
+    "yuv-decoupled",
+    "riemersma",
+];
+
+/// A user-defined dithering algorithm
+///
+/// Wrap it in [`DitheringAlgorithm::Custom`] to use it wherever built-in
+/// algorithms are accepted. `dither` must leave only palette colors in the
+/// image for color replacement and raw output to work.
+pub trait CustomDitherer: Send + Sync {
+    /// Dither `img` in place to colors of `palette`
+    fn dither(&self, img: &mut image::RgbImage, palette: &Palette) -> Result<()>;
+
+    /// Name shown in logs and reports
+    fn name(&self) -> &str;
+}
+
+impl std::fmt::Debug for dyn CustomDitherer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CustomDitherer({})", self.name())
+    }
+}
 
 /// Dithering algorithm selection
+///
+/// Implements `Eq` and `Hash` so options can key caches. Floating-point
+/// parameters compare by bit pattern, so `0.0` and `-0.0` differ and a NaN
+/// equals itself. Custom algorithms are equal only to the same instance.
 #[derive(Debug, Clone)]
 pub enum DitheringAlgorithm {
     /// Error diffusion dithering with various kernels
     ErrorDiffusion(ErrorDiffusionKernel),
+    /// Error diffusion on each RGB channel separately
+    ///
+    /// Each channel is quantized to the distinct values that channel takes
+    /// across the palette, so the output can contain colors outside the
+    /// palette. See
+    /// [`algorithms::error_diffusion::apply_error_diffusion_per_channel`].
+    ErrorDiffusionPerChannel(ErrorDiffusionKernel),
+    /// Error diffusion in YUV space, with separate kernels for luma and chroma
+    ///
+    /// See [`algorithms::error_diffusion::apply_error_diffusion_yuv`].
+    YuvDecoupled {
+        luma_kernel: ErrorDiffusionKernel,
+        chroma_kernel: ErrorDiffusionKernel,
+    },
+    /// Error diffusion along a Hilbert curve with a decaying error history
+    ///
+    /// See [`algorithms::riemersma::apply_riemersma`].
+    Riemersma,
     /// Ordered dithering using Bayer matrix
     Ordered { width: u8, height: u8 },
+    /// Ordered dithering using a magic-square threshold map (size 5 or 9)
+    MagicSquare { size: u8 },
+    /// Ordered dithering using a user-supplied threshold map
+    ///
+    /// Values must be a permutation of `0..width * height`; see
+    /// [`algorithms::ordered::validate_threshold_matrix`].
+    CustomOrdered { matrix: Vec<Vec<usize>> },
+    /// Ordered dithering with a Bayer matrix per pair of adjacent palette colors
+    OrderedPerColor { width: u8, height: u8 },
+    /// Ordered dithering with a void-and-cluster blue-noise map, generated
+    /// row by row while dithering (see
+    /// [`algorithms::void_and_cluster::VoidAndClusterStream`])
+    BlueNoiseStreaming { width: u8, height: u8 },
     /// Random dithering
     Random(RandomMode),
+    /// Halftone screen rotated by `angle_degrees`, `frequency` in radians per pixel
+    Halftone { angle_degrees: f64, frequency: f64 },
     /// Quantization only (no dithering)
     QuantizationOnly,
+    /// Posterize each channel to `levels` values, then quantize (no dithering)
+    Posterize { levels: u8 },
+    /// User-defined algorithm
+    Custom(Arc<dyn CustomDitherer>),
+}
+
+impl DitheringAlgorithm {
+    /// Look up an algorithm by its CLI name (e.g. "floyd-steinberg", "ordered")
+    ///
+    /// Parameterized algorithms use their CLI defaults (4x4 Bayer matrix,
+    /// 5x5 magic square, 16x16 blue-noise map, 45° halftone screen).
+    pub fn from_name(name: &str) -> Option<Self> {
+        let algorithm = match name.to_lowercase().as_str() {
+            "floyd-steinberg" => Self::ErrorDiffusion(ErrorDiffusionKernel::FloydSteinberg),
+            "false-floyd-steinberg" => {
+                Self::ErrorDiffusion(ErrorDiffusionKernel::FalseFloydSteinberg)
+            }
+            "jarvis" => Self::ErrorDiffusion(ErrorDiffusionKernel::Jarvis),
+            "stucki" => Self::ErrorDiffusion(ErrorDiffusionKernel::Stucki),
+            "burkes" => Self::ErrorDiffusion(ErrorDiffusionKernel::Burkes),
+            "sierra3" => Self::ErrorDiffusion(ErrorDiffusionKernel::Sierra3),
+            "sierra2" => Self::ErrorDiffusion(ErrorDiffusionKernel::Sierra2),
+            "sierra24a" => Self::ErrorDiffusion(ErrorDiffusionKernel::Sierra2_4A),
+            "ordered" => Self::Ordered {
+                width: 4,
+                height: 4,
+            },
+            "magic-square" => Self::MagicSquare { size: 5 },
+            "ordered-per-color" => Self::OrderedPerColor {
+                width: 4,
+                height: 4,
+            },
+            "random-rgb" => Self::Random(RandomMode::Rgb),
+            "random-bw" => Self::Random(RandomMode::BlackAndWhite),
+            "halftone" => Self::Halftone {
+                angle_degrees: 45.0,
+                frequency: 0.8,
+            },
+            "none" => Self::QuantizationOnly,
+            "posterize" => Self::Posterize { levels: 4 },
+            "per-channel" => Self::ErrorDiffusionPerChannel(ErrorDiffusionKernel::FloydSteinberg),
+            "yuv-decoupled" => Self::YuvDecoupled {
+                luma_kernel: ErrorDiffusionKernel::FloydSteinberg,
+                chroma_kernel: ErrorDiffusionKernel::Jarvis,
+            },
+            "blue-noise-streaming" => Self::BlueNoiseStreaming {
+                width: 16,
+                height: 16,
+            },
+            "riemersma" => Self::Riemersma,
+            _ => return None,
+        };
+
+        Some(algorithm)
+    }
+
+    /// The CLI name of the algorithm, as accepted by [`DitheringAlgorithm::from_name`]
+    ///
+    /// Custom algorithms return [`CustomDitherer::name`], which cannot be
+    /// parsed back.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::ErrorDiffusion(kernel) => kernel.name(),
+            Self::ErrorDiffusionPerChannel(_) => "per-channel",
+            Self::YuvDecoupled { .. } => "yuv-decoupled",
+            Self::Riemersma => "riemersma",
+            Self::Ordered { .. } => "ordered",
+            Self::MagicSquare { .. } => "magic-square",
+            Self::CustomOrdered { .. } => "custom-ordered",
+            Self::OrderedPerColor { .. } => "ordered-per-color",
+            Self::BlueNoiseStreaming { .. } => "blue-noise-streaming",
+            Self::Random(RandomMode::Rgb) => "random-rgb",
+            Self::Random(RandomMode::BlackAndWhite) => "random-bw",
+            Self::Halftone { .. } => "halftone",
+            Self::QuantizationOnly => "none",
+            Self::Posterize { .. } => "posterize",
+            Self::Custom(ditherer) => ditherer.name(),
+        }
+    }
+
+    /// Parse an algorithm from JSON
+    ///
+    /// Accepts a name string (using default parameters) or an object with a
+    /// `name` and the variant's parameters, e.g.
+    /// `{"name": "ordered", "width": 8, "height": 8}`. Missing parameters
+    /// fall back to the defaults of [`DitheringAlgorithm::from_name`].
+    fn from_json(value: &Value) -> Result<Self> {
+        let (name, params) = match value {
+            Value::String(name) => (name.as_str(), None),
+            Value::Object(obj) => {
+                let name = obj
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| anyhow!("Algorithm object requires a string 'name'"))?;
+                (name, Some(obj))
+            }
+            _ => anyhow::bail!(
+                "Algorithm must be a name string or an object, got {}",
+                value
+            ),
+        };
+
+        if params.and_then(|params| params.get("custom")) == Some(&Value::Bool(true)) {
+            anyhow::bail!(
+                "Algorithm '{}' is a custom ditherer and can't be loaded from JSON",
+                name
+            );
+        }
+
+        // Custom threshold maps have no default, so they are only valid as objects
+        if name == "custom-ordered" {
+            let matrix = params
+                .and_then(|params| params.get("matrix"))
+                .ok_or_else(|| anyhow!("Algorithm 'custom-ordered' requires a 'matrix'"))?;
+            let matrix: Vec<Vec<usize>> = serde_json::from_value(matrix.clone())
+                .context("Invalid 'matrix' for 'custom-ordered'")?;
+            algorithms::ordered::validate_threshold_matrix(&matrix)?;
+            return Ok(Self::CustomOrdered { matrix });
+        }
+
+        let mut algorithm =
+            Self::from_name(name).ok_or_else(|| anyhow!("Unknown algorithm: {}", name))?;
+
+        if let Some(params) = params {
+            match &mut algorithm {
+                Self::Ordered { width, height }
+                | Self::OrderedPerColor { width, height }
+                | Self::BlueNoiseStreaming { width, height } => {
+                    json_param(params, "width", width)?;
+                    json_param(params, "height", height)?;
+                }
+                Self::MagicSquare { size } => json_param(params, "size", size)?,
+                Self::Halftone {
+                    angle_degrees,
+                    frequency,
+                } => {
+                    json_param(params, "angle_degrees", angle_degrees)?;
+                    json_param(params, "frequency", frequency)?;
+                }
+                Self::Posterize { levels } => json_param(params, "levels", levels)?,
+                Self::ErrorDiffusionPerChannel(kernel) => kernel_param(params, "kernel", kernel)?,
+                Self::YuvDecoupled {
+                    luma_kernel,
+                    chroma_kernel,
+                } => {
+                    kernel_param(params, "luma_kernel", luma_kernel)?;
+                    kernel_param(params, "chroma_kernel", chroma_kernel)?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(algorithm)
+    }
+
+    /// Serialize as a name string, or an object for parameterized variants
+    ///
+    /// Custom algorithms become `{"name": ..., "custom": true}`, which
+    /// [`DitheringAlgorithm::from_json`] rejects instead of mistaking the
+    /// name for a built-in algorithm.
+    fn to_json(&self) -> Value {
+        let name = self.name();
+        match self {
+            Self::Ordered { width, height }
+            | Self::OrderedPerColor { width, height }
+            | Self::BlueNoiseStreaming { width, height } => {
+                json!({ "name": name, "width": width, "height": height })
+            }
+            Self::MagicSquare { size } => json!({ "name": name, "size": size }),
+            Self::CustomOrdered { matrix } => json!({ "name": name, "matrix": matrix }),
+            Self::Halftone {
+                angle_degrees,
+                frequency,
+            } => json!({ "name": name, "angle_degrees": angle_degrees, "frequency": frequency }),
+            Self::Posterize { levels } => json!({ "name": name, "levels": levels }),
+            Self::ErrorDiffusionPerChannel(kernel) => {
+                json!({ "name": name, "kernel": kernel.name() })
+            }
+            Self::YuvDecoupled {
+                luma_kernel,
+                chroma_kernel,
+            } => json!({
+                "name": name,
+                "luma_kernel": luma_kernel.name(),
+                "chroma_kernel": chroma_kernel.name(),
+            }),
+            Self::Custom(_) => json!({ "name": name, "custom": true }),
+            _ => json!(name),
+        }
+    }
+}
+
+impl PartialEq for DitheringAlgorithm {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::ErrorDiffusion(a), Self::ErrorDiffusion(b)) => a == b,
+            (Self::ErrorDiffusionPerChannel(a), Self::ErrorDiffusionPerChannel(b)) => a == b,
+            (
+                Self::YuvDecoupled {
+                    luma_kernel,
+                    chroma_kernel,
+                },
+                Self::YuvDecoupled {
+                    luma_kernel: other_luma,
+                    chroma_kernel: other_chroma,
+                },
+            ) => luma_kernel == other_luma && chroma_kernel == other_chroma,
+            (
+                Self::Ordered { width, height },
+                Self::Ordered {
+                    width: other_width,
+                    height: other_height,
+                },
+            ) => width == other_width && height == other_height,
+            (Self::MagicSquare { size }, Self::MagicSquare { size: other_size }) => {
+                size == other_size
+            }
+            (
+                Self::CustomOrdered { matrix },
+                Self::CustomOrdered {
+                    matrix: other_matrix,
+                },
+            ) => matrix == other_matrix,
+            (
+                Self::OrderedPerColor { width, height },
+                Self::OrderedPerColor {
+                    width: other_width,
+                    height: other_height,
+                },
+            ) => width == other_width && height == other_height,
+            (
+                Self::BlueNoiseStreaming { width, height },
+                Self::BlueNoiseStreaming {
+                    width: other_width,
+                    height: other_height,
+                },
+            ) => width == other_width && height == other_height,
+            (Self::Random(a), Self::Random(b)) => a == b,
+            (
+                Self::Halftone {
+                    angle_degrees,
+                    frequency,
+                },
+                Self::Halftone {
+                    angle_degrees: other_angle,
+                    frequency: other_frequency,
+                },
+            ) => {
+                angle_degrees.to_bits() == other_angle.to_bits()
+                    && frequency.to_bits() == other_frequency.to_bits()
+            }
+            (Self::Riemersma, Self::Riemersma)
+            | (Self::QuantizationOnly, Self::QuantizationOnly) => true,
+            (
+                Self::Posterize { levels },
+                Self::Posterize {
+                    levels: other_levels,
+                },
+            ) => levels == other_levels,
+            (Self::Custom(a), Self::Custom(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for DitheringAlgorithm {}
+
+impl std::hash::Hash for DitheringAlgorithm {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Self::ErrorDiffusion(kernel) => kernel.hash(state),
+            Self::ErrorDiffusionPerChannel(kernel) => kernel.hash(state),
+            Self::YuvDecoupled {
+                luma_kernel,
+                chroma_kernel,
+            } => (luma_kernel, chroma_kernel).hash(state),
+            Self::Ordered { width, height } => (width, height).hash(state),
+            Self::MagicSquare { size } => size.hash(state),
+            Self::CustomOrdered { matrix } => matrix.hash(state),
+            Self::OrderedPerColor { width, height } => (width, height).hash(state),
+            Self::BlueNoiseStreaming { width, height } => (width, height).hash(state),
+            Self::Random(mode) => mode.hash(state),
+            Self::Halftone {
+                angle_degrees,
+                frequency,
+            } => (angle_degrees.to_bits(), frequency.to_bits()).hash(state),
+            Self::Riemersma | Self::QuantizationOnly => {}
+            Self::Posterize { levels } => levels.hash(state),
+            Self::Custom(ditherer) => Arc::as_ptr(ditherer).cast::<()>().hash(state),
+        }
+    }
+}
+
+/// Overwrite `target` with the kernel named by `params[key]` if present
+fn kernel_param(
+    params: &Map<String, Value>,
+    key: &str,
+    target: &mut ErrorDiffusionKernel,
+) -> Result<()> {
+    if let Some(value) = params.get(key) {
+        *target = value
+            .as_str()
+            .and_then(ErrorDiffusionKernel::from_name)
+            .ok_or_else(|| anyhow!("Invalid algorithm parameter '{}': {}", key, value))?;
+    }
+    Ok(())
+}
+
+/// Overwrite `target` with `params[key]` if present
+fn json_param<T: serde::de::DeserializeOwned>(
+    params: &Map<String, Value>,
+    key: &str,
+    target: &mut T,
+) -> Result<()> {
+    if let Some(value) = params.get(key) {
+        *target = serde_json::from_value(value.clone())
+            .with_context(|| format!("Invalid algorithm parameter '{}': {}", key, value))?;
+    }
+    Ok(())
 }
 
 /// Error diffusion kernel types
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ErrorDiffusionKernel {
     FloydSteinberg,
     FalseFloydSteinberg,
@@ -32,19 +446,60 @@ pub enum ErrorDiffusionKernel {
     Sierra2_4A,
 }
 
+impl ErrorDiffusionKernel {
+    /// Look up a kernel by its algorithm name (e.g. "jarvis")
+    pub fn from_name(name: &str) -> Option<Self> {
+        match DitheringAlgorithm::from_name(name)? {
+            DitheringAlgorithm::ErrorDiffusion(kernel) => Some(kernel),
+            _ => None,
+        }
+    }
+
+    /// The algorithm name of the kernel, as accepted by [`ErrorDiffusionKernel::from_name`]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::FloydSteinberg => "floyd-steinberg",
+            Self::FalseFloydSteinberg => "false-floyd-steinberg",
+            Self::Jarvis => "jarvis",
+            Self::Stucki => "stucki",
+            Self::Burkes => "burkes",
+            Self::Sierra3 => "sierra3",
+            Self::Sierra2 => "sierra2",
+            Self::Sierra2_4A => "sierra24a",
+        }
+    }
+}
+
 /// Random dithering mode
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum RandomMode {
     Rgb,
     BlackAndWhite,
 }
 
 /// Complete dithering configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct DitherOptions {
     pub algorithm: DitheringAlgorithm,
     pub palette: Palette,
     pub serpentine: bool,
+    /// Pixel traversal order for error diffusion (ignored by other algorithms)
+    pub scan_order: ScanOrder,
+    /// Noise shaping filter order for whole-image raster error diffusion;
+    /// 0 disables it
+    ///
+    /// Noise-shaped rows are always scanned left to right, so `serpentine`
+    /// has no effect when this is set. See
+    /// [`algorithms::error_diffusion::apply_error_diffusion_noise_shaped`].
+    pub noise_shaping_order: u8,
+    /// Snap each channel to this many evenly spaced levels before dithering
+    ///
+    /// See [`engine::map_to_histogram_nearest`]; `None` disables it.
+    pub pre_quantize_bins: Option<u8>,
+    /// Steps applied to the image before anything else
+    ///
+    /// Not part of the JSON form.
+    pub preprocessing: PreprocessingChain,
 }
 
 impl Default for DitherOptions {
@@ -53,6 +508,346 @@ impl Default for DitherOptions {
             algorithm: DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::FloydSteinberg),
             palette: Palette::default(),
             serpentine: false,
+            scan_order: ScanOrder::Raster,
+            noise_shaping_order: 0,
+            pre_quantize_bins: None,
+            preprocessing: PreprocessingChain::default(),
+        }
+    }
+}
+
+impl DitherOptions {
+    /// Parse options from a JSON string
+    ///
+    /// See the `TryFrom<serde_json::Value>` implementation for the format.
+    pub fn from_json_str(s: &str) -> Result<DitherOptions> {
+        let value: Value =
+            serde_json::from_str(s).context("Failed to parse dither options JSON")?;
+        DitherOptions::try_from(value)
+    }
+
+    /// Add a closure to the end of the preprocessing chain
+    pub fn with_preprocessing_lambda<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&mut RgbImage) + Send + Sync + 'static,
+    {
+        self.preprocessing.push(Box::new(LambdaPreprocessor(f)));
+        self
+    }
+
+    /// Add several closures to the end of the preprocessing chain, in order
+    pub fn with_preprocessing_lambdas<F>(self, fns: Vec<F>) -> Self
+    where
+        F: Fn(&mut RgbImage) + Send + Sync + 'static,
+    {
+        fns.into_iter().fold(self, Self::with_preprocessing_lambda)
+    }
+}
+
+/// Parse options from a JSON object
+///
+/// All fields are optional and default to [`DitherOptions::default`]:
+///
+/// * `algorithm` - a name such as `"floyd-steinberg"`, or an object with a
+///   `name` and parameters (`{"name": "halftone", "angle_degrees": 15.0}`);
+///   custom algorithms, written as `{"name": ..., "custom": true}`, are
+///   rejected
+/// * `palette` - a built-in palette name, or `{"name": ..., "colors": [hex, ...]}`
+/// * `serpentine` - boolean
+/// * `scan_order` - `"raster"` or `"morton"`
+/// * `noise_shaping_order` - integer 0–255
+/// * `pre_quantize_bins` - integer 0–255 or `null`
+impl TryFrom<Value> for DitherOptions {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Value) -> Result<Self> {
+        let obj = value
+            .as_object()
+            .ok_or_else(|| anyhow!("Dither options must be a JSON object, got {}", value))?;
+        let mut options = DitherOptions::default();
+
+        if let Some(algorithm) = obj.get("algorithm") {
+            options.algorithm = DitheringAlgorithm::from_json(algorithm)?;
+        }
+
+        if let Some(palette) = obj.get("palette") {
+            options.palette = match palette {
+                Value::String(name) => PaletteManager::new()?.get_palette(name)?,
+                Value::Object(palette) => {
+                    let name = palette
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .unwrap_or("custom");
+                    let colors = palette
+                        .get("colors")
+                        .and_then(Value::as_array)
+                        .ok_or_else(|| anyhow!("Palette object requires a 'colors' array"))?
+                        .iter()
+                        .map(|color| {
+                            let hex = color
+                                .as_str()
+                                .ok_or_else(|| anyhow!("Palette colors must be hex strings"))?;
+                            convert::hex_to_rgb(hex)
+                                .map(Rgb)
+                                .with_context(|| format!("Invalid palette color: {}", hex))
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    Palette::new(name, colors)
+                }
+                _ => anyhow::bail!(
+                    "Palette must be a name string or an object, got {}",
+                    palette
+                ),
+            };
+        }
+
+        if let Some(serpentine) = obj.get("serpentine") {
+            options.serpentine = serpentine
+                .as_bool()
+                .ok_or_else(|| anyhow!("'serpentine' must be a boolean, got {}", serpentine))?;
+        }
+
+        if let Some(scan_order) = obj.get("scan_order") {
+            options.scan_order = scan_order
+                .as_str()
+                .and_then(ScanOrder::from_name)
+                .ok_or_else(|| anyhow!("Unknown scan order: {}", scan_order))?;
+        }
+
+        if let Some(order) = obj.get("noise_shaping_order") {
+            options.noise_shaping_order = serde_json::from_value(order.clone())
+                .with_context(|| format!("'noise_shaping_order' must be 0-255, got {}", order))?;
+        }
+
+        if let Some(bins) = obj.get("pre_quantize_bins") {
+            options.pre_quantize_bins =
+                serde_json::from_value(bins.clone()).with_context(|| {
+                    format!("'pre_quantize_bins' must be 0-255 or null, got {}", bins)
+                })?;
+        }
+
+        Ok(options)
+    }
+}
+
+/// Serialize options in the format `TryFrom<Value>` reads
+///
+/// Custom algorithms are written with a `"custom": true` marker, so the
+/// result records them but can't be loaded back.
+impl From<&DitherOptions> for Value {
+    fn from(options: &DitherOptions) -> Self {
+        let colors: Vec<String> = options
+            .palette
+            .colors
+            .iter()
+            .map(convert::rgb_to_hex)
+            .collect();
+        json!({
+            "algorithm": options.algorithm.to_json(),
+            "palette": { "name": options.palette.name, "colors": colors },
+            "serpentine": options.serpentine,
+            "scan_order": options.scan_order.name(),
+            "noise_shaping_order": options.noise_shaping_order,
+            "pre_quantize_bins": options.pre_quantize_bins,
+        })
+    }
+}
+
+/// Dithering configuration for multi-frame (animated) content
+///
+/// Wraps the per-frame `DitherOptions` with a temporal carry-over factor:
+/// the residual quantization error of each frame is scaled by `carry_over`
+/// (0.0–1.0) and seeded into the next frame, which reduces flicker between
+/// frames on displays that show animated content.
+#[derive(Debug, Clone)]
+pub struct AnimationDitherOptions {
+    pub dither: DitherOptions,
+    pub carry_over: f64,
+}
+
+impl Default for AnimationDitherOptions {
+    fn default() -> Self {
+        Self {
+            dither: DitherOptions::default(),
+            carry_over: 0.5,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(options: &DitherOptions) -> DitherOptions {
+        let json = Value::from(options).to_string();
+        DitherOptions::from_json_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let palette = PaletteManager::new()
+            .unwrap()
+            .get_palette("spectra6")
+            .unwrap();
+        let kernels = [
+            ErrorDiffusionKernel::FloydSteinberg,
+            ErrorDiffusionKernel::FalseFloydSteinberg,
+            ErrorDiffusionKernel::Jarvis,
+            ErrorDiffusionKernel::Stucki,
+            ErrorDiffusionKernel::Burkes,
+            ErrorDiffusionKernel::Sierra3,
+            ErrorDiffusionKernel::Sierra2,
+            ErrorDiffusionKernel::Sierra2_4A,
+        ];
+        let algorithms = kernels
+            .into_iter()
+            .map(DitheringAlgorithm::ErrorDiffusion)
+            .chain([
+                DitheringAlgorithm::Ordered {
+                    width: 8,
+                    height: 2,
+                },
+                DitheringAlgorithm::MagicSquare { size: 9 },
+                DitheringAlgorithm::CustomOrdered {
+                    matrix: vec![vec![0, 2, 4], vec![5, 3, 1]],
+                },
+                DitheringAlgorithm::OrderedPerColor {
+                    width: 2,
+                    height: 8,
+                },
+                DitheringAlgorithm::Random(RandomMode::Rgb),
+                DitheringAlgorithm::Random(RandomMode::BlackAndWhite),
+                DitheringAlgorithm::Halftone {
+                    angle_degrees: 15.0,
+                    frequency: 1.25,
+                },
+                DitheringAlgorithm::QuantizationOnly,
+                DitheringAlgorithm::Posterize { levels: 3 },
+                DitheringAlgorithm::ErrorDiffusionPerChannel(ErrorDiffusionKernel::Stucki),
+                DitheringAlgorithm::BlueNoiseStreaming {
+                    width: 8,
+                    height: 4,
+                },
+                DitheringAlgorithm::YuvDecoupled {
+                    luma_kernel: ErrorDiffusionKernel::Burkes,
+                    chroma_kernel: ErrorDiffusionKernel::Sierra2,
+                },
+                DitheringAlgorithm::Riemersma,
+            ]);
+
+        for algorithm in algorithms {
+            let options = DitherOptions {
+                algorithm,
+                palette: palette.clone(),
+                serpentine: true,
+                scan_order: ScanOrder::Morton,
+                noise_shaping_order: 2,
+                pre_quantize_bins: Some(8),
+                ..Default::default()
+            };
+            assert_eq!(round_trip(&options), options);
+        }
+    }
+
+    #[test]
+    fn test_json_parsing() {
+        let options = DitherOptions::from_json_str(
+            r##"{"algorithm": "jarvis", "palette": "spectra6", "serpentine": true}"##,
+        )
+        .unwrap();
+        assert_eq!(
+            options.algorithm,
+            DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Jarvis)
+        );
+        assert_eq!(options.palette.name, "spectra6");
+        assert!(options.serpentine);
+
+        // Missing parameters use the defaults
+        let options =
+            DitherOptions::from_json_str(r##"{"algorithm": {"name": "ordered", "width": 2}}"##)
+                .unwrap();
+        assert_eq!(
+            options.algorithm,
+            DitheringAlgorithm::Ordered {
+                width: 2,
+                height: 4
+            }
+        );
+        assert_eq!(options.palette, Palette::default());
+
+        let options = DitherOptions::from_json_str(
+            r##"{"palette": {"name": "duo", "colors": ["#000", "#FF0000"]}}"##,
+        )
+        .unwrap();
+        assert_eq!(
+            options.palette.colors,
+            vec![Rgb::new(0, 0, 0), Rgb::new(255, 0, 0)]
+        );
+    }
+
+    #[test]
+    fn test_json_errors() {
+        let err = DitherOptions::from_json_str(r##"{"algorithm": "blur"}"##).unwrap_err();
+        assert!(err.to_string().contains("Unknown algorithm: blur"));
+
+        assert!(DitherOptions::from_json_str(r##"{"palette": "missing"}"##).is_err());
+        assert!(DitherOptions::from_json_str(r##"{"serpentine": "yes"}"##).is_err());
+        assert!(DitherOptions::from_json_str(
+            r##"{"algorithm": {"name": "ordered", "width": "wide"}}"##
+        )
+        .is_err());
+        assert!(DitherOptions::from_json_str(
+            r##"{"algorithm": {"name": "per-channel", "kernel": "ordered"}}"##
+        )
+        .is_err());
+        assert!(DitherOptions::from_json_str("[]").is_err());
+    }
+
+    #[test]
+    fn test_algorithm_names() {
+        for name in ALGORITHM_NAMES {
+            let algorithm = DitheringAlgorithm::from_name(name).unwrap();
+            assert_eq!(algorithm.name(), name);
         }
     }
+
+    fn hash_of<T: std::hash::Hash>(value: &T) -> u64 {
+        use std::hash::Hasher;
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_options_hash() {
+        let options = DitherOptions::default();
+        assert_eq!(hash_of(&options), hash_of(&options.clone()));
+
+        let ordered = DitherOptions {
+            algorithm: DitheringAlgorithm::Ordered {
+                width: 4,
+                height: 4,
+            },
+            ..Default::default()
+        };
+        assert_ne!(options, ordered);
+        assert_ne!(hash_of(&options), hash_of(&ordered));
+
+        let mut cache = std::collections::HashMap::new();
+        cache.insert(options.clone(), "fs");
+        cache.insert(ordered, "ordered");
+        assert_eq!(cache.get(&DitherOptions::default()), Some(&"fs"));
+    }
+
+    #[test]
+    fn test_halftone_eq_uses_bits() {
+        let halftone = |angle_degrees| DitheringAlgorithm::Halftone {
+            angle_degrees,
+            frequency: 0.7,
+        };
+        assert_eq!(halftone(45.0), halftone(45.0));
+        assert_eq!(hash_of(&halftone(45.0)), hash_of(&halftone(45.0)));
+        assert_ne!(halftone(45.0), halftone(30.0));
+        assert_eq!(halftone(f64::NAN), halftone(f64::NAN));
+    }
 }
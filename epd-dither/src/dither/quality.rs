@@ -0,0 +1,112 @@
+//! Quality metrics for dithered images
+
+use crate::color::{distance::find_closest_color, Palette, Rgb};
+use anyhow::Result;
+use image::RgbImage;
+
+/// Shannon entropy (in bits) of how pixels are spread over the palette
+///
+/// Each pixel counts towards its nearest palette color, and the entropy
+/// is `H = -Σ p_i log2(p_i)` over the share `p_i` of each color. It ranges
+/// from 0 (a single color covers the image) to `log2(N)` for an N-color
+/// palette whose colors are used equally. Empty images have entropy 0.
+pub fn color_utilization_entropy(img: &RgbImage, palette: &Palette) -> Result<f64> {
+    if palette.is_empty() {
+        anyhow::bail!("Cannot compute color utilization with an empty palette");
+    }
+
+    let mut counts = vec![0; palette.len()];
+    for pixel in img.pixels() {
+        let (idx, _) = find_closest_color(&Rgb(pixel.0), &palette.colors)
+            .expect("Palette should not be empty");
+        counts[idx] += 1;
+    }
+
+    Ok(entropy(&counts))
+}
+
+/// [`color_utilization_entropy`] normalized to 0–1 by its maximum `log2(N)`
+///
+/// 1.0 means every palette color covers the same share of the image.
+/// Single-color palettes are always fully uniform.
+pub fn color_utilization_uniformity(img: &RgbImage, palette: &Palette) -> Result<f64> {
+    let entropy = color_utilization_entropy(img, palette)?;
+    if palette.len() == 1 {
+        return Ok(1.0);
+    }
+
+    Ok(entropy / (palette.len() as f64).log2())
+}
+
+/// Shannon entropy in bits of a histogram
+pub(crate) fn entropy(counts: &[usize]) -> f64 {
+    let total: usize = counts.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bw_palette() -> Palette {
+        Palette::new("bw", vec![Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)])
+    }
+
+    #[test]
+    fn test_entropy_of_even_split() {
+        let img = RgbImage::from_fn(8, 8, |x, _| {
+            if x < 4 {
+                image::Rgb([0, 0, 0])
+            } else {
+                image::Rgb([255, 255, 255])
+            }
+        });
+
+        assert_eq!(color_utilization_entropy(&img, &bw_palette()).unwrap(), 1.0);
+        assert_eq!(
+            color_utilization_uniformity(&img, &bw_palette()).unwrap(),
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_entropy_of_single_color() {
+        let img = RgbImage::new(8, 8);
+
+        assert_eq!(color_utilization_entropy(&img, &bw_palette()).unwrap(), 0.0);
+        assert_eq!(
+            color_utilization_uniformity(&img, &bw_palette()).unwrap(),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_uniformity_of_four_colors() {
+        let palette = Palette::new(
+            "four",
+            vec![
+                Rgb::new(0, 0, 0),
+                Rgb::new(255, 255, 255),
+                Rgb::new(255, 0, 0),
+                Rgb::new(0, 0, 255),
+            ],
+        );
+        // Half black, half white: 1 bit out of a possible 2
+        let img = RgbImage::from_fn(4, 4, |_, y| image::Rgb([(y / 2 * 255) as u8; 3]));
+
+        assert_eq!(color_utilization_entropy(&img, &palette).unwrap(), 1.0);
+        assert_eq!(color_utilization_uniformity(&img, &palette).unwrap(), 0.5);
+        assert!(color_utilization_entropy(&img, &Palette::new("empty", vec![])).is_err());
+    }
+}
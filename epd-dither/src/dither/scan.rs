@@ -0,0 +1,235 @@
+//! Pixel traversal orders for error diffusion
+
+/// Order in which error diffusion visits pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ScanOrder {
+    /// Row by row, left to right (right to left on odd rows when serpentine)
+    #[default]
+    Raster,
+    /// Z-order curve over the smallest enclosing power-of-two square
+    Morton,
+}
+
+impl ScanOrder {
+    /// Parse a scan order from its CLI name ("raster", "morton")
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "raster" => Some(Self::Raster),
+            "morton" => Some(Self::Morton),
+            _ => None,
+        }
+    }
+
+    /// The CLI name of the scan order
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Raster => "raster",
+            Self::Morton => "morton",
+        }
+    }
+}
+
+/// Iterator over pixel coordinates in Morton (Z-order) sequence
+///
+/// Walks the curve over the smallest power-of-two square covering the
+/// image and skips coordinates outside it, so every pixel of a
+/// `width`×`height` image is yielded exactly once.
+#[derive(Debug, Clone)]
+pub struct MortonOrder {
+    width: u32,
+    height: u32,
+    index: u64,
+    end: u64,
+}
+
+impl MortonOrder {
+    pub fn new(width: u32, height: u32) -> Self {
+        let side = width.max(height).max(1).next_power_of_two() as u64;
+        let end = if width == 0 || height == 0 {
+            0
+        } else {
+            side * side
+        };
+
+        Self {
+            width,
+            height,
+            index: 0,
+            end,
+        }
+    }
+}
+
+/// Morton-order coordinates of an image
+pub fn morton_curve_order(width: u32, height: u32) -> MortonOrder {
+    MortonOrder::new(width, height)
+}
+
+/// Gather the even bits of `value` into the low half (inverse of bit interleaving)
+fn compact_bits(mut value: u64) -> u32 {
+    value &= 0x5555_5555_5555_5555;
+    value = (value | (value >> 1)) & 0x3333_3333_3333_3333;
+    value = (value | (value >> 2)) & 0x0F0F_0F0F_0F0F_0F0F;
+    value = (value | (value >> 4)) & 0x00FF_00FF_00FF_00FF;
+    value = (value | (value >> 8)) & 0x0000_FFFF_0000_FFFF;
+    value = (value | (value >> 16)) & 0x0000_0000_FFFF_FFFF;
+    value as u32
+}
+
+impl Iterator for MortonOrder {
+    type Item = (u32, u32);
+
+    fn next(&mut self) -> Option<(u32, u32)> {
+        while self.index < self.end {
+            let x = compact_bits(self.index);
+            let y = compact_bits(self.index >> 1);
+            self.index += 1;
+
+            if x < self.width && y < self.height {
+                return Some((x, y));
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over pixel coordinates along a Hilbert curve
+///
+/// Like [`MortonOrder`], walks the curve over the smallest power-of-two
+/// square covering the image and skips coordinates outside it. Inside that
+/// square every step moves to an adjacent pixel; skipping can only break
+/// adjacency where the curve leaves and re-enters the image.
+#[derive(Debug, Clone)]
+pub struct HilbertOrder {
+    width: u32,
+    height: u32,
+    side: u64,
+    index: u64,
+    end: u64,
+}
+
+impl HilbertOrder {
+    pub fn new(width: u32, height: u32) -> Self {
+        let side = width.max(height).max(1).next_power_of_two() as u64;
+        let end = if width == 0 || height == 0 {
+            0
+        } else {
+            side * side
+        };
+
+        Self {
+            width,
+            height,
+            side,
+            index: 0,
+            end,
+        }
+    }
+}
+
+/// Hilbert-curve coordinates of an image
+pub fn hilbert_curve_order(width: u32, height: u32) -> HilbertOrder {
+    HilbertOrder::new(width, height)
+}
+
+/// Coordinates of position `index` on the Hilbert curve over a `side`×`side` square
+fn hilbert_point(side: u64, index: u64) -> (u64, u64) {
+    let (mut x, mut y) = (0, 0);
+    let mut t = index;
+    let mut s = 1;
+    while s < side {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        // Rotate the quadrant so the sub-curves connect
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x, y)
+}
+
+impl Iterator for HilbertOrder {
+    type Item = (u32, u32);
+
+    fn next(&mut self) -> Option<(u32, u32)> {
+        while self.index < self.end {
+            let (x, y) = hilbert_point(self.side, self.index);
+            self.index += 1;
+
+            if x < self.width as u64 && y < self.height as u64 {
+                return Some((x as u32, y as u32));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_morton_pattern() {
+        let first: Vec<_> = morton_curve_order(4, 4).take(8).collect();
+        assert_eq!(
+            first,
+            vec![
+                (0, 0),
+                (1, 0),
+                (0, 1),
+                (1, 1),
+                (2, 0),
+                (3, 0),
+                (2, 1),
+                (3, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_morton_visits_every_pixel_once() {
+        for (width, height) in [(1, 1), (4, 4), (5, 3), (7, 16), (33, 2)] {
+            let mut seen = vec![false; (width * height) as usize];
+            let mut count = 0;
+            for (x, y) in morton_curve_order(width, height) {
+                let idx = (y * width + x) as usize;
+                assert!(!seen[idx], "({}, {}) visited twice", x, y);
+                seen[idx] = true;
+                count += 1;
+            }
+            assert_eq!(count, width * height);
+        }
+
+        assert_eq!(morton_curve_order(0, 5).count(), 0);
+    }
+
+    #[test]
+    fn test_hilbert_visits_every_pixel_once() {
+        for (width, height) in [(1, 1), (4, 4), (5, 3), (7, 16), (33, 2)] {
+            let mut seen = vec![false; (width * height) as usize];
+            for (x, y) in hilbert_curve_order(width, height) {
+                let idx = (y * width + x) as usize;
+                assert!(!seen[idx], "({}, {}) visited twice", x, y);
+                seen[idx] = true;
+            }
+            assert!(seen.iter().all(|&s| s), "{}x{}", width, height);
+        }
+
+        // On a power-of-two square every step goes to a neighbor
+        let points: Vec<_> = hilbert_curve_order(16, 16).collect();
+        assert_eq!(points[0], (0, 0));
+        for pair in points.windows(2) {
+            let distance = pair[0].0.abs_diff(pair[1].0) + pair[0].1.abs_diff(pair[1].1);
+            assert_eq!(distance, 1, "{:?}", pair);
+        }
+        assert_eq!(hilbert_curve_order(0, 5).count(), 0);
+    }
+}
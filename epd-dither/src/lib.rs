@@ -2,21 +2,115 @@
 //!
 //! This library provides high-quality dithering algorithms optimized for
 //! e-ink displays with limited color palettes.
+//!
+//! Diagnostics are emitted through the [`log`](https://docs.rs/log) facade
+//! when the `logging` feature (on by default) is enabled; install any logger
+//! to see them.
+
+// Forward to the `log` facade, or type-check the arguments and discard them
+// when the `logging` feature is disabled
+macro_rules! log_warn {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "logging")]
+        log::warn!($($arg)*);
+        #[cfg(not(feature = "logging"))]
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+macro_rules! log_debug {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "logging")]
+        log::debug!($($arg)*);
+        #[cfg(not(feature = "logging"))]
+        let _ = format_args!($($arg)*);
+    }};
+}
 
 pub mod color;
 pub mod device;
 pub mod dither;
+pub mod output;
 pub mod scaling;
 
-pub use color::{Palette, Rgb, Rgba};
+pub use color::{DistanceMetric, Palette, Rgb, Rgba};
 pub use device::{DeviceManager, DeviceSpec};
 pub use dither::{DitherOptions, DitheringAlgorithm, ErrorDiffusionKernel};
-pub use scaling::{FitMode, ScalingFilter};
+pub use scaling::{FitMode, LetterboxBackground, ScalingFilter};
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Errors callers may want to match on
+///
+/// Library functions return `anyhow::Result`; use
+/// `error.downcast_ref::<EpdDitherError>()` to detect these cases.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EpdDitherError {
+    /// Dithering was stopped through a [`CancellationToken`]
+    #[error("Dithering was cancelled")]
+    Cancelled,
+}
+
+/// Read side of a cancellation flag, checked by long-running operations
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Write side of a cancellation flag; may be sent to another thread
+#[derive(Debug, Clone)]
+pub struct CancellationTokenHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Create a token and the handle that cancels it
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> (Self, CancellationTokenHandle) {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        (
+            Self {
+                cancelled: Arc::clone(&cancelled),
+            },
+            CancellationTokenHandle { cancelled },
+        )
+    }
+
+    /// Check whether cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+impl CancellationTokenHandle {
+    /// Request cancellation
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
 
 /// Process an image with the given dithering options
-pub fn process_image(
+pub fn process_image(img: &mut image::RgbImage, options: &DitherOptions) -> anyhow::Result<()> {
+    dither::engine::dither_image(img, options)
+}
+
+/// Process a grayscale image with the given dithering options
+pub fn process_gray_image(
+    img: &mut image::GrayImage,
+    options: &DitherOptions,
+) -> anyhow::Result<()> {
+    dither::engine::dither_gray_image(img, options)
+}
+
+/// Process an image, reporting progress and honoring cancellation
+///
+/// See [`dither::engine::dither_image_with_progress`].
+pub fn process_image_with_progress(
     img: &mut image::RgbImage,
     options: &DitherOptions,
+    progress: impl FnMut(u32, u32),
+    cancel: Option<&CancellationToken>,
 ) -> anyhow::Result<()> {
-    dither::engine::dither_image(img, options)
+    dither::engine::dither_image_with_progress(img, options, progress, cancel)
 }
@@ -1,15 +1,53 @@
 //! EPD Dither - CLI tool for dithering images for e-ink displays
 
 use anyhow::{Context, Result};
-use clap::{Parser, ValueEnum};
+use clap::{parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
 use epd_dither::{
-    color::{convert, palette::PaletteManager, Rgb},
-    device::DeviceManager,
-    dither::{engine, DitheringAlgorithm, DitherOptions, ErrorDiffusionKernel, RandomMode},
-    scaling::{self, FitMode, ScalingFilter},
+    color::{
+        adjust, convert, distance,
+        palette::{self, PaletteManager},
+        simulate, DistanceMetric, Rgb,
+    },
+    device::{self, DeviceManager},
+    dither::{
+        algorithms::{diffusion_map, ordered},
+        engine, quality, DitherOptions, DitheringAlgorithm, ErrorDiffusionKernel, RandomMode,
+        ScanOrder,
+    },
+    output::{
+        bmp,
+        epd_raw::{self, ControllerFormat},
+        png as indexed_png, xbm,
+    },
+    scaling::{self, AspectRatio, FitMode, LetterboxBackground, ScalingFilter},
+    CancellationToken,
 };
 use std::path::PathBuf;
 
+/// Cell size in pixels for the checkerboard letterbox pattern
+const LETTERBOX_CHECKER_CELL_SIZE: u32 = 8;
+
+/// Color marking changed pixels in `--diff-output` images
+const DIFF_HIGHLIGHT_COLOR: Rgb = Rgb([255, 0, 255]);
+
+/// Fixed seed so `--dequantize-noise` output is reproducible
+const DEQUANTIZE_NOISE_SEED: u64 = 0x5EED;
+
+/// Window size of the variance mask used by --adaptive-dithering
+const ADAPTIVE_DITHERING_WINDOW: u32 = 7;
+
+/// Side length of `--show-diffusion-pattern` images before upscaling, and
+/// the nearest-neighbor upscale factor so single pixels are visible
+const DIFFUSION_PATTERN_SIZE: u32 = 7;
+const DIFFUSION_PATTERN_SCALE: u32 = 32;
+
+/// Rough per-pixel cost of each algorithm family, used by `--dry-run`
+const ERROR_DIFFUSION_NS_PER_PIXEL: u64 = 60;
+const ORDERED_NS_PER_PIXEL: u64 = 25;
+const RANDOM_NS_PER_PIXEL: u64 = 35;
+const HALFTONE_NS_PER_PIXEL: u64 = 45;
+const QUANTIZE_NS_PER_PIXEL: u64 = 20;
+
 #[derive(Parser)]
 #[command(name = "epd-dither")]
 #[command(author = "EPD Optimize Team")]
@@ -17,11 +55,44 @@ use std::path::PathBuf;
 #[command(about = "Dither images for e-ink/e-paper displays", long_about = None)]
 struct Cli {
     /// Input image file
-    #[arg(short, long, value_name = "FILE", required_unless_present_any = ["list_devices", "list_palettes"])]
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        required_unless_present_any = [
+            "list_devices",
+            "list_devices_for_palette",
+            "detect_device",
+            "list_palettes",
+            "print_palette_distances",
+            "palette_quality_score",
+            "export_device_db",
+            "export_palettes_to",
+            "show_diffusion_pattern",
+            "find_palette_for_color",
+        ]
+    )]
     input: Option<PathBuf>,
 
     /// Output image file
-    #[arg(short, long, value_name = "FILE", required_unless_present_any = ["list_devices", "list_palettes"])]
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        required_unless_present_any = [
+            "list_devices",
+            "list_devices_for_palette",
+            "detect_device",
+            "list_palettes",
+            "print_palette_distances",
+            "palette_quality_score",
+            "export_device_db",
+            "export_palettes_to",
+            "show_diffusion_pattern",
+            "find_palette_for_color",
+            "print_aspect_ratio",
+        ]
+    )]
     output: Option<PathBuf>,
 
     /// Dithering algorithm
@@ -36,18 +107,195 @@ struct Cli {
     #[arg(short, long, value_name = "COLORS")]
     custom_palette: Option<String>,
 
+    /// Choose the built-in palette that best matches the image
+    #[arg(
+        long,
+        conflicts_with_all = ["custom_palette", "load_palette_hex", "load_palette_css", "device"]
+    )]
+    auto_select_palette: bool,
+
+    /// Load the palette from a text file with one hex color per line
+    #[arg(long, value_name = "FILE", conflicts_with = "custom_palette")]
+    load_palette_hex: Option<PathBuf>,
+
+    /// Load palettes from CSS custom properties (`--palette-NAME-COLOR: #HEX;`);
+    /// uses the one named by --palette, or the first
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["custom_palette", "load_palette_hex"])]
+    load_palette_css: Option<PathBuf>,
+
+    /// Fetch the palette from lospec.com by its slug (e.g. "pico-8")
+    #[cfg(feature = "lospec")]
+    #[arg(
+        long,
+        value_name = "SLUG",
+        conflicts_with_all = [
+            "custom_palette",
+            "load_palette_hex",
+            "load_palette_css",
+            "auto_select_palette",
+            "wcag_palette",
+            "palette_from_output",
+        ]
+    )]
+    palette_lospec: Option<String>,
+
+    /// Generate N text colors with at least RATIO WCAG contrast on BG_HEX
+    /// (e.g. "#fff,4.5,4")
+    #[arg(
+        long,
+        value_name = "BG_HEX,RATIO,N",
+        conflicts_with_all = [
+            "custom_palette",
+            "load_palette_hex",
+            "load_palette_css",
+            "auto_select_palette",
+        ]
+    )]
+    wcag_palette: Option<String>,
+
+    /// Reuse the palette embedded in a PNG saved with --embed-metadata
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = [
+            "custom_palette",
+            "load_palette_hex",
+            "load_palette_css",
+            "auto_select_palette",
+            "wcag_palette",
+        ]
+    )]
+    palette_from_output: Option<PathBuf>,
+
     /// Device color set name for final color replacement
-    #[arg(short, long)]
+    #[arg(long)]
     device_colors: Option<String>,
 
     /// Use serpentine scanning for error diffusion
     #[arg(short, long)]
     serpentine: bool,
 
+    /// Pixel scan order for error diffusion (raster, morton)
+    #[arg(long, default_value = "raster")]
+    scan_order: String,
+
+    /// Noise shaping order for error diffusion (0 = off); rows scan left to right
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    noise_shaping_order: u8,
+
     /// Bayer matrix size for ordered dithering (format: WxH)
     #[arg(long, default_value = "4x4")]
     bayer_size: String,
 
+    /// Error diffusion kernel for luma in yuv-decoupled dithering
+    #[arg(
+        long,
+        value_name = "KERNEL",
+        default_value = "floyd-steinberg",
+        value_parser = parse_kernel
+    )]
+    luma_kernel: ErrorDiffusionKernel,
+
+    /// Error diffusion kernel for chroma in yuv-decoupled dithering
+    #[arg(
+        long,
+        value_name = "KERNEL",
+        default_value = "jarvis",
+        value_parser = parse_kernel
+    )]
+    chroma_kernel: ErrorDiffusionKernel,
+
+    /// Blue-noise map size for blue-noise-streaming dithering (format: WxH)
+    #[arg(long, default_value = "16x16", value_parser = parse_blue_noise_size)]
+    blue_noise_size: (u8, u8),
+
+    /// Magic square size for magic-square dithering (5 or 9)
+    #[arg(long, default_value_t = 5)]
+    magic_size: u8,
+
+    /// Ordered dithering with a threshold map from a JSON 2D array
+    /// (values 0..width*height); overrides --algorithm
+    #[arg(long, value_name = "FILE")]
+    custom_threshold_map: Option<PathBuf>,
+
+    /// Pick the algorithm from the image content and the device's palette;
+    /// overrides --algorithm and turns on --serpentine for photos on color
+    /// displays
+    #[arg(long, requires = "device", conflicts_with = "custom_threshold_map")]
+    auto_algorithm: bool,
+
+    /// Screen angle in degrees for halftone dithering
+    #[arg(long, default_value_t = 45.0)]
+    halftone_angle: f64,
+
+    /// Screen frequency in radians per pixel for halftone dithering
+    #[arg(long, default_value_t = 0.8)]
+    halftone_frequency: f64,
+
+    /// Automatic white balance before dithering
+    #[arg(long, value_enum)]
+    white_balance: Option<WhiteBalance>,
+
+    /// Linear color transformation applied before dithering
+    #[arg(long, value_enum)]
+    color_matrix: Option<ColorMatrix>,
+
+    /// Map channels through a 256-byte binary tone curve before dithering
+    #[arg(long, value_name = "FILE")]
+    curves_lut: Option<PathBuf>,
+
+    /// Convert the image into a display's color space (ICC profile) before dithering
+    #[cfg(feature = "icc")]
+    #[arg(long, value_name = "FILE")]
+    icc_profile: Option<PathBuf>,
+
+    /// Darken the corners before dithering: strength and start radius, both 0-1
+    #[arg(long, value_name = "STRENGTH,RADIUS", value_parser = parse_vignette)]
+    vignette: Option<(f64, f64)>,
+
+    /// Posterize each channel to this many levels before dithering
+    #[arg(long, value_name = "LEVELS")]
+    posterize: Option<u8>,
+
+    /// Dither rows in parallel, each trailing the one above (2-row error diffusion kernels only)
+    #[arg(long)]
+    parallel_rows: bool,
+
+    /// Dither in horizontal strips of this many rows to limit working memory
+    #[arg(long, value_name = "N", conflicts_with = "parallel_rows")]
+    chunk_height: Option<u32>,
+
+    /// Dither at this multiple of the resolution, then downscale (2, 4 or 8)
+    #[arg(
+        long,
+        value_name = "FACTOR",
+        conflicts_with_all = ["parallel_rows", "chunk_height"]
+    )]
+    super_sample: Option<u8>,
+
+    /// Match device colors to palette colors by luminance instead of by index
+    #[arg(long)]
+    color_replace_by_luminance: bool,
+
+    /// Add Gaussian noise with this standard deviation before dithering
+    #[arg(long, value_name = "SIGMA")]
+    dequantize_noise: Option<f64>,
+
+    /// Grayscale image scaling dithering strength per pixel (white = full, black = none)
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["parallel_rows", "chunk_height", "super_sample"]
+    )]
+    dither_mask: Option<PathBuf>,
+
+    /// Dither busy areas fully and smooth ones less, from the local variance
+    #[arg(
+        long,
+        conflicts_with_all = ["parallel_rows", "chunk_height", "super_sample", "dither_mask"]
+    )]
+    adaptive_dithering: bool,
+
     /// Skip device color replacement
     #[arg(long)]
     no_color_replace: bool,
@@ -56,6 +304,10 @@ struct Cli {
     #[arg(short = 'd', long)]
     device: Option<String>,
 
+    /// Load the device database from a JSON file instead of the built-in one
+    #[arg(long, value_name = "FILE")]
+    device_db: Option<PathBuf>,
+
     /// Target width for output image (auto-scales before dithering)
     #[arg(long)]
     target_width: Option<u32>,
@@ -64,18 +316,118 @@ struct Cli {
     #[arg(long)]
     target_height: Option<u32>,
 
-    /// Fit mode when resizing (letterbox, crop, fill, contain)
+    /// Fit mode when resizing (letterbox, crop, fill, contain, smart-crop)
     #[arg(long, default_value = "letterbox")]
     fit_mode: String,
 
+    /// Rotate the image when resizing if that reduces letterboxing
+    #[arg(long)]
+    auto_rotate_landscape: bool,
+
     /// Scaling algorithm (nearest, triangle, catmull-rom, gaussian, lanczos3)
     #[arg(long, default_value = "lanczos3")]
     scaling_algorithm: String,
 
+    /// Pad the width and height to a multiple of N after scaling (letterbox color)
+    #[arg(long, value_name = "N")]
+    pad_to_multiple: Option<u32>,
+
+    /// Rotate the image according to its EXIF orientation before scaling
+    #[arg(long)]
+    auto_rotate: bool,
+
+    /// Rotate the image clockwise before scaling (90, 180 or 270 degrees)
+    #[arg(long, value_name = "DEGREES")]
+    rotate: Option<u16>,
+
     /// Background color for letterbox mode (hex color, e.g., #ffffff)
     #[arg(long, default_value = "#ffffff")]
     letterbox_color: String,
 
+    /// Letterbox bar pattern (solid, checker, gradient)
+    #[arg(long, default_value = "solid")]
+    letterbox_pattern: String,
+
+    /// Secondary letterbox color for checker and gradient patterns (hex color)
+    #[arg(long, default_value = "#000000")]
+    letterbox_secondary_color: String,
+
+    /// Invert the palette before dithering (negative-image display modes)
+    #[arg(long)]
+    invert_palette: bool,
+
+    /// Snap every palette color to the nearest of the 216 web-safe colors
+    #[arg(long)]
+    snap_to_web_safe: bool,
+
+    /// Invert the final output image (white on black)
+    #[arg(long)]
+    invert_output: bool,
+
+    /// Also write an image highlighting the pixels changed by dithering
+    #[arg(long, value_name = "FILE")]
+    diff_output: Option<PathBuf>,
+
+    /// Also write the original and dithered images side by side
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = [
+            "parallel_rows",
+            "chunk_height",
+            "super_sample",
+            "dither_mask",
+            "adaptive_dithering",
+        ]
+    )]
+    comparison_output: Option<PathBuf>,
+
+    /// Print how many pixels use each palette color after dithering
+    #[arg(long)]
+    print_color_usage: bool,
+
+    /// Print how evenly the palette colors are used after dithering
+    #[arg(long)]
+    print_entropy: bool,
+
+    /// Dither with every built-in algorithm (default parameters) and write
+    /// `{stem}_{algorithm}{.ext}` files plus a `{stem}_comparison.png` grid
+    #[arg(long)]
+    multi_algorithm: bool,
+
+    /// Also write a map coloring each pixel by its palette index (distinct hues)
+    #[arg(long, value_name = "FILE")]
+    coverage_map_output: Option<PathBuf>,
+
+    /// Also write `<output>_simulated.png` showing the result seen from this distance
+    #[arg(long, value_name = "CM")]
+    simulate_view_distance: Option<f64>,
+
+    /// Display PPI for --simulate-view-distance (defaults to the device's PPI)
+    #[arg(long, value_name = "N", requires = "simulate_view_distance")]
+    simulate_ppi: Option<u32>,
+
+    /// Write a raw controller frame buffer instead of an image file
+    #[arg(long, value_enum)]
+    controller: Option<Controller>,
+
+    /// Output image format
+    #[arg(
+        long,
+        value_enum,
+        default_value = "auto",
+        conflicts_with = "controller"
+    )]
+    output_format: OutputFormat,
+
+    /// Store the dither options in a tEXt chunk of the PNG output
+    #[arg(long, conflicts_with = "controller")]
+    embed_metadata: bool,
+
+    /// Also write a thumbnail of the result, SIZE pixels on its longer side
+    #[arg(long, num_args = 2, value_names = ["SIZE", "FILE"])]
+    generate_thumbnail: Option<Vec<String>>,
+
     /// List available palettes and exit
     #[arg(long)]
     list_palettes: bool,
@@ -84,6 +436,51 @@ struct Cli {
     #[arg(long)]
     list_devices: bool,
 
+    /// List the devices that use a palette and exit
+    #[arg(long, value_name = "PALETTE_NAME")]
+    list_devices_for_palette: Option<String>,
+
+    /// List the devices with a resolution (in either orientation) and exit
+    #[arg(long, value_name = "WxH", value_parser = parse_resolution)]
+    detect_device: Option<(u32, u32)>,
+
+    /// Export the device database (json or toml) to a file and exit
+    #[arg(long, num_args = 2, value_names = ["FORMAT", "FILE"])]
+    export_device_db: Option<Vec<String>>,
+
+    /// Export all palettes and device color sets as JSON files to a directory and exit
+    #[arg(long, value_name = "DIR")]
+    export_palettes_to: Option<PathBuf>,
+
+    /// Write an image of an error diffusion kernel's weights and exit
+    #[arg(long, num_args = 2, value_names = ["KERNEL", "OUTPUT"])]
+    show_diffusion_pattern: Option<Vec<String>>,
+
+    /// Print the 5 built-in palette colors closest to a hex color and exit
+    #[arg(long, value_name = "HEX")]
+    find_palette_for_color: Option<String>,
+
+    /// Print pairwise distances between the palette colors and exit
+    #[arg(long)]
+    print_palette_distances: bool,
+
+    /// Print how evenly the palette colors span the color space and exit
+    #[arg(long)]
+    palette_quality_score: bool,
+
+    /// Time every algorithm on the input image and print the results
+    #[cfg(feature = "bench")]
+    #[arg(long)]
+    benchmark: bool,
+
+    /// Print the aspect ratio of the input image (and device, if set) and exit
+    #[arg(long)]
+    print_aspect_ratio: bool,
+
+    /// Report the resolved settings without processing or writing output
+    #[arg(long)]
+    dry_run: bool,
+
     /// Verbose output
     #[arg(short, long)]
     verbose: bool,
@@ -109,16 +506,30 @@ enum Algorithm {
     Sierra24a,
     /// Ordered dithering (Bayer matrix)
     Ordered,
+    /// Ordered dithering (magic-square threshold map)
+    MagicSquare,
+    /// Ordered dithering with a Bayer matrix per pair of adjacent palette colors
+    OrderedPerColor,
+    /// Ordered dithering with a void-and-cluster blue-noise map
+    BlueNoiseStreaming,
     /// Random RGB dithering
     RandomRgb,
     /// Random black and white dithering
     RandomBw,
+    /// Angle-rotated halftone screen
+    Halftone,
+    /// Floyd-Steinberg on each RGB channel separately (may leave the palette)
+    PerChannel,
+    /// Error diffusion in YUV with separate luma and chroma kernels
+    YuvDecoupled,
+    /// Error diffusion along a Hilbert curve
+    Riemersma,
     /// Quantization only (no dithering)
     None,
 }
 
 impl Algorithm {
-    fn to_dithering_algorithm(&self, bayer_size: (u8, u8)) -> DitheringAlgorithm {
+    fn to_dithering_algorithm(self, bayer_size: (u8, u8), cli: &Cli) -> DitheringAlgorithm {
         match self {
             Algorithm::FloydSteinberg => {
                 DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::FloydSteinberg)
@@ -126,21 +537,11 @@ impl Algorithm {
             Algorithm::FalseFloydSteinberg => {
                 DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::FalseFloydSteinberg)
             }
-            Algorithm::Jarvis => {
-                DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Jarvis)
-            }
-            Algorithm::Stucki => {
-                DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Stucki)
-            }
-            Algorithm::Burkes => {
-                DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Burkes)
-            }
-            Algorithm::Sierra3 => {
-                DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Sierra3)
-            }
-            Algorithm::Sierra2 => {
-                DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Sierra2)
-            }
+            Algorithm::Jarvis => DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Jarvis),
+            Algorithm::Stucki => DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Stucki),
+            Algorithm::Burkes => DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Burkes),
+            Algorithm::Sierra3 => DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Sierra3),
+            Algorithm::Sierra2 => DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Sierra2),
             Algorithm::Sierra24a => {
                 DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Sierra2_4A)
             }
@@ -148,13 +549,102 @@ impl Algorithm {
                 width: bayer_size.0,
                 height: bayer_size.1,
             },
+            Algorithm::OrderedPerColor => DitheringAlgorithm::OrderedPerColor {
+                width: bayer_size.0,
+                height: bayer_size.1,
+            },
+            Algorithm::BlueNoiseStreaming => DitheringAlgorithm::BlueNoiseStreaming {
+                width: cli.blue_noise_size.0,
+                height: cli.blue_noise_size.1,
+            },
+            Algorithm::MagicSquare => DitheringAlgorithm::MagicSquare {
+                size: cli.magic_size,
+            },
             Algorithm::RandomRgb => DitheringAlgorithm::Random(RandomMode::Rgb),
             Algorithm::RandomBw => DitheringAlgorithm::Random(RandomMode::BlackAndWhite),
+            Algorithm::Halftone => DitheringAlgorithm::Halftone {
+                angle_degrees: cli.halftone_angle,
+                frequency: cli.halftone_frequency,
+            },
+            Algorithm::YuvDecoupled => DitheringAlgorithm::YuvDecoupled {
+                luma_kernel: cli.luma_kernel,
+                chroma_kernel: cli.chroma_kernel,
+            },
+            Algorithm::Riemersma => DitheringAlgorithm::Riemersma,
+            Algorithm::PerChannel => {
+                DitheringAlgorithm::ErrorDiffusionPerChannel(ErrorDiffusionKernel::FloydSteinberg)
+            }
             Algorithm::None => DitheringAlgorithm::QuantizationOnly,
         }
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum WhiteBalance {
+    /// Scale channels so the image averages to gray
+    GrayWorld,
+    /// Scale channels so the brightest pixel becomes white
+    MaxWhite,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum ColorMatrix {
+    /// Brownish old-photo tone
+    Sepia,
+    /// Shift colors towards orange
+    Warm,
+    /// Shift colors towards blue
+    Cool,
+    /// Convert to gray
+    Desaturate,
+}
+
+impl ColorMatrix {
+    fn matrix(self) -> [[f64; 3]; 3] {
+        match self {
+            ColorMatrix::Sepia => adjust::SEPIA_MATRIX,
+            ColorMatrix::Warm => adjust::WARM_FILTER_MATRIX,
+            ColorMatrix::Cool => adjust::COOL_FILTER_MATRIX,
+            ColorMatrix::Desaturate => adjust::DESATURATE_MATRIX,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Controller {
+    /// SSD1681 (1bpp black and white)
+    Ssd1681,
+    /// UC8176 (4bpp palette indices)
+    Uc8176,
+    /// IT8951 (4bpp/8bpp grayscale with load-image header)
+    It8951,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Infer the format from the output file extension
+    Auto,
+    /// PNG with a PLTE palette chunk, preserving exact palette colors
+    IndexedPng,
+    /// XBM C source (one bitplane per color for palettes of more than 2
+    /// colors); device colors are not applied
+    Xbm,
+    /// Uncompressed 24-bit BMP
+    Bmp,
+    /// Uncompressed 8-bit BMP with a color table
+    BmpIndexed,
+}
+
+impl From<Controller> for ControllerFormat {
+    fn from(controller: Controller) -> Self {
+        match controller {
+            Controller::Ssd1681 => ControllerFormat::Ssd1681,
+            Controller::Uc8176 => ControllerFormat::Uc8176,
+            Controller::It8951 => ControllerFormat::It8951,
+        }
+    }
+}
+
 fn parse_bayer_size(size_str: &str) -> Result<(u8, u8)> {
     let parts: Vec<&str> = size_str.split('x').collect();
     if parts.len() != 2 {
@@ -175,6 +665,259 @@ fn parse_bayer_size(size_str: &str) -> Result<(u8, u8)> {
     Ok((width, height))
 }
 
+fn parse_kernel(name: &str) -> Result<ErrorDiffusionKernel> {
+    ErrorDiffusionKernel::from_name(name)
+        .with_context(|| format!("Not an error diffusion kernel: {}", name))
+}
+
+fn parse_resolution(size_str: &str) -> Result<(u32, u32)> {
+    let (width, height) = size_str
+        .split_once('x')
+        .context("Invalid resolution format. Expected WxH (e.g., 800x480)")?;
+    let width: u32 = width.parse().context("Invalid width in resolution")?;
+    let height: u32 = height.parse().context("Invalid height in resolution")?;
+
+    Ok((width, height))
+}
+
+fn parse_blue_noise_size(size_str: &str) -> Result<(u8, u8)> {
+    let (width, height) = size_str
+        .split_once('x')
+        .context("Invalid blue-noise size format. Expected WxH (e.g., 16x16)")?;
+    let width: u8 = width.parse().context("Invalid width in blue-noise size")?;
+    let height: u8 = height
+        .parse()
+        .context("Invalid height in blue-noise size")?;
+
+    if width == 0 || height == 0 {
+        anyhow::bail!("Blue-noise map size must be at least 1x1");
+    }
+
+    Ok((width, height))
+}
+
+fn parse_vignette(value: &str) -> Result<(f64, f64)> {
+    let (strength, radius) = value
+        .split_once(',')
+        .context("Invalid vignette format. Expected STRENGTH,RADIUS (e.g., 0.5,0.6)")?;
+    let strength: f64 = strength
+        .trim()
+        .parse()
+        .context("Invalid vignette strength")?;
+    let radius: f64 = radius.trim().parse().context("Invalid vignette radius")?;
+
+    if !(0.0..=1.0).contains(&strength) || !(0.0..=1.0).contains(&radius) {
+        anyhow::bail!("Vignette strength and radius must be between 0 and 1");
+    }
+
+    Ok((strength, radius))
+}
+
+/// Arguments that never touch the image between resizing and dithering:
+/// they act before the resize, configure the dithering itself or only
+/// apply to the dithered output
+const FUSABLE_ARGS: &[&str] = &[
+    "input",
+    "output",
+    "algorithm",
+    "palette",
+    "custom_palette",
+    "load_palette_hex",
+    "load_palette_css",
+    "palette_lospec",
+    "wcag_palette",
+    "palette_from_output",
+    "device_colors",
+    "serpentine",
+    "scan_order",
+    "noise_shaping_order",
+    "bayer_size",
+    "luma_kernel",
+    "chroma_kernel",
+    "blue_noise_size",
+    "magic_size",
+    "custom_threshold_map",
+    "halftone_angle",
+    "halftone_frequency",
+    "color_replace_by_luminance",
+    "no_color_replace",
+    "device",
+    "device_db",
+    "target_width",
+    "target_height",
+    "fit_mode",
+    "scaling_algorithm",
+    "auto_rotate",
+    "rotate",
+    "letterbox_color",
+    "letterbox_pattern",
+    "letterbox_secondary_color",
+    "invert_palette",
+    "snap_to_web_safe",
+    "invert_output",
+    "print_color_usage",
+    "print_entropy",
+    "coverage_map_output",
+    "simulate_view_distance",
+    "simulate_ppi",
+    "controller",
+    "output_format",
+    "embed_metadata",
+    "generate_thumbnail",
+    "verbose",
+];
+
+/// Whether resizing can be fused with dithering: true when every argument
+/// given on the command line is in [`FUSABLE_ARGS`], so a new step between
+/// the two is never skipped by accident
+fn fuses_resize_and_dither(matches: &ArgMatches) -> bool {
+    Cli::command().get_arguments().all(|arg| {
+        let id = arg.get_id().as_str();
+        FUSABLE_ARGS.contains(&id) || matches.value_source(id) != Some(ValueSource::CommandLine)
+    })
+}
+
+fn estimated_ns_per_pixel(algorithm: &DitheringAlgorithm) -> u64 {
+    match algorithm {
+        DitheringAlgorithm::ErrorDiffusion(_)
+        | DitheringAlgorithm::ErrorDiffusionPerChannel(_)
+        | DitheringAlgorithm::YuvDecoupled { .. }
+        | DitheringAlgorithm::Riemersma => ERROR_DIFFUSION_NS_PER_PIXEL,
+        DitheringAlgorithm::Ordered { .. }
+        | DitheringAlgorithm::MagicSquare { .. }
+        | DitheringAlgorithm::CustomOrdered { .. }
+        | DitheringAlgorithm::OrderedPerColor { .. }
+        | DitheringAlgorithm::BlueNoiseStreaming { .. } => ORDERED_NS_PER_PIXEL,
+        DitheringAlgorithm::Random(_) => RANDOM_NS_PER_PIXEL,
+        DitheringAlgorithm::Halftone { .. } => HALFTONE_NS_PER_PIXEL,
+        DitheringAlgorithm::QuantizationOnly | DitheringAlgorithm::Posterize { .. } => {
+            QUANTIZE_NS_PER_PIXEL
+        }
+        // Unknown cost; assume the slowest built-in family
+        DitheringAlgorithm::Custom(_) => ERROR_DIFFUSION_NS_PER_PIXEL,
+    }
+}
+
+/// Dither `img` with every built-in algorithm, save each result and a
+/// comparison grid next to `output`, and print timing and error per algorithm
+///
+/// Results are saved as dithered, without device color replacement or the
+/// other output options.
+fn run_multi_algorithm(
+    img: &image::RgbImage,
+    options: &DitherOptions,
+    output: &std::path::Path,
+) -> Result<()> {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = output
+        .extension()
+        .map_or("png".into(), |ext| ext.to_string_lossy());
+
+    println!("{:<24} {:>10} {:>10}", "Algorithm", "Time (ms)", "MSE");
+    let mut results = Vec::with_capacity(epd_dither::dither::ALGORITHM_NAMES.len());
+    for name in epd_dither::dither::ALGORITHM_NAMES {
+        let options = DitherOptions {
+            algorithm: DitheringAlgorithm::from_name(name).expect("Built-in algorithm name"),
+            ..options.clone()
+        };
+        let (dithered, stats) = engine::dither_image_copy(img, &options)?;
+        println!(
+            "{:<24} {:>10.1} {:>10.1}",
+            name,
+            stats.elapsed.as_secs_f64() * 1000.0,
+            stats.mse
+        );
+
+        let path = output.with_file_name(format!("{}_{}.{}", stem, name, extension));
+        dithered
+            .save(&path)
+            .with_context(|| format!("Failed to save image: {}", path.display()))?;
+        results.push(dithered);
+    }
+
+    let columns = (results.len() as f64).sqrt().ceil() as u32;
+    let path = output.with_file_name(format!("{}_comparison.png", stem));
+    engine::tile_images(&results, columns)?
+        .save(&path)
+        .with_context(|| format!("Failed to save comparison grid: {}", path.display()))?;
+    println!(
+        "\nSaved {} results and comparison grid {}",
+        results.len(),
+        path.display()
+    );
+
+    Ok(())
+}
+
+fn print_dry_run(
+    cli: &Cli,
+    options: &DitherOptions,
+    output: &std::path::Path,
+    (width, height): (u32, u32),
+) {
+    println!("Dry run - no output will be written\n");
+    println!(
+        "Palette: {} ({} colors)",
+        options.palette.name,
+        options.palette.len()
+    );
+    for color in &options.palette.colors {
+        println!("  {}", convert::rgb_to_hex(color));
+    }
+    println!("Algorithm: {:?}", options.algorithm);
+    println!("Serpentine: {}", options.serpentine);
+    println!("Scan order: {}", options.scan_order.name());
+    println!("Fit mode: {}", cli.fit_mode);
+    println!("Scaling filter: {}", cli.scaling_algorithm);
+    println!("Output dimensions: {}x{}", width, height);
+    println!("Rows: {}", height);
+    println!("Output: {}", output.display());
+
+    let pixels = width as u64 * height as u64;
+    let estimate_ms = pixels * estimated_ns_per_pixel(&options.algorithm) / 1_000_000;
+    println!("Estimated processing time: ~{} ms", estimate_ms);
+}
+
+/// Distinct colors of the final image, in the order of the dithering palette
+/// colors they are closest to
+///
+/// Device color replacement and inversion change the colors after dithering,
+/// so the dithering palette itself may not match the output.
+fn output_colors(img: &image::RgbImage, palette: &epd_dither::Palette) -> epd_dither::Palette {
+    let mut colors: Vec<Rgb> = Vec::new();
+    for pixel in img.pixels() {
+        if !colors.contains(&Rgb(pixel.0)) {
+            colors.push(Rgb(pixel.0));
+            // Too many for a PLTE chunk; let the encoder report it
+            if colors.len() > indexed_png::MAX_INDEXED_COLORS {
+                break;
+            }
+        }
+    }
+    colors.sort_by_key(|color| {
+        distance::find_closest_color(color, &palette.colors).map_or(0, |(idx, _)| idx)
+    });
+
+    epd_dither::Palette::new(palette.name.clone(), colors)
+}
+
+/// C identifier for XBM output, derived from the output file stem
+fn xbm_variable_name(output: &std::path::Path) -> String {
+    let stem = output
+        .file_stem()
+        .map_or("image".into(), |stem| stem.to_string_lossy());
+    let name: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    if name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        name
+    } else {
+        format!("_{}", name)
+    }
+}
+
 fn parse_custom_palette(palette_str: &str) -> Result<Vec<Rgb>> {
     palette_str
         .split(',')
@@ -187,11 +930,95 @@ fn parse_custom_palette(palette_str: &str) -> Result<Vec<Rgb>> {
         .collect()
 }
 
+/// Parse a `BG_HEX,RATIO,N` spec and generate the WCAG palette
+fn parse_wcag_palette(spec: &str) -> Result<epd_dither::Palette> {
+    let [background, ratio, n] = spec.split(',').map(str::trim).collect::<Vec<_>>()[..] else {
+        anyhow::bail!("WCAG palette must be BG_HEX,RATIO,N, got: {}", spec);
+    };
+    let background = convert::hex_to_rgb(background)
+        .with_context(|| format!("Invalid hex color: {}", background))?;
+    let ratio: f64 = ratio
+        .parse()
+        .with_context(|| format!("Invalid contrast ratio: {}", ratio))?;
+    let n: usize = n
+        .parse()
+        .with_context(|| format!("Invalid color count: {}", n))?;
+    palette::generate_wcag_compliant_palette(Rgb(background), ratio, n)
+}
+
+/// Custom colors, palette files or Lospec take precedence over the named palette
+fn resolve_palette(
+    cli: &Cli,
+    palette_manager: &PaletteManager,
+    name: &str,
+) -> Result<epd_dither::Palette> {
+    #[cfg(feature = "lospec")]
+    if let Some(slug) = &cli.palette_lospec {
+        return epd_dither::Palette::from_lospec_url_blocking(slug);
+    }
+
+    if let Some(path) = &cli.palette_from_output {
+        epd_dither::Palette::from_png_metadata(path)
+    } else if let Some(spec) = &cli.wcag_palette {
+        parse_wcag_palette(spec)
+    } else if let Some(custom) = &cli.custom_palette {
+        Ok(epd_dither::Palette::new(
+            "custom",
+            parse_custom_palette(custom)?,
+        ))
+    } else if let Some(path) = &cli.load_palette_hex {
+        epd_dither::Palette::from_hex_file(path)
+    } else if let Some(path) = &cli.load_palette_css {
+        let mut palettes = epd_dither::Palette::from_css_file(path)?;
+        if palettes.is_empty() {
+            anyhow::bail!("No palettes found in {}", path.display());
+        }
+        let index = palettes.iter().position(|p| p.name == name).unwrap_or(0);
+        Ok(palettes.swap_remove(index))
+    } else {
+        palette_manager.get_palette(name)
+    }
+}
+
+/// Swap dithered palette colors for device colors, by index or by luminance
+fn replace_device_colors(
+    cli: &Cli,
+    img: &mut image::RgbImage,
+    palette: &epd_dither::Palette,
+    device_colors: Vec<Rgb>,
+) -> Result<()> {
+    if cli.color_replace_by_luminance {
+        let device_palette = epd_dither::Palette::new("device", device_colors);
+        engine::replace_colors_by_luminance(img, palette, &device_palette)?;
+    } else {
+        engine::replace_colors(img, &palette.colors, &device_colors)?;
+    }
+    Ok(())
+}
+
 fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+    // Library warnings are always shown; --verbose adds debug output.
+    // RUST_LOG overrides both.
+    #[cfg(feature = "cli")]
+    env_logger::Builder::new()
+        .filter_level(if cli.verbose {
+            log::LevelFilter::Debug
+        } else {
+            log::LevelFilter::Warn
+        })
+        .parse_default_env()
+        .format_timestamp(None)
+        .init();
 
     let palette_manager = PaletteManager::new()?;
-    let device_manager = DeviceManager::new()?;
+    let device_manager = if let Some(path) = &cli.device_db {
+        DeviceManager::from_file(path)?
+    } else {
+        DeviceManager::new()?
+    };
 
     // Handle --list-devices
     if cli.list_devices {
@@ -204,15 +1031,14 @@ fn main() -> Result<()> {
         for tech in techs {
             println!("{}:", tech);
             for (id, spec) in &devices_by_tech[tech] {
-                println!(
-                    "  {:20} - {} ({}×{}, {} PPI, palette: {})",
-                    id,
-                    spec.name,
-                    spec.resolution.width,
-                    spec.resolution.height,
-                    spec.ppi,
-                    spec.palette
-                );
+                let mut framerate = String::new();
+                if let Some(hz) = spec.max_framerate_hz {
+                    framerate += &format!(", full refresh {} Hz", hz);
+                }
+                if let Some(hz) = spec.partial_refresh_framerate_hz {
+                    framerate += &format!(", partial refresh {} Hz", hz);
+                }
+                println!("  {:20} - {}{}", id, spec, framerate);
             }
             println!();
         }
@@ -222,6 +1048,95 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    // Handle --list-devices-for-palette
+    if let Some(palette_name) = &cli.list_devices_for_palette {
+        let devices = device_manager.devices_with_palette(palette_name);
+        if devices.is_empty() {
+            println!("No devices use palette '{}'", palette_name);
+            return Ok(());
+        }
+
+        println!("Devices using palette {}:\n", palette_name);
+        for (id, spec) in devices {
+            println!(
+                "  {:20} - {} ({}×{}, {})",
+                id,
+                spec.name,
+                spec.resolution.width,
+                spec.resolution.height,
+                spec.display_technology
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some((width, height)) = cli.detect_device {
+        let devices = device_manager.device_for_resolution_landscape(width, height);
+        if devices.is_empty() {
+            println!("No devices with resolution {}×{}", width, height);
+            return Ok(());
+        }
+
+        println!("Devices with resolution {}×{}:\n", width, height);
+        for (id, spec) in devices {
+            println!(
+                "  {:20} - {} ({}, {})",
+                id, spec.name, spec.resolution, spec.display_technology
+            );
+        }
+        return Ok(());
+    }
+
+    // Handle --show-diffusion-pattern
+    if let Some(args) = &cli.show_diffusion_pattern {
+        let (name, path) = (&args[0], &args[1]);
+        let Some(DitheringAlgorithm::ErrorDiffusion(kernel)) = DitheringAlgorithm::from_name(name)
+        else {
+            anyhow::bail!("Not an error diffusion kernel: {}", name);
+        };
+
+        let pattern = diffusion_map::generate_diffusion_pattern(kernel, DIFFUSION_PATTERN_SIZE);
+        let scaled = DIFFUSION_PATTERN_SIZE * DIFFUSION_PATTERN_SCALE;
+        image::imageops::resize(
+            &pattern,
+            scaled,
+            scaled,
+            image::imageops::FilterType::Nearest,
+        )
+        .save(path)
+        .with_context(|| format!("Failed to save diffusion pattern: {}", path))?;
+        println!("Saved {} diffusion pattern to {}", name, path);
+        return Ok(());
+    }
+
+    // Handle --export-device-db
+    if let Some(args) = &cli.export_device_db {
+        let (format, path) = (&args[0], &args[1]);
+        let contents = match format.to_lowercase().as_str() {
+            "json" => device_manager.export_json()?,
+            "toml" => device_manager.export_toml()?,
+            _ => anyhow::bail!(
+                "Unknown device database format: {}. Valid options: json, toml",
+                format
+            ),
+        };
+        std::fs::write(path, contents)
+            .with_context(|| format!("Failed to write device database: {}", path))?;
+        println!(
+            "Exported {} devices to {}",
+            device_manager.list_devices().len(),
+            path
+        );
+        return Ok(());
+    }
+
+    // Handle --export-palettes-to
+    if let Some(dir) = &cli.export_palettes_to {
+        let written = palette_manager.save_to_directory(dir)?;
+        println!("Exported {} palette files to {}", written, dir.display());
+        return Ok(());
+    }
+
     // Handle --list-palettes
     if cli.list_palettes {
         println!("Available palettes:");
@@ -236,9 +1151,70 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
-    // Unwrap input/output (guaranteed to exist after list commands)
+    // Handle --find-palette-for-color
+    if let Some(hex) = &cli.find_palette_for_color {
+        let target = convert::hex_to_rgb(hex)
+            .map(Rgb)
+            .with_context(|| format!("Invalid hex color: {}", hex))?;
+
+        println!(
+            "Closest palette colors to {}:",
+            convert::rgb_to_hex(&target)
+        );
+        let matches = palette_manager.search_by_color(&target, DistanceMetric::Euclidean, 5);
+        for (name, color) in matches {
+            println!(
+                "  {:12} {} (distance {:.2})",
+                name,
+                convert::rgb_to_hex(&color),
+                distance::euclidean_distance(&target, &color)
+            );
+        }
+        return Ok(());
+    }
+
+    // Handle --print-palette-distances
+    if cli.print_palette_distances {
+        let palette = resolve_palette(&cli, &palette_manager, &cli.palette)?;
+
+        println!(
+            "Pairwise distances for palette {} (Euclidean):\n",
+            palette.name
+        );
+        let hex: Vec<String> = palette.colors.iter().map(convert::rgb_to_hex).collect();
+        println!(
+            "{:>9}{}",
+            "",
+            hex.iter().map(|h| format!("{:>9}", h)).collect::<String>()
+        );
+        for (row, name) in palette
+            .distance_matrix(DistanceMetric::Euclidean)
+            .iter()
+            .zip(&hex)
+        {
+            let cells: String = row.iter().map(|d| format!("{:>9.2}", d)).collect();
+            println!("{:>9}{}", name, cells);
+        }
+        println!(
+            "\nMinimum separation: {:.2}",
+            palette.min_separation(DistanceMetric::Euclidean)
+        );
+        return Ok(());
+    }
+
+    // Handle --palette-quality-score
+    if cli.palette_quality_score {
+        let palette = resolve_palette(&cli, &palette_manager, &cli.palette)?;
+        println!(
+            "Perceptual quality of palette {}: {:.3} (1.0 = evenly distributed)",
+            palette.name,
+            distance::perceptual_palette_quality(&palette.colors)
+        );
+        return Ok(());
+    }
+
+    // Unwrap input (guaranteed to exist after list commands)
     let input = cli.input.as_ref().expect("Input file required");
-    let output = cli.output.as_ref().expect("Output file required");
 
     // Validate input file exists
     if !input.exists() {
@@ -254,15 +1230,29 @@ fn main() -> Result<()> {
         if cli.verbose {
             println!("Loading device configuration: {}", device_id);
         }
-        Some(device_manager.get_device(device_id)?)
+        let spec = device_manager.get_device(device_id)?;
+        spec.validate(&palette_manager)
+            .with_context(|| format!("Invalid device configuration: {}", device_id))?;
+        Some(spec)
     } else {
         None
     };
 
     // Load image
-    let img = image::open(input)
-        .with_context(|| format!("Failed to open image: {}", input.display()))?;
-    let mut rgb_img = img.to_rgb8();
+    let mut rgb_img = if cli.auto_rotate {
+        scaling::auto_rotate_from_exif(input)?
+    } else {
+        image::open(input)
+            .with_context(|| format!("Failed to open image: {}", input.display()))?
+            .to_rgb8()
+    };
+
+    if let Some(degrees) = cli.rotate {
+        if cli.verbose {
+            println!("Rotating image by {} degrees", degrees);
+        }
+        rgb_img = scaling::rotate_image(&rgb_img, degrees)?;
+    }
 
     if cli.verbose {
         println!(
@@ -272,6 +1262,35 @@ fn main() -> Result<()> {
         );
     }
 
+    if cli.print_aspect_ratio {
+        let (width, height) = rgb_img.dimensions();
+        let input_ratio = AspectRatio::from_resolution(width, height);
+        println!("Input: {}x{} ({})", width, height, input_ratio.reduce());
+
+        if let Some(ref device) = device_spec {
+            let device_ratio = scaling::device_aspect_ratio(device);
+            let (fit_width, fit_height) =
+                input_ratio.scale_to_fit(device.resolution.width, device.resolution.height);
+            println!(
+                "Device: {}x{} ({})",
+                device.resolution.width, device.resolution.height, device_ratio
+            );
+            println!(
+                "Matches device: {}",
+                if device_ratio.matches_resolution(width, height) {
+                    "yes"
+                } else {
+                    "no"
+                }
+            );
+            println!("Fits device at: {}x{}", fit_width, fit_height);
+        }
+        return Ok(());
+    }
+
+    // Guaranteed to exist unless only printing information
+    let output = cli.output.as_ref().expect("Output file required");
+
     // Determine target resolution (device > CLI args > original)
     let (target_width, target_height) = if let Some(ref device) = device_spec {
         if cli.verbose {
@@ -291,7 +1310,9 @@ fn main() -> Result<()> {
         (rgb_img.width(), rgb_img.height())
     };
 
-    // Resize image if needed
+    // Resize image if needed (dry runs only compute the resulting size)
+    let mut output_dimensions = rgb_img.dimensions();
+    let mut fused_resize = None;
     if target_width != rgb_img.width() || target_height != rgb_img.height() {
         if cli.verbose {
             println!(
@@ -309,34 +1330,108 @@ fn main() -> Result<()> {
         let scaling_filter = ScalingFilter::from_str(&cli.scaling_algorithm)?;
         let letterbox_color = convert::hex_to_rgb(&cli.letterbox_color)
             .with_context(|| format!("Invalid letterbox color: {}", cli.letterbox_color))?;
-
-        rgb_img = scaling::resize_image(
-            &rgb_img,
-            target_width,
-            target_height,
-            fit_mode,
-            scaling_filter,
+        let letterbox_secondary = convert::hex_to_rgb(&cli.letterbox_secondary_color)
+            .with_context(|| {
+                format!("Invalid letterbox color: {}", cli.letterbox_secondary_color)
+            })?;
+        let letterbox_background = LetterboxBackground::from_pattern(
+            &cli.letterbox_pattern,
             letterbox_color,
+            letterbox_secondary,
+            LETTERBOX_CHECKER_CELL_SIZE,
         )?;
 
-        if cli.verbose {
-            println!("Resized to: {}x{}", rgb_img.width(), rgb_img.height());
+        if cli.dry_run {
+            let (width, height) = rgb_img.dimensions();
+            let (width, height) = match scaling::detect_rotation_from_dimensions(
+                width,
+                height,
+                target_width,
+                target_height,
+            ) {
+                Some(_) if cli.auto_rotate_landscape => (height, width),
+                _ => (width, height),
+            };
+            output_dimensions =
+                scaling::output_dimensions(width, height, target_width, target_height, fit_mode);
+        } else if fuses_resize_and_dither(&matches)
+            && matches!(letterbox_background, LetterboxBackground::Solid(_))
+        {
+            // Nothing runs between scaling and dithering, so both happen together below
+            fused_resize = Some((target_width, target_height, fit_mode, scaling_filter));
+            output_dimensions = scaling::output_dimensions(
+                rgb_img.width(),
+                rgb_img.height(),
+                target_width,
+                target_height,
+                fit_mode,
+            );
+        } else {
+            rgb_img = scaling::resize_image(
+                &rgb_img,
+                target_width,
+                target_height,
+                fit_mode,
+                scaling_filter,
+                letterbox_background,
+                cli.auto_rotate_landscape,
+            )?;
+            output_dimensions = rgb_img.dimensions();
+
+            if cli.verbose {
+                println!("Resized to: {}x{}", rgb_img.width(), rgb_img.height());
+            }
         }
     }
 
-    // Determine palette (device > CLI arg > custom)
-    let palette_name = if let Some(ref device) = device_spec {
+    if let Some(multiple) = cli.pad_to_multiple {
+        let (width, height) = output_dimensions;
+        output_dimensions = scaling::padded_dimensions(width, height, multiple);
+        if !cli.dry_run {
+            let background = convert::hex_to_rgb(&cli.letterbox_color)
+                .with_context(|| format!("Invalid letterbox color: {}", cli.letterbox_color))?;
+            rgb_img = scaling::pad_to_multiple(&rgb_img, multiple, Rgb(background));
+
+            if cli.verbose {
+                println!("Padded to: {}x{}", rgb_img.width(), rgb_img.height());
+            }
+        }
+    }
+
+    let auto_palette = if cli.auto_select_palette {
+        let names = palette_manager.list_palettes();
+        let candidates: Vec<&str> = names.iter().map(String::as_str).collect();
+        let name = palette_manager.closest_palette(&rgb_img, &candidates)?;
+        if cli.verbose {
+            println!("Auto-selected palette: {}", name);
+        }
+        Some(name)
+    } else {
+        None
+    };
+
+    // Determine palette (auto-selected > device > CLI arg > custom)
+    let palette_name = if let Some(ref name) = auto_palette {
+        name
+    } else if let Some(ref device) = device_spec {
         &device.palette
     } else {
         &cli.palette
     };
 
     // Get or create palette
-    let palette = if let Some(custom) = &cli.custom_palette {
-        let colors = parse_custom_palette(custom)?;
-        epd_dither::Palette::new("custom", colors)
+    let palette = resolve_palette(&cli, &palette_manager, palette_name)?;
+
+    let palette = if cli.snap_to_web_safe {
+        palette.to_nearest_web_safe()
     } else {
-        palette_manager.get_palette(palette_name)?
+        palette
+    };
+
+    let palette = if cli.invert_palette {
+        palette.inverted()
+    } else {
+        palette
     };
 
     if cli.verbose {
@@ -346,26 +1441,273 @@ fn main() -> Result<()> {
     // Parse bayer size
     let bayer_size = parse_bayer_size(&cli.bayer_size)?;
 
+    let mut serpentine = cli.serpentine;
+    let algorithm = if let Some(path) = &cli.custom_threshold_map {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read threshold map: {}", path.display()))?;
+        let matrix = ordered::load_threshold_matrix_from_json(&json)
+            .with_context(|| format!("Invalid threshold map: {}", path.display()))?;
+        DitheringAlgorithm::CustomOrdered { matrix }
+    } else if let (true, Some(device)) = (cli.auto_algorithm, &device_spec) {
+        let content = device::detect_content_type(&rgb_img);
+        let algorithm = device::suggest_algorithm(device, content);
+        serpentine |= device::suggest_serpentine(device, content);
+        if cli.verbose {
+            println!(
+                "Detected {:?} content, using {}{}",
+                content,
+                algorithm.name(),
+                if serpentine { " (serpentine)" } else { "" }
+            );
+        }
+        algorithm
+    } else {
+        cli.algorithm.to_dithering_algorithm(bayer_size, &cli)
+    };
+
     // Create dither options
     let options = DitherOptions {
-        algorithm: cli.algorithm.to_dithering_algorithm(bayer_size),
+        algorithm,
         palette: palette.clone(),
-        serpentine: cli.serpentine,
+        serpentine,
+        scan_order: ScanOrder::from_name(&cli.scan_order).with_context(|| {
+            format!(
+                "Invalid scan order: {}. Valid options: raster, morton",
+                cli.scan_order
+            )
+        })?,
+        noise_shaping_order: cli.noise_shaping_order,
+        pre_quantize_bins: None,
+        ..Default::default()
     };
 
+    if cli.dry_run {
+        print_dry_run(&cli, &options, output, output_dimensions);
+        return Ok(());
+    }
+
+    #[cfg(feature = "bench")]
+    if cli.benchmark {
+        println!(
+            "Benchmarking algorithms on {}x{} image:",
+            rgb_img.width(),
+            rgb_img.height()
+        );
+        let results = epd_dither::dither::bench::benchmark_algorithms(&rgb_img, &palette, 3)?;
+        for (name, duration) in results {
+            println!("  {:22} {:>10.2} ms", name, duration.as_secs_f64() * 1000.0);
+        }
+        return Ok(());
+    }
+
     if cli.verbose {
         println!("Algorithm: {:?}", cli.algorithm);
         println!("Dithering image...");
     }
 
+    if let Some(white_balance) = cli.white_balance {
+        if cli.verbose {
+            println!("Applying {:?} white balance", white_balance);
+        }
+        match white_balance {
+            WhiteBalance::GrayWorld => adjust::auto_white_balance_gray_world(&mut rgb_img),
+            WhiteBalance::MaxWhite => adjust::auto_white_balance_max_white(&mut rgb_img),
+        }
+    }
+
+    if let Some(color_matrix) = cli.color_matrix {
+        if cli.verbose {
+            println!("Applying {:?} color matrix", color_matrix);
+        }
+        adjust::apply_color_matrix(&mut rgb_img, color_matrix.matrix());
+    }
+
+    if let Some(path) = &cli.curves_lut {
+        if cli.verbose {
+            println!("Applying tone curve: {}", path.display());
+        }
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read curves LUT: {}", path.display()))?;
+        let lut: [u8; 256] = bytes.as_slice().try_into().map_err(|_| {
+            anyhow::anyhow!(
+                "Curves LUT {} must be exactly 256 bytes, got {}",
+                path.display(),
+                bytes.len()
+            )
+        })?;
+        adjust::apply_curves(&mut rgb_img, &lut);
+    }
+
+    #[cfg(feature = "icc")]
+    if let Some(path) = &cli.icc_profile {
+        if cli.verbose {
+            println!("Applying display ICC profile: {}", path.display());
+        }
+        adjust::apply_display_icc(&mut rgb_img, path)?;
+    }
+
+    if let Some((strength, radius)) = cli.vignette {
+        if cli.verbose {
+            println!(
+                "Applying vignette (strength {}, radius {})",
+                strength, radius
+            );
+        }
+        adjust::apply_vignette(&mut rgb_img, strength, radius);
+    }
+
+    if let Some(levels) = cli.posterize {
+        if cli.verbose {
+            println!("Posterizing to {} levels per channel", levels);
+        }
+        adjust::posterize(&mut rgb_img, levels)?;
+    }
+
+    if let Some(sigma) = cli.dequantize_noise {
+        if cli.verbose {
+            println!("Adding dequantization noise (sigma {})", sigma);
+        }
+        adjust::dequantize_noise(&mut rgb_img, sigma, DEQUANTIZE_NOISE_SEED);
+    }
+
+    if cli.multi_algorithm {
+        return run_multi_algorithm(&rgb_img, &options, output);
+    }
+
+    let original = cli.diff_output.as_ref().map(|_| rgb_img.clone());
+
     // Apply dithering
-    epd_dither::process_image(&mut rgb_img, &options)?;
+    if let Some((width, height, fit_mode, filter)) = fused_resize {
+        let background = convert::hex_to_rgb(&cli.letterbox_color)?;
+        rgb_img = scaling::resize_and_dither_pipeline(
+            &rgb_img,
+            width,
+            height,
+            fit_mode,
+            filter,
+            Rgb(background),
+            &options,
+        )?;
+
+        if cli.verbose {
+            println!(
+                "Resized and dithered to: {}x{}",
+                rgb_img.width(),
+                rgb_img.height()
+            );
+        }
+    } else if let Some(factor) = cli.super_sample {
+        if cli.verbose {
+            println!("Super-sampling at {}x", factor);
+        }
+        rgb_img = engine::super_sample_dither(&rgb_img, &options, factor)?;
+    } else if let Some(path) = &cli.comparison_output {
+        let (dithered, stats) = engine::dither_image_copy(&rgb_img, &options)?;
+        if cli.verbose {
+            println!(
+                "Dithered in {:.2?}: {} pixels changed, MSE {:.2}",
+                stats.elapsed, stats.changed_pixels, stats.mse
+            );
+            println!("Saving comparison image: {}", path.display());
+        }
+        engine::side_by_side(&rgb_img, &dithered)?
+            .save(path)
+            .with_context(|| format!("Failed to save comparison image: {}", path.display()))?;
+        rgb_img = dithered;
+    } else if let Some(path) = &cli.dither_mask {
+        if cli.verbose {
+            println!("Loading dither mask: {}", path.display());
+        }
+        let mask = image::open(path)
+            .with_context(|| format!("Failed to open dither mask: {}", path.display()))?
+            .to_luma8();
+        engine::dither_with_mask(&mut rgb_img, &mask, &options)?;
+    } else if cli.adaptive_dithering {
+        if cli.verbose {
+            println!(
+                "Computing variance mask ({0}x{0} window)",
+                ADAPTIVE_DITHERING_WINDOW
+            );
+        }
+        let mask = adjust::compute_variance_mask(&rgb_img, ADAPTIVE_DITHERING_WINDOW);
+        engine::dither_with_mask(&mut rgb_img, &mask, &options)?;
+    } else if cli.parallel_rows {
+        engine::dither_image_parallel_rows(&mut rgb_img, &options)?;
+    } else if let Some(strip_height) = cli.chunk_height {
+        engine::dither_image_chunked(&mut rgb_img, &options, strip_height)?;
+    } else {
+        let (cancel_token, cancel_handle) = CancellationToken::new();
+        #[cfg(feature = "signal-handling")]
+        ctrlc::set_handler(move || cancel_handle.cancel())
+            .context("Failed to install Ctrl-C handler")?;
+        #[cfg(not(feature = "signal-handling"))]
+        drop(cancel_handle);
 
-    // Optionally replace colors with device colors
-    if !cli.no_color_replace {
+        epd_dither::process_image_with_progress(
+            &mut rgb_img,
+            &options,
+            |_, _| {},
+            Some(&cancel_token),
+        )?;
+    }
+
+    if let (Some(path), Some(original)) = (&cli.diff_output, original) {
+        if cli.verbose {
+            println!("Saving diff image: {}", path.display());
+        }
+        engine::diff_image(&original, &rgb_img, DIFF_HIGHLIGHT_COLOR)?
+            .save(path)
+            .with_context(|| format!("Failed to save diff image: {}", path.display()))?;
+    }
+
+    if cli.print_entropy {
+        let entropy = quality::color_utilization_entropy(&rgb_img, &palette)?;
+        let uniformity = quality::color_utilization_uniformity(&rgb_img, &palette)?;
+        println!(
+            "Color utilization entropy: {:.3} bits (max {:.3}), uniformity {:.3}",
+            entropy,
+            (palette.len() as f64).log2(),
+            uniformity
+        );
+    }
+
+    if cli.print_color_usage {
+        let report = engine::color_usage_report(&rgb_img, &palette);
+        println!(
+            "Color usage: {} pixels in palette, {} not in palette",
+            report.replaced_count, report.unmatched_count
+        );
+        if report.unused_palette_indices.is_empty() {
+            println!("All {} palette colors are used", palette.len());
+        } else {
+            let unused: Vec<String> = report
+                .unused_palette_indices
+                .iter()
+                .map(|&idx| format!("{} ({})", idx, convert::rgb_to_hex(&palette.colors[idx])))
+                .collect();
+            println!("Unused palette colors: {}", unused.join(", "));
+        }
+    }
+
+    if let Some(path) = &cli.coverage_map_output {
+        if cli.verbose {
+            println!("Saving palette coverage map: {}", path.display());
+        }
+        engine::generate_palette_coverage_map(&rgb_img, &palette)?
+            .save(path)
+            .with_context(|| format!("Failed to save coverage map: {}", path.display()))?;
+    }
+
+    // Optionally replace colors with device colors (raw controller output
+    // and XBM bitmaps are encoded from the dithering palette instead)
+    let encodes_palette = cli.controller.is_some() || cli.output_format == OutputFormat::Xbm;
+    if !cli.no_color_replace && !encodes_palette {
         if let Some(device_colors_name) = &cli.device_colors {
             if cli.verbose {
-                println!("Replacing colors with device colors: {}", device_colors_name);
+                println!(
+                    "Replacing colors with device colors: {}",
+                    device_colors_name
+                );
             }
 
             let device_colors = palette_manager.get_device_colors(device_colors_name)?;
@@ -378,27 +1720,119 @@ fn main() -> Result<()> {
                 );
             }
 
-            engine::replace_colors(&mut rgb_img, &palette.colors, &device_colors)?;
-        } else if cli.palette != "custom" {
-            // Auto-detect matching device colors
+            replace_device_colors(&cli, &mut rgb_img, &palette, device_colors)?;
+        } else {
+            // Auto-detect matching device colors, by name or by similar colors
             if cli.verbose {
-                println!("Auto-detecting device colors for palette: {}", cli.palette);
+                println!("Auto-detecting device colors for palette: {}", palette.name);
             }
 
-            if let Ok(device_colors) = palette_manager.get_device_colors(&cli.palette) {
-                engine::replace_colors(&mut rgb_img, &palette.colors, &device_colors)?;
+            if let Some(device_colors) = palette_manager.auto_device_colors(&palette) {
+                replace_device_colors(&cli, &mut rgb_img, &palette, device_colors)?;
             }
         }
     }
 
+    if cli.invert_output {
+        if cli.verbose {
+            println!("Inverting output image");
+        }
+        engine::invert_colors(&mut rgb_img);
+    }
+
+    if let Some(distance) = cli.simulate_view_distance {
+        let ppi = cli
+            .simulate_ppi
+            .or(device_spec.as_ref().map(|spec| spec.ppi))
+            .context("--simulate-view-distance needs --simulate-ppi or --device")?;
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        let path = output.with_file_name(format!("{}_simulated.png", stem));
+        if cli.verbose {
+            println!(
+                "Saving {} cm viewing simulation: {}",
+                distance,
+                path.display()
+            );
+        }
+        simulate::simulate_display(&rgb_img, distance, ppi)?
+            .save(&path)
+            .with_context(|| format!("Failed to save simulation: {}", path.display()))?;
+    }
+
+    if let Some(args) = &cli.generate_thumbnail {
+        let size: u32 = args[0]
+            .parse()
+            .with_context(|| format!("Invalid thumbnail size: {}", args[0]))?;
+        let path = std::path::Path::new(&args[1]);
+        if cli.verbose {
+            println!("Saving {}px thumbnail: {}", size, path.display());
+        }
+        scaling::generate_thumbnail(&rgb_img, size)
+            .save(path)
+            .with_context(|| format!("Failed to save thumbnail: {}", path.display()))?;
+    }
+
+    if let Some(controller) = cli.controller {
+        if cli.verbose {
+            println!(
+                "Writing {:?} frame buffer: {}",
+                controller,
+                output.display()
+            );
+        }
+
+        let data = epd_raw::encode(&rgb_img, &palette, controller.into())?;
+        std::fs::write(output, data)
+            .with_context(|| format!("Failed to write frame buffer: {}", output.display()))?;
+        return Ok(());
+    }
+
     if cli.verbose {
         println!("Saving output: {}", output.display());
     }
 
     // Save output
-    rgb_img
-        .save(output)
-        .with_context(|| format!("Failed to save image: {}", output.display()))?;
+    match cli.output_format {
+        OutputFormat::Auto => rgb_img
+            .save(output)
+            .with_context(|| format!("Failed to save image: {}", output.display()))?,
+        OutputFormat::IndexedPng => {
+            let colors = output_colors(&rgb_img, &palette);
+            indexed_png::save_indexed_png(&rgb_img, &colors, output)?
+        }
+        OutputFormat::Xbm => {
+            // Bits and planes follow the dithering palette, even where the
+            // image uses only some of its colors
+            let name = xbm_variable_name(output);
+            let source = if palette.len() <= 2 {
+                xbm::to_xbm(&rgb_img, &palette, &name)?
+            } else {
+                xbm::to_xbm_planes(&rgb_img, &palette, &name)?.join("\n")
+            };
+            std::fs::write(output, source)
+                .with_context(|| format!("Failed to write XBM: {}", output.display()))?;
+        }
+        OutputFormat::Bmp => bmp::save_bmp(&rgb_img, output)?,
+        OutputFormat::BmpIndexed => {
+            let colors = output_colors(&rgb_img, &palette);
+            std::fs::write(output, bmp::to_bmp_indexed(&rgb_img, &colors)?)
+                .with_context(|| format!("Failed to write BMP file: {}", output.display()))?;
+        }
+    }
+
+    if cli.embed_metadata {
+        let is_png = match cli.output_format {
+            OutputFormat::Auto => output
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("png")),
+            OutputFormat::IndexedPng => true,
+            _ => false,
+        };
+        if !is_png {
+            anyhow::bail!("--embed-metadata needs PNG output: {}", output.display());
+        }
+        indexed_png::embed_dither_metadata(output, &options)?;
+    }
 
     if cli.verbose {
         println!("Done!");
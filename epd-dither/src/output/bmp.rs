@@ -0,0 +1,188 @@
+//! Uncompressed BMP output for display drivers that read BMP files directly
+
+use crate::color::Palette;
+use anyhow::{Context, Result};
+use image::RgbImage;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Largest palette an 8-bit indexed BMP can hold
+pub const MAX_BMP_COLORS: usize = 256;
+
+/// Size of the BITMAPFILEHEADER plus the BITMAPINFOHEADER
+const HEADERS_LEN: usize = 14 + 40;
+/// Print resolution written to the header (72 DPI)
+const PIXELS_PER_METER: i32 = 2835;
+
+/// Encode an image as a 24-bit uncompressed BMP
+///
+/// Rows are stored bottom-up in BGR order and padded to a multiple of
+/// 4 bytes, the layout expected by the Waveshare and Arduino e-paper
+/// drivers.
+pub fn to_bmp(img: &RgbImage) -> Vec<u8> {
+    let (width, height) = img.dimensions();
+    let row_len = padded_row_len(width as usize * 3);
+
+    let mut data = bmp_headers(width, height, 24, 0, row_len);
+    for row in img.rows().rev() {
+        let start = data.len();
+        for pixel in row {
+            data.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+        }
+        data.resize(start + row_len, 0);
+    }
+
+    data
+}
+
+/// Encode an image as an 8-bit palette-indexed BMP using `palette` as its color table
+///
+/// Every pixel must be exactly one of the palette colors; duplicate palette
+/// entries resolve to the first occurrence.
+pub fn to_bmp_indexed(img: &RgbImage, palette: &Palette) -> Result<Vec<u8>> {
+    if palette.is_empty() || palette.len() > MAX_BMP_COLORS {
+        anyhow::bail!(
+            "Indexed BMP needs 1 to {} palette colors, got {}",
+            MAX_BMP_COLORS,
+            palette.len()
+        );
+    }
+
+    let mut indices = HashMap::new();
+    for (idx, color) in palette.colors.iter().enumerate() {
+        indices.entry(color.0).or_insert(idx as u8);
+    }
+
+    let (width, height) = img.dimensions();
+    let row_len = padded_row_len(width as usize);
+
+    let mut data = bmp_headers(width, height, 8, palette.len(), row_len);
+    for color in &palette.colors {
+        data.extend_from_slice(&[color.b(), color.g(), color.r(), 0]);
+    }
+    for (y, row) in img.rows().enumerate().rev() {
+        let start = data.len();
+        for (x, pixel) in row.enumerate() {
+            let idx = indices.get(&pixel.0).copied().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Pixel ({}, {}) color #{:02X}{:02X}{:02X} is not in palette '{}'",
+                    x,
+                    y,
+                    pixel[0],
+                    pixel[1],
+                    pixel[2],
+                    palette.name
+                )
+            })?;
+            data.push(idx);
+        }
+        data.resize(start + row_len, 0);
+    }
+
+    Ok(data)
+}
+
+/// Save an image as a 24-bit uncompressed BMP
+pub fn save_bmp(img: &RgbImage, path: &Path) -> Result<()> {
+    std::fs::write(path, to_bmp(img))
+        .with_context(|| format!("Failed to write BMP file: {}", path.display()))
+}
+
+fn padded_row_len(bytes: usize) -> usize {
+    bytes.next_multiple_of(4)
+}
+
+/// BITMAPFILEHEADER and BITMAPINFOHEADER for an uncompressed bottom-up image
+fn bmp_headers(width: u32, height: u32, bits: u16, colors: usize, row_len: usize) -> Vec<u8> {
+    let pixel_offset = HEADERS_LEN + colors * 4;
+    let image_len = row_len * height as usize;
+
+    let mut data = Vec::with_capacity(pixel_offset + image_len);
+    data.extend_from_slice(b"BM");
+    data.extend_from_slice(&((pixel_offset + image_len) as u32).to_le_bytes());
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(&(pixel_offset as u32).to_le_bytes());
+
+    data.extend_from_slice(&40u32.to_le_bytes());
+    data.extend_from_slice(&(width as i32).to_le_bytes());
+    data.extend_from_slice(&(height as i32).to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&bits.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes()); // BI_RGB, no compression
+    data.extend_from_slice(&(image_len as u32).to_le_bytes());
+    data.extend_from_slice(&PIXELS_PER_METER.to_le_bytes());
+    data.extend_from_slice(&PIXELS_PER_METER.to_le_bytes());
+    data.extend_from_slice(&(colors as u32).to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes());
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Rgb;
+
+    fn test_palette() -> Palette {
+        Palette::new(
+            "test",
+            vec![
+                Rgb::new(0, 0, 0),
+                Rgb::new(255, 255, 255),
+                Rgb::new(200, 30, 40),
+            ],
+        )
+    }
+
+    /// 5 pixels per row, so both encodings need row padding
+    fn test_image(palette: &Palette) -> RgbImage {
+        RgbImage::from_fn(5, 3, |x, y| {
+            image::Rgb(palette.colors[(x + 2 * y) as usize % 3].0)
+        })
+    }
+
+    fn decode(data: &[u8]) -> RgbImage {
+        image::load_from_memory_with_format(data, image::ImageFormat::Bmp)
+            .unwrap()
+            .to_rgb8()
+    }
+
+    #[test]
+    fn test_bmp_round_trip() {
+        let img = RgbImage::from_fn(5, 3, |x, y| image::Rgb([x as u8 * 50, y as u8 * 80, 7]));
+        let data = to_bmp(&img);
+
+        assert_eq!(data.len(), HEADERS_LEN + 16 * 3);
+        assert_eq!(decode(&data), img);
+    }
+
+    #[test]
+    fn test_bmp_indexed_round_trip() {
+        let palette = test_palette();
+        let img = test_image(&palette);
+        let data = to_bmp_indexed(&img, &palette).unwrap();
+
+        assert_eq!(data[28], 8);
+        assert_eq!(data.len(), HEADERS_LEN + 3 * 4 + 8 * 3);
+        assert_eq!(decode(&data), img);
+    }
+
+    #[test]
+    fn test_bmp_indexed_rejects_unknown_colors() {
+        let img = RgbImage::from_pixel(2, 2, image::Rgb([1, 2, 3]));
+        assert!(to_bmp_indexed(&img, &test_palette()).is_err());
+
+        let too_many = Palette::new("big", vec![Rgb::new(1, 2, 3); 257]);
+        assert!(to_bmp_indexed(&img, &too_many).is_err());
+    }
+
+    #[test]
+    fn test_save_bmp() {
+        let img = test_image(&test_palette());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.bmp");
+        save_bmp(&img, &path).unwrap();
+
+        assert_eq!(image::open(&path).unwrap().to_rgb8(), img);
+    }
+}
@@ -0,0 +1,235 @@
+//! Raw frame buffer encoding for common e-paper controllers
+
+use crate::color::{convert::rgb_to_luma, distance::find_closest_color, Palette, Rgb};
+use anyhow::Result;
+use image::RgbImage;
+
+/// Controller frame buffer layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControllerFormat {
+    /// Solomon SSD1681: 1 bit per pixel, MSB first, 1 = white
+    Ssd1681,
+    /// UltraChip UC8176: 4 bits per pixel palette indices, high nibble first
+    Uc8176,
+    /// ITE IT8951: 10-byte load-image header, then 4bpp or 8bpp gray levels
+    It8951,
+}
+
+/// IT8951 pixel format code for 4 bits per pixel
+const IT8951_FORMAT_4BPP: u16 = 2;
+/// IT8951 pixel format code for 8 bits per pixel
+const IT8951_FORMAT_8BPP: u16 = 3;
+
+/// Encode a dithered image as a controller frame buffer
+///
+/// Each pixel is mapped to its nearest palette color first, so images that
+/// were dithered with `palette` encode exactly.
+///
+/// * SSD1681 requires at most 2 colors; the lighter color is written as 1.
+///   Rows are padded to a byte boundary.
+/// * UC8176 requires at most 16 colors and writes palette indices. Rows are
+///   padded to a byte boundary.
+/// * IT8951 writes gray levels (4bpp for palettes of up to 16 colors,
+///   otherwise 8bpp) packed into little-endian 16-bit words, after a header
+///   holding the `LD_IMG_AREA` arguments: mode, x, y, width and height.
+///   Rows are padded to a 16-bit word boundary.
+pub fn encode(img: &RgbImage, palette: &Palette, controller: ControllerFormat) -> Result<Vec<u8>> {
+    if palette.is_empty() {
+        anyhow::bail!("Cannot encode with an empty palette");
+    }
+
+    match controller {
+        ControllerFormat::Ssd1681 => encode_ssd1681(img, palette),
+        ControllerFormat::Uc8176 => encode_uc8176(img, palette),
+        ControllerFormat::It8951 => encode_it8951(img, palette),
+    }
+}
+
+fn palette_index(pixel: &image::Rgb<u8>, palette: &[Rgb]) -> usize {
+    let (idx, _) = find_closest_color(&Rgb(pixel.0), palette).expect("Palette should not be empty");
+    idx
+}
+
+fn encode_ssd1681(img: &RgbImage, palette: &Palette) -> Result<Vec<u8>> {
+    if palette.len() > 2 {
+        anyhow::bail!(
+            "SSD1681 supports at most 2 colors, palette '{}' has {}",
+            palette.name,
+            palette.len()
+        );
+    }
+
+    let white = (0..palette.len())
+        .max_by_key(|&idx| rgb_to_luma(&palette.colors[idx]))
+        .expect("Palette should not be empty");
+
+    let row_bytes = (img.width() as usize).div_ceil(8);
+    let mut data = vec![0u8; row_bytes * img.height() as usize];
+
+    for (x, y, pixel) in img.enumerate_pixels() {
+        if palette_index(pixel, &palette.colors) == white {
+            data[y as usize * row_bytes + x as usize / 8] |= 0x80 >> (x % 8);
+        }
+    }
+
+    Ok(data)
+}
+
+fn encode_uc8176(img: &RgbImage, palette: &Palette) -> Result<Vec<u8>> {
+    if palette.len() > 16 {
+        anyhow::bail!(
+            "UC8176 supports at most 16 colors, palette '{}' has {}",
+            palette.name,
+            palette.len()
+        );
+    }
+
+    let row_bytes = (img.width() as usize).div_ceil(2);
+    let mut data = vec![0u8; row_bytes * img.height() as usize];
+
+    for (x, y, pixel) in img.enumerate_pixels() {
+        let idx = palette_index(pixel, &palette.colors) as u8;
+        let shift = if x % 2 == 0 { 4 } else { 0 };
+        data[y as usize * row_bytes + x as usize / 2] |= idx << shift;
+    }
+
+    Ok(data)
+}
+
+fn encode_it8951(img: &RgbImage, palette: &Palette) -> Result<Vec<u8>> {
+    let (width, height) = img.dimensions();
+    if width > u16::MAX as u32 || height > u16::MAX as u32 {
+        anyhow::bail!(
+            "IT8951 images are limited to 65535x65535, got {}x{}",
+            width,
+            height
+        );
+    }
+
+    let four_bpp = palette.len() <= 16;
+    let (format, pixels_per_word) = if four_bpp {
+        (IT8951_FORMAT_4BPP, 4)
+    } else {
+        (IT8951_FORMAT_8BPP, 2)
+    };
+
+    // LD_IMG_AREA arguments: endian type (0 = little) << 8 | format << 4 | rotate
+    let mut data = Vec::new();
+    for word in [format << 4, 0, 0, width as u16, height as u16] {
+        data.extend_from_slice(&word.to_le_bytes());
+    }
+
+    let row_bytes = (width as usize).div_ceil(pixels_per_word) * 2;
+    let header_len = data.len();
+    data.resize(header_len + row_bytes * height as usize, 0);
+
+    let levels: Vec<u8> = palette.colors.iter().map(rgb_to_luma).collect();
+    for (x, y, pixel) in img.enumerate_pixels() {
+        let gray = levels[palette_index(pixel, &palette.colors)];
+        let row = header_len + y as usize * row_bytes;
+        if four_bpp {
+            // Little-endian words put the first pixel in the low nibble
+            let shift = if x % 2 == 0 { 0 } else { 4 };
+            data[row + x as usize / 2] |= (gray >> 4) << shift;
+        } else {
+            data[row + x as usize] = gray;
+        }
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bw_palette() -> Palette {
+        Palette::new("bw", vec![Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)])
+    }
+
+    /// Alternating white/black pixels, starting with white
+    fn stripes(width: u32, height: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, _| {
+            if x % 2 == 0 {
+                image::Rgb([255, 255, 255])
+            } else {
+                image::Rgb([0, 0, 0])
+            }
+        })
+    }
+
+    #[test]
+    fn test_ssd1681_packing() {
+        let data = encode(&stripes(8, 2), &bw_palette(), ControllerFormat::Ssd1681).unwrap();
+        assert_eq!(data, vec![0xAA, 0xAA]);
+    }
+
+    #[test]
+    fn test_ssd1681_row_padding() {
+        // 10 pixels per row pad to 2 bytes, unused bits stay 0
+        let img = RgbImage::from_pixel(10, 1, image::Rgb([255, 255, 255]));
+        let data = encode(&img, &bw_palette(), ControllerFormat::Ssd1681).unwrap();
+        assert_eq!(data, vec![0xFF, 0xC0]);
+    }
+
+    #[test]
+    fn test_ssd1681_rejects_color_palette() {
+        let palette = Palette::new(
+            "bwr",
+            vec![
+                Rgb::new(0, 0, 0),
+                Rgb::new(255, 255, 255),
+                Rgb::new(255, 0, 0),
+            ],
+        );
+        assert!(encode(&stripes(8, 1), &palette, ControllerFormat::Ssd1681).is_err());
+    }
+
+    #[test]
+    fn test_uc8176_packing() {
+        let palette = Palette::new(
+            "four",
+            vec![
+                Rgb::new(0, 0, 0),
+                Rgb::new(255, 255, 255),
+                Rgb::new(255, 0, 0),
+                Rgb::new(255, 255, 0),
+            ],
+        );
+        let colors = [
+            [0, 0, 0],
+            [255, 255, 255],
+            [255, 0, 0],
+            [255, 255, 0],
+            [255, 0, 0],
+        ];
+        let img = RgbImage::from_fn(5, 1, |x, _| image::Rgb(colors[x as usize]));
+
+        let data = encode(&img, &palette, ControllerFormat::Uc8176).unwrap();
+        assert_eq!(data, vec![0x01, 0x23, 0x20]);
+    }
+
+    #[test]
+    fn test_it8951_header_and_4bpp_packing() {
+        let data = encode(&stripes(5, 1), &bw_palette(), ControllerFormat::It8951).unwrap();
+
+        // Header: mode 0x0020 (little endian, 4bpp, no rotation), x, y, w, h
+        assert_eq!(&data[..10], &[0x20, 0x00, 0, 0, 0, 0, 5, 0, 1, 0]);
+        // Pixels 0..4 are F,0,F,0,F; row padded to 8 pixels (two words)
+        assert_eq!(&data[10..], &[0x0F, 0x0F, 0x0F, 0x00]);
+    }
+
+    #[test]
+    fn test_it8951_8bpp_for_large_palettes() {
+        let colors: Vec<Rgb> = (0..32).map(|i| Rgb::new(i * 8, i * 8, i * 8)).collect();
+        let palette = Palette::new("gray32", colors);
+        let img = RgbImage::from_fn(3, 1, |x, _| {
+            let v = (x * 8) as u8;
+            image::Rgb([v, v, v])
+        });
+
+        let data = encode(&img, &palette, ControllerFormat::It8951).unwrap();
+        assert_eq!(&data[..2], &[0x30, 0x00]);
+        assert_eq!(&data[10..], &[0, 8, 16, 0]);
+    }
+}
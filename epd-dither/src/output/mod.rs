@@ -0,0 +1,6 @@
+//! Output encoders for sending dithered images to display hardware
+
+pub mod bmp;
+pub mod epd_raw;
+pub mod png;
+pub mod xbm;
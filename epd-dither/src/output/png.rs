@@ -0,0 +1,226 @@
+//! Palette-indexed PNG output and embedded dither metadata
+
+use crate::color::Palette;
+use crate::dither::DitherOptions;
+use anyhow::{Context, Result};
+use image::RgbImage;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+/// Largest palette an indexed PNG can hold
+pub const MAX_INDEXED_COLORS: usize = 256;
+
+/// Keyword of the `tEXt` chunk holding the dither options as JSON
+pub const METADATA_KEYWORD: &str = "EpdDither";
+
+/// Save an image as an indexed-color PNG using `palette` as its PLTE chunk
+///
+/// Pixels are stored as 8-bit palette indices, so viewers cannot
+/// re-quantize the image and the exact palette colors are preserved. Every
+/// pixel must be exactly one of the palette colors; duplicate palette
+/// entries resolve to the first occurrence.
+pub fn save_indexed_png(img: &RgbImage, palette: &Palette, path: &Path) -> Result<()> {
+    if palette.is_empty() || palette.len() > MAX_INDEXED_COLORS {
+        anyhow::bail!(
+            "Indexed PNG needs 1 to {} palette colors, got {}",
+            MAX_INDEXED_COLORS,
+            palette.len()
+        );
+    }
+
+    let mut indices = HashMap::new();
+    for (idx, color) in palette.colors.iter().enumerate() {
+        indices.entry(color.0).or_insert(idx as u8);
+    }
+
+    let data = img
+        .enumerate_pixels()
+        .map(|(x, y, pixel)| {
+            indices.get(&pixel.0).copied().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Pixel ({}, {}) color #{:02X}{:02X}{:02X} is not in palette '{}'",
+                    x,
+                    y,
+                    pixel[0],
+                    pixel[1],
+                    pixel[2],
+                    palette.name
+                )
+            })
+        })
+        .collect::<Result<Vec<u8>>>()?;
+
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create PNG file: {}", path.display()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), img.width(), img.height());
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(
+        palette
+            .colors
+            .iter()
+            .flat_map(|color| color.0)
+            .collect::<Vec<u8>>(),
+    );
+
+    let mut writer = encoder
+        .write_header()
+        .context("Failed to write PNG header")?;
+    writer
+        .write_image_data(&data)
+        .context("Failed to write PNG image data")?;
+    writer.finish().context("Failed to finish PNG file")?;
+
+    Ok(())
+}
+
+/// Add the dither options to an existing PNG file as a `tEXt` chunk
+///
+/// The file is rewritten with the same pixel data, color type and palette,
+/// plus a chunk with keyword [`METADATA_KEYWORD`] holding the options in
+/// the JSON format of [`DitherOptions::from_json_str`]. Read it back with
+/// [`read_dither_metadata`].
+pub fn embed_dither_metadata(path: &Path, options: &DitherOptions) -> Result<()> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open PNG: {}", path.display()))?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    decoder.set_transformations(png::Transformations::IDENTITY);
+    let mut reader = decoder
+        .read_info()
+        .with_context(|| format!("Failed to read PNG header: {}", path.display()))?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let frame = reader
+        .next_frame(&mut data)
+        .with_context(|| format!("Failed to read PNG image data: {}", path.display()))?;
+    data.truncate(frame.buffer_size());
+    let info = reader.info();
+
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create PNG file: {}", path.display()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), info.width, info.height);
+    encoder.set_color(info.color_type);
+    encoder.set_depth(info.bit_depth);
+    if let Some(palette) = &info.palette {
+        encoder.set_palette(palette.to_vec());
+    }
+    if let Some(trns) = &info.trns {
+        encoder.set_trns(trns.to_vec());
+    }
+    encoder
+        .add_text_chunk(
+            METADATA_KEYWORD.to_string(),
+            Value::from(options).to_string(),
+        )
+        .context("Failed to add PNG metadata chunk")?;
+
+    let mut writer = encoder
+        .write_header()
+        .context("Failed to write PNG header")?;
+    writer
+        .write_image_data(&data)
+        .context("Failed to write PNG image data")?;
+    writer.finish().context("Failed to finish PNG file")?;
+
+    Ok(())
+}
+
+/// Read the dither options JSON embedded by [`embed_dither_metadata`]
+pub fn read_dither_metadata(path: &Path) -> Result<Value> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open PNG: {}", path.display()))?;
+    let mut reader = png::Decoder::new(BufReader::new(file))
+        .read_info()
+        .with_context(|| format!("Failed to read PNG header: {}", path.display()))?;
+    // Text chunks may follow the image data
+    let mut data = vec![0; reader.output_buffer_size()];
+    reader
+        .next_frame(&mut data)
+        .with_context(|| format!("Failed to read PNG image data: {}", path.display()))?;
+    reader
+        .finish()
+        .with_context(|| format!("Failed to read PNG: {}", path.display()))?;
+
+    let chunk = reader
+        .info()
+        .uncompressed_latin1_text
+        .iter()
+        .find(|chunk| chunk.keyword == METADATA_KEYWORD)
+        .with_context(|| format!("No {} metadata in {}", METADATA_KEYWORD, path.display()))?;
+    serde_json::from_str(&chunk.text).with_context(|| {
+        format!(
+            "Invalid {} metadata in {}",
+            METADATA_KEYWORD,
+            path.display()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Rgb;
+
+    fn test_palette() -> Palette {
+        Palette::new(
+            "test",
+            vec![
+                Rgb::new(0, 0, 0),
+                Rgb::new(255, 255, 255),
+                Rgb::new(200, 30, 40),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_indexed_png_round_trip() {
+        let palette = test_palette();
+        let img = RgbImage::from_fn(7, 5, |x, y| {
+            image::Rgb(palette.colors[(x + y) as usize % 3].0)
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("indexed.png");
+        save_indexed_png(&img, &palette, &path).unwrap();
+
+        let loaded = image::open(&path).unwrap().to_rgb8();
+        assert_eq!(loaded, img);
+    }
+
+    #[test]
+    fn test_indexed_png_rejects_unknown_colors() {
+        let img = RgbImage::from_pixel(2, 2, image::Rgb([1, 2, 3]));
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("indexed.png");
+
+        assert!(save_indexed_png(&img, &test_palette(), &path).is_err());
+
+        let too_many = Palette::new("big", vec![Rgb::new(1, 2, 3); 257]);
+        assert!(save_indexed_png(&img, &too_many, &path).is_err());
+    }
+
+    #[test]
+    fn test_dither_metadata_round_trip() {
+        let palette = test_palette();
+        let img = RgbImage::from_fn(7, 5, |x, y| {
+            image::Rgb(palette.colors[(x * y) as usize % 3].0)
+        });
+        let options = DitherOptions {
+            palette: palette.clone(),
+            serpentine: true,
+            ..Default::default()
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("indexed.png");
+        save_indexed_png(&img, &palette, &path).unwrap();
+        assert!(read_dither_metadata(&path).is_err());
+
+        embed_dither_metadata(&path, &options).unwrap();
+        assert_eq!(image::open(&path).unwrap().to_rgb8(), img);
+        let embedded = DitherOptions::try_from(read_dither_metadata(&path).unwrap()).unwrap();
+        assert_eq!(embedded, options);
+    }
+}
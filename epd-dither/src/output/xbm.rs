@@ -0,0 +1,258 @@
+//! XBM (X Bitmap) output for embedding images in C code
+//!
+//! XBM files are C source: two `#define`s for the size and a byte array
+//! with rows packed LSB first, as used by Adafruit GFX, U8g2 and most
+//! e-paper driver libraries. Set bits are drawn in the foreground color.
+
+use crate::color::{convert::rgb_to_luma, distance::find_closest_color, Palette, Rgb};
+use anyhow::Result;
+use image::RgbImage;
+use std::fmt::Write;
+
+/// Bytes per line of the generated array, as written by X11 `bitmap`
+const BYTES_PER_LINE: usize = 12;
+
+/// Luminance below which the only color of a one-color palette is black
+const SINGLE_COLOR_BLACK_BELOW: u8 = 128;
+
+/// Convert an image to a monochrome XBM with black as the set bit
+///
+/// Each pixel is mapped to its nearest palette color. Colors darker than
+/// the median luminance of the palette become black, and so does a color
+/// at the median unless it is the brightest; the rest become white. A
+/// single-color palette is black if its luminance is below 128. Palettes
+/// of several colors that all have the same luminance can't be split and
+/// are rejected. `variable_name` prefixes the generated `_width`,
+/// `_height` and `_bits` identifiers and must be a valid C identifier.
+pub fn to_xbm(img: &RgbImage, palette: &Palette, variable_name: &str) -> Result<String> {
+    check_palette(palette)?;
+    check_identifier(variable_name)?;
+
+    let mut lumas: Vec<u8> = palette.colors.iter().map(rgb_to_luma).collect();
+    lumas.sort_unstable();
+    let (darkest, brightest) = (lumas[0], lumas[lumas.len() - 1]);
+
+    let black: Vec<bool> = if darkest == brightest {
+        if palette.len() > 1 {
+            anyhow::bail!(
+                "Cannot create a monochrome XBM: all {} palette colors have luminance {}",
+                palette.len(),
+                darkest
+            );
+        }
+        vec![darkest < SINGLE_COLOR_BLACK_BELOW]
+    } else {
+        let middle = lumas.len() / 2;
+        let median = if lumas.len().is_multiple_of(2) {
+            (lumas[middle - 1] as f64 + lumas[middle] as f64) / 2.0
+        } else {
+            lumas[middle] as f64
+        };
+        palette
+            .colors
+            .iter()
+            .map(|color| {
+                let luma = rgb_to_luma(color) as f64;
+                luma < median || (luma == median && luma < brightest as f64)
+            })
+            .collect()
+    };
+
+    Ok(format_xbm(
+        img,
+        variable_name,
+        |idx| black[idx],
+        &palette.colors,
+    ))
+}
+
+/// Convert an image to one XBM bitplane per palette color
+///
+/// Plane `i` has a bit set wherever the pixel's nearest palette color is
+/// `palette.colors[i]`, and is named `{variable_name}_{i}`. Multi-color
+/// panels typically take one such plane per ink.
+pub fn to_xbm_planes(
+    img: &RgbImage,
+    palette: &Palette,
+    variable_name: &str,
+) -> Result<Vec<String>> {
+    check_palette(palette)?;
+    check_identifier(variable_name)?;
+
+    Ok((0..palette.len())
+        .map(|plane| {
+            let name = format!("{}_{}", variable_name, plane);
+            format_xbm(img, &name, |idx| idx == plane, &palette.colors)
+        })
+        .collect())
+}
+
+fn check_palette(palette: &Palette) -> Result<()> {
+    if palette.is_empty() {
+        anyhow::bail!("Cannot create XBM with an empty palette");
+    }
+    Ok(())
+}
+
+fn check_identifier(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        anyhow::bail!("XBM variable name '{}' is not a valid C identifier", name);
+    }
+    Ok(())
+}
+
+/// Write the XBM source, setting the bits of pixels whose palette index is `is_set`
+fn format_xbm(
+    img: &RgbImage,
+    name: &str,
+    is_set: impl Fn(usize) -> bool,
+    palette: &[Rgb],
+) -> String {
+    let (width, height) = img.dimensions();
+    let row_bytes = (width as usize).div_ceil(8);
+    let mut bits = vec![0u8; row_bytes * height as usize];
+
+    for (x, y, pixel) in img.enumerate_pixels() {
+        let (idx, _) =
+            find_closest_color(&Rgb(pixel.0), palette).expect("Palette should not be empty");
+        if is_set(idx) {
+            bits[y as usize * row_bytes + x as usize / 8] |= 1 << (x % 8);
+        }
+    }
+
+    let mut xbm = String::new();
+    // Writing to a String cannot fail
+    let _ = writeln!(xbm, "#define {}_width {}", name, width);
+    let _ = writeln!(xbm, "#define {}_height {}", name, height);
+    let _ = write!(xbm, "static unsigned char {}_bits[] = {{\n   ", name);
+    for (i, byte) in bits.iter().enumerate() {
+        if i > 0 {
+            xbm.push_str(if i % BYTES_PER_LINE == 0 {
+                ",\n   "
+            } else {
+                ", "
+            });
+        }
+        let _ = write!(xbm, "0x{:02x}", byte);
+    }
+    xbm.push_str(" };\n");
+
+    xbm
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bw_palette() -> Palette {
+        Palette::new("bw", vec![Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)])
+    }
+
+    /// Checkerboard starting with a white pixel
+    fn checkerboard(width: u32, height: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| {
+            if (x + y) % 2 == 0 {
+                image::Rgb([255, 255, 255])
+            } else {
+                image::Rgb([0, 0, 0])
+            }
+        })
+    }
+
+    #[test]
+    fn test_to_xbm_reference() {
+        let xbm = to_xbm(&checkerboard(8, 2), &bw_palette(), "logo").unwrap();
+        assert_eq!(
+            xbm,
+            "#define logo_width 8\n\
+             #define logo_height 2\n\
+             static unsigned char logo_bits[] = {\n   0xaa, 0x55 };\n"
+        );
+    }
+
+    #[test]
+    fn test_to_xbm_row_padding_and_wrapping() {
+        // 10 pixels pad to 2 bytes per row; 7 rows give 14 bytes over two lines
+        let img = RgbImage::from_pixel(10, 7, image::Rgb([0, 0, 0]));
+        let xbm = to_xbm(&img, &bw_palette(), "_pad").unwrap();
+
+        let lines: Vec<&str> = xbm.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[3].matches("0xff").count(), 6);
+        assert_eq!(lines[3].matches("0x03").count(), 6);
+        assert_eq!(lines[4], "   0xff, 0x03 };");
+    }
+
+    #[test]
+    fn test_to_xbm_rejects_invalid_names() {
+        for name in ["", "9lives", "my-logo", "a b"] {
+            assert!(
+                to_xbm(&checkerboard(8, 1), &bw_palette(), name).is_err(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_to_xbm_planes() {
+        let palette = Palette::new(
+            "bwr",
+            vec![
+                Rgb::new(0, 0, 0),
+                Rgb::new(255, 255, 255),
+                Rgb::new(255, 0, 0),
+            ],
+        );
+        let colors = [[0, 0, 0], [255, 255, 255], [255, 0, 0], [255, 0, 0]];
+        let img = RgbImage::from_fn(4, 1, |x, _| image::Rgb(colors[x as usize]));
+
+        let planes = to_xbm_planes(&img, &palette, "img").unwrap();
+        assert_eq!(planes.len(), 3);
+        assert!(planes[0].contains("img_0_bits[] = {\n   0x01 };"));
+        assert!(planes[1].contains("img_1_bits[] = {\n   0x02 };"));
+        assert!(planes[2].contains("img_2_bits[] = {\n   0x0c };"));
+    }
+
+    #[test]
+    fn test_to_xbm_single_color_palettes() {
+        let img = RgbImage::from_pixel(8, 1, image::Rgb([255, 255, 255]));
+        let white = Palette::new("white", vec![Rgb::new(255, 255, 255)]);
+        assert!(to_xbm(&img, &white, "img")
+            .unwrap()
+            .contains("{\n   0x00 };"));
+
+        let black = Palette::new("black", vec![Rgb::new(0, 0, 0)]);
+        assert!(to_xbm(&img, &black, "img")
+            .unwrap()
+            .contains("{\n   0xff };"));
+    }
+
+    #[test]
+    fn test_to_xbm_splits_at_the_median() {
+        // Three colors: the median (gray) is black, the brightest is white
+        let palette = Palette::new(
+            "bgw",
+            vec![
+                Rgb::new(0, 0, 0),
+                Rgb::new(128, 128, 128),
+                Rgb::new(255, 255, 255),
+            ],
+        );
+        let colors = [[0, 0, 0], [128, 128, 128], [255, 255, 255]];
+        let img = RgbImage::from_fn(3, 1, |x, _| image::Rgb(colors[x as usize]));
+        assert!(to_xbm(&img, &palette, "img")
+            .unwrap()
+            .contains("{\n   0x03 };"));
+
+        // Two colors with the same luminance can't be told apart
+        let gray = Rgb::new(128, 128, 128);
+        let equal = Palette::new("equal", vec![gray, gray]);
+        assert!(to_xbm(&img, &equal, "img").is_err());
+    }
+}
@@ -1,7 +1,14 @@
 //! Image scaling and resizing for e-ink displays
 
-use anyhow::Result;
+use crate::color::Rgb;
+use crate::device::DeviceSpec;
+use crate::dither::{engine, DitherOptions};
+use anyhow::{Context, Result};
 use image::{imageops::FilterType, RgbImage};
+use std::path::Path;
+
+#[cfg(feature = "smart-crop")]
+pub mod saliency;
 
 /// Fit mode for resizing images to target resolution
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -14,17 +21,26 @@ pub enum FitMode {
     Fill,
     /// Contain image within bounds (like letterbox but without bars)
     Contain,
+    /// Crop like `Crop`, but centered on the most detailed region
+    #[cfg(feature = "smart-crop")]
+    SmartCrop,
 }
 
 impl FitMode {
     /// Parse fit mode from string
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Result<Self> {
         match s.to_lowercase().as_str() {
             "letterbox" => Ok(FitMode::Letterbox),
             "crop" => Ok(FitMode::Crop),
             "fill" | "stretch" => Ok(FitMode::Fill),
             "contain" => Ok(FitMode::Contain),
-            _ => anyhow::bail!("Invalid fit mode: {}. Valid options: letterbox, crop, fill, contain", s),
+            #[cfg(feature = "smart-crop")]
+            "smart-crop" => Ok(FitMode::SmartCrop),
+            _ => anyhow::bail!(
+                "Invalid fit mode: {}. Valid options: letterbox, crop, fill, contain",
+                s
+            ),
         }
     }
 }
@@ -46,6 +62,7 @@ pub enum ScalingFilter {
 
 impl ScalingFilter {
     /// Parse scaling filter from string
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Result<Self> {
         match s.to_lowercase().as_str() {
             "nearest" => Ok(ScalingFilter::Nearest),
@@ -69,6 +86,162 @@ impl ScalingFilter {
     }
 }
 
+/// Background fill for the bars added in letterbox mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LetterboxBackground {
+    /// A single solid color
+    Solid([u8; 3]),
+    /// Alternating squares of two colors with the given cell size in pixels
+    Checkerboard([u8; 3], [u8; 3], u32),
+    /// Horizontal linear gradient from the first color (left) to the second (right)
+    Gradient([u8; 3], [u8; 3]),
+}
+
+impl LetterboxBackground {
+    /// Parse a pattern name (solid, checker, gradient) with its colors
+    ///
+    /// `secondary` is ignored for solid backgrounds and `cell_size` is only
+    /// used for checkerboards.
+    pub fn from_pattern(
+        pattern: &str,
+        primary: [u8; 3],
+        secondary: [u8; 3],
+        cell_size: u32,
+    ) -> Result<Self> {
+        match pattern.to_lowercase().as_str() {
+            "solid" => Ok(LetterboxBackground::Solid(primary)),
+            "checker" | "checkerboard" => Ok(LetterboxBackground::Checkerboard(
+                primary,
+                secondary,
+                cell_size.max(1),
+            )),
+            "gradient" => Ok(LetterboxBackground::Gradient(primary, secondary)),
+            _ => anyhow::bail!(
+                "Invalid letterbox pattern: {}. Valid options: solid, checker, gradient",
+                pattern
+            ),
+        }
+    }
+
+    /// Render the background as a full canvas of the given size
+    pub fn render(&self, width: u32, height: u32) -> RgbImage {
+        match *self {
+            LetterboxBackground::Solid(color) => {
+                RgbImage::from_pixel(width, height, image::Rgb(color))
+            }
+            LetterboxBackground::Checkerboard(first, second, cell_size) => {
+                let cell_size = cell_size.max(1);
+                RgbImage::from_fn(width, height, |x, y| {
+                    if (x / cell_size + y / cell_size) % 2 == 0 {
+                        image::Rgb(first)
+                    } else {
+                        image::Rgb(second)
+                    }
+                })
+            }
+            LetterboxBackground::Gradient(start, end) => {
+                let span = width.saturating_sub(1).max(1) as f64;
+                RgbImage::from_fn(width, height, |x, _| {
+                    let t = x as f64 / span;
+                    let lerp = |a: u8, b: u8| (a as f64 * (1.0 - t) + b as f64 * t).round() as u8;
+                    image::Rgb([
+                        lerp(start[0], end[0]),
+                        lerp(start[1], end[1]),
+                        lerp(start[2], end[2]),
+                    ])
+                })
+            }
+        }
+    }
+}
+
+impl From<[u8; 3]> for LetterboxBackground {
+    fn from(color: [u8; 3]) -> Self {
+        LetterboxBackground::Solid(color)
+    }
+}
+
+/// Relative tolerance used by [`AspectRatio::matches_resolution`]
+const ASPECT_RATIO_TOLERANCE: f64 = 0.01;
+
+/// Width-to-height ratio of an image or display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AspectRatio {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl AspectRatio {
+    /// Aspect ratio of a resolution, as given (not reduced)
+    pub fn from_resolution(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    /// Reduce to lowest terms (e.g. 1920:1080 -> 16:9)
+    pub fn reduce(&self) -> Self {
+        let divisor = gcd(self.width, self.height);
+        if divisor == 0 {
+            return *self;
+        }
+
+        Self {
+            width: self.width / divisor,
+            height: self.height / divisor,
+        }
+    }
+
+    /// Ratio as a floating point value (width / height)
+    pub fn as_f64(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+
+    /// Check whether a resolution has this aspect ratio, within 1%
+    pub fn matches_resolution(&self, width: u32, height: u32) -> bool {
+        if height == 0 || self.height == 0 {
+            return false;
+        }
+
+        let ratio = self.as_f64();
+        let other = width as f64 / height as f64;
+        (other - ratio).abs() <= ratio * ASPECT_RATIO_TOLERANCE
+    }
+
+    /// Largest size with this aspect ratio that fits within the bounds
+    pub fn scale_to_fit(&self, max_width: u32, max_height: u32) -> (u32, u32) {
+        if self.width == 0 || self.height == 0 {
+            return (0, 0);
+        }
+
+        let (width, height) = (self.width as u64, self.height as u64);
+        if max_width as u64 * height <= max_height as u64 * width {
+            // Width is the limiting dimension
+            let scaled = (max_width as u64 * height + width / 2) / width;
+            (max_width, scaled.min(max_height as u64) as u32)
+        } else {
+            let scaled = (max_height as u64 * width + height / 2) / height;
+            (scaled.min(max_width as u64) as u32, max_height)
+        }
+    }
+}
+
+impl std::fmt::Display for AspectRatio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.width, self.height)
+    }
+}
+
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Reduced aspect ratio of a device's native resolution
+pub fn device_aspect_ratio(spec: &DeviceSpec) -> AspectRatio {
+    AspectRatio::from_resolution(spec.resolution.width, spec.resolution.height).reduce()
+}
+
 /// Calculate dimensions for resizing with given fit mode
 fn calculate_dimensions(
     src_width: u32,
@@ -128,42 +301,346 @@ fn calculate_dimensions(
 
             (scaled_width, scaled_height, offset_x, offset_y)
         }
+        #[cfg(feature = "smart-crop")]
+        FitMode::SmartCrop => {
+            // Same scale as Crop; the offset is chosen from the image content
+            calculate_dimensions(
+                src_width,
+                src_height,
+                target_width,
+                target_height,
+                FitMode::Crop,
+            )
+        }
+    }
+}
+
+/// Crop a `width`×`height` window centered as close to `center` as possible
+///
+/// The window is shifted to stay inside the image.
+pub fn crop_at(img: &RgbImage, center: (f64, f64), width: u32, height: u32) -> RgbImage {
+    let start = |center: f64, size: u32, limit: u32| {
+        let max = limit.saturating_sub(size) as f64;
+        (center - size as f64 / 2.0).round().clamp(0.0, max) as u32
+    };
+    let x = start(center.0, width, img.width());
+    let y = start(center.1, height, img.height());
+
+    image::imageops::crop_imm(img, x, y, width, height).to_image()
+}
+
+/// Dimensions of the image [`resize_image`] would produce
+///
+/// Equal to the target for every mode except `Contain`, which keeps the
+/// scaled size without padding.
+pub fn output_dimensions(
+    src_width: u32,
+    src_height: u32,
+    target_width: u32,
+    target_height: u32,
+    fit_mode: FitMode,
+) -> (u32, u32) {
+    if fit_mode == FitMode::Contain {
+        let (width, height, _, _) =
+            calculate_dimensions(src_width, src_height, target_width, target_height, fit_mode);
+        (width, height)
+    } else {
+        (target_width, target_height)
+    }
+}
+
+/// Rotation that makes an image fit a display with less letterboxing
+///
+/// Returns `Some(90)` for a portrait image on a landscape display,
+/// `Some(270)` for the opposite case (both clockwise, see
+/// [`rotate_image`]) and `None` when the image already covers at least as
+/// much of the display as it would rotated, including square images and
+/// zero sizes.
+pub fn detect_rotation_from_dimensions(
+    img_w: u32,
+    img_h: u32,
+    disp_w: u32,
+    disp_h: u32,
+) -> Option<u16> {
+    if img_w == 0 || img_h == 0 || disp_w == 0 || disp_h == 0 {
+        return None;
+    }
+
+    // The covered area grows with the scale that fits the image inside
+    let fit_scale = |w: u32, h: u32| (disp_w as f64 / w as f64).min(disp_h as f64 / h as f64);
+    if fit_scale(img_h, img_w) <= fit_scale(img_w, img_h) {
+        None
+    } else if img_h > img_w {
+        Some(90)
+    } else {
+        Some(270)
     }
 }
 
 /// Resize image to target dimensions with specified fit mode and filter
+///
+/// With `auto_rotate`, the image is first rotated if that matches the
+/// target's orientation better (see [`detect_rotation_from_dimensions`]).
 pub fn resize_image(
     img: &RgbImage,
     target_width: u32,
     target_height: u32,
     fit_mode: FitMode,
     filter: ScalingFilter,
-    background_color: [u8; 3],
+    background: LetterboxBackground,
+    auto_rotate: bool,
 ) -> Result<RgbImage> {
-    let (scaled_width, scaled_height, offset_x, offset_y) =
-        calculate_dimensions(img.width(), img.height(), target_width, target_height, fit_mode);
+    let rotated;
+    let img = match detect_rotation_from_dimensions(
+        img.width(),
+        img.height(),
+        target_width,
+        target_height,
+    ) {
+        Some(degrees) if auto_rotate => {
+            rotated = rotate_image(img, degrees)?;
+            &rotated
+        }
+        _ => img,
+    };
+
+    let (scaled_width, scaled_height, offset_x, offset_y) = calculate_dimensions(
+        img.width(),
+        img.height(),
+        target_width,
+        target_height,
+        fit_mode,
+    );
 
     // Resize the image
-    let resized = image::imageops::resize(img, scaled_width, scaled_height, filter.to_filter_type());
+    let resized =
+        image::imageops::resize(img, scaled_width, scaled_height, filter.to_filter_type());
 
-    if fit_mode == FitMode::Letterbox {
-        // Create canvas with background color
-        let mut canvas = RgbImage::from_pixel(target_width, target_height, image::Rgb(background_color));
+    match fit_mode {
+        FitMode::Letterbox => {
+            // Create canvas with the letterbox background
+            let mut canvas = background.render(target_width, target_height);
 
-        // Copy resized image onto canvas
-        image::imageops::overlay(&mut canvas, &resized, offset_x as i64, offset_y as i64);
+            // Copy resized image onto canvas
+            image::imageops::overlay(&mut canvas, &resized, offset_x as i64, offset_y as i64);
 
-        Ok(canvas)
-    } else if fit_mode == FitMode::Crop {
-        // Crop from center
-        let crop_x = (-offset_x) as u32;
-        let crop_y = (-offset_y) as u32;
+            Ok(canvas)
+        }
+        FitMode::Crop => {
+            // Crop from center
+            let crop_x = (-offset_x) as u32;
+            let crop_y = (-offset_y) as u32;
 
-        Ok(image::imageops::crop_imm(&resized, crop_x, crop_y, target_width, target_height).to_image())
-    } else {
+            Ok(
+                image::imageops::crop_imm(&resized, crop_x, crop_y, target_width, target_height)
+                    .to_image(),
+            )
+        }
+        #[cfg(feature = "smart-crop")]
+        FitMode::SmartCrop => {
+            let center = saliency::compute_gradient_saliency_center(&resized);
+            Ok(crop_at(&resized, center, target_width, target_height))
+        }
         // Fill or Contain - already at target size
-        Ok(resized)
+        FitMode::Fill | FitMode::Contain => Ok(resized),
+    }
+}
+
+/// Resize an image and dither it, avoiding intermediate copies
+///
+/// Produces the same image as [`resize_image`] with a solid `background`
+/// followed by dithering; auto-rotation is not applied. Crop mode crops
+/// the resized image in place and letterbox mode writes it straight into
+/// the canvas, so no intermediate copy is made. Algorithms that work pixel
+/// by pixel (see [`engine::pixel_ditherer`]) then dither the prepared
+/// output in place; error diffusion and the other whole-image algorithms
+/// go through [`engine::dither_image`].
+pub fn resize_and_dither_pipeline(
+    img: &RgbImage,
+    target_width: u32,
+    target_height: u32,
+    fit_mode: FitMode,
+    filter: ScalingFilter,
+    background: Rgb,
+    options: &DitherOptions,
+) -> Result<RgbImage> {
+    let ditherer = engine::pixel_ditherer(options)?;
+    let (scaled_width, scaled_height, offset_x, offset_y) = calculate_dimensions(
+        img.width(),
+        img.height(),
+        target_width,
+        target_height,
+        fit_mode,
+    );
+    let filter = filter.to_filter_type();
+
+    let mut output = match fit_mode {
+        FitMode::Letterbox => {
+            let resized = image::imageops::resize(img, scaled_width, scaled_height, filter);
+            let (offset_x, offset_y) = (offset_x as u32, offset_y as u32);
+
+            RgbImage::from_fn(target_width, target_height, |x, y| {
+                let pixel = if x >= offset_x && y >= offset_y {
+                    resized.get_pixel_checked(x - offset_x, y - offset_y)
+                } else {
+                    None
+                };
+                pixel.map_or(image::Rgb(background.0), |pixel| *pixel)
+            })
+        }
+        FitMode::Crop => {
+            // Move the crop window to the front of the resized buffer
+            // instead of copying it out
+            let resized = image::imageops::resize(img, scaled_width, scaled_height, filter);
+            let (crop_x, crop_y) = ((-offset_x) as usize, (-offset_y) as usize);
+            let source_row = scaled_width as usize * 3;
+            let target_row = target_width as usize * 3;
+
+            let mut buffer = resized.into_raw();
+            for y in 0..target_height as usize {
+                let start = (y + crop_y) * source_row + crop_x * 3;
+                buffer.copy_within(start..start + target_row, y * target_row);
+            }
+            buffer.truncate(target_row * target_height as usize);
+            RgbImage::from_raw(target_width, target_height, buffer)
+                .expect("Cropped buffer matches the target size")
+        }
+        FitMode::Fill | FitMode::Contain => {
+            image::imageops::resize(img, scaled_width, scaled_height, filter)
+        }
+        #[cfg(feature = "smart-crop")]
+        FitMode::SmartCrop => {
+            let resized = image::imageops::resize(img, scaled_width, scaled_height, filter);
+            let center = saliency::compute_gradient_saliency_center(&resized);
+            crop_at(&resized, center, target_width, target_height)
+        }
+    };
+
+    match ditherer {
+        Some(dither_pixel) => {
+            engine::prepare_input(&mut output, options);
+            for (x, y, pixel) in output.enumerate_pixels_mut() {
+                pixel.0 = dither_pixel(x, y, pixel.0);
+            }
+        }
+        None => engine::dither_image(&mut output, options)?,
     }
+
+    Ok(output)
+}
+
+/// Scale an image down for a quick preview
+///
+/// The longer side becomes `max_side` and the aspect ratio is kept; uses
+/// the fast [`ScalingFilter::Triangle`].
+pub fn generate_thumbnail(img: &RgbImage, max_side: u32) -> RgbImage {
+    generate_thumbnail_with_filter(img, max_side, ScalingFilter::Triangle)
+}
+
+/// [`generate_thumbnail`] with a chosen filter
+///
+/// Neither side of the result is smaller than 1 pixel.
+pub fn generate_thumbnail_with_filter(
+    img: &RgbImage,
+    max_side: u32,
+    filter: ScalingFilter,
+) -> RgbImage {
+    let (width, height) = img.dimensions();
+    let longest = width.max(height).max(1) as f64;
+    let scale = max_side.max(1) as f64 / longest;
+    let side = |size: u32| ((size as f64 * scale).round() as u32).max(1);
+
+    image::imageops::resize(img, side(width), side(height), filter.to_filter_type())
+}
+
+/// Dimensions rounded up to the next multiple of `multiple`
+///
+/// A `multiple` of 0 or 1 leaves the dimensions unchanged.
+pub fn padded_dimensions(width: u32, height: u32, multiple: u32) -> (u32, u32) {
+    if multiple <= 1 {
+        return (width, height);
+    }
+    (
+        width.div_ceil(multiple) * multiple,
+        height.div_ceil(multiple) * multiple,
+    )
+}
+
+/// Pad an image on the right and bottom to a multiple of `multiple` pixels
+///
+/// Some controllers need buffers aligned to 8 or 16 pixels. The original
+/// image stays in the top-left corner and new pixels are `background`.
+pub fn pad_to_multiple(img: &RgbImage, multiple: u32, background: Rgb) -> RgbImage {
+    let (width, height) = padded_dimensions(img.width(), img.height(), multiple);
+    if (width, height) == img.dimensions() {
+        return img.clone();
+    }
+
+    let mut padded = RgbImage::from_pixel(width, height, image::Rgb(background.0));
+    image::imageops::replace(&mut padded, img, 0, 0);
+    padded
+}
+
+/// Row width in pixels after padding to whole bytes at 1 bit per pixel
+pub fn required_width_for_1bpp(width: u32) -> u32 {
+    width.div_ceil(8) * 8
+}
+
+/// Rotate an image clockwise by 0, 90, 180 or 270 degrees
+pub fn rotate_image(img: &RgbImage, degrees: u16) -> Result<RgbImage> {
+    match degrees {
+        0 => Ok(img.clone()),
+        90 => Ok(image::imageops::rotate90(img)),
+        180 => Ok(image::imageops::rotate180(img)),
+        270 => Ok(image::imageops::rotate270(img)),
+        _ => anyhow::bail!(
+            "Invalid rotation: {}. Valid options: 0, 90, 180, 270",
+            degrees
+        ),
+    }
+}
+
+/// Apply an EXIF orientation value (1-8) to an image
+///
+/// Unknown orientation values leave the image unchanged.
+pub fn apply_exif_orientation(img: &RgbImage, orientation: u32) -> RgbImage {
+    use image::imageops::{flip_horizontal, flip_vertical, rotate180, rotate270, rotate90};
+
+    match orientation {
+        2 => flip_horizontal(img),
+        3 => rotate180(img),
+        4 => flip_vertical(img),
+        5 => flip_horizontal(&rotate90(img)),
+        6 => rotate90(img),
+        7 => flip_horizontal(&rotate270(img)),
+        8 => rotate270(img),
+        _ => img.clone(),
+    }
+}
+
+/// Load an image and rotate/flip it according to its EXIF `Orientation` tag
+///
+/// Images without EXIF data (or without an orientation tag) are returned
+/// as stored.
+pub fn auto_rotate_from_exif(path: &Path) -> Result<RgbImage> {
+    let img = image::open(path)
+        .with_context(|| format!("Failed to open image: {}", path.display()))?
+        .to_rgb8();
+
+    let orientation = read_exif_orientation(path).unwrap_or(1);
+
+    Ok(apply_exif_orientation(&img, orientation))
+}
+
+/// Read the EXIF orientation tag of an image file, if present
+fn read_exif_orientation(path: &Path) -> Option<u32> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut reader = std::io::BufReader::new(file);
+    let exif = exif::Reader::new().read_from_container(&mut reader).ok()?;
+
+    exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?
+        .value
+        .get_uint(0)
 }
 
 #[cfg(test)]
@@ -204,9 +681,9 @@ mod tests {
 
         // Tall source into wide target - should letterbox left/right
         let (w, h, ox, oy) = calculate_dimensions(900, 1600, 800, 600, FitMode::Letterbox);
-        assert_eq!(w, 337); // 600 * (900/1600)
+        assert_eq!(w, 338); // 600 * (900/1600) = 337.5, rounded
         assert_eq!(h, 600);
-        assert_eq!(ox, 231); // (800 - 337) / 2
+        assert_eq!(ox, 231); // (800 - 338) / 2
         assert_eq!(oy, 0);
     }
 
@@ -228,11 +705,411 @@ mod tests {
             600,
             FitMode::Fill,
             ScalingFilter::Nearest,
-            [255, 255, 255],
+            LetterboxBackground::Solid([255, 255, 255]),
+            false,
         )
         .unwrap();
 
         assert_eq!(resized.width(), 800);
         assert_eq!(resized.height(), 600);
     }
+
+    #[test]
+    fn test_rotate_image_90() {
+        let mut img = RgbImage::new(10, 20);
+        for (x, y, pixel) in img.enumerate_pixels_mut() {
+            *pixel = image::Rgb([x as u8, y as u8, 0]);
+        }
+
+        let rotated = rotate_image(&img, 90).unwrap();
+        assert_eq!(rotated.dimensions(), (20, 10));
+
+        // Clockwise: source (x, y) ends up at (height - 1 - y, x)
+        for (x, y, pixel) in img.enumerate_pixels() {
+            assert_eq!(rotated.get_pixel(19 - y, x), pixel);
+        }
+    }
+
+    #[test]
+    fn test_rotate_image_invalid_degrees() {
+        let img = RgbImage::new(4, 4);
+        assert_eq!(rotate_image(&img, 180).unwrap().dimensions(), (4, 4));
+        assert_eq!(rotate_image(&img, 270).unwrap().dimensions(), (4, 4));
+        assert!(rotate_image(&img, 45).is_err());
+    }
+
+    #[test]
+    fn test_apply_exif_orientation() {
+        let mut img = RgbImage::new(3, 2);
+        img.put_pixel(0, 0, image::Rgb([255, 0, 0]));
+
+        // Orientation 6 means the camera was rotated; rotate 90° clockwise
+        let rotated = apply_exif_orientation(&img, 6);
+        assert_eq!(rotated.dimensions(), (2, 3));
+        assert_eq!(rotated.get_pixel(1, 0), &image::Rgb([255, 0, 0]));
+
+        // Orientation 2 is a horizontal mirror
+        let mirrored = apply_exif_orientation(&img, 2);
+        assert_eq!(mirrored.get_pixel(2, 0), &image::Rgb([255, 0, 0]));
+
+        // Orientation 1 (normal) is a no-op
+        assert_eq!(apply_exif_orientation(&img, 1), img);
+    }
+
+    #[test]
+    fn test_letterbox_checkerboard() {
+        let black = [0, 0, 0];
+        let white = [255, 255, 255];
+        let canvas = LetterboxBackground::Checkerboard(black, white, 4).render(16, 8);
+
+        assert_eq!(canvas.get_pixel(0, 0).0, black);
+        assert_eq!(canvas.get_pixel(3, 3).0, black);
+        // Crossing a cell boundary horizontally or vertically flips the color
+        assert_eq!(canvas.get_pixel(4, 0).0, white);
+        assert_eq!(canvas.get_pixel(0, 4).0, white);
+        assert_eq!(canvas.get_pixel(4, 4).0, black);
+        assert_eq!(canvas.get_pixel(15, 7).0, black);
+    }
+
+    #[test]
+    fn test_letterbox_gradient() {
+        let start = [0, 0, 0];
+        let end = [255, 128, 0];
+        let canvas = LetterboxBackground::Gradient(start, end).render(32, 4);
+
+        assert_eq!(canvas.get_pixel(0, 0).0, start);
+        assert_eq!(canvas.get_pixel(31, 3).0, end);
+
+        // Each column is at least as bright as the previous one
+        for x in 1..32 {
+            let previous = canvas.get_pixel(x - 1, 0);
+            let current = canvas.get_pixel(x, 0);
+            assert!(current[0] >= previous[0] && current[1] >= previous[1]);
+        }
+    }
+
+    #[test]
+    fn test_resize_letterbox_uses_background() {
+        let img = RgbImage::from_pixel(100, 50, image::Rgb([255, 0, 0]));
+        let background = LetterboxBackground::Checkerboard([0, 0, 0], [0, 0, 255], 2);
+        let resized = resize_image(
+            &img,
+            100,
+            100,
+            FitMode::Letterbox,
+            ScalingFilter::Nearest,
+            background,
+            false,
+        )
+        .unwrap();
+
+        // Bars above the image use the checkerboard, the image stays centered
+        assert_eq!(resized.get_pixel(0, 0).0, [0, 0, 0]);
+        assert_eq!(resized.get_pixel(2, 0).0, [0, 0, 255]);
+        assert_eq!(resized.get_pixel(50, 50).0, [255, 0, 0]);
+    }
+
+    #[test]
+    fn test_output_dimensions_match_resize() {
+        let img = RgbImage::new(300, 200);
+        for fit_mode in [
+            FitMode::Letterbox,
+            FitMode::Crop,
+            FitMode::Fill,
+            FitMode::Contain,
+        ] {
+            let resized = resize_image(
+                &img,
+                100,
+                100,
+                fit_mode,
+                ScalingFilter::Nearest,
+                LetterboxBackground::from([255, 255, 255]),
+                false,
+            )
+            .unwrap();
+            assert_eq!(
+                output_dimensions(300, 200, 100, 100, fit_mode),
+                resized.dimensions(),
+                "{:?}",
+                fit_mode
+            );
+        }
+    }
+
+    #[test]
+    fn test_aspect_ratio_reduce() {
+        assert_eq!(
+            AspectRatio::from_resolution(1920, 1080).reduce(),
+            AspectRatio {
+                width: 16,
+                height: 9
+            }
+        );
+        assert_eq!(
+            AspectRatio::from_resolution(800, 480).reduce().to_string(),
+            "5:3"
+        );
+        assert_eq!(
+            AspectRatio::from_resolution(0, 0).reduce(),
+            AspectRatio::from_resolution(0, 0)
+        );
+    }
+
+    #[test]
+    fn test_aspect_ratio_matching_and_fit() {
+        let ratio = AspectRatio {
+            width: 16,
+            height: 9,
+        };
+        assert!(ratio.matches_resolution(1920, 1080));
+        assert!(ratio.matches_resolution(1366, 768));
+        assert!(!ratio.matches_resolution(1600, 1200));
+
+        assert_eq!(ratio.scale_to_fit(800, 800), (800, 450));
+        assert_eq!(ratio.scale_to_fit(1600, 90), (160, 90));
+    }
+
+    #[test]
+    fn test_device_aspect_ratio() {
+        let manager = crate::device::DeviceManager::new().unwrap();
+        let spec = manager.get_device("spectra6-7.3").unwrap();
+        assert_eq!(
+            device_aspect_ratio(&spec),
+            AspectRatio {
+                width: 5,
+                height: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_pad_to_multiple() {
+        let img = RgbImage::from_pixel(100, 100, image::Rgb([10, 20, 30]));
+        let padded = pad_to_multiple(&img, 16, Rgb::new(255, 255, 255));
+
+        assert_eq!(padded.dimensions(), (112, 112));
+        assert_eq!(padded.get_pixel(0, 0).0, [10, 20, 30]);
+        assert_eq!(padded.get_pixel(99, 99).0, [10, 20, 30]);
+        assert_eq!(padded.get_pixel(100, 50).0, [255, 255, 255]);
+        assert_eq!(padded.get_pixel(50, 111).0, [255, 255, 255]);
+
+        assert_eq!(pad_to_multiple(&padded, 16, Rgb::new(0, 0, 0)), padded);
+        assert_eq!(padded_dimensions(100, 100, 0), (100, 100));
+    }
+
+    #[test]
+    fn test_required_width_for_1bpp() {
+        assert_eq!(required_width_for_1bpp(0), 0);
+        assert_eq!(required_width_for_1bpp(1), 8);
+        assert_eq!(required_width_for_1bpp(800), 800);
+        assert_eq!(required_width_for_1bpp(801), 808);
+    }
+
+    #[test]
+    fn test_resize_and_dither_pipeline() {
+        use crate::dither::{DitheringAlgorithm, ErrorDiffusionKernel};
+
+        let img = RgbImage::from_fn(120, 80, |x, y| image::Rgb([x as u8 * 2, y as u8 * 3, 90]));
+        let bw = [Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)];
+
+        for algorithm in [
+            DitheringAlgorithm::Ordered {
+                width: 4,
+                height: 4,
+            },
+            DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::FloydSteinberg),
+        ] {
+            let options = DitherOptions {
+                algorithm,
+                palette: crate::Palette::new("bw", bw.to_vec()),
+                ..Default::default()
+            };
+            for fit_mode in [FitMode::Letterbox, FitMode::Crop, FitMode::Fill] {
+                let result = resize_and_dither_pipeline(
+                    &img,
+                    50,
+                    50,
+                    fit_mode,
+                    ScalingFilter::Triangle,
+                    Rgb::new(255, 255, 255),
+                    &options,
+                )
+                .unwrap();
+
+                assert_eq!(result.dimensions(), (50, 50));
+                assert!(result.pixels().all(|p| bw.contains(&Rgb(p.0))));
+            }
+        }
+    }
+
+    #[test]
+    fn test_pipeline_matches_resize_then_dither() {
+        use crate::dither::{DitheringAlgorithm, ErrorDiffusionKernel};
+
+        let img = RgbImage::from_fn(120, 80, |x, y| image::Rgb([x as u8 * 2, y as u8 * 3, 90]));
+        let palette = crate::Palette::new(
+            "bwr",
+            vec![
+                Rgb::new(0, 0, 0),
+                Rgb::new(255, 255, 255),
+                Rgb::new(255, 0, 0),
+            ],
+        );
+        let background = [200, 200, 200];
+        let fit_modes = [
+            FitMode::Letterbox,
+            FitMode::Crop,
+            FitMode::Fill,
+            FitMode::Contain,
+            #[cfg(feature = "smart-crop")]
+            FitMode::SmartCrop,
+        ];
+
+        let algorithms = [
+            DitheringAlgorithm::Ordered {
+                width: 4,
+                height: 4,
+            },
+            DitheringAlgorithm::QuantizationOnly,
+            DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::FloydSteinberg),
+        ];
+        for (algorithm, pre_quantize_bins) in algorithms
+            .iter()
+            .flat_map(|algorithm| [(algorithm, None), (algorithm, Some(2))])
+        {
+            let options = DitherOptions {
+                algorithm: algorithm.clone(),
+                palette: palette.clone(),
+                pre_quantize_bins,
+                ..Default::default()
+            };
+            for fit_mode in fit_modes {
+                for (width, height) in [(50, 50), (70, 30), (31, 64)] {
+                    let mut expected = resize_image(
+                        &img,
+                        width,
+                        height,
+                        fit_mode,
+                        ScalingFilter::Triangle,
+                        LetterboxBackground::Solid(background),
+                        false,
+                    )
+                    .unwrap();
+                    engine::dither_image(&mut expected, &options).unwrap();
+
+                    let fused = resize_and_dither_pipeline(
+                        &img,
+                        width,
+                        height,
+                        fit_mode,
+                        ScalingFilter::Triangle,
+                        Rgb(background),
+                        &options,
+                    )
+                    .unwrap();
+
+                    assert!(
+                        fused == expected,
+                        "{} (bins {:?}) {:?} {}x{} differs from resize_image",
+                        options.algorithm.name(),
+                        pre_quantize_bins,
+                        fit_mode,
+                        width,
+                        height
+                    );
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "smart-crop")]
+    #[test]
+    fn test_smart_crop_keeps_offset_subject() {
+        // Dark circle near the right edge of a wide white image
+        let img = RgbImage::from_fn(400, 200, |x, y| {
+            let (dx, dy) = (x as f64 - 340.0, y as f64 - 100.0);
+            if dx * dx + dy * dy < 30.0 * 30.0 {
+                image::Rgb([0, 0, 0])
+            } else {
+                image::Rgb([255, 255, 255])
+            }
+        });
+        let dark_pixels = |fit_mode| {
+            let white = LetterboxBackground::Solid([255, 255, 255]);
+            let resized = resize_image(
+                &img,
+                100,
+                100,
+                fit_mode,
+                ScalingFilter::Triangle,
+                white,
+                false,
+            )
+            .unwrap();
+            assert_eq!(resized.dimensions(), (100, 100));
+            resized.pixels().filter(|p| p[0] < 128).count()
+        };
+
+        assert_eq!(dark_pixels(FitMode::Crop), 0);
+        // The whole circle (radius 15 after scaling) stays in frame
+        let kept = dark_pixels(FitMode::SmartCrop);
+        assert!(kept > 600, "Only {} dark pixels kept", kept);
+        assert_eq!(FitMode::from_str("smart-crop").unwrap(), FitMode::SmartCrop);
+    }
+
+    #[test]
+    fn test_crop_at_clamps_to_image() {
+        let img = RgbImage::from_fn(10, 4, |x, _| image::Rgb([x as u8, 0, 0]));
+
+        assert_eq!(crop_at(&img, (5.0, 2.0), 4, 4).get_pixel(0, 0)[0], 3);
+        assert_eq!(crop_at(&img, (100.0, 2.0), 4, 4).get_pixel(0, 0)[0], 6);
+        assert_eq!(crop_at(&img, (-3.0, 2.0), 4, 4).get_pixel(0, 0)[0], 0);
+    }
+
+    #[test]
+    fn test_generate_thumbnail() {
+        let img = RgbImage::from_pixel(1600, 900, image::Rgb([10, 20, 30]));
+        let thumbnail = generate_thumbnail(&img, 200);
+        assert_eq!(thumbnail.width(), 200);
+        assert!((112..=113).contains(&thumbnail.height()));
+
+        let portrait = RgbImage::new(30, 400);
+        let thumbnail = generate_thumbnail_with_filter(&portrait, 200, ScalingFilter::Nearest);
+        assert_eq!(thumbnail.dimensions(), (15, 200));
+        assert_eq!(generate_thumbnail(&portrait, 1).dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn test_detect_rotation_from_dimensions() {
+        assert_eq!(
+            detect_rotation_from_dimensions(480, 800, 800, 480),
+            Some(90)
+        );
+        assert_eq!(
+            detect_rotation_from_dimensions(800, 480, 480, 800),
+            Some(270)
+        );
+        assert_eq!(detect_rotation_from_dimensions(800, 480, 800, 480), None);
+        assert_eq!(detect_rotation_from_dimensions(500, 500, 800, 480), None);
+        assert_eq!(detect_rotation_from_dimensions(0, 800, 800, 480), None);
+
+        // A portrait image fills a landscape display once rotated
+        let img = RgbImage::from_fn(48, 80, |_, y| image::Rgb([if y < 40 { 0 } else { 255 }; 3]));
+        let background = LetterboxBackground::Solid([255, 0, 0]);
+        let resized = resize_image(
+            &img,
+            80,
+            48,
+            FitMode::Letterbox,
+            ScalingFilter::Nearest,
+            background,
+            true,
+        )
+        .unwrap();
+        assert_eq!(resized.dimensions(), (80, 48));
+        assert!(resized.pixels().all(|p| p.0 != [255, 0, 0]));
+        // Rotated clockwise, the top (black) half ends up on the right
+        assert_eq!(resized.get_pixel(79, 24).0, [0; 3]);
+    }
 }
@@ -0,0 +1,81 @@
+//! Gradient-based saliency for choosing a crop anchor
+
+use crate::color::{convert::rgb_to_luma, Rgb};
+use image::RgbImage;
+
+/// Find the center of mass of an image's high-gradient regions
+///
+/// Computes the Sobel gradient magnitude of the luma channel and averages
+/// the positions of pixels whose magnitude is above the image mean,
+/// weighted by magnitude. Edges and texture mark the subject in most
+/// photos, while skies and flat backgrounds contribute nothing. Returns
+/// the image center for images without any gradient.
+pub fn compute_gradient_saliency_center(img: &RgbImage) -> (f64, f64) {
+    let (width, height) = img.dimensions();
+    let center = (width as f64 / 2.0, height as f64 / 2.0);
+    if width < 3 || height < 3 {
+        return center;
+    }
+
+    let luma: Vec<f64> = img
+        .pixels()
+        .map(|p| rgb_to_luma(&Rgb(p.0)) as f64)
+        .collect();
+    let at = |x: u32, y: u32| luma[(y * width + x) as usize];
+
+    let mut magnitudes = Vec::with_capacity(((width - 2) * (height - 2)) as usize);
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x - 1, y)
+                - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x, y - 1)
+                - at(x + 1, y - 1);
+            magnitudes.push((x, y, (gx * gx + gy * gy).sqrt()));
+        }
+    }
+
+    let mean = magnitudes.iter().map(|&(_, _, m)| m).sum::<f64>() / magnitudes.len() as f64;
+    let (mut total, mut sum_x, mut sum_y) = (0.0, 0.0, 0.0);
+    for &(x, y, magnitude) in magnitudes.iter().filter(|&&(_, _, m)| m > mean) {
+        total += magnitude;
+        sum_x += (x as f64 + 0.5) * magnitude;
+        sum_y += (y as f64 + 0.5) * magnitude;
+    }
+
+    if total == 0.0 {
+        center
+    } else {
+        (sum_x / total, sum_y / total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saliency_center_follows_subject() {
+        let img = RgbImage::from_fn(200, 100, |x, y| {
+            let (dx, dy) = (x as f64 - 150.0, y as f64 - 30.0);
+            if dx * dx + dy * dy < 15.0 * 15.0 {
+                image::Rgb([0, 0, 0])
+            } else {
+                image::Rgb([255, 255, 255])
+            }
+        });
+
+        let (x, y) = compute_gradient_saliency_center(&img);
+        assert!((x - 150.0).abs() < 2.0, "x = {}", x);
+        assert!((y - 30.0).abs() < 2.0, "y = {}", y);
+    }
+
+    #[test]
+    fn test_saliency_center_of_flat_image() {
+        let img = RgbImage::from_pixel(40, 20, image::Rgb([90, 90, 90]));
+        assert_eq!(compute_gradient_saliency_center(&img), (20.0, 10.0));
+    }
+}
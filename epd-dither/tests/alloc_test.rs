@@ -0,0 +1,94 @@
+//! Allocation accounting for the fused resize-and-dither pipeline
+//!
+//! Lives in its own test binary because it installs a global allocator.
+
+use epd_dither::{
+    color::{Palette, Rgb},
+    dither::{DitherOptions, DitheringAlgorithm},
+    process_image,
+    scaling::{self, FitMode, LetterboxBackground, ScalingFilter},
+};
+use image::RgbImage;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// System allocator that counts the bytes requested
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn bytes_allocated_by<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let result = f();
+    let bytes = ALLOCATED.load(Ordering::Relaxed) - before;
+    drop(result);
+    bytes
+}
+
+#[test]
+fn test_pipeline_allocates_less_than_two_steps() {
+    let img = RgbImage::from_fn(400, 300, |x, y| image::Rgb([x as u8, y as u8, 128]));
+    let options = DitherOptions {
+        algorithm: DitheringAlgorithm::Ordered {
+            width: 4,
+            height: 4,
+        },
+        palette: Palette::new("bw", vec![Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)]),
+        ..Default::default()
+    };
+    let (width, height, filter) = (100, 100, ScalingFilter::Triangle);
+
+    let two_steps = || {
+        let mut resized = scaling::resize_image(
+            &img,
+            width,
+            height,
+            FitMode::Crop,
+            filter,
+            LetterboxBackground::Solid([255, 255, 255]),
+            false,
+        )
+        .unwrap();
+        process_image(&mut resized, &options).unwrap();
+        resized
+    };
+    let pipeline = || {
+        scaling::resize_and_dither_pipeline(
+            &img,
+            width,
+            height,
+            FitMode::Crop,
+            filter,
+            Rgb::new(255, 255, 255),
+            &options,
+        )
+        .unwrap()
+    };
+
+    // Warm up one-time caches (threshold maps) before measuring
+    two_steps();
+    pipeline();
+
+    let two_step_bytes = bytes_allocated_by(two_steps);
+    let pipeline_bytes = bytes_allocated_by(pipeline);
+    assert!(
+        pipeline_bytes < two_step_bytes,
+        "Pipeline allocated {} bytes, two steps {}",
+        pipeline_bytes,
+        two_step_bytes
+    );
+}
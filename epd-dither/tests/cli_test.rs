@@ -0,0 +1,304 @@
+//! Integration tests for the epd-dither command line tool
+
+use image::RgbImage;
+use std::process::Command;
+
+fn epd_dither() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_epd-dither"))
+}
+
+fn write_test_image(dir: &std::path::Path) -> std::path::PathBuf {
+    let path = dir.join("input.png");
+    let img = RgbImage::from_fn(40, 20, |x, y| {
+        let value = ((x + y) * 4) as u8;
+        image::Rgb([value, value, value])
+    });
+    img.save(&path).unwrap();
+    path
+}
+
+#[test]
+fn test_dry_run_writes_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = write_test_image(dir.path());
+    let output = dir.path().join("output.png");
+
+    let result = epd_dither()
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .args([
+            "-p",
+            "spectra6",
+            "--target-width",
+            "20",
+            "--target-height",
+            "20",
+            "--dry-run",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    assert!(!output.exists());
+
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(stdout.contains("spectra6"), "{}", stdout);
+    assert!(stdout.contains("20x20"), "{}", stdout);
+}
+
+#[test]
+fn test_controller_output_is_raw_frame_buffer() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = write_test_image(dir.path());
+    let output = dir.path().join("frame.bin");
+
+    let result = epd_dither()
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .args(["-p", "default", "--controller", "ssd1681"])
+        .output()
+        .unwrap();
+
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    // 40x20 at 1bpp is 5 bytes per row
+    assert_eq!(std::fs::read(&output).unwrap().len(), 5 * 20);
+}
+
+#[test]
+fn test_indexed_png_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = write_test_image(dir.path());
+    let output = dir.path().join("indexed.png");
+
+    let result = epd_dither()
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .args(["-p", "spectra6", "--output-format", "indexed-png"])
+        .output()
+        .unwrap();
+
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+
+    let decoder = png::Decoder::new(std::fs::File::open(&output).unwrap());
+    let reader = decoder.read_info().unwrap();
+    assert_eq!(reader.info().color_type, png::ColorType::Indexed);
+}
+
+#[test]
+fn test_xbm_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = write_test_image(dir.path());
+    let output = dir.path().join("1-logo.h");
+
+    let result = epd_dither()
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .args(["-c", "#000000,#FFFFFF", "--output-format", "xbm"])
+        .output()
+        .unwrap();
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+
+    let source = std::fs::read_to_string(&output).unwrap();
+    assert!(source.starts_with("#define _1_logo_width "), "{}", source);
+    assert!(source.contains("static unsigned char _1_logo_bits[] = {"));
+}
+
+#[test]
+fn test_xbm_output_follows_dithering_palette() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("logo.h");
+    let xbm = |img: RgbImage, palette: &str| {
+        let input = dir.path().join("input.png");
+        img.save(&input).unwrap();
+        let result = epd_dither()
+            .arg("-i")
+            .arg(&input)
+            .arg("-o")
+            .arg(&output)
+            .args(["-c", palette, "--output-format", "xbm"])
+            .output()
+            .unwrap();
+        assert!(
+            result.status.success(),
+            "{}",
+            String::from_utf8_lossy(&result.stderr)
+        );
+        std::fs::read_to_string(&output).unwrap()
+    };
+
+    // An all-white image sets no bits
+    let white = RgbImage::from_pixel(16, 4, image::Rgb([255, 255, 255]));
+    let source = xbm(white, "#000000,#FFFFFF");
+    assert_eq!(source.matches("0x00").count(), 8, "{}", source);
+    assert!(!source.contains("0xff"), "{}", source);
+
+    // One plane per palette color, even for colors the image doesn't use
+    let white_and_red = RgbImage::from_fn(16, 4, |x, _| {
+        if x < 8 {
+            image::Rgb([255, 255, 255])
+        } else {
+            image::Rgb([255, 0, 0])
+        }
+    });
+    let source = xbm(white_and_red, "#000000,#FFFFFF,#FF0000");
+    for plane in 0..3 {
+        assert!(
+            source.contains(&format!("static unsigned char logo_{}_bits[]", plane)),
+            "{}",
+            source
+        );
+    }
+    assert!(source.contains("logo_0_bits[] = {\n   0x00, 0x00,"));
+    assert!(source.contains("logo_1_bits[] = {\n   0xff, 0x00,"));
+    assert!(source.contains("logo_2_bits[] = {\n   0x00, 0xff,"));
+}
+
+#[test]
+fn test_multi_algorithm_writes_every_algorithm() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = write_test_image(dir.path());
+    let output = dir.path().join("photo.png");
+
+    let palette = epd_dither::color::palette::PaletteManager::new()
+        .unwrap()
+        .get_palette("spectra6")
+        .unwrap();
+
+    let result = epd_dither()
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .args(["-p", "spectra6", "--multi-algorithm"])
+        .output()
+        .unwrap();
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    assert!(!output.exists());
+
+    // Per-channel levels of the palette, which per-channel error diffusion uses
+    let channel_levels: Vec<Vec<u8>> = (0..3)
+        .map(|channel| palette.colors.iter().map(|c| c.0[channel]).collect())
+        .collect();
+    for name in epd_dither::dither::ALGORITHM_NAMES {
+        let path = dir.path().join(format!("photo_{}.png", name));
+        let img = image::open(&path).unwrap().to_rgb8();
+        let in_output_range = |p: &image::Rgb<u8>| match name {
+            // Random dithering thresholds each channel to 0 or 255
+            "random-rgb" | "random-bw" => p.0.iter().all(|&v| v == 0 || v == 255),
+            "per-channel" => (0..3).all(|c| channel_levels[c].contains(&p.0[c])),
+            _ => palette.colors.contains(&epd_dither::color::Rgb(p.0)),
+        };
+        assert!(
+            img.pixels().all(in_output_range),
+            "{} has colors outside its output range",
+            name
+        );
+    }
+    assert!(dir.path().join("photo_comparison.png").exists());
+}
+
+#[test]
+fn test_palette_from_output_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = write_test_image(dir.path());
+    let first = dir.path().join("first.png");
+    let second = dir.path().join("second.png");
+
+    let result = epd_dither()
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&first)
+        .args(["-c", "#000,#fff,#f00", "--embed-metadata"])
+        .output()
+        .unwrap();
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+
+    let palette = epd_dither::Palette::from_png_metadata(&first).unwrap();
+    assert_eq!(palette.colors.len(), 3);
+
+    let result = epd_dither()
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&second)
+        .arg("--palette-from-output")
+        .arg(&first)
+        .output()
+        .unwrap();
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+
+    assert_eq!(
+        image::open(&first).unwrap().to_rgb8(),
+        image::open(&second).unwrap().to_rgb8()
+    );
+}
+
+#[test]
+fn test_exclusive_dither_modes_conflict() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = write_test_image(dir.path());
+    let output = dir.path().join("output.png");
+    let comparison = dir.path().join("comparison.png");
+
+    for extra in [
+        vec![
+            "--comparison-output",
+            comparison.to_str().unwrap(),
+            "--super-sample",
+            "2",
+        ],
+        vec!["--adaptive-dithering", "--parallel-rows"],
+        vec!["--super-sample", "2", "--chunk-height", "8"],
+    ] {
+        let result = epd_dither()
+            .arg("-i")
+            .arg(&input)
+            .arg("-o")
+            .arg(&output)
+            .args(&extra)
+            .output()
+            .unwrap();
+
+        assert!(!result.status.success(), "{:?} should be rejected", extra);
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        assert!(stderr.contains("cannot be used with"), "{}", stderr);
+    }
+    assert!(!output.exists());
+}
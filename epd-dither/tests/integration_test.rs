@@ -2,8 +2,9 @@
 
 use epd_dither::{
     color::{palette::PaletteManager, Palette, Rgb},
-    dither::{DitheringAlgorithm, DitherOptions, ErrorDiffusionKernel},
-    process_image,
+    device::DeviceManager,
+    dither::{DitherOptions, DitheringAlgorithm, ErrorDiffusionKernel},
+    process_gray_image, process_image,
 };
 use image::RgbImage;
 
@@ -22,6 +23,7 @@ fn test_basic_dithering() {
         algorithm: DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::FloydSteinberg),
         palette,
         serpentine: false,
+        ..Default::default()
     };
 
     // Should not panic
@@ -64,6 +66,7 @@ fn test_all_error_diffusion_algorithms() {
             algorithm: DitheringAlgorithm::ErrorDiffusion(algo),
             palette: palette.clone(),
             serpentine: false,
+            ..Default::default()
         };
 
         process_image(&mut test_img, &options).expect("Dithering should succeed");
@@ -86,6 +89,7 @@ fn test_ordered_dithering() {
         },
         palette,
         serpentine: false,
+        ..Default::default()
     };
 
     process_image(&mut img, &options).expect("Dithering should succeed");
@@ -129,6 +133,7 @@ fn test_serpentine_mode() {
         algorithm: DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::FloydSteinberg),
         palette,
         serpentine: true,
+        ..Default::default()
     };
 
     process_image(&mut img, &options).expect("Serpentine dithering should succeed");
@@ -159,6 +164,7 @@ fn test_multi_color_palette() {
         algorithm: DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::FloydSteinberg),
         palette: palette.clone(),
         serpentine: false,
+        ..Default::default()
     };
 
     process_image(&mut img, &options).expect("Multi-color dithering should succeed");
@@ -173,3 +179,256 @@ fn test_multi_color_palette() {
         );
     }
 }
+
+#[test]
+fn test_halftone_dithering() {
+    let mut img = RgbImage::new(32, 32);
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let value = ((x + y) * 255 / 62) as u8;
+        *pixel = image::Rgb([value, value, value]);
+    }
+
+    let palette = Palette::new("test", vec![Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)]);
+
+    let dither_with_angle = |angle_degrees: f64| {
+        let mut test_img = img.clone();
+        let options = DitherOptions {
+            algorithm: DitheringAlgorithm::Halftone {
+                angle_degrees,
+                frequency: 0.8,
+            },
+            palette: palette.clone(),
+            serpentine: false,
+            ..Default::default()
+        };
+        process_image(&mut test_img, &options).expect("Halftone dithering should succeed");
+        test_img
+    };
+
+    let horizontal = dither_with_angle(0.0);
+    let diagonal = dither_with_angle(45.0);
+
+    for pixel in horizontal.pixels().chain(diagonal.pixels()) {
+        let color = Rgb::new(pixel[0], pixel[1], pixel[2]);
+        assert!(
+            palette.colors.contains(&color),
+            "Pixel {:?} not in palette",
+            color
+        );
+    }
+
+    let differing = horizontal
+        .pixels()
+        .zip(diagonal.pixels())
+        .filter(|(a, b)| a != b)
+        .count();
+    assert!(
+        differing > 32,
+        "Only {} pixels differ between angles",
+        differing
+    );
+}
+
+#[test]
+fn test_device_database_json_round_trip() {
+    let mut manager = DeviceManager::new().unwrap();
+    let ids = manager.list_devices();
+    let json = manager.export_json().unwrap();
+
+    manager.clear();
+    assert!(manager.list_devices().is_empty());
+
+    assert_eq!(manager.import_json(&json).unwrap(), ids.len());
+    assert_eq!(manager.list_devices(), ids);
+}
+
+#[test]
+fn test_gray_image_dithering() {
+    let mut img = image::GrayImage::from_fn(4, 4, |x, y| image::Luma([((x + y * 4) * 17) as u8]));
+
+    let options = DitherOptions {
+        algorithm: DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::FloydSteinberg),
+        palette: Palette::new("bw", vec![Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)]),
+        serpentine: false,
+        ..Default::default()
+    };
+
+    process_gray_image(&mut img, &options).expect("Dithering should succeed");
+    for &value in img.iter() {
+        assert!(
+            value == 0 || value == 255,
+            "Pixel should be 0 or 255, got {}",
+            value
+        );
+    }
+}
+
+/// Logger that records every message so tests can assert on library diagnostics
+#[cfg(feature = "logging")]
+struct CaptureLogger {
+    records: std::sync::Mutex<Vec<(log::Level, String)>>,
+}
+
+#[cfg(feature = "logging")]
+impl log::Log for CaptureLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        self.records
+            .lock()
+            .unwrap()
+            .push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(feature = "logging")]
+#[test]
+fn test_replace_colors_logs_warning() {
+    static LOGGER: CaptureLogger = CaptureLogger {
+        records: std::sync::Mutex::new(Vec::new()),
+    };
+    log::set_logger(&LOGGER).expect("Only this test installs a logger");
+    log::set_max_level(log::LevelFilter::Warn);
+
+    // The gray pixels match neither original color
+    let mut img = RgbImage::from_pixel(4, 4, image::Rgb([128, 128, 128]));
+    let original = [Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)];
+    let replacement = [Rgb::new(10, 10, 10), Rgb::new(240, 240, 240)];
+    epd_dither::dither::engine::replace_colors(&mut img, &original, &replacement).unwrap();
+
+    let records = LOGGER.records.lock().unwrap();
+    assert!(records
+        .iter()
+        .any(|(level, message)| *level == log::Level::Warn && message.contains("not replaced")));
+}
+
+#[test]
+fn test_cancellation_stops_after_a_row() {
+    use epd_dither::{process_image_with_progress, CancellationToken, EpdDitherError};
+
+    let source = RgbImage::from_fn(16, 16, |x, y| {
+        image::Rgb([(x * 16) as u8, (y * 16) as u8, 100])
+    });
+    let mut img = source.clone();
+    let options = DitherOptions {
+        palette: Palette::new("bw", vec![Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)]),
+        ..Default::default()
+    };
+
+    let (token, handle) = CancellationToken::new();
+    std::thread::spawn(move || handle.cancel()).join().unwrap();
+
+    let mut rows_reported = 0;
+    let result = process_image_with_progress(
+        &mut img,
+        &options,
+        |done, _| rows_reported = done,
+        Some(&token),
+    );
+    let error = result.expect_err("Dithering should be cancelled");
+    assert_eq!(
+        error.downcast_ref::<EpdDitherError>(),
+        Some(&EpdDitherError::Cancelled)
+    );
+
+    // The first row finishes before the token is checked; the last is untouched
+    assert_eq!(rows_reported, 1);
+    let row = |img: &RgbImage, y: u32| (0..16).map(|x| *img.get_pixel(x, y)).collect::<Vec<_>>();
+    assert_ne!(row(&img, 0), row(&source, 0));
+    assert_eq!(row(&img, 15), row(&source, 15));
+}
+
+/// Paints every pixel with the first palette color
+struct FirstColorDitherer;
+
+impl epd_dither::dither::CustomDitherer for FirstColorDitherer {
+    fn dither(&self, img: &mut RgbImage, palette: &Palette) -> anyhow::Result<()> {
+        let first = palette
+            .colors
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("Empty palette"))?;
+        for pixel in img.pixels_mut() {
+            pixel.0 = first.0;
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "first-color"
+    }
+}
+
+#[test]
+fn test_custom_ditherer() {
+    let mut img = RgbImage::from_fn(8, 8, |x, y| {
+        image::Rgb([(x * 32) as u8, (y * 32) as u8, 200])
+    });
+    let options = DitherOptions {
+        algorithm: DitheringAlgorithm::Custom(std::sync::Arc::new(FirstColorDitherer)),
+        palette: Palette::new("rgb", vec![Rgb::new(255, 0, 0), Rgb::new(0, 0, 255)]),
+        ..Default::default()
+    };
+
+    process_image(&mut img, &options).expect("Dithering should succeed");
+    assert!(img.pixels().all(|p| p.0 == [255, 0, 0]));
+    assert_eq!(options.algorithm.name(), "first-color");
+    assert_eq!(
+        format!("{:?}", options.algorithm),
+        "Custom(CustomDitherer(first-color))"
+    );
+
+    // Serialized options record the custom algorithm but can't be loaded back
+    let json = serde_json::Value::from(&options);
+    assert_eq!(
+        json["algorithm"],
+        serde_json::json!({ "name": "first-color", "custom": true })
+    );
+    let err = DitherOptions::try_from(json).unwrap_err();
+    assert!(err.to_string().contains("custom ditherer"), "{}", err);
+}
+
+#[test]
+fn test_chunked_dithering_matches_single_chunk() {
+    use epd_dither::dither::engine::{dither_image, dither_image_chunked};
+
+    let source = RgbImage::from_fn(37, 23, |x, y| {
+        image::Rgb([(x * 7) as u8, (y * 11) as u8, ((x + y) * 5) as u8])
+    });
+    let palette = PaletteManager::new()
+        .unwrap()
+        .get_palette("spectra6")
+        .unwrap();
+
+    for algorithm in [
+        DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::FloydSteinberg),
+        DitheringAlgorithm::ErrorDiffusion(ErrorDiffusionKernel::Jarvis),
+        DitheringAlgorithm::Ordered {
+            width: 4,
+            height: 4,
+        },
+        DitheringAlgorithm::QuantizationOnly,
+    ] {
+        let options = DitherOptions {
+            algorithm,
+            palette: palette.clone(),
+            serpentine: true,
+            ..Default::default()
+        };
+
+        let mut one_chunk = source.clone();
+        dither_image_chunked(&mut one_chunk, &options, source.height()).unwrap();
+        let mut row_by_row = source.clone();
+        dither_image_chunked(&mut row_by_row, &options, 1).unwrap();
+        let mut whole = source.clone();
+        dither_image(&mut whole, &options).unwrap();
+
+        assert_eq!(one_chunk, row_by_row, "{}", options.algorithm.name());
+        assert_eq!(one_chunk, whole, "{}", options.algorithm.name());
+    }
+
+    assert!(dither_image_chunked(&mut source.clone(), &DitherOptions::default(), 0).is_err());
+}